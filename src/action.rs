@@ -1,6 +1,15 @@
-use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::mpsc::SyncSender};
+use std::{
+  collections::{HashMap, HashSet},
+  path::PathBuf,
+  str::FromStr,
+  sync::mpsc::SyncSender,
+  time::{Duration, Instant},
+};
 
 use eframe::egui;
+use serde::Deserialize;
+
+use crate::{config, util};
 
 // ------------------------------------ Action
 
@@ -15,7 +24,16 @@ pub enum Action {
   ToggleStatusBar,
   ToggleToolBar,
   ToggleExplorer,
+  ToggleSearch,
   ToggleTerminal,
+  NewTerminal,
+  CheckProblems,
+  /// 运行 .egui_code/tasks.toml 中定义的任务
+  RunTask(String,),
+  /// 重新运行最后运行的任务
+  RerunTask,
+  /// 取消正在运行的任务
+  CancelTask,
   ToggleVerticalTabBar,
   ZoomIn,
   ZoomOut,
@@ -27,6 +45,44 @@ pub enum Action {
   OpenSettingWindow,
   OpenFolder,
   SetOpenDir(PathBuf,),
+  Undo,
+  Redo,
+  Find,
+  Replace,
+  FindNext,
+  FindPrevious,
+  /// 在光标处触发语言服务器的补全
+  TriggerCompletion,
+  /// 跳转到光标处符号的定义
+  GotoDefinition,
+  /// 查找光标处符号的引用
+  FindReferences,
+  /// 保存当前文件
+  SaveFile,
+  /// 在光标所在行的行尾换行并缩进
+  NewLineAndIndent,
+  /// 设置当前文件的语言，None 表示自动检测
+  SetLanguage(Option<String,>,),
+  /// 重新加载语法，包括配置目录中的 .sublime-syntax 文件
+  ReloadSyntaxes,
+  /// 撤销文件树中最近一次文件操作
+  UndoFileOperation,
+  /// 文件树中 显示/隐藏 被 .gitignore 等过滤规则排除的文件
+  ToggleFilteredFiles,
+  /// 快速打开文件
+  QuickOpen,
+  /// 命令面板
+  CommandPalette,
+  /// 界面偏好和布局恢复默认设置
+  ResetSettings,
+  /// 未保存修改对话框：保存选中的文件，丢弃其余文件的修改
+  SaveUnsaved(Vec<PathBuf,>,),
+  /// 未保存修改对话框：丢弃所有修改
+  DiscardUnsaved,
+  /// 未保存修改对话框：取消关闭/退出
+  CancelUnsaved,
+  /// 已处理未保存的修改，直接退出
+  ExitAppConfirmed,
 }
 
 impl Action {
@@ -52,7 +108,13 @@ pub fn action_name(action: &Action,) -> &'static str {
     Action::ToggleStatusBar => "ToggleStatusBar",
     Action::ToggleToolBar => "ToggleToolBar",
     Action::ToggleExplorer => "ToggleExplorer",
+    Action::ToggleSearch => "ToggleSearch",
     Action::ToggleTerminal => "ToggleTerminal",
+    Action::NewTerminal => "NewTerminal",
+    Action::CheckProblems => "CheckProblems",
+    Action::RunTask(_,) => "RunTask",
+    Action::RerunTask => "RerunTask",
+    Action::CancelTask => "CancelTask",
     Action::ToggleVerticalTabBar => "ToggleVerticalTabBar",
     Action::ZoomIn => "ZoomIn",
     Action::ZoomOut => "ZoomOut",
@@ -64,31 +126,85 @@ pub fn action_name(action: &Action,) -> &'static str {
     Action::OpenSettingWindow => "OpenSettingWindow",
     Action::OpenFolder => "OpenFolder",
     Action::SetOpenDir(_,) => "SetOpenDir",
+    Action::Undo => "Undo",
+    Action::Redo => "Redo",
+    Action::Find => "Find",
+    Action::Replace => "Replace",
+    Action::FindNext => "FindNext",
+    Action::FindPrevious => "FindPrevious",
+    Action::TriggerCompletion => "TriggerCompletion",
+    Action::GotoDefinition => "GotoDefinition",
+    Action::FindReferences => "FindReferences",
+    Action::SaveFile => "SaveFile",
+    Action::NewLineAndIndent => "NewLineAndIndent",
+    Action::SetLanguage(_,) => "SetLanguage",
+    Action::ReloadSyntaxes => "ReloadSyntaxes",
+    Action::UndoFileOperation => "UndoFileOperation",
+    Action::ToggleFilteredFiles => "ToggleFilteredFiles",
+    Action::QuickOpen => "QuickOpen",
+    Action::CommandPalette => "CommandPalette",
+    Action::ResetSettings => "ResetSettings",
+    Action::SaveUnsaved(_,) => "SaveUnsaved",
+    Action::DiscardUnsaved => "DiscardUnsaved",
+    Action::CancelUnsaved => "CancelUnsaved",
+    Action::ExitAppConfirmed => "ExitAppConfirmed",
   }
 }
 
 static ACTION_MAP: once_cell::sync::Lazy<HashMap<&str, Action,>,> =
   once_cell::sync::Lazy::new(|| {
     [
+      Action::ExitApp,
       Action::ToggleFullScreen,
       Action::ToggleDecorations,
       Action::ToggleStatusBar,
       Action::ToggleToolBar,
       Action::ToggleExplorer,
+      Action::ToggleSearch,
       Action::ToggleTerminal,
+      Action::NewTerminal,
+      Action::CheckProblems,
+      Action::RerunTask,
+      Action::CancelTask,
       Action::ToggleVerticalTabBar,
       Action::ZoomIn,
       Action::ZoomOut,
       Action::ZoomReset,
       Action::OpenDebugWindow,
       Action::OpenPuffinViewer,
+      Action::OpenAboutWindow,
+      Action::OpenSettingWindow,
       Action::OpenFolder,
+      Action::Undo,
+      Action::Redo,
+      Action::Find,
+      Action::Replace,
+      Action::FindNext,
+      Action::FindPrevious,
+      Action::TriggerCompletion,
+      Action::GotoDefinition,
+      Action::FindReferences,
+      Action::SaveFile,
+      Action::NewLineAndIndent,
+      Action::ReloadSyntaxes,
+      Action::UndoFileOperation,
+      Action::ToggleFilteredFiles,
+      Action::QuickOpen,
+      Action::CommandPalette,
+      Action::ResetSettings,
     ]
     .into_iter()
     .map(|v| (v.name(), v,),)
     .collect::<HashMap<&str, Action,>>()
   },);
 
+/// 所有不需要参数的 action，按名称排序
+pub fn actions() -> Vec<&'static Action,> {
+  let mut actions = ACTION_MAP.values().collect::<Vec<_,>>();
+  actions.sort_by_key(|v| v.name(),);
+  actions
+}
+
 pub fn parse_action(action_name: &str,) -> Result<&'static Action, String,> {
   ACTION_MAP
     .get(action_name,)
@@ -106,10 +222,146 @@ pub trait Handle {
 
 // ------------------------------------ KeyActions
 
+/// 配置目录中的快捷键配置文件
+pub const KEYBINDINGS_FILE_NAME: &str = "keybindings.toml";
+
+/// 组合键中等待下一个按键的时间
+const CHORD_TIMEOUT: Duration = Duration::from_secs(3,);
+
+/// 用户快捷键配置，见配置目录中的 keybindings.toml
+///
+/// ```toml
+/// # 绑定（已被默认绑定的快捷键会被覆盖）
+/// [[bindings]]
+/// key = "Ctrl+Shift+T"
+/// action = "ToggleTerminal"
+///
+/// # 组合键，依次按下；when 限定生效的上下文
+/// [[bindings]]
+/// key = "Ctrl+K Ctrl+Z"
+/// action = "UndoFileOperation"
+/// when = "fileTreeFocused"
+///
+/// # action 以 - 开头表示解绑
+/// [[bindings]]
+/// key = "Ctrl+P"
+/// action = "-QuickOpen"
+/// ```
+#[derive(Debug, Default, Deserialize,)]
+pub struct KeybindingsConfig {
+  #[serde(default)]
+  bindings: Vec<KeyBindingConfig,>,
+}
+
+#[derive(Debug, Deserialize,)]
+struct KeyBindingConfig {
+  key: String,
+  action: String,
+  when: Option<String,>,
+}
+
+/// 快捷键生效的上下文
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord,)]
+pub enum When {
+  /// 编辑器有焦点
+  EditorFocused,
+  /// 最近点击的是文件树
+  FileTreeFocused,
+  /// 有模态窗口（如退出确认、命令面板）打开
+  ModalOpen,
+  /// 终端有焦点，此时不限上下文的快捷键不生效，按键发送给终端
+  TerminalFocused,
+}
+
+impl When {
+  pub fn name(&self,) -> &'static str {
+    match self {
+      When::EditorFocused => "editorFocused",
+      When::FileTreeFocused => "fileTreeFocused",
+      When::ModalOpen => "modalOpen",
+      When::TerminalFocused => "terminalFocused",
+    }
+  }
+}
+
+impl FromStr for When {
+  type Err = String;
+
+  fn from_str(s: &str,) -> Result<Self, Self::Err,> {
+    [
+      When::EditorFocused,
+      When::FileTreeFocused,
+      When::ModalOpen,
+      When::TerminalFocused,
+    ]
+    .into_iter()
+    .find(|v| v.name() == s,)
+    .ok_or(format!("无法解析 when => {}", s),)
+  }
+}
+
+/// 当前的上下文，每帧由 app 提供
+#[derive(Debug, Clone, Copy, Default,)]
+pub struct KeyContext {
+  pub editor_focused: bool,
+  pub file_tree_focused: bool,
+  pub modal_open: bool,
+  pub terminal_focused: bool,
+}
+
+impl KeyContext {
+  /// 有模态窗口时只有限定为 modalOpen 的快捷键生效，不影响主界面；
+  /// 终端有焦点时只有限定为 terminalFocused 的快捷键生效
+  fn is_active(
+    &self,
+    when: Option<When,>,
+  ) -> bool {
+    match when {
+      None => !self.modal_open && !self.terminal_focused,
+      Some(When::EditorFocused,) => self.editor_focused && !self.modal_open,
+      Some(When::FileTreeFocused,) => self.file_tree_focused && !self.modal_open,
+      Some(When::ModalOpen,) => self.modal_open,
+      Some(When::TerminalFocused,) => self.terminal_focused && !self.modal_open,
+    }
+  }
+}
+
+/// 快捷键：依次按下的一个或多个按键（如 Ctrl+K Ctrl+C），可限定生效的上下文
+#[derive(Debug, Clone, PartialEq, Eq, Hash,)]
+pub struct KeyBinding {
+  pub keys: Vec<egui::KeyboardShortcut,>,
+  pub when: Option<When,>,
+}
+
+impl From<egui::KeyboardShortcut,> for KeyBinding {
+  fn from(key: egui::KeyboardShortcut,) -> Self {
+    Self {
+      keys: vec![key],
+      when: None,
+    }
+  }
+}
+
+/// 按键的处理结果
+#[derive(Debug, Clone, PartialEq,)]
+enum KeyResult {
+  Action(Action,),
+  /// 组合键，等待下一个按键
+  Pending,
+  /// 组合键的下一个按键不匹配
+  Cancelled,
+  NoMatch,
+}
+
 #[derive(Debug,)]
 pub struct KeyActions {
   tx: SyncSender<Action,>,
-  map: HashMap<egui::KeyboardShortcut, Action,>,
+  map: HashMap<KeyBinding, Action,>,
+  /// 用户配置的快捷键
+  user_keys: HashSet<KeyBinding,>,
+  /// 组合键中已按下的按键
+  pending: Vec<egui::KeyboardShortcut,>,
+  pending_since: Option<Instant,>,
 }
 
 impl KeyActions {
@@ -117,21 +369,111 @@ impl KeyActions {
     Self {
       tx,
       map: HashMap::new(),
+      user_keys: HashSet::new(),
+      pending: vec![],
+      pending_since: None,
     }
   }
 
   pub fn init(&mut self,) -> Result<(), String,> {
     self.load_default()?;
+    self.load_user_config();
     Ok((),)
   }
 
+  /// 重新加载默认快捷键和用户配置
+  pub fn reload(&mut self,) {
+    self.map.clear();
+    self.user_keys.clear();
+    self.clear_pending();
+    if let Err(e,) = self.load_default() {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(Duration::from_secs(5,),),);
+    }
+    self.load_user_config();
+  }
+
+  /// 应用配置目录中的 keybindings.toml，错误通过 toast 提示
+  fn load_user_config(&mut self,) {
+    let errors = match config::load_toml::<KeybindingsConfig,>(KEYBINDINGS_FILE_NAME,) {
+      Ok(config,) => self.apply_user_config(&config,),
+      Err(e,) => vec![e],
+    };
+    for e in errors {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(Duration::from_secs(5,),),);
+    }
+  }
+
+  /// 先解绑再绑定，用户的绑定覆盖默认的绑定，返回无法应用的配置项
+  pub fn apply_user_config(
+    &mut self,
+    config: &KeybindingsConfig,
+  ) -> Vec<String,> {
+    let mut errors = vec![];
+    let mut bindings: Vec<(KeyBinding, &Action,),> = vec![];
+    for binding in config.bindings.iter() {
+      let result =
+        parse_key_binding(&binding.key, binding.when.as_deref(),).and_then(|key_binding| {
+          let (unbind, action_name,) = match binding.action.strip_prefix('-',) {
+            Some(v,) => (true, v,),
+            None => (false, binding.action.as_str(),),
+          };
+          Ok((key_binding, unbind, parse_action(action_name,)?,),)
+        },);
+      match result {
+        Ok((key_binding, true, action,),) => {
+          if self.map.get(&key_binding,) == Some(action,) {
+            self.map.remove(&key_binding,);
+            self.user_keys.remove(&key_binding,);
+          } else {
+            errors.push(format!("{} 未绑定到 {}", binding.key, action.name()),);
+          }
+        }
+        Ok((key_binding, false, action,),) => bindings.push((key_binding, action,),),
+        Err(e,) => errors.push(e,),
+      }
+    }
+
+    let mut bound = HashMap::new();
+    for (key_binding, action,) in bindings {
+      if let Some(act,) = bound.insert(key_binding.clone(), action,) {
+        errors.push(format!(
+          "key冲突，已绑定 {} => {} ",
+          format_key_binding(&key_binding,),
+          act.name()
+        ),);
+      }
+      self.map.insert(key_binding.clone(), action.clone(),);
+      self.user_keys.insert(key_binding,);
+    }
+    errors
+      .into_iter()
+      .map(|e| format!("{KEYBINDINGS_FILE_NAME}: {e}"),)
+      .collect()
+  }
+
+  /// 生效的快捷键，按 action 名称排序，bool 表示是否为用户配置
+  pub fn bindings(&self,) -> Vec<(&KeyBinding, &Action, bool,),> {
+    let mut bindings = self
+      .map
+      .iter()
+      .map(|(key, action,)| (key, action, self.user_keys.contains(key,),),)
+      .collect::<Vec<_,>>();
+    bindings.sort_by_key(|(key, action, _,)| (action.name(), format_key_binding(key,),),);
+    bindings
+  }
+
   pub fn insert(
     &mut self,
-    key: egui::KeyboardShortcut,
+    key: impl Into<KeyBinding,>,
     action: Action,
   ) -> Result<(), String,> {
+    let key = key.into();
     if let Some(act,) = self.map.get(&key,) {
-      let key_name = key.format(&egui::ModifierNames::NAMES, cfg!(target_os = "macos"),);
+      let key_name = format_key_binding(&key,);
       let action_name = act.name();
 
       return Err(format!("key冲突，已绑定 {} => {} ", key_name, action_name),);
@@ -151,46 +493,177 @@ impl KeyActions {
     self.insert(parse_shortcut("Ctrl+Minus",)?, Action::ZoomOut,)?;
     self.insert(parse_shortcut("Ctrl+Plus",)?, Action::ZoomIn,)?;
     self.insert(parse_shortcut("Ctrl+0",)?, Action::ZoomReset,)?;
-    self.insert(parse_shortcut("F12",)?, Action::OpenDebugWindow,)?;
+    // F12 在编辑器中为跳转到定义
+    self.insert(parse_shortcut("Ctrl+Shift+F12",)?, Action::OpenDebugWindow,)?;
     self.insert(parse_shortcut("Alt+F12",)?, Action::OpenPuffinViewer,)?;
     self.insert(parse_shortcut("Ctrl+F12",)?, Action::OpenAboutWindow,)?;
     self.insert(parse_shortcut("Ctrl+Shift+Q",)?, Action::ExitApp,)?;
     self.insert(parse_shortcut("Ctrl+Shift+O",)?, Action::OpenFolder,)?;
     self.insert(parse_shortcut("Ctrl+Shift+S",)?, Action::OpenSettingWindow,)?;
     self.insert(parse_shortcut("Alt+1",)?, Action::ToggleExplorer,)?;
+    self.insert(parse_shortcut("Ctrl+Shift+F",)?, Action::ToggleSearch,)?;
     self.insert(parse_shortcut("Alt+3",)?, Action::ToggleTerminal,)?;
     self.insert(parse_shortcut("Alt+4",)?, Action::ToggleStatusBar,)?;
     self.insert(parse_shortcut("Alt+5",)?, Action::ToggleToolBar,)?;
+    self.insert(parse_shortcut("Ctrl+Z",)?, Action::Undo,)?;
+    self.insert(parse_shortcut("Ctrl+Y",)?, Action::Redo,)?;
+    self.insert(parse_shortcut("Ctrl+Shift+Z",)?, Action::Redo,)?;
+    self.insert(parse_shortcut("Ctrl+F",)?, Action::Find,)?;
+    self.insert(parse_shortcut("Ctrl+H",)?, Action::Replace,)?;
+    self.insert(parse_shortcut("F3",)?, Action::FindNext,)?;
+    self.insert(parse_shortcut("Shift+F3",)?, Action::FindPrevious,)?;
+    self.insert(parse_shortcut("Ctrl+P",)?, Action::QuickOpen,)?;
+    self.insert(parse_shortcut("Ctrl+Shift+P",)?, Action::CommandPalette,)?;
+    self.insert(parse_shortcut("Ctrl+Shift+B",)?, Action::RerunTask,)?;
+    self.insert(
+      parse_key_binding("Ctrl+K Ctrl+O", None,)?,
+      Action::OpenFolder,
+    )?;
+    self.insert(
+      parse_key_binding("Ctrl+K Ctrl+S", None,)?,
+      Action::OpenSettingWindow,
+    )?;
+    self.insert(
+      parse_key_binding("Ctrl+Z", Some(When::FileTreeFocused.name(),),)?,
+      Action::UndoFileOperation,
+    )?;
+    self.insert(
+      parse_key_binding("Alt+3", Some(When::TerminalFocused.name(),),)?,
+      Action::ToggleTerminal,
+    )?;
+    self.insert(
+      parse_key_binding("Ctrl+Space", Some(When::EditorFocused.name(),),)?,
+      Action::TriggerCompletion,
+    )?;
+    self.insert(
+      parse_key_binding("F12", Some(When::EditorFocused.name(),),)?,
+      Action::GotoDefinition,
+    )?;
+    self.insert(
+      parse_key_binding("Shift+F12", Some(When::EditorFocused.name(),),)?,
+      Action::FindReferences,
+    )?;
+    self.insert(
+      parse_key_binding("Ctrl+S", Some(When::EditorFocused.name(),),)?,
+      Action::SaveFile,
+    )?;
+    self.insert(
+      parse_key_binding("Ctrl+J", Some(When::EditorFocused.name(),),)?,
+      Action::NewLineAndIndent,
+    )?;
 
     Ok((),)
   }
 
+  /// action 绑定的快捷键，不限上下文的、按键少的在前
   pub fn get_action_keys<'a,>(
     &'a self,
     action: &'a Action,
-  ) -> Vec<&'a egui::KeyboardShortcut,> {
+  ) -> Vec<&'a KeyBinding,> {
     let mut ret = vec![];
     for (key, act,) in self.map.iter() {
       if act == action {
         ret.push(key,);
       }
     }
+    ret.sort_by_key(|v| (v.when.is_some(), v.keys.len(), format_key_binding(v,),),);
     ret
   }
+
+  /// 组合键中已按下的按键，如 `Ctrl+K`
+  pub fn pending_chord(&self,) -> Option<String,> {
+    (!self.pending.is_empty()).then(|| format_keys(&self.pending,),)
+  }
+
+  fn clear_pending(&mut self,) {
+    self.pending.clear();
+    self.pending_since = None;
+  }
+
+  /// 处理一个按键，限定上下文的快捷键优先于不限上下文的，同一优先级中组合键优先
+  fn on_key(
+    &mut self,
+    key: egui::Key,
+    modifiers: egui::Modifiers,
+    context: &KeyContext,
+  ) -> KeyResult {
+    let depth = self.pending.len();
+    let pressed = |v: &egui::KeyboardShortcut| v.key == key && modifiers.matches(v.modifiers,);
+    let mut candidates = self
+      .map
+      .iter()
+      .filter(|(b, _,)| context.is_active(b.when,),)
+      .filter(|(b, _,)| {
+        b.keys.len() > depth && b.keys[..depth] == self.pending[..] && pressed(&b.keys[depth],)
+      },)
+      .collect::<Vec<_,>>();
+    if candidates.is_empty() {
+      if depth == 0 {
+        return KeyResult::NoMatch;
+      }
+      self.clear_pending();
+      return KeyResult::Cancelled;
+    }
+
+    let specific = candidates.iter().any(|(b, _,)| b.when.is_some(),);
+    candidates.retain(|(b, _,)| b.when.is_some() == specific,);
+    if let Some((b, _,),) = candidates.iter().find(|(b, _,)| b.keys.len() > depth + 1,) {
+      let next = b.keys[depth];
+      self.pending.push(next,);
+      self.pending_since = Some(Instant::now(),);
+      return KeyResult::Pending;
+    }
+    let action = candidates[0].1.clone();
+    self.clear_pending();
+    KeyResult::Action(action,)
+  }
 }
 
 impl KeyActions {
+  /// 处理本帧的按键，匹配的按键（包括组合键中的按键）被消费，不再传给其他组件
   pub fn bind_to_context(
-    &self,
+    &mut self,
     ctx: &egui::Context,
+    context: &KeyContext,
   ) {
+    if self
+      .pending_since
+      .is_some_and(|v| v.elapsed() >= CHORD_TIMEOUT,)
+    {
+      self.clear_pending();
+    }
     ctx.input_mut(|input| {
-      for (key, act,) in self.map.iter() {
-        if input.consume_shortcut(key,) {
-          let _ = self.tx.send(act.clone(),);
+      // 被消费的按键产生的文本也不输入
+      let mut drop_text = false;
+      input.events.retain(|event| match event {
+        egui::Event::Key {
+          key,
+          pressed: true,
+          modifiers,
+          ..
+        } => match self.on_key(*key, *modifiers, context,) {
+          KeyResult::Action(action,) => {
+            let _ = self.tx.send(action,);
+            drop_text = true;
+            false
+          }
+          KeyResult::Pending | KeyResult::Cancelled => {
+            drop_text = true;
+            false
+          }
+          KeyResult::NoMatch => true,
+        },
+        egui::Event::Text(_,) if drop_text => {
+          drop_text = false;
+          false
         }
-      }
+        _ => true,
+      },);
     },);
+    // 超时后清除状态栏中的提示
+    if let Some(since,) = self.pending_since {
+      ctx.request_repaint_after(CHORD_TIMEOUT.saturating_sub(since.elapsed(),),);
+    }
   }
 }
 
@@ -336,15 +809,47 @@ pub fn format_key(key: &egui::KeyboardShortcut,) -> String {
   key.format(&egui::ModifierNames::NAMES, cfg!(target_os = "macos"),)
 }
 
+/// 解析快捷键，多个按键以空格分隔，如 `Ctrl+K Ctrl+C`
+pub fn parse_key_binding(
+  keys: &str,
+  when: Option<&str,>,
+) -> Result<KeyBinding, String,> {
+  let keys = keys
+    .split_whitespace()
+    .map(parse_shortcut,)
+    .collect::<Result<Vec<_,>, _,>>()?;
+  if keys.is_empty() {
+    return Err("快捷键为空".into(),);
+  }
+  Ok(KeyBinding {
+    keys,
+    when: when.map(str::parse,).transpose()?,
+  },)
+}
+
+pub fn format_keys(keys: &[egui::KeyboardShortcut],) -> String {
+  keys.iter().map(format_key,).collect::<Vec<_,>>().join(" ",)
+}
+
+/// 如 `Ctrl+K Ctrl+C` 或 `Ctrl+Z (fileTreeFocused)`
+pub fn format_key_binding(key: &KeyBinding,) -> String {
+  match key.when {
+    Some(when,) => format!("{} ({})", format_keys(&key.keys), when.name()),
+    None => format_keys(&key.keys,),
+  }
+}
+
 #[cfg(test)]
 mod test {
   use std::sync::mpsc::sync_channel;
 
   use eframe::egui::{self};
 
-  use crate::action::{format_key, parse_key, parse_modifier, parse_shortcut, Action};
+  use crate::action::{
+    format_key_binding, parse_key, parse_key_binding, parse_modifier, parse_shortcut, Action,
+  };
 
-  use super::KeyActions;
+  use super::{KeyActions, KeyContext, KeyResult, KeybindingsConfig};
 
   #[test]
   fn print_keyactions() {
@@ -352,7 +857,7 @@ mod test {
     let mut ka = KeyActions::new(tx,);
     let _ = ka.init();
     for (key, act,) in ka.map.iter() {
-      let key_name = format_key(key,);
+      let key_name = format_key_binding(key,);
       let action_name = act.name();
       println!("{} => {:?}:{}", key_name, act, action_name);
     }
@@ -393,6 +898,217 @@ mod test {
     );
   }
 
+  #[test]
+  fn test_apply_user_config() {
+    let (tx, _,) = sync_channel(10,);
+    let mut ka = KeyActions::new(tx,);
+    ka.load_default().unwrap();
+    let config: KeybindingsConfig = toml::from_str(
+      r#"
+      [[bindings]]
+      key = "Ctrl+Shift+T"
+      action = "ToggleTerminal"
+      [[bindings]]
+      key = "Ctrl+F"
+      action = "QuickOpen"
+      [[bindings]]
+      key = "Ctrl+P"
+      action = "-QuickOpen"
+      [[bindings]]
+      key = "Ctrl+H"
+      action = "-Find"
+      [[bindings]]
+      key = "Ctrl+Shift+Y"
+      action = "ZoomIn"
+      [[bindings]]
+      key = "Ctrl+Shift+Y"
+      action = "ZoomOut"
+      [[bindings]]
+      key = "Ctrl+Foo"
+      action = "ZoomIn"
+      [[bindings]]
+      key = "Ctrl+K"
+      action = "NotAnAction"
+      "#,
+    )
+    .unwrap();
+    let errors = ka.apply_user_config(&config,);
+    assert_eq!(errors.len(), 4, "{errors:?}");
+
+    let key = |v: &str| parse_key_binding(v, None,).unwrap();
+    let keys = |action: &Action| {
+      ka.get_action_keys(action,)
+        .into_iter()
+        .map(format_key_binding,)
+        .collect::<Vec<_,>>()
+    };
+    // 新增和覆盖
+    assert_eq!(
+      keys(&Action::ToggleTerminal),
+      vec!["Alt+3", "Ctrl+Shift+T", "Alt+3 (terminalFocused)"]
+    );
+    assert_eq!(keys(&Action::QuickOpen), vec!["Ctrl+F"]);
+    assert!(keys(&Action::Find).is_empty());
+    // 解绑不匹配时保留
+    assert_eq!(keys(&Action::Replace), vec!["Ctrl+H"]);
+    assert!(ka
+      .bindings()
+      .iter()
+      .any(|(k, a, user,)| **k == key("Ctrl+F") && **a == Action::QuickOpen && *user));
+  }
+
+  #[test]
+  fn test_chord_and_when() {
+    let (tx, _,) = sync_channel(10,);
+    let mut ka = KeyActions::new(tx,);
+    let insert = |ka: &mut KeyActions, keys: &str, when: Option<&str,>, action: Action| {
+      ka.insert(parse_key_binding(keys, when,).unwrap(), action,)
+        .unwrap();
+    };
+    insert(&mut ka, "Ctrl+K Ctrl+C", None, Action::ZoomIn,);
+    insert(&mut ka, "Ctrl+K", None, Action::ZoomOut,);
+    insert(&mut ka, "Ctrl+Z", None, Action::Undo,);
+    insert(
+      &mut ka,
+      "Ctrl+Z",
+      Some("fileTreeFocused",),
+      Action::UndoFileOperation,
+    );
+    insert(&mut ka, "Y", Some("modalOpen",), Action::ExitApp,);
+    assert!(ka
+      .insert(parse_key_binding("Ctrl+Z", None).unwrap(), Action::Redo)
+      .is_err());
+    assert!(parse_key_binding("Ctrl+K Ctrl+Foo", None).is_err());
+    assert!(parse_key_binding("Ctrl+K", Some("focused")).is_err());
+    assert_eq!(
+      format_key_binding(&parse_key_binding("Ctrl+K  Ctrl+C", Some("editorFocused")).unwrap()),
+      "Ctrl+K Ctrl+C (editorFocused)"
+    );
+
+    let ctrl = egui::Modifiers::CTRL;
+    let none = egui::Modifiers::NONE;
+    let editor = KeyContext {
+      editor_focused: true,
+      ..Default::default()
+    };
+    // 组合键优先，等待下一个按键
+    assert_eq!(ka.on_key(egui::Key::K, ctrl, &editor), KeyResult::Pending);
+    assert_eq!(ka.pending_chord(), Some("Ctrl+K".into()));
+    assert_eq!(
+      ka.on_key(egui::Key::C, ctrl, &editor),
+      KeyResult::Action(Action::ZoomIn)
+    );
+    assert_eq!(ka.pending_chord(), None);
+    // 下一个按键不匹配时取消
+    assert_eq!(ka.on_key(egui::Key::K, ctrl, &editor), KeyResult::Pending);
+    assert_eq!(ka.on_key(egui::Key::X, ctrl, &editor), KeyResult::Cancelled);
+    assert_eq!(ka.on_key(egui::Key::X, ctrl, &editor), KeyResult::NoMatch);
+
+    // 同一按键在不同上下文中不同
+    assert_eq!(
+      ka.on_key(egui::Key::Z, ctrl, &editor),
+      KeyResult::Action(Action::Undo)
+    );
+    let file_tree = KeyContext {
+      file_tree_focused: true,
+      ..Default::default()
+    };
+    assert_eq!(
+      ka.on_key(egui::Key::Z, ctrl, &file_tree),
+      KeyResult::Action(Action::UndoFileOperation)
+    );
+    // 模态窗口打开时其他快捷键不生效
+    let modal = KeyContext {
+      modal_open: true,
+      ..Default::default()
+    };
+    assert_eq!(ka.on_key(egui::Key::Z, ctrl, &modal), KeyResult::NoMatch);
+    assert_eq!(
+      ka.on_key(egui::Key::Y, none, &modal),
+      KeyResult::Action(Action::ExitApp)
+    );
+    assert_eq!(ka.on_key(egui::Key::Y, none, &editor), KeyResult::NoMatch);
+
+    // 终端有焦点时按键发送给终端
+    let terminal = KeyContext {
+      terminal_focused: true,
+      ..Default::default()
+    };
+    assert_eq!(ka.on_key(egui::Key::Z, ctrl, &terminal), KeyResult::NoMatch);
+    assert_eq!(ka.on_key(egui::Key::K, ctrl, &terminal), KeyResult::NoMatch);
+    assert_eq!(ka.pending_chord(), None);
+  }
+
+  #[test]
+  fn test_default_keys_reachable() {
+    let (tx, _,) = sync_channel(10,);
+    let mut ka = KeyActions::new(tx,);
+    ka.load_default().unwrap();
+    let editor = KeyContext {
+      editor_focused: true,
+      ..Default::default()
+    };
+    let none = egui::Modifiers::NONE;
+    assert_eq!(
+      ka.on_key(egui::Key::F12, none, &editor),
+      KeyResult::Action(Action::GotoDefinition)
+    );
+    assert_eq!(
+      ka.on_key(egui::Key::F12, none, &KeyContext::default()),
+      KeyResult::NoMatch
+    );
+    let ctrl_shift = egui::Modifiers::CTRL | egui::Modifiers::SHIFT;
+    assert_eq!(
+      ka.on_key(egui::Key::F12, ctrl_shift, &editor),
+      KeyResult::Action(Action::OpenDebugWindow)
+    );
+
+    // 保存只在编辑器中生效，模态窗口打开时不保存
+    let ctrl = egui::Modifiers::CTRL;
+    assert_eq!(
+      ka.on_key(egui::Key::S, ctrl, &editor),
+      KeyResult::Action(Action::SaveFile)
+    );
+    let modal = KeyContext {
+      modal_open: true,
+      ..editor
+    };
+    assert_eq!(ka.on_key(egui::Key::S, ctrl, &modal), KeyResult::NoMatch);
+    assert_eq!(ka.on_key(egui::Key::J, ctrl, &modal), KeyResult::NoMatch);
+    // 组合键的第二个按键不受影响
+    assert_eq!(ka.on_key(egui::Key::K, ctrl, &editor), KeyResult::Pending);
+    assert_eq!(
+      ka.on_key(egui::Key::S, ctrl, &editor),
+      KeyResult::Action(Action::OpenSettingWindow)
+    );
+  }
+
+  #[test]
+  fn test_terminal_focused_default_keys() {
+    let (tx, _,) = sync_channel(10,);
+    let mut ka = KeyActions::new(tx,);
+    ka.load_default().unwrap();
+    let terminal = KeyContext {
+      terminal_focused: true,
+      ..Default::default()
+    };
+    let ctrl = egui::Modifiers::CTRL;
+    for key in [
+      egui::Key::Z,
+      egui::Key::Y,
+      egui::Key::F,
+      egui::Key::H,
+      egui::Key::P,
+      egui::Key::K,
+    ] {
+      assert_eq!(ka.on_key(key, ctrl, &terminal), KeyResult::NoMatch);
+    }
+    assert_eq!(
+      ka.on_key(egui::Key::Num3, egui::Modifiers::ALT, &terminal),
+      KeyResult::Action(Action::ToggleTerminal)
+    );
+  }
+
   #[test]
   fn test_action_compare() {
     assert_eq!(Action::ToggleFullScreen, Action::ToggleFullScreen);
@@ -1,29 +1,56 @@
 use std::{
   cell::RefCell,
+  path::{Path, PathBuf},
   rc::Rc,
   sync::mpsc::{sync_channel, Receiver, SyncSender},
+  time::Instant,
 };
 
-use eframe::egui;
+use eframe::egui::{self, containers::panel::PanelState};
 
 use crate::{
-  action::{self, Action, Handle, KeyActions},
+  action::{self, Action, Handle, KeyActions, KeyContext},
+  backup,
   component::{
-    file_tree::{self, Node},
+    command_palette::CommandPalette,
+    file_tree::{self, FileTreeState, Node},
     menu_bar::MenuBar,
     open_file::OpenFiles,
+    outline_panel::OutlinePanel,
+    problems_panel::ProblemsPanel,
+    quick_open::QuickOpen,
+    references_panel::ReferencesPanel,
+    search_panel::SearchPanel,
     status_bar::StatusBar,
+    task_panel::TaskPanel,
+    terminal_panel::TerminalPanel,
     tool_bar::{self, ToolBar},
   },
-  dev_tool, font, frame_history, id,
+  config, dev_tool, font, frame_history,
+  fs_watcher::FsWatcher,
+  hl, id,
   images::{self, CachedImage},
-  style, text, ui, util,
+  lsp::{LspEvent, LspManager},
+  path_filter::PathFilter,
+  settings::{self, LayoutSettings, LspSettings, ProblemsSettings, Session, Settings, UiSettings},
+  style, task, text, ui, util,
   window::about::AboutWindow,
   window::debug::DebugWindow,
   window::exit::ExitWindow,
   window::setting::SettingWindow,
 };
 
+/// 底部面板显示的内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+enum BottomPanelView {
+  Problems,
+  /// 任务的输出
+  Output,
+  /// 查找引用的结果
+  References,
+  Terminal,
+}
+
 pub struct MyApp {
   exit_app: bool,
   fullscreen: bool,
@@ -35,6 +62,7 @@ pub struct MyApp {
   show_tool_bar: bool,
   show_status_bar: bool,
   show_terminal: bool,
+  bottom_panel_view: BottomPanelView,
 
   exit_window: ExitWindow,
 
@@ -56,12 +84,40 @@ pub struct MyApp {
 
   // 打开的目录
   open_dir: Option<Rc<RefCell<Node,>,>,>,
+  file_tree_state: FileTreeState,
+  fs_watcher: FsWatcher,
+  // 计算监视的文件夹时打开的文件
+  watched_files: Vec<PathBuf,>,
+  // 上次备份未保存文件的时间
+  last_backup: Instant,
+  // 上次检查设置变化的时间，和最近保存的设置
+  last_settings_check: Instant,
+  saved_settings: Settings,
+  // 探索面板默认宽，拖动调整后的宽度由 egui 记录
+  explorer_width: f32,
+  // 文件树区域，用于判断文件树是否获得焦点
+  file_tree_rect: egui::Rect,
+  file_tree_focused: bool,
+  // 配置目录
+  config_watcher: FsWatcher,
 
   // 打开的文件列表
   open_files: OpenFiles,
+  terminal_panel: TerminalPanel,
+  problems_panel: ProblemsPanel,
+  task_panel: TaskPanel,
+  references_panel: ReferencesPanel,
+  outline_panel: OutlinePanel,
+  lsp: LspManager,
+
+  // 文件夹内查找
+  search_panel: SearchPanel,
+  quick_open: QuickOpen,
+  command_palette: CommandPalette,
 
   key_actions: KeyActions,
 
+  egui_ctx: egui::Context,
   tx: SyncSender<Action,>,
   rx: Receiver<Action,>,
 }
@@ -75,9 +131,23 @@ impl eframe::App for MyApp {
   }
 
   fn on_close_event(&mut self,) -> bool {
+    // 有未保存的文件时，先在对话框中选择保存或丢弃
+    if !self.exit_window.is_allowed_to_close() && self.open_files.prompt_unsaved_on_exit() {
+      return false;
+    }
     self.exit_window.on_frame_close_event()
   }
 
+  fn on_exit(
+    &mut self,
+    _gl: Option<&eframe::glow::Context,>,
+  ) {
+    // 等待后台的备份完成，退出前同步写入最新的备份
+    backup::flush();
+    self.open_files.sync_backups();
+    self.save_settings();
+  }
+
   fn update(
     &mut self,
     ctx: &egui::Context,
@@ -91,7 +161,9 @@ impl eframe::App for MyApp {
     // 记录 frame
     frame_history::record_frame_time(ctx, frame,);
     // 快捷键绑定
-    self.key_actions.bind_to_context(ctx,);
+    let key_context = self.key_context(ctx,);
+    self.key_actions.bind_to_context(ctx, &key_context,);
+    self.file_tree_rect = egui::Rect::NOTHING;
     // 配置 Style
     self.configure_style(ctx,);
     // fullscreen
@@ -102,16 +174,41 @@ impl eframe::App for MyApp {
     ctx.set_pixels_per_point(self.zoom,);
     // Toast UI
     util::toaster().show(ctx,);
+    // 同步磁盘上的变化
+    self.sync_disk_changes();
+    // 重新加载修改的配置
+    self.sync_config_changes();
+    // 定期备份未保存的文件
+    self.sync_backups(ctx,);
+    // 定期保存修改的设置
+    self.sync_settings(ctx,);
+    self.terminal_panel.sync();
+    if let Some(diagnostics,) = self.task_panel.sync() {
+      self.problems_panel.set_diagnostics(diagnostics,);
+    }
+    if self.problems_panel.sync() {
+      let diagnostics = self.problems_panel.diagnostics();
+      self.open_files.set_diagnostics(diagnostics,);
+    }
     // 退出确认窗口
     self.exit_window.show(ctx, frame,);
+    // 未保存修改对话框
+    self.open_files.show_unsaved_dialog(ctx,);
     // Debug 窗口
     self.debug_window.show(ctx, &mut self.show_debug_window,);
     // 设置窗口
-    self
-      .setting_window
-      .show(ctx, &mut self.show_setting_window,);
+    self.setting_window.show(
+      ctx,
+      &mut self.show_setting_window,
+      &self.key_actions,
+      &self.tx,
+    );
     // 关于窗口
     self.about_window.show(ctx, &mut self.show_about_window,);
+    // 快速打开文件
+    self.quick_open.show(ctx, &mut self.open_files,);
+    // 命令面板
+    self.command_palette.show(ctx, &self.key_actions,);
 
     // 主界面
     ui::central_panel(ctx,).show(ctx, |ui| {
@@ -122,13 +219,23 @@ impl eframe::App for MyApp {
       }
 
       // 有其他 window 可交互时，main pane 不可交互
-      ui.set_enabled(!self.show_setting_window && !self.exit_window.show,);
+      ui.set_enabled(
+        !self.show_setting_window
+          && !self.exit_window.show
+          && !self.open_files.is_unsaved_dialog_open()
+          && !self.quick_open.is_show()
+          && !self.command_palette.is_show(),
+      );
+      let pending_chord = self.key_actions.pending_chord();
 
       // 主界面
       self.show_top_menu_bar(ui, frame,);
-      self
-        .status_bar
-        .show(ui, self.show_status_bar, self.open_files.current_file(),);
+      self.status_bar.show(
+        ui,
+        self.show_status_bar,
+        self.open_files.current_file(),
+        pending_chord.as_deref(),
+      );
       self.show_center_panel(ui,);
     },);
 
@@ -145,6 +252,8 @@ impl eframe::App for MyApp {
         }
       }
     }
+    // 同步语言服务器
+    self.sync_lsp();
   }
 }
 
@@ -162,7 +271,14 @@ impl MyApp {
     let mut key_actions = KeyActions::new(tx.clone(),);
     key_actions.init().expect("init KeyAction 失败",);
 
-    Self {
+    // 监视配置目录，修改配置后立即生效
+    let mut config_watcher = FsWatcher::new(cc.egui_ctx.clone(),);
+    if let Some(dir,) = config::config_dir() {
+      let _ = std::fs::create_dir_all(&dir,);
+      config_watcher.watch(Some(&dir,),);
+    }
+
+    let mut app = Self {
       key_actions,
 
       exit_app: false,
@@ -191,22 +307,88 @@ impl MyApp {
       show_tool_bar: true,
 
       show_terminal: false,
+      bottom_panel_view: BottomPanelView::Terminal,
 
       logo_image,
 
       open_dir: None,
+      file_tree_state: FileTreeState::default(),
+      fs_watcher: FsWatcher::new(cc.egui_ctx.clone(),),
+      watched_files: vec![],
+      last_backup: Instant::now(),
+      last_settings_check: Instant::now(),
+      saved_settings: Settings::default(),
+      explorer_width: style::EXPLORER_MIN_WIDTH,
+      file_tree_rect: egui::Rect::NOTHING,
+      file_tree_focused: false,
+      config_watcher,
       open_files: OpenFiles::new(tx.clone(),),
+      terminal_panel: TerminalPanel::default(),
+      problems_panel: ProblemsPanel::default(),
+      task_panel: TaskPanel::default(),
+      references_panel: ReferencesPanel::default(),
+      outline_panel: OutlinePanel::default(),
+      lsp: LspManager::new(cc.egui_ctx.clone(),),
+      search_panel: SearchPanel::default(),
+      quick_open: QuickOpen::default(),
+      command_palette: CommandPalette::new(tx.clone(),),
       vertical_tab_bar: false,
 
+      egui_ctx: cc.egui_ctx.clone(),
       tx,
       rx,
+    };
+
+    // 恢复上次退出时的设置
+    let settings = Settings::load().unwrap_or_else(|e| {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(std::time::Duration::from_secs(5,),),);
+      Settings::default()
+    },);
+    app.apply_settings(&settings,);
+    app.restore_session(&settings.session,);
+    app.saved_settings = app.settings();
+    for e in app.open_files.restore_backups() {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(std::time::Duration::from_secs(5,),),);
     }
+    app
   }
 }
 
 // ------------------------------------ UI
 
 impl MyApp {
+  /// 当前快捷键上下文
+  fn key_context(
+    &mut self,
+    ctx: &egui::Context,
+  ) -> KeyContext {
+    // 上一帧的文件树区域内按下鼠标时，文件树获得焦点；在其他区域按下时失去焦点
+    if let Some(pos,) = ctx.input(|i| {
+      i.pointer
+        .any_pressed()
+        .then(|| i.pointer.interact_pos(),)
+        .flatten()
+    },)
+    {
+      self.file_tree_focused = self.file_tree_rect.contains(pos,);
+    }
+    let terminal_focused = self.show_terminal && self.terminal_panel.has_focus(ctx,);
+    KeyContext {
+      editor_focused: !self.file_tree_focused && self.open_files.has_editor_focus(ctx,),
+      file_tree_focused: self.file_tree_focused && !terminal_focused,
+      modal_open: self.exit_window.show
+        || self.open_files.is_unsaved_dialog_open()
+        || self.show_setting_window
+        || self.quick_open.is_show()
+        || self.command_palette.is_show(),
+      terminal_focused,
+    }
+  }
+
   /// 配置 Style
   fn configure_style(
     &mut self,
@@ -248,7 +430,9 @@ impl MyApp {
               },);
           }
           // 顶部菜单栏
-          self.menu_bar.show(ui, &self.key_actions,);
+          self
+            .menu_bar
+            .show(ui, &self.key_actions, &self.task_panel.task_names(),);
         },);
       },);
   }
@@ -258,74 +442,112 @@ impl MyApp {
     &mut self,
     ui: &mut egui::Ui,
   ) {
-    ui::central_panel( ui.ctx())
-    .show_inside(ui, |ui| {
+    ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
       ui.horizontal_centered(|ui| {
         // 工具栏
-        self.tool_bar.show(ui,self.show_tool_bar);
+        self.tool_bar.show(ui, self.show_tool_bar,);
         //
-        self.show_explorer_side_panel(ui);
+        self.show_explorer_side_panel(ui,);
         //
-        ui::central_panel(ui.ctx())
-          .show_inside(ui, |ui| {
-            // terminal
-            ui::bottom_panel("terminal_panel", ui.ctx())
-            .resizable(true)
-            .default_height(style::TERMINAL_PANEL_DEFAULT_HEIGHT)
-            .max_height(style::TERMINAL_PANEL_MAX_HEIGHT)
+        self.show_search_side_panel(ui,);
+        //
+        ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
+          // terminal
+          ui::bottom_panel("terminal_panel", ui.ctx(),)
+            .resizable(true,)
+            .default_height(style::TERMINAL_PANEL_DEFAULT_HEIGHT,)
+            .max_height(style::TERMINAL_PANEL_MAX_HEIGHT,)
             .show_animated_inside(ui, self.show_terminal, |ui| {
-              ui::top_panel("terminal_tab_bar", ui.ctx())
-                .exact_height(style::EXPLORER_TOP_HEIGHT)
+              let open_dir = self.open_dir_path();
+              ui::top_panel("terminal_tab_bar", ui.ctx(),)
+                .exact_height(style::EXPLORER_TOP_HEIGHT,)
                 .show_inside(ui, |ui| {
                   ui.horizontal_centered(|ui| {
                     let spacing_size = ui.spacing().item_spacing;
-                    ui.add_space(spacing_size.x);
-                    if ui.button("问题").clicked() {
-                    }
-                    if ui.button("调试").clicked() {
+                    ui.add_space(spacing_size.x,);
+                    let (errors, warnings,) = self.problems_panel.counts();
+                    ui.selectable_value(
+                      &mut self.bottom_panel_view,
+                      BottomPanelView::Problems,
+                      format!("问题 {errors}/{warnings}"),
+                    );
+                    ui.selectable_value(
+                      &mut self.bottom_panel_view,
+                      BottomPanelView::Output,
+                      "输出",
+                    );
+                    ui.selectable_value(
+                      &mut self.bottom_panel_view,
+                      BottomPanelView::References,
+                      format!("引用 {}", self.references_panel.count()),
+                    );
+                    ui.selectable_value(
+                      &mut self.bottom_panel_view,
+                      BottomPanelView::Terminal,
+                      "终端",
+                    );
+                    match self.bottom_panel_view {
+                      BottomPanelView::Problems | BottomPanelView::References => {}
+                      BottomPanelView::Output => {
+                        ui.separator();
+                        self.task_panel.show_tab_bar(ui, open_dir.as_deref(),);
+                      }
+                      BottomPanelView::Terminal => {
+                        ui.separator();
+                        self.terminal_panel.show_tab_bar(ui, open_dir.as_deref(),);
+                      }
                     }
-                    if ui.button("终端").clicked() {
-                    }
-                  });
-                });
-
-                egui::ScrollArea::both().show(ui, |ui| {
-                  ui.set_width(ui.available_width());
-                  ui.set_height(ui.available_height());
-                  ui.style_mut().wrap = Some(false);
-                  ui.monospace(">>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>> ");
-                });
-            });
-
-            ui::central_panel(ui.ctx())
-              .show_inside(ui,|ui|{
-
-              if self.open_files.is_empty(){
-                return;
-              }
+                  },);
+                },);
 
-              let open_dir = self
-                .open_dir
-                .as_ref()
-                .map(|v| RefCell::borrow(v).path().to_owned());
-              //
-              // tab bar
-              self.open_files.show_tab_bar(ui, self.vertical_tab_bar, open_dir.as_ref(),&self.key_actions)
-              .response.context_menu(|ui|{
-                if ui.button("vertical tab bar").clicked() {
+              ui::central_panel(ui.ctx(),).show_inside(ui, |ui| match self.bottom_panel_view {
+                BottomPanelView::Problems => {
+                  self
+                    .problems_panel
+                    .show(ui, open_dir.as_deref(), &mut self.open_files,);
+                }
+                BottomPanelView::Output => self.task_panel.show_output(ui,),
+                BottomPanelView::References => {
+                  self
+                    .references_panel
+                    .show(ui, open_dir.as_deref(), &mut self.open_files,);
+                }
+                BottomPanelView::Terminal => self.terminal_panel.show_terminal(ui,),
+              },);
+            },);
+
+          ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
+            if self.open_files.is_empty() {
+              return;
+            }
+
+            let open_dir = self.open_dir_path();
+            //
+            // tab bar
+            self
+              .open_files
+              .show_tab_bar(
+                ui,
+                self.vertical_tab_bar,
+                open_dir.as_ref(),
+                &self.key_actions,
+              )
+              .response
+              .context_menu(|ui| {
+                if ui.button("vertical tab bar",).clicked() {
                   ui.close_menu();
                   self.toggle_vertical_tab_bar();
                 }
-              });
-              // 编辑器
-              ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
-                self.open_files.show_text_editor(ui);
               },);
-              //
-            });
-          });
-        });
-    });
+            // 编辑器
+            ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
+              self.open_files.show_text_editor(ui,);
+            },);
+            //
+          },);
+        },);
+      },);
+    },);
   }
 
   /// 探索面板
@@ -336,6 +558,7 @@ impl MyApp {
     ui::left_panel(id::EXPLORER, ui.ctx(),)
       .resizable(true,)
       .min_width(style::EXPLORER_MIN_WIDTH,)
+      .default_width(self.explorer_width,)
       .show_animated_inside(ui, self.show_explorer(), |ui| {
         ui.style_mut().wrap = Some(false,);
         // 探索
@@ -346,6 +569,18 @@ impl MyApp {
               let spacing_size = ui.spacing().item_spacing;
               ui.add_space(spacing_size.x,);
               ui.heading("探索",);
+              ui.with_layout(egui::Layout::right_to_left(egui::Align::Center,), |ui| {
+                ui.add_space(spacing_size.x,);
+                let (icon, hover_text,) = if self.file_tree_state.show_filtered() {
+                  (font::NerdFont::EYE, "隐藏被忽略的文件",)
+                } else {
+                  (font::NerdFont::EYE_CLOSED, "显示被忽略的文件",)
+                };
+                let button = egui::Button::new(icon.utf().to_string(),).frame(false,);
+                if ui.add(button,).on_hover_text(hover_text,).clicked() {
+                  let _ = self.tx.send(Action::ToggleFilteredFiles,);
+                }
+              },);
             },);
           },);
         // 时间线
@@ -369,30 +604,37 @@ impl MyApp {
         ui::bottom_panel("outline", ui.ctx(),)
           .resizable(true,)
           .show_inside(ui, |ui| {
-            let (_state, _header_res, _body_res,) = ui::custom_collapsing(
+            let sort_hover_text = self.outline_panel.sort_order().name();
+            let (_state, header_res, _body_res,) = ui::custom_collapsing(
               ui,
               "outline_collapsing",
               "大纲",
               false,
               |ui| {
                 let more_action_button = egui::Button::new("",).frame(false,);
-                if ui.add(more_action_button,).clicked() {
-                  //
-                }
+                let next_sort_order = ui
+                  .add(more_action_button,)
+                  .on_hover_text(sort_hover_text,)
+                  .clicked();
                 let collapse_button = egui::Button::new("",).frame(false,);
-                if ui.add(collapse_button,).clicked() {
-                  //
-                }
-              },
-              |ui| {
-                if ui.selectable_label(false, "function",).clicked() {}
-                if ui.selectable_label(false, "struct",).clicked() {}
-                if ui.selectable_label(false, "impl",).clicked() {}
+                let collapse_all = ui
+                  .add(collapse_button,)
+                  .on_hover_text("全部折叠",)
+                  .clicked();
+                (next_sort_order, collapse_all,)
               },
+              |ui| self.outline_panel.show(ui, &mut self.open_files,),
             );
+            let (next_sort_order, collapse_all,) = header_res.inner;
+            if next_sort_order {
+              self.outline_panel.next_sort_order();
+            }
+            if collapse_all {
+              self.outline_panel.collapse_all();
+            }
           },);
         // 文件
-        ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
+        let response = ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
           if self.open_dir.is_none() {
             ui.vertical_centered_justified(|ui| {
               ui.monospace("当前未打开文件夹",);
@@ -403,14 +645,43 @@ impl MyApp {
           } else {
             puffin::profile_scope!("show_tree");
             let node = self.open_dir.as_ref().unwrap();
-            file_tree::show_tree(node, node, ui, &mut self.open_files, vec![],);
+            file_tree::show_tree(
+              node,
+              node,
+              ui,
+              &mut self.open_files,
+              &mut self.file_tree_state,
+              vec![],
+            );
           }
         },);
+        self.file_tree_rect = response.response.rect;
         //
       },);
   }
 }
 
+impl MyApp {
+  /// 搜索面板
+  fn show_search_side_panel(
+    &mut self,
+    ui: &mut egui::Ui,
+  ) {
+    ui::left_panel(id::SEARCH, ui.ctx(),)
+      .resizable(true,)
+      .min_width(style::EXPLORER_MIN_WIDTH,)
+      .show_animated_inside(ui, self.show_search(), |ui| {
+        let open_dir = self
+          .open_dir
+          .as_ref()
+          .map(|v| RefCell::borrow(v,).path().to_owned(),);
+        self
+          .search_panel
+          .show(ui, open_dir.as_ref(), &mut self.open_files,);
+      },);
+  }
+}
+
 // ------------------------------------ Data update
 
 impl MyApp {
@@ -458,6 +729,85 @@ impl MyApp {
 
   pub fn toggle_terminal(&mut self,) {
     self.show_terminal = !self.show_terminal;
+    if self.show_terminal
+      && self.bottom_panel_view == BottomPanelView::Terminal
+      && self.terminal_panel.is_empty()
+    {
+      self.new_terminal();
+    }
+  }
+
+  /// 在打开的文件夹中新建终端
+  pub fn new_terminal(&mut self,) {
+    let cwd = self.open_dir_path();
+    self
+      .terminal_panel
+      .new_terminal(&self.egui_ctx, cwd.as_deref(),);
+    self.show_terminal = true;
+    self.bottom_panel_view = BottomPanelView::Terminal;
+  }
+
+  /// 在打开的文件夹中运行检查命令，显示问题面板
+  pub fn check_problems(&mut self,) {
+    let root = self.open_dir_path();
+    self
+      .problems_panel
+      .start_check(&self.egui_ctx, root.as_deref(),);
+    self.show_terminal = true;
+    self.bottom_panel_view = BottomPanelView::Problems;
+  }
+
+  /// 运行任务，显示任务的输出
+  pub fn run_task(
+    &mut self,
+    name: &str,
+  ) {
+    let root = self.open_dir_path();
+    let result = self
+      .task_panel
+      .run_task(&self.egui_ctx, root.as_deref(), name,);
+    self.on_task_started(result,);
+  }
+
+  /// 重新运行最后运行的任务
+  pub fn rerun_task(&mut self,) {
+    let root = self.open_dir_path();
+    let result = self.task_panel.rerun_task(&self.egui_ctx, root.as_deref(),);
+    self.on_task_started(result,);
+  }
+
+  fn on_task_started(
+    &mut self,
+    result: Result<(), String,>,
+  ) {
+    match result {
+      Ok(_,) => {
+        self.show_terminal = true;
+        self.bottom_panel_view = BottomPanelView::Output;
+      }
+      Err(e,) => {
+        util::toaster()
+          .error(e,)
+          .set_duration(Some(std::time::Duration::from_secs(5,),),);
+      }
+    }
+  }
+
+  /// 重新读取打开的文件夹中的任务定义
+  fn reload_tasks(&mut self,) {
+    let root = self.open_dir_path();
+    if let Err(e,) = self.task_panel.load_tasks(root.as_deref(),) {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(std::time::Duration::from_secs(5,),),);
+    }
+  }
+
+  fn open_dir_path(&self,) -> Option<PathBuf,> {
+    self
+      .open_dir
+      .as_ref()
+      .map(|v| RefCell::borrow(v,).path().to_owned(),)
   }
 
   pub fn show_explorer(&self,) -> bool {
@@ -468,6 +818,14 @@ impl MyApp {
     }
   }
 
+  pub fn show_search(&self,) -> bool {
+    if let Some(item,) = self.tool_bar.current_item() {
+      item.id == tool_bar::ToolId::Search
+    } else {
+      false
+    }
+  }
+
   pub fn open_about_window(&mut self,) {
     self.show_about_window = true;
   }
@@ -480,18 +838,296 @@ impl MyApp {
     self.show_debug_window = true;
   }
 
+  pub fn quick_open(&mut self,) {
+    let open_dir = self
+      .open_dir
+      .as_ref()
+      .map(|v| RefCell::borrow(v,).path().to_owned(),);
+    self.command_palette.close();
+    self.quick_open.open(open_dir.as_ref(),);
+  }
+
+  pub fn reload_syntaxes(&mut self,) {
+    let count = hl::reload_syntax_set();
+    util::toaster()
+      .info(format!("已重新加载 {count} 个语法"),)
+      .set_duration(Some(std::time::Duration::from_secs(3,),),);
+  }
+
   fn set_open_dir(
     &mut self,
-    dir_path: Option<std::path::PathBuf,>,
+    dir_path: Option<PathBuf,>,
   ) {
+    let filter = dir_path
+      .as_deref()
+      .map(PathFilter::load,)
+      .unwrap_or_default();
+    match &dir_path {
+      Some(_,) => self.fs_watcher.watch_filtered(filter.clone(),),
+      None => self.fs_watcher.stop(),
+    }
+    self.file_tree_state.set_filter(filter,);
     self.open_dir = dir_path.map(|v| {
       Rc::new(RefCell::new(Node::Dir {
         path: v,
         children: Default::default(),
         parent: None,
         expand: true,
+        filtered: false,
       },),)
     },);
+    self.reload_tasks();
+    self.lsp.set_root(self.open_dir_path(),);
+    self.problems_panel.clear_lsp_diagnostics();
+    self.outline_panel.clear_lsp_paths();
+  }
+
+  /// 同步打开的文件到语言服务器，发送编辑器的请求，处理语言服务器的响应
+  fn sync_lsp(&mut self,) {
+    for e in self.lsp.sync_documents(&self.open_files.lsp_documents(),) {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(std::time::Duration::from_secs(5,),),);
+    }
+    for request in self.open_files.take_lsp_requests() {
+      self.lsp.request(request,);
+    }
+    let current_file = self.open_files.current_file();
+    if let Some(request,) = self.outline_panel.sync(&self.egui_ctx, current_file,) {
+      self.lsp.request(request,);
+    }
+    let events = self.lsp.poll();
+    if !events.is_empty() {
+      // 在下一帧显示诊断等
+      self.egui_ctx.request_repaint();
+    }
+    for event in events {
+      match event {
+        LspEvent::Diagnostics {
+          path,
+          diagnostics,
+        } => {
+          self.problems_panel.set_lsp_diagnostics(path, diagnostics,);
+        }
+        LspEvent::Completion {
+          path,
+          position,
+          items,
+        } => self.open_files.show_completion(&path, position, items,),
+        LspEvent::Hover {
+          path,
+          position,
+          text,
+        } => self.open_files.set_hover(&path, position, text,),
+        LspEvent::Definition(locations,) => {
+          let Some(location,) = locations.first() else {
+            util::toaster()
+              .info("没有找到定义",)
+              .set_duration(Some(std::time::Duration::from_secs(3,),),);
+            continue;
+          };
+          match self.open_files.open_file(&location.path,) {
+            Ok(_,) => self.open_files.goto_position(location.start, location.end,),
+            Err(e,) => {
+              util::toaster()
+                .error(format!("无法读取文件：{:?}\nErr: {e}", location.path),)
+                .set_duration(Some(std::time::Duration::from_secs(5,),),);
+            }
+          }
+        }
+        LspEvent::Symbols {
+          path,
+          symbols,
+        } => {
+          self.outline_panel.set_lsp_symbols(path, symbols,);
+        }
+        LspEvent::References(locations,) => {
+          self.references_panel.set_locations(locations,);
+          self.show_terminal = true;
+          self.bottom_panel_view = BottomPanelView::References;
+        }
+        LspEvent::Error(e,) => {
+          util::toaster()
+            .error(e,)
+            .set_duration(Some(std::time::Duration::from_secs(5,),),);
+        }
+      }
+    }
+  }
+
+  /// 文件树和已打开的文件跟随磁盘上的变化
+  fn sync_disk_changes(&mut self,) {
+    // 只监视文件树中已加载的文件夹和已打开的文件所在的文件夹，文件树或打开的文件变化时才重新计算
+    if let Some(root,) = &self.open_dir {
+      let tree_changed = self.file_tree_state.take_tree_changed();
+      if tree_changed || !self.watched_files.iter().eq(self.open_files.paths(),) {
+        self.watched_files = self.open_files.paths().cloned().collect();
+        let mut dirs = file_tree::watched_dirs(root,);
+        dirs.extend(
+          self
+            .watched_files
+            .iter()
+            .filter_map(|v| v.parent(),)
+            .map(Path::to_path_buf,),
+        );
+        self.fs_watcher.set_dirs(dirs,);
+      }
+    }
+    let paths = self.fs_watcher.take_changed_paths();
+    if paths.is_empty() {
+      return;
+    }
+    if let Some(root,) = &self.open_dir {
+      file_tree::on_disk_change(root, &paths, self.file_tree_state.filter(),);
+      self.file_tree_state.mark_tree_changed();
+    }
+    self.open_files.on_disk_change(&paths,);
+    let tasks_file = self.open_dir_path().map(|v| v.join(task::TASKS_FILE,),);
+    if tasks_file.is_some_and(|v| paths.contains(&v,),) {
+      self.reload_tasks();
+    }
+  }
+
+  /// 每隔 BACKUP_INTERVAL 备份一次未保存的文件，进程意外退出后也可以恢复
+  fn sync_backups(
+    &mut self,
+    ctx: &egui::Context,
+  ) {
+    if !self.open_files.need_backup() {
+      return;
+    }
+    let elapsed = self.last_backup.elapsed();
+    if elapsed >= backup::BACKUP_INTERVAL {
+      self.open_files.sync_backups();
+      self.last_backup = Instant::now();
+    } else {
+      ctx.request_repaint_after(backup::BACKUP_INTERVAL - elapsed,);
+    }
+  }
+
+  /// 每隔 SAVE_INTERVAL 检查一次设置，有变化时保存，进程意外退出后也可以恢复
+  fn sync_settings(
+    &mut self,
+    ctx: &egui::Context,
+  ) {
+    let elapsed = self.last_settings_check.elapsed();
+    if elapsed >= settings::SAVE_INTERVAL {
+      if self.settings() != self.saved_settings {
+        self.save_settings();
+      }
+      self.last_settings_check = Instant::now();
+    } else {
+      ctx.request_repaint_after(settings::SAVE_INTERVAL - elapsed,);
+    }
+  }
+
+  /// 配置目录中的文件修改后重新加载
+  fn sync_config_changes(&mut self,) {
+    let paths = self.config_watcher.take_changed_paths();
+    if paths.iter().any(|v| {
+      v.file_name()
+        .is_some_and(|v| v == action::KEYBINDINGS_FILE_NAME,)
+    },)
+    {
+      self.key_actions.reload();
+    }
+  }
+
+  /// 当前的设置
+  fn settings(&self,) -> Settings {
+    let (open_files, current_index,) = self.open_files.session();
+    let explorer_width = PanelState::load(&self.egui_ctx, egui::Id::new(id::EXPLORER,),)
+      .map_or(self.explorer_width, |v| v.rect.width(),);
+    Settings {
+      ui: UiSettings {
+        zoom: (self.zoom != self.zoom_default).then_some(self.zoom,),
+        transparency: ui::transparency(),
+        dark_mode: self.egui_ctx.style().visuals.dark_mode,
+        dark_theme: hl::selected_theme(true,),
+        light_theme: hl::selected_theme(false,),
+      },
+      layout: LayoutSettings {
+        vertical_tab_bar: self.vertical_tab_bar,
+        show_tool_bar: self.show_tool_bar,
+        show_status_bar: self.show_status_bar,
+        show_terminal: self.show_terminal,
+        explorer_width,
+        tool: self.tool_bar.current_item().map(|v| v.id.clone(),),
+      },
+      problems: ProblemsSettings {
+        check_command: self.problems_panel.command().to_string(),
+      },
+      lsp: LspSettings {
+        servers: self.lsp.servers().clone(),
+      },
+      session: Session {
+        open_dir: self
+          .open_dir
+          .as_ref()
+          .map(|v| RefCell::borrow(v,).path().to_owned(),),
+        open_files,
+        current_index,
+      },
+    }
+  }
+
+  /// 应用界面偏好和布局，不包括会话
+  fn apply_settings(
+    &mut self,
+    settings: &Settings,
+  ) {
+    let ui_settings = &settings.ui;
+    self.zoom = ui_settings.zoom.map_or(self.zoom_default, ui::zoom_clamp,);
+    ui::set_transparency(ui_settings.transparency,);
+    self.egui_ctx.set_visuals(
+      if ui_settings.dark_mode {
+        egui::Visuals::dark()
+      } else {
+        egui::Visuals::light()
+      },
+    );
+    hl::select_theme(true, &ui_settings.dark_theme,);
+    hl::select_theme(false, &ui_settings.light_theme,);
+
+    let layout = &settings.layout;
+    self.vertical_tab_bar = layout.vertical_tab_bar;
+    self.show_tool_bar = layout.show_tool_bar;
+    self.show_status_bar = layout.show_status_bar;
+    self.show_terminal = layout.show_terminal;
+    // 清除 egui 记录的宽度，使默认宽生效
+    self.explorer_width = layout.explorer_width.max(style::EXPLORER_MIN_WIDTH,);
+    self
+      .egui_ctx
+      .data_mut(|d| d.remove::<PanelState>(egui::Id::new(id::EXPLORER,),),);
+    self.tool_bar.select_item(layout.tool.as_ref(),);
+
+    self
+      .problems_panel
+      .set_command(&settings.problems.check_command,);
+    self.lsp.set_servers(settings.lsp.servers.clone(),);
+  }
+
+  /// 重新打开上次的文件夹和文件
+  fn restore_session(
+    &mut self,
+    session: &Session,
+  ) {
+    if let Some(dir,) = session.open_dir.as_ref().filter(|v| v.is_dir(),) {
+      self.set_open_dir(Some(dir.clone(),),);
+    }
+    for e in self.open_files.restore_session(session,) {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(std::time::Duration::from_secs(5,),),);
+    }
+  }
+
+  fn save_settings(&mut self,) {
+    let settings = self.settings();
+    if let Err(e,) = settings.save() {
+      tracing::error!("保存设置失败 => {e}");
+    }
+    self.saved_settings = settings;
   }
 
   fn on_mode_changed(&mut self,) {
@@ -510,12 +1146,22 @@ impl action::Handle for MyApp {
     match action {
       Action::NoOp => {}
       Action::ExitApp => self.exit_app(),
+      Action::ExitAppConfirmed => {
+        self.exit_window.allow_close();
+        self.exit_app();
+      }
       Action::ToggleFullScreen => self.toggle_fullscreen(),
       Action::ToggleDecorations => self.toggle_decorations(),
       Action::ToggleStatusBar => self.toggle_status_bar(),
       Action::ToggleToolBar => self.toggle_tool_bar(),
       Action::ToggleExplorer => { /*  此处不处理，交由 ToolBar 处理*/ }
+      Action::ToggleSearch => { /*  此处不处理，交由 ToolBar 处理*/ }
       Action::ToggleTerminal => self.toggle_terminal(),
+      Action::NewTerminal => self.new_terminal(),
+      Action::CheckProblems => self.check_problems(),
+      Action::RunTask(name,) => self.run_task(name,),
+      Action::RerunTask => self.rerun_task(),
+      Action::CancelTask => self.task_panel.cancel_task(),
       Action::ToggleVerticalTabBar => self.toggle_vertical_tab_bar(),
       Action::ZoomIn => self.zoom_in(),
       Action::ZoomOut => self.zoom_out(),
@@ -536,6 +1182,41 @@ impl action::Handle for MyApp {
           let _ = self.tx.send(Action::ToggleExplorer,);
         }
       }
+      Action::Undo
+      | Action::Redo
+      | Action::Find
+      | Action::Replace
+      | Action::FindNext
+      | Action::FindPrevious
+      | Action::TriggerCompletion
+      | Action::GotoDefinition
+      | Action::FindReferences
+      | Action::SaveFile
+      | Action::NewLineAndIndent
+      | Action::SetLanguage(_,)
+      | Action::SaveUnsaved(_,)
+      | Action::DiscardUnsaved
+      | Action::CancelUnsaved => { /* 此处不处理，交由 OpenFiles 处理 */ }
+      Action::ReloadSyntaxes => self.reload_syntaxes(),
+      Action::UndoFileOperation => self
+        .file_tree_state
+        .undo(self.open_dir.as_ref(), &mut self.open_files,),
+      Action::ToggleFilteredFiles => self.file_tree_state.toggle_show_filtered(),
+      Action::QuickOpen => self.quick_open(),
+      Action::CommandPalette => {
+        self.quick_open.close();
+        self.reload_tasks();
+        self.command_palette.open(self.task_panel.task_names(),);
+      }
+      Action::ResetSettings => {
+        // 只重置界面和布局，保留检查命令、语言服务器等配置
+        let settings = Settings {
+          ui: Default::default(),
+          layout: Default::default(),
+          ..self.settings()
+        };
+        self.apply_settings(&settings,);
+      }
     }
   }
 }
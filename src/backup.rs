@@ -0,0 +1,253 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::mpsc::{channel, Sender},
+  thread::JoinHandle,
+  time::Duration,
+};
+
+use eframe::egui;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, util::LineEnding};
+
+/// 备份有未保存修改的文件的间隔
+pub const BACKUP_INTERVAL: Duration = Duration::from_secs(5,);
+
+/// 备份目录，位于配置目录
+pub fn backup_dir() -> Option<PathBuf,> {
+  config::config_dir().map(|v| v.join("backups",),)
+}
+
+/// 未保存文件的备份，下次启动时恢复为未保存状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize,)]
+pub struct Backup {
+  pub path: PathBuf,
+  /// 编码名称，如 UTF-8
+  pub encoding: String,
+  pub line_ending: LineEnding,
+  /// 选区 (起点, 光标) 的 char 索引
+  pub cursor: Option<(usize, usize,),>,
+  pub content: String,
+}
+
+/// 文件对应的备份文件，文件名为路径的 hash
+fn backup_file(
+  dir: &Path,
+  path: &Path,
+) -> PathBuf {
+  // FNV-1a，保证不同版本间 hash 一致
+  let hash = path
+    .to_string_lossy()
+    .bytes()
+    .fold(0xcbf29ce484222325_u64, |hash, b| {
+      (hash ^ b as u64).wrapping_mul(0x100000001b3,)
+    },);
+  dir.join(format!("{hash:016x}.toml"),)
+}
+
+/// 写入备份，先写入临时文件再重命名，写入过程中崩溃不会破坏已有的备份
+pub fn save(
+  dir: &Path,
+  backup: &Backup,
+) -> Result<(), String,> {
+  let file = backup_file(dir, &backup.path,);
+  let tmp_file = file.with_extension("tmp",);
+  let text = toml::to_string(backup,)
+    .map_err(|e| format!("序列化备份 {} 失败: {e}", backup.path.display()),)?;
+  std::fs::create_dir_all(dir,)
+    .and_then(|_| std::fs::write(&tmp_file, text,),)
+    .and_then(|_| std::fs::rename(&tmp_file, &file,),)
+    .map_err(|e| format!("写入备份 {} 失败: {e}", file.display()),)
+}
+
+/// 备份线程的任务，按发送的顺序执行，删除备份后不会被之前的写入覆盖
+enum Job {
+  /// backup 的 content 在备份线程中由 content 生成，大文件也不阻塞 UI
+  Save {
+    dir: PathBuf,
+    backup: Backup,
+    content: Rope,
+  },
+  Remove {
+    dir: PathBuf,
+    path: PathBuf,
+  },
+}
+
+impl Job {
+  fn run(self,) {
+    let result = match self {
+      Job::Save {
+        dir,
+        mut backup,
+        content,
+      } => {
+        backup.content = content.to_string();
+        save(&dir, &backup,)
+      }
+      Job::Remove {
+        dir,
+        path,
+      } => remove(&dir, &path,),
+    };
+    if let Err(e,) = result {
+      tracing::error!("{e}");
+    }
+  }
+}
+
+/// 备份线程的任务 channel 和线程
+type Worker = (Sender<Job,>, JoinHandle<(),>,);
+
+/// 备份线程，flush 后为 None
+static WORKER: once_cell::sync::Lazy<egui::mutex::Mutex<Option<Worker,>,>,> =
+  once_cell::sync::Lazy::new(|| {
+    let (tx, rx,) = channel::<Job,>();
+    let handle = std::thread::spawn(move || {
+      for job in rx {
+        job.run();
+      }
+    },);
+    egui::mutex::Mutex::new(Some((tx, handle,),),)
+  },);
+
+/// 在备份线程中执行，flush 后在当前线程执行
+fn run_in_background(job: Job,) {
+  let tx = WORKER.lock().as_ref().map(|(tx, _,)| tx.clone(),);
+  match tx {
+    Some(tx,) => {
+      if let Err(e,) = tx.send(job,) {
+        e.0.run();
+      }
+    }
+    None => job.run(),
+  }
+}
+
+/// 在备份线程中写入备份，content 为文件内容的快照
+pub fn save_in_background(
+  dir: &Path,
+  backup: Backup,
+  content: Rope,
+) {
+  run_in_background(Job::Save {
+    dir: dir.to_owned(),
+    backup,
+    content,
+  },);
+}
+
+/// 在备份线程中删除文件的备份
+pub fn remove_in_background(
+  dir: &Path,
+  path: &Path,
+) {
+  run_in_background(Job::Remove {
+    dir: dir.to_owned(),
+    path: path.to_owned(),
+  },);
+}
+
+/// 等待备份线程完成所有任务，之后的备份都在当前线程中执行，用于退出前
+pub fn flush() {
+  let worker = WORKER.lock().take();
+  if let Some((tx, handle,),) = worker {
+    drop(tx,);
+    let _ = handle.join();
+  }
+}
+
+/// 删除文件的备份，备份不存在时忽略
+pub fn remove(
+  dir: &Path,
+  path: &Path,
+) -> Result<(), String,> {
+  let file = backup_file(dir, path,);
+  match std::fs::remove_file(&file,) {
+    Err(e,) if e.kind() != std::io::ErrorKind::NotFound => {
+      Err(format!("删除备份 {} 失败: {e}", file.display()),)
+    }
+    _ => Ok((),),
+  }
+}
+
+/// 读取目录中所有的备份，返回 (备份, 读取失败的错误信息)
+pub fn load_all(dir: &Path,) -> (Vec<Backup,>, Vec<String,>,) {
+  let mut backups = vec![];
+  let mut errors = vec![];
+  let Ok(read_dir,) = std::fs::read_dir(dir,) else {
+    return (backups, errors,);
+  };
+  let mut files = read_dir
+    .filter_map(|v| v.ok().map(|v| v.path(),),)
+    .filter(|v| v.extension().is_some_and(|v| v == "toml",),)
+    .collect::<Vec<_,>>();
+  files.sort();
+  for file in files {
+    let backup = std::fs::read_to_string(&file,)
+      .map_err(|e| e.to_string(),)
+      .and_then(|v| toml::from_str::<Backup,>(&v,).map_err(|e| e.to_string(),),);
+    match backup {
+      Ok(backup,) => backups.push(backup,),
+      Err(e,) => errors.push(format!("读取备份 {} 失败: {e}", file.display()),),
+    }
+  }
+  (backups, errors,)
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use super::{flush, load_all, remove, remove_in_background, save, save_in_background, Backup};
+  use crate::{test_util::TempDir, util::LineEnding};
+
+  #[test]
+  fn test_backup() {
+    let dir = TempDir::new("backup",);
+
+    let a = Backup {
+      path: PathBuf::from("/tmp/project/a.rs",),
+      encoding: "UTF-8".into(),
+      line_ending: LineEnding::Crlf,
+      cursor: Some((1, 3,),),
+      content: "fn main() {\r\n  \"引号\"\r\n}\r\n".into(),
+    };
+    let b = Backup {
+      path: PathBuf::from("/tmp/project/b.txt",),
+      encoding: "GBK".into(),
+      line_ending: LineEnding::Lf,
+      cursor: None,
+      content: "".into(),
+    };
+    save(&dir, &a,).unwrap();
+    save(&dir, &b,).unwrap();
+    // 覆盖同一文件的备份
+    let mut a2 = a.clone();
+    a2.content.push_str("// new\r\n",);
+    save(&dir, &a2,).unwrap();
+
+    let (mut backups, errors,) = load_all(&dir,);
+    backups.sort_by(|x, y| x.path.cmp(&y.path,),);
+    assert!(errors.is_empty());
+    assert_eq!(backups, vec![a2.clone(), b.clone()]);
+
+    // 损坏的备份不影响其他备份
+    std::fs::write(dir.join("broken.toml",), "path = ",).unwrap();
+    remove(&dir, &a2.path,).unwrap();
+    remove(&dir, &a2.path,).unwrap();
+    let (backups, errors,) = load_all(&dir,);
+    assert_eq!(backups, vec![b.clone()]);
+    assert_eq!(errors.len(), 1);
+
+    // 备份线程按顺序写入和删除，content 由快照生成
+    let content = a.content.clone();
+    a2.content.clear();
+    save_in_background(&dir, a2.clone(), content.as_str().into(),);
+    remove_in_background(&dir, &b.path,);
+    flush();
+    let (backups, _,) = load_all(&dir,);
+    assert_eq!(backups, vec![a]);
+  }
+}
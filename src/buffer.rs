@@ -0,0 +1,299 @@
+use std::{borrow::Cow, ops::Range};
+
+use eframe::egui;
+use once_cell::unsync::OnceCell;
+use ropey::Rope;
+
+/// 一次编辑影响的行：编辑前的 first_line..=old_last_line 行被替换为 first_line..=new_last_line 行
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct LineEdit {
+  pub first_line: usize,
+  pub old_last_line: usize,
+  pub new_last_line: usize,
+}
+
+/// 基于 Rope 的文本缓冲区
+///
+/// 行/偏移 的查找与编辑都是 O(log n)，
+/// egui::TextEdit 需要连续的 &str，因此 as_str 会按需拼接出完整文本并缓存到下一次编辑，
+/// 大文件的编辑器不调用 as_str，编辑时只修改 Rope
+#[derive(Debug, Clone, Default,)]
+pub struct Buffer {
+  rope: Rope,
+  flat: OnceCell<String,>,
+  /// 还未被取走的编辑影响的行，供增量高亮使用
+  line_edits: Vec<LineEdit,>,
+}
+
+impl From<String,> for Buffer {
+  fn from(text: String,) -> Self {
+    Self {
+      rope: Rope::from_str(&text,),
+      flat: OnceCell::new(),
+      line_edits: vec![],
+    }
+  }
+}
+
+impl From<&str,> for Buffer {
+  fn from(text: &str,) -> Self {
+    Self::from(text.to_string(),)
+  }
+}
+
+impl Buffer {
+  pub fn as_str(&self,) -> &str {
+    self.flat.get_or_init(|| self.rope.to_string(),)
+  }
+
+  pub fn len_bytes(&self,) -> usize {
+    self.rope.len_bytes()
+  }
+
+  pub fn len_chars(&self,) -> usize {
+    self.rope.len_chars()
+  }
+
+  /// 行数，以 '\n' 结尾时最后还有一个空行
+  pub fn len_lines(&self,) -> usize {
+    self.rope.len_lines()
+  }
+
+  /// 第 line_index 行（从 0 开始）的内容，包含换行符
+  pub fn line(
+    &self,
+    line_index: usize,
+  ) -> Cow<'_, str,> {
+    self.rope.line(line_index,).into()
+  }
+
+  /// 第 line_index 行不包含换行符的 char 数
+  pub fn line_len_chars(
+    &self,
+    line_index: usize,
+  ) -> usize {
+    let line = self.rope.line(line_index,);
+    let mut len = line.len_chars();
+    if len > 0 && line.char(len - 1,) == '\n' {
+      len -= 1;
+      if len > 0 && line.char(len - 1,) == '\r' {
+        len -= 1;
+      }
+    }
+    len
+  }
+
+  /// byte_range 中的内容
+  pub fn byte_slice(
+    &self,
+    byte_range: Range<usize,>,
+  ) -> Cow<'_, str,> {
+    self.rope.byte_slice(byte_range,).into()
+  }
+
+  pub fn char_to_byte(
+    &self,
+    char_index: usize,
+  ) -> usize {
+    self.rope.char_to_byte(char_index.min(self.len_chars(),),)
+  }
+
+  /// byte 索引不在 char 边界上时，返回其所在的 char
+  pub fn byte_to_char(
+    &self,
+    byte_index: usize,
+  ) -> usize {
+    self.rope.byte_to_char(byte_index.min(self.len_bytes(),),)
+  }
+
+  /// 把 byte 索引向前调整到 char 边界上
+  pub fn floor_char_boundary(
+    &self,
+    byte_index: usize,
+  ) -> usize {
+    self.char_to_byte(self.byte_to_char(byte_index,),)
+  }
+
+  pub fn char_to_line(
+    &self,
+    char_index: usize,
+  ) -> usize {
+    self.rope.char_to_line(char_index.min(self.len_chars(),),)
+  }
+
+  pub fn line_to_char(
+    &self,
+    line_index: usize,
+  ) -> usize {
+    self.rope.line_to_char(line_index.min(self.len_lines(),),)
+  }
+
+  pub fn line_to_byte(
+    &self,
+    line_index: usize,
+  ) -> usize {
+    self.rope.line_to_byte(line_index.min(self.len_lines(),),)
+  }
+
+  /// char 索引 => (行, 列)，均从 0 开始
+  pub fn char_to_line_column(
+    &self,
+    char_index: usize,
+  ) -> (usize, usize,) {
+    let line = self.char_to_line(char_index,);
+    (
+      line,
+      char_index.min(self.len_chars(),) - self.line_to_char(line,),
+    )
+  }
+
+  /// (行, 列) => char 索引，越界的行列截断到最后一行/行尾
+  pub fn line_column_to_char(
+    &self,
+    (line, column,): (usize, usize,),
+  ) -> usize {
+    let line = line.min(self.len_lines().saturating_sub(1,),);
+    self.line_to_char(line,) + column.min(self.line_len_chars(line,),)
+  }
+
+  /// 当前文本的快照，clone Rope 是 O(1) 的，可以发送到其他线程
+  pub fn snapshot(&self,) -> Rope {
+    self.rope.clone()
+  }
+
+  /// 取走上次调用以来所有编辑影响的行，按编辑的顺序排列
+  pub fn take_line_edits(&mut self,) -> Vec<LineEdit,> {
+    std::mem::take(&mut self.line_edits,)
+  }
+
+  /// 用 text 替换 byte_range 中的内容
+  pub fn replace_range(
+    &mut self,
+    byte_range: Range<usize,>,
+    text: &str,
+  ) {
+    let start = self.byte_to_char(byte_range.start,);
+    let end = self.byte_to_char(byte_range.end,);
+    self.replace_char_range(start..end, text,);
+  }
+
+  fn replace_char_range(
+    &mut self,
+    char_range: Range<usize,>,
+    text: &str,
+  ) {
+    if char_range.is_empty() && text.is_empty() {
+      return;
+    }
+    let first_line = self.rope.char_to_line(char_range.start,);
+    self.line_edits.push(LineEdit {
+      first_line,
+      old_last_line: self.rope.char_to_line(char_range.end,),
+      new_last_line: first_line + text.bytes().filter(|&b| b == b'\n',).count(),
+    },);
+    // 已拼接的缓存失效，下次调用 as_str 时重新拼接
+    self.flat.take();
+    if !char_range.is_empty() {
+      self.rope.remove(char_range.clone(),);
+    }
+    self.rope.insert(char_range.start, text,);
+  }
+}
+
+impl egui::TextBuffer for Buffer {
+  fn is_mutable(&self,) -> bool {
+    true
+  }
+
+  fn as_str(&self,) -> &str {
+    Buffer::as_str(self,)
+  }
+
+  fn byte_index_from_char_index(
+    &self,
+    char_index: usize,
+  ) -> usize {
+    self.char_to_byte(char_index,)
+  }
+
+  fn insert_text(
+    &mut self,
+    text: &str,
+    char_index: usize,
+  ) -> usize {
+    self.replace_char_range(char_index..char_index, text,);
+    text.chars().count()
+  }
+
+  fn delete_char_range(
+    &mut self,
+    char_range: Range<usize,>,
+  ) {
+    assert!(char_range.start <= char_range.end);
+    self.replace_char_range(char_range, "",);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use eframe::egui::TextBuffer;
+  use once_cell::unsync::OnceCell;
+  use ropey::Rope;
+
+  use super::{Buffer, LineEdit};
+
+  #[test]
+  fn test_buffer_edit() {
+    let mut buffer = Buffer::from("hello\n世界\n",);
+    buffer.insert_text("!", 5,);
+    buffer.delete_char_range(8..9,);
+    assert_eq!(buffer.as_str(), "hello!\n世\n");
+
+    // 编辑只修改 Rope，拼接的缓存失效
+    let mut buffer = Buffer {
+      rope: Rope::from_str(buffer.as_str(),),
+      flat: OnceCell::new(),
+      line_edits: vec![],
+    };
+    buffer.as_str();
+    buffer.replace_range(7..10, "W",);
+    assert!(buffer.flat.get().is_none());
+    assert_eq!(buffer.as_str(), "hello!\nW\n");
+    assert_eq!(buffer.len_bytes(), 9);
+
+    buffer.replace_range(3..8, "\n\n\n",);
+    assert_eq!(
+      buffer.take_line_edits(),
+      vec![
+        LineEdit {
+          first_line: 1,
+          old_last_line: 1,
+          new_last_line: 1,
+        },
+        LineEdit {
+          first_line: 0,
+          old_last_line: 1,
+          new_last_line: 3,
+        },
+      ]
+    );
+    assert!(buffer.take_line_edits().is_empty());
+  }
+
+  #[test]
+  fn test_buffer_lookup() {
+    let buffer = Buffer::from("ab\r\n中文\n\nx",);
+    assert_eq!(buffer.len_lines(), 4);
+    assert_eq!(buffer.line(1,), "中文\n");
+    assert_eq!(buffer.line_len_chars(0,), 2);
+    assert_eq!(buffer.line_len_chars(3,), 1);
+    assert_eq!(buffer.line_to_byte(2,), 11);
+    assert_eq!(buffer.char_to_line_column(5,), (1, 1));
+    assert_eq!(buffer.char_to_line_column(100,), (3, 1));
+    assert_eq!(buffer.line_column_to_char((1, 1,),), 5);
+    assert_eq!(buffer.line_column_to_char((0, 10,),), 2);
+    assert_eq!(buffer.line_column_to_char((100, 0,),), 8);
+    assert_eq!(buffer.floor_char_boundary(5,), 4);
+    assert_eq!(buffer.byte_to_char(7,), 5);
+  }
+}
@@ -0,0 +1,434 @@
+use std::{borrow::Cow, path::PathBuf, sync::mpsc::SyncSender};
+
+use eframe::egui;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::{
+  action::{self, Action, KeyActions},
+  hl,
+};
+
+use super::quick_open::match_layout_job;
+
+/// 需要输入参数的 action
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+enum ParamAction {
+  ZoomSet,
+  SetOpenDir,
+  SetLanguage,
+}
+
+impl ParamAction {
+  const ALL: [ParamAction; 3] = [
+    ParamAction::ZoomSet,
+    ParamAction::SetOpenDir,
+    ParamAction::SetLanguage,
+  ];
+
+  fn name(&self,) -> &'static str {
+    match self {
+      ParamAction::ZoomSet => Action::ZoomSet(1.,).name(),
+      ParamAction::SetOpenDir => Action::SetOpenDir(PathBuf::new(),).name(),
+      ParamAction::SetLanguage => Action::SetLanguage(None,).name(),
+    }
+  }
+
+  fn hint_text(&self,) -> &'static str {
+    match self {
+      ParamAction::ZoomSet => "缩放比例，如 1.5",
+      ParamAction::SetOpenDir => "文件夹路径",
+      ParamAction::SetLanguage => "语言名称，留空表示自动检测",
+    }
+  }
+
+  /// 解析用户输入的参数
+  fn parse(
+    &self,
+    param: &str,
+  ) -> Result<Action, String,> {
+    let param = param.trim();
+    match self {
+      ParamAction::ZoomSet => param
+        .parse::<f32>()
+        .ok()
+        .filter(|v| v.is_finite() && *v > 0.,)
+        .map(Action::ZoomSet,)
+        .ok_or(format!("无效的缩放比例: {param}"),),
+      ParamAction::SetOpenDir => {
+        let path = PathBuf::from(param,);
+        if path.is_dir() {
+          Ok(Action::SetOpenDir(path,),)
+        } else {
+          Err(format!("文件夹不存在: {param}"),)
+        }
+      }
+      ParamAction::SetLanguage => {
+        if param.is_empty() {
+          return Ok(Action::SetLanguage(None,),);
+        }
+        hl::language_names()
+          .into_iter()
+          .find(|v| v.eq_ignore_ascii_case(param,),)
+          .map(|v| Action::SetLanguage(Some(v,),),)
+          .ok_or(format!("未知的语言: {param}"),)
+      }
+    }
+  }
+}
+
+/// 命令面板中的一项
+#[derive(Debug, Clone, PartialEq,)]
+enum Command {
+  Action(&'static Action,),
+  Param(ParamAction,),
+  /// 运行任务
+  Task(String,),
+}
+
+impl Command {
+  fn name(&self,) -> Cow<'static, str,> {
+    match self {
+      Command::Action(v,) => v.name().into(),
+      Command::Param(v,) => v.name().into(),
+      Command::Task(v,) => format!("{}: {v}", Action::RunTask(String::new(),).name()).into(),
+    }
+  }
+}
+
+/// 匹配的命令
+#[derive(Debug, Clone, PartialEq,)]
+struct CommandMatch {
+  command: Command,
+  /// 匹配的字符位置（char index）
+  indices: Vec<usize,>,
+}
+
+/// 所有命令，包括 tasks 中的任务，按名称排序
+fn commands(tasks: &[String],) -> Vec<Command,> {
+  let mut commands = action::actions()
+    .into_iter()
+    .map(Command::Action,)
+    .chain(ParamAction::ALL.into_iter().map(Command::Param,),)
+    .chain(tasks.iter().cloned().map(Command::Task,),)
+    .collect::<Vec<_,>>();
+  commands.sort_by_key(|v| v.name(),);
+  commands
+}
+
+/// 模糊匹配命令名称，按匹配分数排序
+fn match_commands(
+  pattern: &str,
+  tasks: &[String],
+) -> Vec<CommandMatch,> {
+  let pattern = pattern.replace(' ', "",);
+  let matcher = SkimMatcherV2::default().smart_case();
+  let mut results = commands(tasks,)
+    .into_iter()
+    .filter_map(|command| {
+      let (score, indices,) = if pattern.is_empty() {
+        (0, vec![],)
+      } else {
+        matcher.fuzzy_indices(&command.name(), &pattern,)?
+      };
+      Some((
+        score,
+        CommandMatch {
+          command,
+          indices,
+        },
+      ),)
+    },)
+    .collect::<Vec<_,>>();
+  // 稳定排序，同分时保持名称顺序
+  results.sort_by_key(|v| std::cmp::Reverse(v.0,),);
+  results.into_iter().map(|v| v.1,).collect()
+}
+
+/// 命令面板（Ctrl+Shift+P）：列出所有 action 及其快捷键，需要参数的 action 在面板中输入参数
+pub struct CommandPalette {
+  tx: SyncSender<Action,>,
+  show: bool,
+  query: String,
+  request_focus: bool,
+  /// 打开的文件夹中定义的任务
+  tasks: Vec<String,>,
+  selected_index: usize,
+  results: Vec<CommandMatch,>,
+  /// 正在输入参数的 action
+  param: Option<ParamAction,>,
+  param_error: Option<String,>,
+}
+
+impl CommandPalette {
+  pub fn new(tx: SyncSender<Action,>,) -> Self {
+    Self {
+      tx,
+      show: false,
+      query: String::new(),
+      request_focus: false,
+      tasks: vec![],
+      selected_index: 0,
+      results: vec![],
+      param: None,
+      param_error: None,
+    }
+  }
+
+  /// 打开命令面板，tasks 为可以运行的任务名称
+  pub fn open(
+    &mut self,
+    tasks: Vec<String,>,
+  ) {
+    self.show = true;
+    self.query.clear();
+    self.request_focus = true;
+    self.selected_index = 0;
+    self.tasks = tasks;
+    self.results = match_commands("", &self.tasks,);
+    self.param = None;
+    self.param_error = None;
+  }
+
+  pub fn is_show(&self,) -> bool {
+    self.show
+  }
+
+  pub fn close(&mut self,) {
+    self.show = false;
+    self.results.clear();
+  }
+
+  /// 执行选中的命令，需要参数时切换到参数输入
+  fn confirm(&mut self,) {
+    if let Some(param,) = self.param {
+      match param.parse(&self.query,) {
+        Ok(action,) => {
+          let _ = self.tx.send(action,);
+          self.close();
+        }
+        Err(e,) => {
+          self.param_error = Some(e,);
+          self.request_focus = true;
+        }
+      }
+      return;
+    }
+    let Some(m,) = self.results.get(self.selected_index,) else {
+      self.request_focus = true;
+      return;
+    };
+    match &m.command {
+      Command::Action(action,) => {
+        let _ = self.tx.send((*action).clone(),);
+        self.close();
+      }
+      Command::Param(param,) => {
+        self.param = Some(*param,);
+        self.query.clear();
+        self.request_focus = true;
+      }
+      Command::Task(name,) => {
+        let _ = self.tx.send(Action::RunTask(name.clone(),),);
+        self.close();
+      }
+    }
+  }
+}
+
+// ------------------------------------ UI
+
+impl CommandPalette {
+  pub fn show(
+    &mut self,
+    ctx: &egui::Context,
+    key_actions: &KeyActions,
+  ) {
+    if !self.show {
+      return;
+    }
+
+    let response = egui::Window::new("command_palette",)
+      .title_bar(false,)
+      .collapsible(false,)
+      .resizable(false,)
+      .anchor(egui::Align2::CENTER_TOP, egui::vec2(0., 40.,),)
+      .fixed_size(egui::vec2(500., 0.,),)
+      .show(ctx, |ui| self.show_ui(ui, key_actions,),);
+
+    // 按 Esc 或点击外部时关闭
+    let clicked_outside = response.is_some_and(|r| {
+      ctx.input(|i| {
+        i.pointer.any_pressed()
+          && i
+            .pointer
+            .interact_pos()
+            .is_some_and(|pos| !r.response.rect.contains(pos,),)
+      },)
+    },);
+    if clicked_outside || ctx.input(|i| i.key_pressed(egui::Key::Escape,),) {
+      self.close();
+    }
+  }
+
+  fn show_ui(
+    &mut self,
+    ui: &mut egui::Ui,
+    key_actions: &KeyActions,
+  ) {
+    // 上下选择，需在 TextEdit 之前处理
+    let (up, down,) = ui.input_mut(|i| {
+      (
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp,),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown,),
+      )
+    },);
+    if up {
+      self.selected_index = self.selected_index.saturating_sub(1,);
+    }
+    if down && self.selected_index + 1 < self.results.len() {
+      self.selected_index += 1;
+    }
+
+    if let Some(param,) = self.param {
+      ui.label(param.name(),);
+    }
+    let hint_text = self.param.map_or("输入命令名称", |v| v.hint_text(),);
+    let query_edit = egui::TextEdit::singleline(&mut self.query,)
+      .hint_text(hint_text,)
+      .desired_width(f32::INFINITY,);
+    let response = ui.add(query_edit,);
+    if std::mem::take(&mut self.request_focus,) {
+      response.request_focus();
+    }
+    if response.changed() {
+      self.param_error = None;
+      if self.param.is_none() {
+        self.selected_index = 0;
+        self.results = match_commands(&self.query, &self.tasks,);
+      }
+    }
+    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter,),) {
+      self.confirm();
+      return;
+    }
+
+    // 输入参数
+    if self.param.is_some() {
+      if let Some(e,) = &self.param_error {
+        ui.colored_label(ui.visuals().error_fg_color, e,);
+      }
+      return;
+    }
+    if self.results.is_empty() {
+      ui.weak("没有匹配的命令",);
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+      .max_height(400.,)
+      .auto_shrink([false, true,],)
+      .show(ui, |ui| {
+        for (i, m,) in self.results.iter().enumerate() {
+          let selected = i == self.selected_index;
+          ui.horizontal(|ui| {
+            let job = match_layout_job(ui, &m.command.name(), &m.indices,);
+            let response = ui.add(egui::SelectableLabel::new(selected, job,),);
+            if selected && (up || down) {
+              response.scroll_to_me(None,);
+            }
+            if response.clicked() {
+              clicked = Some(i,);
+            }
+            // 快捷键
+            if let Command::Action(action,) = &m.command {
+              let keys = key_actions.get_action_keys(action,);
+              if let Some(key,) = keys.first() {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center,), |ui| {
+                  ui.weak(action::format_keys(&key.keys,),);
+                },);
+              }
+            }
+          },);
+        }
+      },);
+    if let Some(i,) = clicked {
+      self.selected_index = i;
+      self.confirm();
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::mpsc::sync_channel;
+
+  use super::{match_commands, CommandPalette, ParamAction};
+  use crate::action::Action;
+
+  #[test]
+  fn test_command_palette() {
+    let tasks = ["build".to_string(), "test".to_string(),];
+    let names = |pattern: &str| {
+      match_commands(pattern, &tasks,)
+        .into_iter()
+        .map(|v| v.command.name().into_owned(),)
+        .collect::<Vec<_,>>()
+    };
+    // 列出所有 action，包括需要参数的 action
+    let all = names("",);
+    assert!(all.contains(&"ToggleFullScreen".to_string()));
+    assert!(all.contains(&"ZoomSet".to_string()));
+    assert!(all.contains(&"QuickOpen".to_string()));
+    assert!(!all.contains(&"NoOp".to_string()));
+    assert_eq!(names("zoomin")[0], "ZoomIn");
+    assert_eq!(names("tgl full")[0], "ToggleFullScreen");
+    assert!(names("xyzxyz").is_empty());
+    // 任务
+    assert!(all.contains(&"RunTask: build".to_string()));
+    assert_eq!(names("runtask test")[0], "RunTask: test");
+
+    assert_eq!(
+      ParamAction::ZoomSet.parse(" 1.5 "),
+      Ok(Action::ZoomSet(1.5))
+    );
+    assert!(ParamAction::ZoomSet.parse("abc").is_err());
+    assert!(ParamAction::ZoomSet.parse("-1").is_err());
+    assert_eq!(
+      ParamAction::SetLanguage.parse(""),
+      Ok(Action::SetLanguage(None))
+    );
+    assert_eq!(
+      ParamAction::SetLanguage.parse("rust"),
+      Ok(Action::SetLanguage(Some("Rust".into())))
+    );
+    let dir = std::env::temp_dir();
+    assert_eq!(
+      ParamAction::SetOpenDir.parse(dir.to_str().unwrap()),
+      Ok(Action::SetOpenDir(dir.clone()))
+    );
+    assert!(ParamAction::SetOpenDir
+      .parse(dir.join("egui_code_not_exists").to_str().unwrap())
+      .is_err());
+
+    // 需要参数的 action 先切换到参数输入，再发送
+    let (tx, rx,) = sync_channel(10,);
+    let mut palette = CommandPalette::new(tx,);
+    palette.open(tasks.to_vec(),);
+    palette.query = "zoomset".into();
+    palette.results = match_commands(&palette.query, &palette.tasks,);
+    palette.confirm();
+    assert_eq!(palette.param, Some(ParamAction::ZoomSet));
+    assert!(rx.try_recv().is_err());
+    palette.query = "2".into();
+    palette.confirm();
+    assert_eq!(rx.try_recv(), Ok(Action::ZoomSet(2.)));
+    assert!(!palette.show);
+
+    // 选中任务时发送 RunTask
+    palette.open(tasks.to_vec(),);
+    palette.query = "build".into();
+    palette.results = match_commands(&palette.query, &palette.tasks,);
+    palette.confirm();
+    assert_eq!(rx.try_recv(), Ok(Action::RunTask("build".into())));
+  }
+}
@@ -0,0 +1,261 @@
+use std::{ops::Range, path::PathBuf};
+
+use eframe::egui;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::{buffer::Buffer, lsp::CompletionItem, text};
+
+use super::quick_open::match_layout_job;
+
+/// 弹窗中最多显示的行数
+const MAX_VISIBLE_ITEMS: usize = 10;
+
+/// 补全弹窗中的按键
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq,)]
+pub struct CompletionInput {
+  /// Enter / Tab
+  pub accept: bool,
+  /// Esc
+  pub close: bool,
+}
+
+/// 补全弹窗：输入时按光标前的单词过滤补全项
+pub struct CompletionPopup {
+  pub path: PathBuf,
+  /// 单词的起始位置（char 索引）
+  word_start: usize,
+  items: Vec<CompletionItem,>,
+  /// 匹配的项在 items 中的索引及匹配的字符位置
+  matches: Vec<(usize, Vec<usize,>,),>,
+  selected_index: usize,
+}
+
+/// 是否为单词中的字符
+fn is_word_char(c: char,) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+/// char_index 所在单词的起始位置，向前查找单词字符
+pub fn word_start(
+  content: &Buffer,
+  char_index: usize,
+) -> usize {
+  let char_index = char_index.min(content.len_chars(),);
+  let (line, column,) = content.char_to_line_column(char_index,);
+  let line_start = char_index - column;
+  let prefix = content
+    .line(line,)
+    .chars()
+    .take(column,)
+    .collect::<Vec<_,>>();
+  let len = prefix
+    .iter()
+    .rev()
+    .take_while(|c| is_word_char(**c,),)
+    .count();
+  line_start + column - len
+}
+
+impl CompletionPopup {
+  pub fn new(
+    path: PathBuf,
+    word_start: usize,
+    items: Vec<CompletionItem,>,
+  ) -> Self {
+    let matches = (0..items.len()).map(|i| (i, vec![],),).collect();
+    Self {
+      path,
+      word_start,
+      items,
+      matches,
+      selected_index: 0,
+    }
+  }
+
+  /// 处理上下选择，需在 TextEdit 之前调用
+  pub fn consume_keys(
+    &mut self,
+    ui: &mut egui::Ui,
+  ) -> CompletionInput {
+    let (up, down, enter, tab, escape,) = ui.input_mut(|i| {
+      (
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp,),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown,),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::Enter,),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::Tab,),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::Escape,),
+      )
+    },);
+    if up {
+      self.selected_index = self.selected_index.saturating_sub(1,);
+    }
+    if down && self.selected_index + 1 < self.matches.len() {
+      self.selected_index += 1;
+    }
+    CompletionInput {
+      accept: enter || tab,
+      close: escape,
+    }
+  }
+
+  /// 光标移动或输入后重新过滤，光标离开单词或没有匹配项时返回 false
+  pub fn update(
+    &mut self,
+    content: &Buffer,
+    cursor: usize,
+  ) -> bool {
+    if cursor < self.word_start || word_start(content, cursor,) != self.word_start {
+      return false;
+    }
+    let start = content.char_to_byte(self.word_start,);
+    let prefix = content.byte_slice(start..content.char_to_byte(cursor,),);
+    let matcher = SkimMatcherV2::default().smart_case();
+    let mut matches = self
+      .items
+      .iter()
+      .enumerate()
+      .filter_map(|(i, item,)| {
+        if prefix.is_empty() {
+          return Some((0, (i, vec![],),),);
+        }
+        let (score, indices,) = matcher.fuzzy_indices(&item.filter_text, &prefix,)?;
+        // filter_text 与 label 不同时不高亮
+        let indices = if item.filter_text == item.label {
+          indices
+        } else {
+          vec![]
+        };
+        Some((score, (i, indices,),),)
+      },)
+      .collect::<Vec<_,>>();
+    // 稳定排序，同分时保持语言服务器的顺序
+    matches.sort_by_key(|v| std::cmp::Reverse(v.0,),);
+    self.matches = matches.into_iter().map(|v| v.1,).collect();
+    self.selected_index = self
+      .selected_index
+      .min(self.matches.len().saturating_sub(1,),);
+    !self.matches.is_empty()
+  }
+
+  pub fn selected_item(&self,) -> Option<&CompletionItem,> {
+    let (i, _,) = self.matches.get(self.selected_index,)?;
+    self.items.get(*i,)
+  }
+
+  /// 在 pos 下方显示弹窗，返回点击的项
+  pub fn show(
+    &mut self,
+    ui: &egui::Ui,
+    pos: egui::Pos2,
+  ) -> Option<&CompletionItem,> {
+    let mut clicked = None;
+    egui::Area::new("completion_popup",)
+      .order(egui::Order::Foreground,)
+      .fixed_pos(pos,)
+      .show(ui.ctx(), |ui| {
+        egui::Frame::popup(ui.style(),).show(ui, |ui| {
+          let row_height = ui.text_style_height(&egui::TextStyle::Body,)
+            + ui.spacing().item_spacing.y
+            + ui.spacing().button_padding.y * 2.;
+          egui::ScrollArea::vertical()
+            .max_height(row_height * MAX_VISIBLE_ITEMS as f32,)
+            .show(ui, |ui| {
+              ui.set_min_width(240.,);
+              for (n, (i, indices,),) in self.matches.iter().enumerate() {
+                let item = &self.items[*i];
+                let selected = n == self.selected_index;
+                ui.horizontal(|ui| {
+                  let job = match_layout_job(ui, &item.label, indices,);
+                  let response = ui.add(egui::SelectableLabel::new(selected, job,),);
+                  if selected {
+                    response.scroll_to_me(None,);
+                  }
+                  if response.clicked() {
+                    clicked = Some(n,);
+                  }
+                  if let Some(detail,) = &item.detail {
+                    ui.label(
+                      egui::RichText::new(detail,)
+                        .font(text::search_result_font(),)
+                        .weak(),
+                    );
+                  }
+                },);
+              }
+            },);
+        },);
+      },);
+    self.selected_index = clicked?;
+    self.selected_item()
+  }
+
+  /// 补全项替换的范围（byte_range）和插入后光标的位置（char 索引）
+  pub fn edit(
+    &self,
+    item: &CompletionItem,
+    content: &Buffer,
+    cursor: usize,
+  ) -> (Range<usize,>, usize,) {
+    let range = match item.range {
+      // 语言服务器给出的范围不包括请求之后输入的内容
+      Some((start, end,),) => {
+        let start = content.line_column_to_char(start,);
+        start..content.line_column_to_char(end,).max(cursor,)
+      }
+      None => self.word_start..cursor,
+    };
+    let cursor = range.start + item.text.chars().count();
+    (
+      content.char_to_byte(range.start,)..content.char_to_byte(range.end,),
+      cursor,
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use super::{word_start, CompletionPopup};
+  use crate::{buffer::Buffer, lsp::CompletionItem};
+
+  #[test]
+  fn test_completion_popup() {
+    let item = |label: &str, text: &str, range| CompletionItem {
+      label: label.into(),
+      detail: None,
+      filter_text: label.into(),
+      text: text.into(),
+      range,
+    };
+    let content = Buffer::from("let 中 = v.pu\nfoo",);
+    let cursor = "let 中 = v.pu".chars().count();
+    assert_eq!(word_start(&content, cursor), cursor - 2);
+    assert_eq!(word_start(&content, 4), 4);
+
+    let mut popup = CompletionPopup::new(
+      PathBuf::new(),
+      word_start(&content, cursor - 2,),
+      vec![
+        item("len", "len()", None,),
+        item("push", "push()", None,),
+        item("pop", "pop()", Some(((0, 10,), (0, 11,),),),),
+      ],
+    );
+    // 按光标前的单词 pu 过滤
+    assert!(popup.update(&content, cursor));
+    assert_eq!(popup.selected_item().unwrap().label, "push");
+    assert_eq!(popup.matches.len(), 1);
+    let (range, new_cursor,) = popup.edit(popup.selected_item().unwrap(), &content, cursor,);
+    assert_eq!(&content.as_str()[range], "pu");
+    assert_eq!(new_cursor, cursor - 2 + "push()".len());
+
+    // 语言服务器给出的范围延伸到光标
+    let (range, _,) = popup.edit(&popup.items[2], &content, cursor,);
+    assert_eq!(&content.as_str()[range], "pu");
+
+    // 光标离开单词
+    assert!(!popup.update(&content, cursor - 3));
+    assert!(!popup.update(&content, content.len_chars()));
+  }
+}
@@ -2,7 +2,6 @@ use std::{
   borrow::{BorrowMut, Cow},
   cell::RefCell,
   collections::BTreeSet,
-  fs,
   path::{Path, PathBuf},
   rc::{Rc, Weak},
   time::Duration,
@@ -10,7 +9,11 @@ use std::{
 
 use eframe::egui;
 
-use crate::{text, ui, util};
+use crate::{
+  file_op::{self, FileChange, FileOp, FileOpHistory},
+  path_filter::PathFilter,
+  text, ui, util,
+};
 
 use super::open_file::OpenFiles;
 
@@ -21,10 +24,13 @@ pub enum Node {
     children: BTreeSet<Rc<RefCell<Node,>,>,>,
     parent: Option<Weak<RefCell<Node,>,>,>,
     expand: bool,
+    /// 被 .gitignore 等过滤规则排除
+    filtered: bool,
   },
   File {
     path: PathBuf,
     parent: Weak<RefCell<Node,>,>,
+    filtered: bool,
   },
 }
 
@@ -88,10 +94,12 @@ impl Node {
         children: _,
         parent: _,
         expand,
+        filtered: _,
       } => expand.to_owned(),
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => false,
     }
   }
@@ -105,10 +113,12 @@ impl Node {
         children: _,
         parent: _,
         expand,
+        filtered: _,
       } => *expand = to_expand,
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => {}
     }
   }
@@ -119,13 +129,38 @@ impl Node {
         children: _,
         expand: _,
         parent: _,
+        filtered: _,
       } => true,
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => false,
     }
   }
+  pub fn is_filtered(&self,) -> bool {
+    match self {
+      Node::Dir {
+        filtered, ..
+      }
+      | Node::File {
+        filtered, ..
+      } => *filtered,
+    }
+  }
+  fn set_filtered(
+    &mut self,
+    to_filtered: bool,
+  ) {
+    match self {
+      Node::Dir {
+        filtered, ..
+      }
+      | Node::File {
+        filtered, ..
+      } => *filtered = to_filtered,
+    }
+  }
 
   pub fn name(&self,) -> Cow<str,> {
     match self {
@@ -134,11 +169,13 @@ impl Node {
         children: _,
         expand: _,
         parent: _,
-      } => path.file_name().unwrap().to_string_lossy(),
+        filtered: _,
+      } => util::file_name(path,),
       Node::File {
         path,
         parent: _,
-      } => path.file_name().unwrap().to_string_lossy(),
+        filtered: _,
+      } => util::file_name(path,),
     }
   }
 
@@ -149,10 +186,12 @@ impl Node {
         children: _,
         parent: _,
         expand: _,
+        filtered: _,
       } => path,
       Node::File {
         path,
         parent: _,
+        filtered: _,
       } => path,
     }
   }
@@ -167,12 +206,14 @@ impl Node {
         children: _,
         parent,
         expand: _,
+        filtered: _,
       } => {
         let _ = parent.insert(node,);
       }
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => {}
     }
   }
@@ -187,6 +228,7 @@ impl Node {
         children,
         expand: _,
         parent: _,
+        filtered: _,
       } => {
         children.borrow_mut().insert(node,);
         true
@@ -194,6 +236,7 @@ impl Node {
       Node::File {
         path: _,
         parent: _dir,
+        filtered: _,
       } => false,
     }
   }
@@ -205,10 +248,12 @@ impl Node {
         children,
         parent: _,
         expand: _,
+        filtered: _,
       } => Some(children,),
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => None,
     }
   }
@@ -223,16 +268,38 @@ impl Node {
         children,
         expand: _,
         parent: _,
+        filtered: _,
       } => {
         *children = nodes;
       }
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => {}
     }
   }
 
+  pub fn remove_child(
+    &mut self,
+    node: &Rc<RefCell<Node,>,>,
+  ) -> bool {
+    match self {
+      Node::Dir {
+        path: _,
+        children,
+        expand: _,
+        parent: _,
+        filtered: _,
+      } => children.remove(node,),
+      Node::File {
+        path: _,
+        parent: _,
+        filtered: _,
+      } => false,
+    }
+  }
+
   pub fn need_load_children(&self,) -> bool {
     match self {
       Node::Dir {
@@ -240,55 +307,471 @@ impl Node {
         children,
         parent: _,
         expand: _,
+        filtered: _,
       } => children.is_empty(),
       Node::File {
         path: _,
         parent: _,
+        filtered: _,
       } => false,
     }
   }
 }
 
-pub fn load_children(parent: &Rc<RefCell<Node,>,>,) {
+/// 加载展开的文件夹的子节点，返回是否加载
+pub fn load_children(
+  parent: &Rc<RefCell<Node,>,>,
+  filter: &PathFilter,
+) -> bool {
   let parent_ref = parent.borrow();
   if !parent_ref.is_dir() || !parent_ref.is_expand() || !parent_ref.need_load_children() {
+    return false;
+  }
+  drop(parent_ref,);
+
+  match read_children(parent, filter,) {
+    Some(children,) => {
+      RefCell::borrow_mut(parent,).set_children(children,);
+      true
+    }
+    None => false,
+  }
+}
+
+/// 重新读取已加载的文件夹，仍然存在的子节点保持不变（包括展开状态和已加载的子节点），只更新是否被过滤
+pub fn reload_children(
+  parent: &Rc<RefCell<Node,>,>,
+  filter: &PathFilter,
+) {
+  if parent.borrow().need_load_children() {
     return;
   }
+  let Some(children,) = read_children(parent, filter,) else {
+    return;
+  };
+  let parent_ref = parent.borrow();
+  let children = children
+    .into_iter()
+    .map(|v| match parent_ref.children().unwrap().get(&v,) {
+      Some(old,) => {
+        let filtered = v.borrow().is_filtered();
+        RefCell::borrow_mut(old,).set_filtered(filtered,);
+        old.clone()
+      }
+      None => v,
+    },)
+    .collect::<Vec<_,>>();
+  drop(parent_ref,);
+  RefCell::borrow_mut(parent,).set_children(children.into_iter().collect(),);
+}
+
+/// 被过滤的文件夹中的所有 文件/文件夹 都被过滤
+fn read_children(
+  parent: &Rc<RefCell<Node,>,>,
+  filter: &PathFilter,
+) -> Option<BTreeSet<Rc<RefCell<Node,>,>,>,> {
+  let parent_ref = parent.borrow();
   let dir_path = parent_ref.path().to_owned();
+  let parent_filtered = parent_ref.is_filtered();
   drop(parent_ref,);
+  let entries = filter.read_dir(&dir_path,).ok()?;
+  let children = entries
+    .into_iter()
+    .map(|v| {
+      let filtered = parent_filtered || v.filtered;
+      let node = if v.is_dir {
+        Node::Dir {
+          path: v.path,
+          children: BTreeSet::new(),
+          parent: Some(Rc::downgrade(parent,),),
+          expand: false,
+          filtered,
+        }
+      } else {
+        Node::File {
+          path: v.path,
+          parent: Rc::downgrade(parent,),
+          filtered,
+        }
+      };
+      Rc::new(RefCell::new(node,),)
+    },)
+    .collect::<Vec<_,>>();
+  Some(children.into_iter().collect(),)
+}
+
+/// 磁盘上发生变化的路径所在的已加载文件夹重新读取
+pub fn on_disk_change(
+  root: &Rc<RefCell<Node,>,>,
+  paths: &BTreeSet<PathBuf,>,
+  filter: &PathFilter,
+) {
+  let dirs = paths
+    .iter()
+    .filter_map(|v| v.parent(),)
+    .collect::<BTreeSet<_,>>();
+  for dir in dirs {
+    if let Some(node,) = find_node(root, dir,) {
+      reload_children(&node, filter,);
+    }
+  }
+}
+
+/// 需要监视的文件夹：已加载或展开的、未被过滤的文件夹
+pub fn watched_dirs(root: &Rc<RefCell<Node,>,>,) -> BTreeSet<PathBuf,> {
+  let mut dirs = BTreeSet::new();
+  let mut stack = vec![root.clone()];
+  while let Some(node,) = stack.pop() {
+    let node = node.borrow();
+    if !node.is_dir() || node.is_filtered() || (node.need_load_children() && !node.is_expand()) {
+      continue;
+    }
+    dirs.insert(node.path().clone(),);
+    stack.extend(node.children().into_iter().flatten().cloned(),);
+  }
+  dirs
+}
+
+/// 查找 path 对应的节点，未加载的节点返回 None
+pub fn find_node(
+  root: &Rc<RefCell<Node,>,>,
+  path: &Path,
+) -> Option<Rc<RefCell<Node,>,>,> {
+  let mut current = root.borrow().path().to_owned();
+  let relative = path.strip_prefix(&current,).ok()?.to_owned();
+  let mut node = root.clone();
+  for component in relative.components() {
+    current.push(component,);
+    let child = node
+      .borrow()
+      .children()?
+      .iter()
+      .find(|v| v.borrow().path() == &current,)
+      .cloned()?;
+    node = child;
+  }
+  Some(node,)
+}
 
-  if let Ok(rd,) = fs::read_dir(dir_path,) {
-    let mut children: BTreeSet<Rc<RefCell<Node,>,>,> = BTreeSet::new();
-    rd.into_iter()
-      .filter_map(|v| v.ok(),)
-      .filter_map(|v| match v.file_type() {
-        Ok(ft,) => {
-          if ft.is_dir() {
-            let node = Node::Dir {
-              path: v.path(),
-              children: BTreeSet::new(),
-              parent: Some(Rc::downgrade(&parent.clone(),),),
-              expand: false,
-            };
-            Some(node,)
-          } else if ft.is_file() {
-            let node = Node::File {
-              path: v.path(),
-              parent: Rc::downgrade(&parent.clone(),),
-            };
-            Some(node,)
-          } else {
-            None
+/// 根据文件操作的影响更新已加载的节点，未加载的文件夹在展开时再读取
+pub fn update_tree(
+  root: &Rc<RefCell<Node,>,>,
+  change: &FileChange,
+  filter: &PathFilter,
+) {
+  match change {
+    FileChange::Created(path,) => insert_node(root, path, false, filter,),
+    FileChange::Removed(path,) => {
+      remove_node(root, path,);
+    }
+    FileChange::Renamed {
+      from,
+      to,
+    } => {
+      let expand = remove_node(root, from,).is_some_and(|v| v.borrow().is_expand(),);
+      insert_node(root, to, expand, filter,);
+    }
+  }
+}
+
+/// 重新读取所在的文件夹，以得到新节点是否被过滤
+fn insert_node(
+  root: &Rc<RefCell<Node,>,>,
+  path: &Path,
+  expand: bool,
+  filter: &PathFilter,
+) {
+  let Some(parent,) = path.parent().and_then(|v| find_node(root, v,),) else {
+    return;
+  };
+  reload_children(&parent, filter,);
+  if let Some(node,) = find_node(&parent, path,) {
+    RefCell::borrow_mut(&node,).expand(expand,);
+  }
+}
+
+fn remove_node(
+  root: &Rc<RefCell<Node,>,>,
+  path: &Path,
+) -> Option<Rc<RefCell<Node,>,>,> {
+  let parent = path.parent().and_then(|v| find_node(root, v,),)?;
+  let node = find_node(&parent, path,)?;
+  RefCell::borrow_mut(&parent,).remove_child(&node,);
+  Some(node,)
+}
+
+// ------------------------------------ State
+
+/// 待执行的文件操作，在整个文件树显示完后执行，此时没有节点被借用
+type PendingOp = Box<dyn FnOnce() -> Result<FileOp, String,>,>;
+
+/// 行内编辑的名称
+enum NameEditKind {
+  /// 在文件夹中新建文件
+  NewFile(PathBuf,),
+  /// 在文件夹中新建文件夹
+  NewFolder(PathBuf,),
+  Rename(PathBuf,),
+}
+
+struct NameEdit {
+  kind: NameEditKind,
+  name: String,
+  /// 第一帧需要获取焦点
+  request_focus: bool,
+}
+
+/// 文件树的交互状态：行内编辑、拖动、可撤销的文件操作，以及过滤规则
+#[derive(Default,)]
+pub struct FileTreeState {
+  name_edit: Option<NameEdit,>,
+  /// 正在拖动的文件或文件夹
+  dragging: Option<PathBuf,>,
+  pending: Option<PendingOp,>,
+  history: FileOpHistory,
+  filter: PathFilter,
+  /// 是否显示被过滤的 文件/文件夹（变暗显示）
+  show_filtered: bool,
+  /// 文件夹 展开/折叠、加载或文件操作后，需要重新计算监视的文件夹
+  tree_changed: bool,
+}
+
+impl FileTreeState {
+  /// 打开的文件夹变化时重新加载过滤规则
+  pub fn set_filter(
+    &mut self,
+    filter: PathFilter,
+  ) {
+    self.filter = filter;
+    self.tree_changed = true;
+  }
+
+  pub fn filter(&self,) -> &PathFilter {
+    &self.filter
+  }
+
+  pub fn show_filtered(&self,) -> bool {
+    self.show_filtered
+  }
+
+  /// 文件树在外部被修改，如磁盘上的变化
+  pub fn mark_tree_changed(&mut self,) {
+    self.tree_changed = true;
+  }
+
+  /// 取走上次调用以来文件树是否变化
+  pub fn take_tree_changed(&mut self,) -> bool {
+    std::mem::take(&mut self.tree_changed,)
+  }
+
+  pub fn toggle_show_filtered(&mut self,) {
+    self.show_filtered = !self.show_filtered;
+  }
+
+  /// 节点是否需要显示
+  fn is_visible(
+    &self,
+    node: &Rc<RefCell<Node,>,>,
+  ) -> bool {
+    self.show_filtered || !node.borrow().is_filtered()
+  }
+
+  fn start_name_edit(
+    &mut self,
+    kind: NameEditKind,
+  ) {
+    let name = match &kind {
+      NameEditKind::Rename(path,) => util::file_name(path,).into_owned(),
+      _ => String::new(),
+    };
+    self.name_edit = Some(NameEdit {
+      kind,
+      name,
+      request_focus: true,
+    },);
+  }
+
+  fn is_renaming(
+    &self,
+    path: &Path,
+  ) -> bool {
+    matches!(&self.name_edit, Some(NameEdit { kind: NameEditKind::Rename(v), .. }) if v == path)
+  }
+
+  /// 是否正在文件夹 dir 中新建文件或文件夹
+  fn is_creating_in(
+    &self,
+    dir: &Path,
+  ) -> bool {
+    matches!(
+      &self.name_edit,
+      Some(NameEdit { kind: NameEditKind::NewFile(v) | NameEditKind::NewFolder(v), .. }) if v == dir
+    )
+  }
+
+  /// 撤销最近一次文件操作
+  pub fn undo(
+    &mut self,
+    root: Option<&Rc<RefCell<Node,>,>,>,
+    open_files: &mut OpenFiles,
+  ) {
+    match self.history.undo() {
+      Some(Ok(change,),) => {
+        apply_change(root, open_files, &change, &self.filter,);
+        self.tree_changed = true;
+      }
+      Some(Err(e,),) => {
+        util::toaster()
+          .error(format!("撤销文件操作失败: {e}"),)
+          .set_duration(Some(Duration::from_secs(5,),),);
+      }
+      None => {
+        util::toaster()
+          .info("没有可撤销的文件操作",)
+          .set_duration(Some(Duration::from_secs(3,),),);
+      }
+    }
+  }
+
+  /// 执行本帧的文件操作，拖动结束时清空拖动状态
+  fn finish_frame(
+    &mut self,
+    ui: &egui::Ui,
+    root: &Rc<RefCell<Node,>,>,
+    open_files: &mut OpenFiles,
+  ) {
+    if let Some(path,) = &self.dragging {
+      egui::show_tooltip_text(
+        ui.ctx(),
+        egui::Id::new("file_tree_dragging",),
+        util::file_name(path,),
+      );
+      if ui.input(|i| i.pointer.any_released(),) {
+        self.dragging = None;
+      }
+    }
+
+    let Some(pending,) = self.pending.take() else {
+      return;
+    };
+    match pending() {
+      Ok(op,) => {
+        apply_change(Some(root,), open_files, &op.change(), &self.filter,);
+        self.tree_changed = true;
+        // 新建的文件直接打开
+        if let FileOp::Create(path,) = &op {
+          if path.is_file() {
+            let _ = open_files.open_file(path,);
           }
         }
-        Err(_,) => None,
-      },)
-      .for_each(|v| {
-        children.insert(Rc::new(RefCell::new(v,),),);
-      },);
+        self.history.record(op,);
+      }
+      Err(e,) => {
+        util::toaster()
+          .error(format!("文件操作失败: {e}"),)
+          .set_duration(Some(Duration::from_secs(5,),),);
+      }
+    }
+  }
+
+  /// 行内编辑名称，按 Enter 确认，按 Esc 或失去焦点时取消
+  fn name_edit_ui(
+    &mut self,
+    ui: &mut egui::Ui,
+  ) {
+    let Some(edit,) = self.name_edit.as_mut() else {
+      return;
+    };
+    let hint_text = match edit.kind {
+      NameEditKind::NewFile(_,) => "文件名",
+      NameEditKind::NewFolder(_,) => "文件夹名",
+      NameEditKind::Rename(_,) => "",
+    };
+    let response = ui.add(
+      egui::TextEdit::singleline(&mut edit.name,)
+        .hint_text(hint_text,)
+        .desired_width(f32::INFINITY,),
+    );
+    if std::mem::take(&mut edit.request_focus,) {
+      response.request_focus();
+    }
+    if !response.lost_focus() {
+      return;
+    }
+
+    let edit = self.name_edit.take().unwrap();
+    let name = edit.name.trim().to_string();
+    if name.is_empty() || !ui.input(|i| i.key_pressed(egui::Key::Enter,),) {
+      return;
+    }
+    let pending: PendingOp = match edit.kind {
+      NameEditKind::NewFile(dir,) => {
+        Box::new(move || file_op::create_file(&dir.join(name,),).map_err(|e| e.to_string(),),)
+      }
+      NameEditKind::NewFolder(dir,) => {
+        Box::new(move || file_op::create_dir(&dir.join(name,),).map_err(|e| e.to_string(),),)
+      }
+      NameEditKind::Rename(path,) => {
+        let to = path.with_file_name(name,);
+        if to == path {
+          return;
+        }
+        Box::new(move || file_op::rename(&path, &to,).map_err(|e| e.to_string(),),)
+      }
+    };
+    self.pending = Some(pending,);
+  }
 
-    RefCell::borrow_mut(parent,).set_children(children,);
+  /// 开始拖动文件或文件夹
+  fn drag_source(
+    &mut self,
+    response: &egui::Response,
+    path: &Path,
+  ) {
+    if response.drag_started() {
+      self.dragging = Some(path.to_owned(),);
+    }
   }
+
+  /// 拖动到文件夹上松开时，移动到该文件夹中
+  fn drop_target(
+    &mut self,
+    ui: &egui::Ui,
+    response: &egui::Response,
+    dir: &Path,
+  ) {
+    let Some(from,) = &self.dragging else {
+      return;
+    };
+    if from.parent() == Some(dir,)
+      || dir.starts_with(from,)
+      || !ui.rect_contains_pointer(response.rect,)
+    {
+      return;
+    }
+    ui.painter()
+      .rect_stroke(response.rect, 2.0, ui.visuals().selection.stroke,);
+    if ui.input(|i| i.pointer.any_released(),) {
+      let from = from.clone();
+      let to = dir.join(from.file_name().unwrap_or_default(),);
+      self.pending = Some(Box::new(move || {
+        file_op::rename(&from, &to,).map_err(|e| e.to_string(),)
+      },),);
+    }
+  }
+}
+
+/// 更新文件树和已打开的文件
+fn apply_change(
+  root: Option<&Rc<RefCell<Node,>,>,>,
+  open_files: &mut OpenFiles,
+  change: &FileChange,
+  filter: &PathFilter,
+) {
+  if let Some(root,) = root {
+    update_tree(root, change, filter,);
+  }
+  open_files.on_file_change(change,);
 }
 
 // ------------------------------------ UI
@@ -297,11 +780,18 @@ pub fn show_tree(
   root: &Rc<RefCell<Node,>,>,
   ui: &mut egui::Ui,
   open_files: &mut OpenFiles,
+  state: &mut FileTreeState,
   mut prefix: Vec<Rc<RefCell<Node,>,>,>,
 ) {
-  load_children(node,);
-
   let is_root = root == node;
+  // 不显示被过滤的 文件/文件夹
+  if !is_root && !state.is_visible(node,) {
+    return;
+  }
+  if load_children(node, &state.filter,) {
+    state.tree_changed = true;
+  }
+
   let root_path = {
     let root_ref = root.borrow();
     root_ref.path().to_owned()
@@ -312,12 +802,20 @@ pub fn show_tree(
   let path = node_ref.path().to_owned();
   let is_dir = node_ref.is_dir();
   let expand = node_ref.is_expand();
+  let filtered = node_ref.is_filtered();
+
+  // 重命名中
+  if !is_root && state.is_renaming(&path,) {
+    drop(node_ref,);
+    state.name_edit_ui(ui,);
+    return;
+  }
 
   // Dir
   if is_dir {
     // UI
     let response = if is_root {
-      let (state, header_res, body_res,) = ui::custom_collapsing(
+      let (collapsing_state, header_res, body_res,) = ui::custom_collapsing(
         ui,
         "file_tree",
         name,
@@ -326,8 +824,11 @@ pub fn show_tree(
           // TODO
         },
         |ui| {
+          if state.is_creating_in(&path,) {
+            state.name_edit_ui(ui,);
+          }
           node_ref.children().unwrap().iter().for_each(|v| {
-            show_tree(v, root, ui, open_files, vec![],);
+            show_tree(v, root, ui, open_files, state, vec![],);
           },);
         },
       );
@@ -335,17 +836,21 @@ pub fn show_tree(
         header_response: header_res.response,
         body_response: body_res.map(|v| v.response,),
         body_returned: None,
-        openness: state.openness(ui.ctx(),),
+        openness: collapsing_state.openness(ui.ctx(),),
       }
     }
     // not root dir
     else {
       let kids = node_ref.children().unwrap();
-      if kids.len() == 1 {
-        let v = kids.iter().next().unwrap();
+      let visible_kids = kids
+        .iter()
+        .filter(|v| state.is_visible(v,),)
+        .collect::<Vec<_,>>();
+      if visible_kids.len() == 1 && !state.is_creating_in(&path,) {
+        let v = visible_kids[0];
         if v.borrow().is_dir() {
           prefix.push(node.clone(),);
-          show_tree(v, root, ui, open_files, prefix,);
+          show_tree(v, root, ui, open_files, state, prefix,);
           return;
         }
       }
@@ -363,11 +868,20 @@ pub fn show_tree(
         }
         s
       };
+      let mut title = egui::RichText::new(title,);
+      if filtered {
+        title = title.color(ui.visuals().weak_text_color(),);
+      }
       egui::CollapsingHeader::new(title,)
         .default_open(expand,)
+        // 新建时展开文件夹
+        .open(state.is_creating_in(&path,).then_some(true,),)
         .show(ui, |ui| {
+          if state.is_creating_in(&path,) {
+            state.name_edit_ui(ui,);
+          }
           kids.iter().for_each(|v| {
-            show_tree(v, root, ui, open_files, vec![],);
+            show_tree(v, root, ui, open_files, state, vec![],);
           },);
         },)
     };
@@ -375,22 +889,44 @@ pub fn show_tree(
     drop(node_ref,);
 
     // 展开/折叠 action
-    RefCell::borrow_mut(node,).expand(response.fully_open(),);
+    if expand != response.fully_open() {
+      RefCell::borrow_mut(node,).expand(response.fully_open(),);
+      state.tree_changed = true;
+    }
 
     let mut header_response = response.header_response;
+    // 拖动
+    if !is_root {
+      header_response = header_response.interact(egui::Sense::drag(),);
+      state.drag_source(&header_response, &path,);
+    }
+    state.drop_target(ui, &header_response, &path,);
     // 鼠标悬停提示
     header_response = header_response.on_hover_text(path.to_string_lossy(),);
     // 右键菜单
     header_response.context_menu(|ui| {
-      context_menu_ui(ui, &path, &root_path,);
+      context_menu_ui(ui, &path, &root_path, is_dir, is_root, state,);
     },);
+
+    if is_root {
+      state.finish_frame(ui, root, open_files,);
+    }
   }
   // File
   else {
     // UI
     let selected = open_files.is_current_file(&path,);
-    let mut response = ui.selectable_label(selected, name,);
+    let mut name = egui::RichText::new(name,);
+    if filtered {
+      name = name.color(ui.visuals().weak_text_color(),);
+    }
+    let mut response = ui
+      .selectable_label(selected, name,)
+      .interact(egui::Sense::drag(),);
+    drop(node_ref,);
 
+    // 拖动
+    state.drag_source(&response, &path,);
     // 鼠标悬停提示
     response = response.on_hover_text(path.to_string_lossy(),);
     // 鼠标点击
@@ -403,7 +939,7 @@ pub fn show_tree(
     }
     // 右键菜单
     response.context_menu(|ui| {
-      context_menu_ui(ui, &path, &root_path,);
+      context_menu_ui(ui, &path, &root_path, is_dir, is_root, state,);
     },);
   }
 }
@@ -416,6 +952,11 @@ pub enum ContextMenu {
 
 #[derive(Debug, Clone, Copy,)]
 pub enum ContextMenuAction {
+  NewFile,
+  NewFolder,
+  Rename,
+  Duplicate,
+  Delete,
   CopyFullPath,
   CopyRelativePath,
   OpenInNative,
@@ -425,15 +966,54 @@ fn context_menu_ui(
   ui: &mut egui::Ui,
   path: &Path,
   root_path: &PathBuf,
+  is_dir: bool,
+  is_root: bool,
+  state: &mut FileTreeState,
 ) {
   ui.style_mut().wrap = Some(false,);
-  let menus = vec![
+  let mut menus = vec![];
+  if is_dir {
+    menus.extend([
+      ContextMenu::Item(ContextMenuAction::NewFile,),
+      ContextMenu::Item(ContextMenuAction::NewFolder,),
+      ContextMenu::Separator,
+    ],);
+  }
+  // 不能 重命名/删除 根文件夹
+  if !is_root {
+    menus.extend([
+      ContextMenu::Item(ContextMenuAction::Rename,),
+      ContextMenu::Item(ContextMenuAction::Duplicate,),
+      ContextMenu::Item(ContextMenuAction::Delete,),
+      ContextMenu::Separator,
+    ],);
+  }
+  menus.extend([
     ContextMenu::Item(ContextMenuAction::OpenInNative,),
     ContextMenu::Separator,
     ContextMenu::Item(ContextMenuAction::CopyRelativePath,),
     ContextMenu::Item(ContextMenuAction::CopyFullPath,),
-  ];
-  let handle_context_menu = |action, ui: &mut egui::Ui| match action {
+  ],);
+  let mut handle_context_menu = |action, ui: &mut egui::Ui| match action {
+    ContextMenuAction::NewFile => {
+      state.start_name_edit(NameEditKind::NewFile(path.to_owned(),),);
+    }
+    ContextMenuAction::NewFolder => {
+      state.start_name_edit(NameEditKind::NewFolder(path.to_owned(),),);
+    }
+    ContextMenuAction::Rename => {
+      state.start_name_edit(NameEditKind::Rename(path.to_owned(),),);
+    }
+    ContextMenuAction::Duplicate => {
+      let path = path.to_owned();
+      state.pending = Some(Box::new(move || {
+        file_op::duplicate(&path,).map_err(|e| e.to_string(),)
+      },),);
+    }
+    ContextMenuAction::Delete => {
+      let path = path.to_owned();
+      state.pending = Some(Box::new(move || file_op::delete(&path,),),);
+    }
     ContextMenuAction::CopyFullPath => {
       util::set_clipboard(ui.ctx(), path.to_string_lossy(),);
     }
@@ -466,9 +1046,14 @@ fn context_menu_ui(
 
 #[cfg(test)]
 mod test {
-  use std::{cell::RefCell, path::PathBuf, rc::Rc};
+  use std::{cell::RefCell, fs, path::PathBuf, rc::Rc};
 
-  use crate::component::file_tree::{load_children, Node};
+  use crate::{
+    component::file_tree::{find_node, load_children, reload_children, update_tree, Node},
+    file_op::FileChange,
+    path_filter::PathFilter,
+    test_util::TempDir,
+  };
 
   #[test]
   fn test_load_children() {
@@ -477,10 +1062,13 @@ mod test {
       children: Default::default(),
       parent: Default::default(),
       expand: true,
+      filtered: false,
     };
 
     let node = Rc::new(RefCell::new(node,),);
-    load_children(&node,);
+    assert!(load_children(&node, &PathFilter::default(),));
+    // 已加载的文件夹不再加载
+    assert!(!load_children(&node, &PathFilter::default(),));
 
     let node = RefCell::borrow_mut(&node,);
     println!("children count => {}", node.children().unwrap().len());
@@ -495,4 +1083,104 @@ mod test {
     );
     assert!(!node.children().unwrap().is_empty());
   }
+
+  #[test]
+  fn test_update_tree() {
+    let dir = TempDir::new("file_tree",);
+    fs::create_dir_all(dir.join("src",),).unwrap();
+    fs::write(dir.join("src/main.rs",), "",).unwrap();
+    fs::write(dir.join("src/mod.rs",), "",).unwrap();
+
+    let root = Rc::new(RefCell::new(Node::Dir {
+      path: dir.to_path_buf(),
+      children: Default::default(),
+      parent: None,
+      expand: true,
+      filtered: false,
+    },),);
+    let filter = PathFilter::new(&dir, [],).unwrap();
+    load_children(&root, &filter,);
+    let src = find_node(&root, &dir.join("src",),).unwrap();
+    RefCell::borrow_mut(&src,).expand(true,);
+    load_children(&src, &filter,);
+    assert!(find_node(&root, &dir.join("src/main.rs")).is_some());
+
+    fs::rename(dir.join("src/main.rs",), dir.join("lib.rs",),).unwrap();
+    update_tree(
+      &root,
+      &FileChange::Renamed {
+        from: dir.join("src/main.rs",),
+        to: dir.join("lib.rs",),
+      },
+      &filter,
+    );
+    assert!(find_node(&root, &dir.join("src/main.rs")).is_none());
+    let lib = find_node(&root, &dir.join("lib.rs",),).unwrap();
+    assert!(!lib.borrow().is_dir());
+
+    // 未加载的文件夹不更新，展开时再读取
+    fs::create_dir(dir.join("src/a",),).unwrap();
+    fs::write(dir.join("src/a/b.rs",), "",).unwrap();
+    update_tree(
+      &root,
+      &FileChange::Created(dir.join("src/a/b.rs",),),
+      &filter,
+    );
+    update_tree(&root, &FileChange::Created(dir.join("src/a",),), &filter,);
+    let a = find_node(&root, &dir.join("src/a",),).unwrap();
+    assert!(a.borrow().need_load_children());
+
+    update_tree(&root, &FileChange::Removed(dir.join("src",),), &filter,);
+    assert!(find_node(&root, &dir.join("src")).is_none());
+    assert_eq!(root.borrow().children().unwrap().len(), 1);
+  }
+
+  #[test]
+  fn test_reload_children() {
+    let dir = TempDir::new("reload_children",);
+    fs::create_dir_all(dir.join("src",),).unwrap();
+    fs::write(dir.join("a.rs",), "",).unwrap();
+    fs::write(dir.join("src/main.rs",), "",).unwrap();
+
+    let root = Rc::new(RefCell::new(Node::Dir {
+      path: dir.to_path_buf(),
+      children: Default::default(),
+      parent: None,
+      expand: true,
+      filtered: false,
+    },),);
+    let filter = PathFilter::new(&dir, [],).unwrap();
+    load_children(&root, &filter,);
+    let src = find_node(&root, &dir.join("src",),).unwrap();
+    RefCell::borrow_mut(&src,).expand(true,);
+    load_children(&src, &filter,);
+
+    fs::remove_file(dir.join("a.rs",),).unwrap();
+    fs::write(dir.join("b.rs",), "",).unwrap();
+    reload_children(&root, &filter,);
+    assert!(find_node(&root, &dir.join("a.rs")).is_none());
+    assert!(find_node(&root, &dir.join("b.rs")).is_some());
+    // 仍然存在的文件夹保留展开状态和已加载的子节点
+    let reloaded = find_node(&root, &dir.join("src",),).unwrap();
+    assert!(Rc::ptr_eq(&src, &reloaded));
+    assert!(find_node(&root, &dir.join("src/main.rs")).is_some());
+
+    // .gitignore 变化后更新是否被过滤，被过滤的文件夹中的所有项都被过滤
+    fs::create_dir_all(dir.join(".git",),).unwrap();
+    fs::create_dir_all(dir.join("target/debug",),).unwrap();
+    fs::write(dir.join(".gitignore",), "/src\n/target\n",).unwrap();
+    reload_children(&root, &filter,);
+    assert!(src.borrow().is_filtered());
+    assert!(!find_node(&root, &dir.join("b.rs"))
+      .unwrap()
+      .borrow()
+      .is_filtered());
+    let target = find_node(&root, &dir.join("target",),).unwrap();
+    RefCell::borrow_mut(&target,).expand(true,);
+    load_children(&target, &filter,);
+    assert!(find_node(&root, &dir.join("target/debug"))
+      .unwrap()
+      .borrow()
+      .is_filtered());
+  }
 }
@@ -0,0 +1,287 @@
+use std::ops::Range;
+
+use eframe::egui;
+
+use crate::{
+  buffer::Buffer,
+  search::{self, Matcher, SearchOptions},
+  style,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum FindBarEvent {
+  FindNext,
+  FindPrevious,
+  Replace,
+  ReplaceAll,
+  Close,
+}
+
+/// 编辑器上方的 查找/替换 栏
+pub struct FindBar {
+  show: bool,
+  show_replace: bool,
+  query: String,
+  replacement: String,
+  options: SearchOptions,
+  matcher: Option<Matcher,>,
+  error: Option<String,>,
+  matches: Vec<Range<usize,>,>,
+  current_index: usize,
+  dirty: bool,
+  request_focus: bool,
+  pending_event: Option<FindBarEvent,>,
+}
+
+impl Default for FindBar {
+  fn default() -> Self {
+    Self {
+      show: false,
+      show_replace: false,
+      query: String::new(),
+      replacement: String::new(),
+      options: SearchOptions::default(),
+      matcher: None,
+      error: None,
+      matches: vec![],
+      current_index: usize::MAX,
+      dirty: true,
+      request_focus: false,
+      pending_event: None,
+    }
+  }
+}
+
+impl FindBar {
+  pub fn is_show(&self,) -> bool {
+    self.show
+  }
+
+  /// 打开查找栏，selected 为编辑器中当前选中的文本
+  pub fn open(
+    &mut self,
+    show_replace: bool,
+    selected: Option<&str,>,
+  ) {
+    if let Some(s,) = selected.filter(|v| !v.is_empty() && !v.contains('\n',),) {
+      self.query = s.to_string();
+      self.dirty = true;
+    }
+    self.show = true;
+    self.show_replace = show_replace;
+    self.request_focus = true;
+  }
+
+  /// 通过快捷键等触发的事件，在下一次 show 时返回
+  pub fn send(
+    &mut self,
+    event: FindBarEvent,
+  ) {
+    self.show = true;
+    self.pending_event = Some(event,);
+  }
+
+  pub fn close(&mut self,) {
+    self.show = false;
+    self.matches.clear();
+    self.current_index = usize::MAX;
+    self.dirty = true;
+  }
+
+  /// 文本或查找条件发生变更，下一次 update_matches 时重新查找
+  pub fn invalidate(&mut self,) {
+    self.dirty = true;
+  }
+
+  pub fn matcher(&self,) -> Option<&Matcher,> {
+    self.matcher.as_ref()
+  }
+
+  pub fn replacement(&self,) -> &str {
+    &self.replacement
+  }
+
+  pub fn current_match(&self,) -> Option<Range<usize,>,> {
+    self.matches.get(self.current_index,).cloned()
+  }
+
+  /// 按需重新查找匹配项
+  /// 内容或查找条件变化后才拼接文本并重新查找
+  pub fn update_matches(
+    &mut self,
+    content: &Buffer,
+  ) {
+    if !self.dirty {
+      return;
+    }
+    puffin::profile_function!();
+    self.dirty = false;
+    let text = content.as_str();
+
+    match Matcher::new(&self.query, self.options,) {
+      Ok(matcher,) => {
+        self.matches = matcher.find_all(text,);
+        self.matcher = Some(matcher,);
+        self.error = None;
+      }
+      Err(e,) => {
+        self.matches.clear();
+        self.matcher = None;
+        // 查找内容为空不算错误
+        self.error = if self.query.is_empty() {
+          None
+        } else {
+          Some(e,)
+        };
+      }
+    }
+    if self.current_index >= self.matches.len() {
+      self.current_index = usize::MAX;
+    }
+  }
+
+  /// 从 byte 位置 from 开始选中 下一个/上一个 匹配项
+  pub fn select_next(
+    &mut self,
+    from: usize,
+    forward: bool,
+  ) -> Option<Range<usize,>,> {
+    self.current_index = search::next_match_index(&self.matches, from, forward,)?;
+    self.current_match()
+  }
+
+  /// 用于 LayoutJob 高亮的 (byte_range, 背景色) 列表
+  pub fn highlights(&self,) -> Vec<(Range<usize,>, egui::Color32,),> {
+    self
+      .matches
+      .iter()
+      .enumerate()
+      .map(|(i, r,)| {
+        let color = if i == self.current_index {
+          style::FIND_CURRENT_MATCH_COLOR
+        } else {
+          style::FIND_MATCH_COLOR
+        };
+        (r.clone(), color,)
+      },)
+      .collect()
+  }
+
+  fn counter_text(&self,) -> String {
+    if let Some(e,) = &self.error {
+      e.to_string()
+    } else if self.matches.is_empty() {
+      "无结果".into()
+    } else if self.current_index < self.matches.len() {
+      format!("{} of {}", self.current_index + 1, self.matches.len())
+    } else {
+      format!("? of {}", self.matches.len())
+    }
+  }
+}
+
+// ------------------------------------ UI
+
+impl FindBar {
+  pub fn show(
+    &mut self,
+    ui: &mut egui::Ui,
+  ) -> Option<FindBarEvent,> {
+    let mut event = self.pending_event.take();
+    let query_id = egui::Id::new("find_bar_query",);
+
+    ui.style_mut().wrap = Some(false,);
+    ui.vertical(|ui| {
+      let spacing_size = ui.spacing().item_spacing;
+      ui.add_space(spacing_size.y,);
+      // 查找
+      ui.horizontal(|ui| {
+        ui.add_space(spacing_size.x,);
+        let toggle_text = if self.show_replace { "▼" } else { "▶" };
+        if ui.small_button(toggle_text,).clicked() {
+          self.show_replace = !self.show_replace;
+        }
+
+        let query_edit = egui::TextEdit::singleline(&mut self.query,)
+          .id(query_id,)
+          .hint_text("查找",)
+          .desired_width(style::FIND_BAR_INPUT_WIDTH,);
+        let query_res = ui.add(query_edit,);
+        if self.request_focus {
+          self.request_focus = false;
+          query_res.request_focus();
+        }
+        if query_res.changed() {
+          self.dirty = true;
+        }
+        // Enter 下一个，Shift+Enter 上一个
+        if query_res.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter,),) {
+          event = if ui.input(|i| i.modifiers.shift,) {
+            Some(FindBarEvent::FindPrevious,)
+          } else {
+            Some(FindBarEvent::FindNext,)
+          };
+          query_res.request_focus();
+        }
+
+        let mut options = self.options;
+        ui.toggle_value(&mut options.case_sensitive, "Aa",)
+          .on_hover_text("区分大小写",);
+        ui.toggle_value(&mut options.whole_word, "W",)
+          .on_hover_text("全字匹配",);
+        ui.toggle_value(&mut options.regex, ".*",)
+          .on_hover_text("使用正则表达式",);
+        if options != self.options {
+          self.options = options;
+          self.dirty = true;
+        }
+
+        ui.monospace(self.counter_text(),);
+
+        if ui
+          .button("↑",)
+          .on_hover_text("上一个 (Shift+Enter)",)
+          .clicked()
+        {
+          event = Some(FindBarEvent::FindPrevious,);
+        }
+        if ui.button("↓",).on_hover_text("下一个 (Enter)",).clicked() {
+          event = Some(FindBarEvent::FindNext,);
+        }
+        if ui.button("×",).on_hover_text("关闭 (Escape)",).clicked() {
+          event = Some(FindBarEvent::Close,);
+        }
+      },);
+      // 替换
+      if self.show_replace {
+        ui.horizontal(|ui| {
+          ui.add_space(spacing_size.x,);
+          // 与上方 toggle 按钮对齐
+          ui.add_visible(false, egui::Button::new("▶",).small(),);
+          let replace_edit = egui::TextEdit::singleline(&mut self.replacement,)
+            .hint_text("替换",)
+            .desired_width(style::FIND_BAR_INPUT_WIDTH,);
+          let replace_res = ui.add(replace_edit,);
+          if replace_res.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter,),) {
+            event = Some(FindBarEvent::Replace,);
+            replace_res.request_focus();
+          }
+          if ui.button("替换",).clicked() {
+            event = Some(FindBarEvent::Replace,);
+          }
+          if ui.button("全部替换",).clicked() {
+            event = Some(FindBarEvent::ReplaceAll,);
+          }
+        },);
+      }
+      ui.add_space(spacing_size.y,);
+    },);
+
+    // Escape 关闭
+    if ui.input(|i| i.key_pressed(egui::Key::Escape,),) {
+      event = Some(FindBarEvent::Close,);
+    }
+
+    event
+  }
+}
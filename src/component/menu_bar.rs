@@ -13,6 +13,8 @@ pub enum MenuId {
   Edit,
   View,
   Appearance,
+  Terminal,
+  RunTask,
   About,
 }
 
@@ -20,6 +22,8 @@ pub enum MenuId {
 pub enum Menu {
   SubMenu(MenuId,),
   Item(Action,),
+  /// 打开的文件夹中定义的任务，每个任务一个菜单项
+  Tasks,
   Separator,
 }
 
@@ -34,6 +38,7 @@ impl MenuBar {
       Menu::SubMenu(MenuId::File,),
       vec![
         Tree::new_leaf(Menu::Item(Action::OpenFolder,),),
+        Tree::new_leaf(Menu::Item(Action::QuickOpen,),),
         Tree::new_leaf(Menu::Separator,),
         Tree::new_leaf(Menu::Item(Action::ExitApp,),),
       ],
@@ -41,26 +46,59 @@ impl MenuBar {
     let edit = Tree::new_branch(
       Menu::SubMenu(MenuId::Edit,),
       vec![
-      //   Tree::new_leaf(Menu::Item(Action::OpenFolder,),),
-      //   Tree::new_leaf(Menu::Item(Action::ExitApp,),),
+        Tree::new_leaf(Menu::Item(Action::Undo,),),
+        Tree::new_leaf(Menu::Item(Action::Redo,),),
+        Tree::new_leaf(Menu::Separator,),
+        Tree::new_leaf(Menu::Item(Action::Find,),),
+        Tree::new_leaf(Menu::Item(Action::Replace,),),
+        Tree::new_leaf(Menu::Separator,),
+        Tree::new_leaf(Menu::Item(Action::FindNext,),),
+        Tree::new_leaf(Menu::Item(Action::FindPrevious,),),
+        Tree::new_leaf(Menu::Separator,),
+        Tree::new_leaf(Menu::Item(Action::TriggerCompletion,),),
+        Tree::new_leaf(Menu::Item(Action::GotoDefinition,),),
+        Tree::new_leaf(Menu::Item(Action::FindReferences,),),
+        Tree::new_leaf(Menu::Separator,),
+        Tree::new_leaf(Menu::Item(Action::UndoFileOperation,),),
       ],
     );
 
     let view = Tree::new_branch(
       Menu::SubMenu(MenuId::View,),
-      vec![Tree::new_branch(
-        Menu::SubMenu(MenuId::Appearance,),
-        vec![
-          Tree::new_leaf(Menu::Item(Action::ToggleFullScreen,),),
-          Tree::new_leaf(Menu::Item(Action::ToggleStatusBar,),),
-          Tree::new_leaf(Menu::Item(Action::ToggleToolBar,),),
-          Tree::new_leaf(Menu::Item(Action::ToggleTerminal,),),
-          Tree::new_leaf(Menu::Separator,),
-          Tree::new_leaf(Menu::Item(Action::ZoomIn,),),
-          Tree::new_leaf(Menu::Item(Action::ZoomOut,),),
-          Tree::new_leaf(Menu::Item(Action::ZoomReset,),),
-        ],
-      )],
+      vec![
+        Tree::new_leaf(Menu::Item(Action::CommandPalette,),),
+        Tree::new_leaf(Menu::Separator,),
+        Tree::new_branch(
+          Menu::SubMenu(MenuId::Appearance,),
+          vec![
+            Tree::new_leaf(Menu::Item(Action::ToggleFullScreen,),),
+            Tree::new_leaf(Menu::Item(Action::ToggleStatusBar,),),
+            Tree::new_leaf(Menu::Item(Action::ToggleToolBar,),),
+            Tree::new_leaf(Menu::Item(Action::ToggleTerminal,),),
+            Tree::new_leaf(Menu::Separator,),
+            Tree::new_leaf(Menu::Item(Action::ZoomIn,),),
+            Tree::new_leaf(Menu::Item(Action::ZoomOut,),),
+            Tree::new_leaf(Menu::Item(Action::ZoomReset,),),
+          ],
+        ),
+        Tree::new_leaf(Menu::Item(Action::ReloadSyntaxes,),),
+        Tree::new_leaf(Menu::Item(Action::ToggleFilteredFiles,),),
+      ],
+    );
+
+    let terminal = Tree::new_branch(
+      Menu::SubMenu(MenuId::Terminal,),
+      vec![
+        Tree::new_leaf(Menu::Item(Action::NewTerminal,),),
+        Tree::new_leaf(Menu::Item(Action::CheckProblems,),),
+        Tree::new_leaf(Menu::Separator,),
+        Tree::new_branch(
+          Menu::SubMenu(MenuId::RunTask,),
+          vec![Tree::new_leaf(Menu::Tasks,)],
+        ),
+        Tree::new_leaf(Menu::Item(Action::RerunTask,),),
+        Tree::new_leaf(Menu::Item(Action::CancelTask,),),
+      ],
     );
 
     let about = Tree::new_branch(
@@ -75,7 +113,7 @@ impl MenuBar {
 
     Self {
       tx,
-      menus: vec![file, edit, view, about],
+      menus: vec![file, edit, view, terminal, about],
     }
   }
 }
@@ -89,11 +127,12 @@ impl MenuBar {
     &mut self,
     ui: &mut egui::Ui,
     key_actions: &KeyActions,
+    tasks: &[String],
   ) {
     egui::menu::bar(ui, |ui| {
       let menus = &self.menus;
       for m in menus.iter() {
-        self.show_menu(ui, m, key_actions,);
+        self.show_menu(ui, m, key_actions, tasks,);
       }
     },);
   }
@@ -103,6 +142,7 @@ impl MenuBar {
     ui: &mut egui::Ui,
     menu: &Tree<Menu,>,
     key_actions: &KeyActions,
+    tasks: &[String],
   ) {
     ui.style_mut().wrap = Some(false,);
     match &menu.value {
@@ -111,7 +151,7 @@ impl MenuBar {
         let text = text::menu_text(v,);
         ui.menu_button(text, |ui| {
           for m in menu.kids.iter() {
-            self.show_menu(ui, m, key_actions,);
+            self.show_menu(ui, m, key_actions, tasks,);
           }
         },);
       }
@@ -124,6 +164,18 @@ impl MenuBar {
           let _ = self.tx.send(v.clone(),);
         }
       }
+      // 任务
+      Menu::Tasks => {
+        if tasks.is_empty() {
+          ui.weak("没有定义任务",);
+        }
+        for name in tasks.iter() {
+          if ui.button(name,).clicked() {
+            ui.close_menu();
+            let _ = self.tx.send(Action::RunTask(name.clone(),),);
+          }
+        }
+      }
       // 分割符
       Menu::Separator => {
         ui.separator();
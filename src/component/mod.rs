@@ -1,6 +1,17 @@
+pub mod command_palette;
+pub mod completion;
 pub mod file_tree;
+pub mod find_bar;
 pub mod menu_bar;
 pub mod open_file;
+pub mod outline_panel;
+pub mod problems_panel;
+pub mod quick_open;
+pub mod references_panel;
+pub mod search_panel;
 pub mod status_bar;
+pub mod task_panel;
+pub mod terminal_panel;
 pub mod tool_bar;
 pub mod tree;
+pub mod virtual_editor;
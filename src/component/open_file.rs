@@ -1,49 +1,255 @@
-use std::{path::PathBuf, sync::mpsc::SyncSender, time::Duration, vec};
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  ops::Range,
+  path::{Path, PathBuf},
+  sync::mpsc::SyncSender,
+  time::{Duration, Instant, SystemTime},
+  vec,
+};
 
-use eframe::egui;
+use eframe::egui::{self, text::CCursor, text_edit::CCursorRange};
 use encoding_rs::Encoding;
+use ropey::Rope;
 
 use crate::{
-  action::{self, KeyActions},
-  hl, id, style, text, ui,
+  action::{self, Action, KeyActions},
+  backup::{self, Backup},
+  buffer::Buffer,
+  diagnostic::{self, Diagnostic, EditorMarks},
+  file_op::FileChange,
+  history::{Edit, History, RecordingBuffer},
+  hl, id,
+  lsp::{CompletionItem, DocumentState, LspRequest, RequestKind},
+  settings::{FileSession, Session},
+  style, text, ui,
   util::{self, LineEnding},
+  window::unsaved::{UnsavedDialog, UnsavedIntent},
+};
+
+use super::{
+  completion::{self, CompletionInput, CompletionPopup},
+  find_bar::{FindBar, FindBarEvent},
+  virtual_editor,
 };
 
+/// 超过该大小的文件使用只渲染可见行的编辑器
+const VIRTUAL_EDITOR_MIN_BYTES: usize = 512 * 1024;
+
+/// 鼠标停留多久后请求悬停提示
+const HOVER_DELAY: Duration = Duration::from_millis(500,);
+
+/// 编辑器及其语法高亮
+enum Editor {
+  /// egui::TextEdit，在 layouter 中增量高亮
+  TextEdit(hl::FileHighlighter,),
+  /// 只渲染可见行的编辑器，用于大文件，在后台线程中高亮
+  Virtual(hl::BackgroundHighlighter,),
+}
+
+/// 文件在磁盘上发生的、与编辑器中内容冲突的变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum DiskChange {
+  Modified,
+  Removed,
+}
+
 pub struct OpenFile {
-  content: String,
+  content: Buffer,
   path: PathBuf,
-  changed: bool,
-  cursor_range: Option<egui::widgets::text_edit::CursorRange,>,
+  history: History,
+  cursor_range: Option<CCursorRange,>,
   encoding: &'static Encoding,
   line_ending: LineEnding,
+  editor: Editor,
+  // 语言（syntect syntax 的名称），可在状态栏中手动指定
+  language: String,
+  // 语言是否为自动检测的，重新加载语法后需要重新检测
+  language_detected: bool,
+  // 下一帧需要选中并滚动到的 byte_range
+  pending_selection: Option<Range<usize,>,>,
+  // 最近一次 读取/保存 时文件的修改时间，用于忽略自己保存产生的变化
+  disk_modified: Option<SystemTime,>,
+  // 磁盘上的变化与未保存的修改冲突，显示提示
+  disk_change: Option<DiskChange,>,
+  // 已备份的版本
+  backup_revision: Option<u64,>,
+  // 保存到磁盘的次数，用于通知语言服务器
+  save_count: u64,
 }
 
 impl OpenFile {
-  fn new(path: &PathBuf,) -> Result<Self, std::io::Error,> {
+  fn new(path: &Path,) -> Result<Self, std::io::Error,> {
     // 读取并解码文件
-    let content_bytes = std::fs::read(path,)?;
-    let encoding = util::guess_encoding(&content_bytes,);
-    let content_str = encoding
-      .decode_without_bom_handling_and_without_replacement(&content_bytes,)
-      .ok_or(std::io::Error::other(format!(
-        "读取文件失败：{} 解码失败",
-        encoding.name()
-      ),),)?;
+    let (content_str, encoding,) = util::read_text_file(path,)?;
+    Ok(Self::with_content(path, content_str, encoding,),)
+  }
 
+  fn with_content(
+    path: &Path,
+    content_str: String,
+    encoding: &'static Encoding,
+  ) -> Self {
     // guess line_ending
     let line_ending = util::guess_line_ending(&content_str,);
 
-    let f = Self {
-      content: content_str.into(),
+    Self {
+      editor: if content_str.len() >= VIRTUAL_EDITOR_MIN_BYTES {
+        Editor::Virtual(hl::BackgroundHighlighter::default(),)
+      } else {
+        Editor::TextEdit(hl::FileHighlighter::default(),)
+      },
+      language: hl::detect_language(path, content_str.lines().next().unwrap_or_default(),),
+      language_detected: true,
+      content: Buffer::from(content_str,),
       path: path.to_owned(),
-      changed: false,
+      history: History::default(),
       cursor_range: None,
       encoding,
       line_ending,
+      pending_selection: None,
+      disk_modified: modified_time(path,),
+      disk_change: None,
+      backup_revision: None,
+      save_count: 0,
+    }
+  }
+
+  /// 从备份恢复为未保存状态，文件已在磁盘上被删除时从空内容恢复
+  fn from_backup(backup: &Backup,) -> Result<Self, std::io::Error,> {
+    let mut f = match Self::new(&backup.path,) {
+      Ok(f,) => f,
+      Err(e,) if e.kind() == std::io::ErrorKind::NotFound => {
+        let mut f = Self::with_content(&backup.path, String::new(), encoding_rs::UTF_8,);
+        f.disk_change = Some(DiskChange::Removed,);
+        f
+      }
+      Err(e,) => return Err(e,),
     };
+    f.restore_backup(backup,);
     Ok(f,)
   }
 
+  /// 用备份替换内容，替换也可以撤销
+  fn restore_backup(
+    &mut self,
+    backup: &Backup,
+  ) {
+    self.set_content(&backup.content,);
+    if let Some(encoding,) = Encoding::for_label(backup.encoding.as_bytes(),) {
+      self.encoding = encoding;
+    }
+    self.line_ending = backup.line_ending;
+    if let Some(cursor,) = backup.cursor {
+      self.restore_cursor(cursor,);
+    }
+    self.backup_revision = Some(self.history.revision(),);
+  }
+
+  /// 备份和文本的快照，备份的 content 由备份线程生成
+  fn backup(&self,) -> (Backup, Rope,) {
+    let backup = Backup {
+      path: self.path.clone(),
+      encoding: self.encoding.name().to_string(),
+      line_ending: self.line_ending,
+      cursor: self.session().cursor,
+      content: String::new(),
+    };
+    (backup, self.content.snapshot(),)
+  }
+
+  /// 是否需要更新备份
+  fn need_backup(&self,) -> bool {
+    if self.changed() {
+      self.backup_revision != Some(self.history.revision(),)
+    } else {
+      self.backup_revision.is_some()
+    }
+  }
+
+  /// 备份未保存的修改，没有未保存的修改时删除备份
+  fn sync_backup(
+    &mut self,
+    dir: &Path,
+  ) {
+    if !self.need_backup() {
+      return;
+    }
+    if self.changed() {
+      let (backup, content,) = self.backup();
+      backup::save_in_background(dir, backup, content,);
+      self.backup_revision = Some(self.history.revision(),);
+    } else {
+      self.remove_backup();
+    }
+  }
+
+  /// 保存或丢弃修改后删除备份
+  fn remove_backup(&mut self,) {
+    self.backup_revision = None;
+    if let Some(dir,) = backup::backup_dir() {
+      backup::remove_in_background(&dir, &self.path,);
+    }
+  }
+
+  /// 从磁盘重新读取文件，重新读取也可以撤销
+  pub fn reload(&mut self,) -> Result<(), std::io::Error,> {
+    let f = Self::new(&self.path,)?;
+    self.set_content(f.content.as_str(),);
+    self.encoding = f.encoding;
+    self.line_ending = f.line_ending;
+    self.disk_modified = f.disk_modified;
+    self.disk_change = None;
+    self.history.mark_saved();
+    self.remove_backup();
+    Ok((),)
+  }
+
+  /// 用 text 替换 byte_range 中的内容，并记录到编辑历史
+  fn edit(
+    &mut self,
+    range: Range<usize,>,
+    text: &str,
+  ) {
+    let edit = Edit {
+      index: range.start,
+      deleted: self.content.byte_slice(range,).into_owned(),
+      inserted: text.to_string(),
+    };
+    edit.apply(&mut self.content,);
+    self.history.record(vec![edit],);
+  }
+
+  /// 替换全部内容，只记录有变化的部分
+  fn set_content(
+    &mut self,
+    content: &str,
+  ) {
+    if let Some(edit,) = Edit::diff(self.content.as_str(), content,) {
+      edit.apply(&mut self.content,);
+      self.history.record(vec![edit],);
+    }
+  }
+
+  fn undo(&mut self,) {
+    if let Some(selection,) = self.history.undo(&mut self.content,) {
+      self.pending_selection = Some(selection,);
+    }
+  }
+
+  fn redo(&mut self,) {
+    if let Some(selection,) = self.history.redo(&mut self.content,) {
+      self.pending_selection = Some(selection,);
+    }
+  }
+
+  /// 第 line_number 行（从 1 开始）起始位置的 byte 索引
+  fn line_start_index(
+    &self,
+    line_number: usize,
+  ) -> usize {
+    self.content.line_to_byte(line_number.saturating_sub(1,),)
+  }
+
   pub fn id(&self,) -> egui::Id {
     egui::Id::new(format!("text_editor_{}", self.path.to_string_lossy()),)
   }
@@ -52,8 +258,19 @@ impl OpenFile {
     &self.path
   }
 
-  pub fn extension(&self,) -> std::borrow::Cow<str,> {
-    self.path.extension().unwrap().to_string_lossy()
+  /// 语言，即 syntect syntax 的名称
+  pub fn language(&self,) -> &str {
+    &self.language
+  }
+
+  /// 手动指定语言，None 表示重新自动检测
+  fn set_language(
+    &mut self,
+    language: Option<String,>,
+  ) {
+    self.language_detected = language.is_none();
+    self.language =
+      language.unwrap_or_else(|| hl::detect_language(&self.path, &self.content.line(0,),),);
   }
 
   pub fn encoding(&self,) -> &'static Encoding {
@@ -65,28 +282,98 @@ impl OpenFile {
   }
 
   pub fn changed(&self,) -> bool {
-    self.changed
+    !self.history.is_clean()
+  }
+
+  /// 编辑历史的版本，内容变化时改变
+  pub fn revision(&self,) -> u64 {
+    self.history.revision()
+  }
+
+  /// 当前内容的快照
+  pub fn snapshot(&self,) -> Rope {
+    self.content.snapshot()
+  }
+
+  /// cursor 所在的 (行, 列)，从 0 开始，列为 char 索引
+  pub fn cursor_position(&self,) -> Option<(usize, usize,),> {
+    self
+      .cursor_range
+      .map(|v| self.content.char_to_line_column(v.primary.index,),)
+  }
+
+  pub fn save(&mut self,) -> std::io::Result<(),> {
+    let (content_bytes, _, _,) = self.encoding.encode(self.content.as_str(),);
+    std::fs::write(&self.path, content_bytes,)?;
+    self.disk_modified = modified_time(&self.path,);
+    self.disk_change = None;
+    self.history.mark_saved();
+    self.save_count += 1;
+    self.remove_backup();
+    Ok((),)
   }
 
-  fn mark_changed(&mut self,) {
-    self.changed = true;
+  /// 当前选中文本的 char_range
+  fn selected_char_range(&self,) -> Option<Range<usize,>,> {
+    self.cursor_range.map(|v| {
+      let [start, end] = v.sorted();
+      start.index..end.index
+    },)
   }
 
-  pub fn save(&self,) -> std::io::Result<(),> {
-    let (content_bytes, _, _,) = self.encoding.encode(&self.content,);
-    std::fs::write(&self.path, content_bytes,)
+  /// 当前选中文本的 byte_range
+  fn selected_byte_range(&self,) -> Option<Range<usize,>,> {
+    self
+      .selected_char_range()
+      .map(|v| self.content.char_to_byte(v.start,)..self.content.char_to_byte(v.end,),)
+  }
+
+  fn selected_text(&self,) -> Option<&str,> {
+    self
+      .selected_byte_range()
+      .map(|v| &self.content.as_str()[v],)
+  }
+
+  /// 对 char_index 处的请求
+  fn lsp_request(
+    &self,
+    kind: RequestKind,
+    char_index: usize,
+  ) -> LspRequest {
+    LspRequest {
+      kind,
+      path: self.path.clone(),
+      position: self.content.char_to_line_column(char_index,),
+    }
+  }
+
+  /// 保存到会话中的状态
+  fn session(&self,) -> FileSession {
+    FileSession {
+      path: self.path.clone(),
+      cursor: self
+        .cursor_range
+        .map(|v| (v.secondary.index, v.primary.index,),),
+    }
   }
 
+  /// 恢复会话中的选区，文件内容可能已变化，索引超出范围时移到末尾
+  fn restore_cursor(
+    &mut self,
+    (from, to,): (usize, usize,),
+  ) {
+    self.pending_selection =
+      Some(self.content.char_to_byte(from,)..self.content.char_to_byte(to,),);
+  }
+
+  /// cursor 所在的 (行, 列, 选中字符数)，行列从 1 开始
   pub fn cursor_stat(&self,) -> Option<(usize, usize, usize,),> {
     match self.cursor_range {
       Some(range,) => {
-        let to = range.primary;
-        let from = range.secondary;
-        Some((
-          to.rcursor.row + 1,
-          to.rcursor.column + 1,
-          to.ccursor.index.abs_diff(from.ccursor.index,),
-        ),)
+        let to = range.primary.index;
+        let from = range.secondary.index;
+        let (line, column,) = self.content.char_to_line_column(to,);
+        Some((line + 1, column + 1, to.abs_diff(from,),),)
       }
       None => None,
     }
@@ -125,6 +412,36 @@ pub struct OpenFiles {
   files: Vec<OpenFile,>,
   current_index: usize,
   current_index_changed: bool,
+  find_bar: FindBar,
+  // 关闭文件或退出应用时，处理未保存的修改
+  unsaved_dialog: Option<UnsavedDialog,>,
+  // 编译器诊断，在编辑器中显示
+  diagnostics: BTreeMap<PathBuf, Vec<Diagnostic,>,>,
+  // 等待发送到语言服务器的请求
+  lsp_requests: Vec<LspRequest,>,
+  completion: Option<CompletionPopup,>,
+  hover: Option<HoverState,>,
+}
+
+/// 鼠标悬停的位置及语言服务器返回的提示
+struct HoverState {
+  path: PathBuf,
+  /// char 索引
+  index: usize,
+  since: Instant,
+  requested: bool,
+  text: Option<String,>,
+}
+
+/// TextEdit 显示后需要的信息
+struct EditorFeedback {
+  /// cursor 在屏幕上的位置
+  cursor_rect: Option<egui::Rect,>,
+  /// 鼠标悬停的字符（char 索引）
+  hovered_index: Option<usize,>,
+  changed: bool,
+  /// Ctrl+点击
+  command_clicked: bool,
 }
 
 impl OpenFiles {
@@ -134,6 +451,12 @@ impl OpenFiles {
       files: vec![],
       current_index: usize::MAX,
       current_index_changed: false,
+      find_bar: FindBar::default(),
+      unsaved_dialog: None,
+      diagnostics: BTreeMap::new(),
+      lsp_requests: vec![],
+      completion: None,
+      hover: None,
     }
   }
   fn set_current_index(
@@ -142,6 +465,163 @@ impl OpenFiles {
   ) {
     self.current_index = index;
     self.current_index_changed = true;
+    self.find_bar.invalidate();
+  }
+
+  /// 当前打开的文件及其选区
+  pub fn session(&self,) -> (Vec<FileSession,>, usize,) {
+    (
+      self.files.iter().map(OpenFile::session,).collect(),
+      self.current_index,
+    )
+  }
+
+  /// 重新打开会话中的文件，返回打开失败的错误信息
+  pub fn restore_session(
+    &mut self,
+    session: &Session,
+  ) -> Vec<String,> {
+    let mut errors = vec![];
+    let mut current_index = usize::MAX;
+    for (i, file_session,) in session.open_files.iter().enumerate() {
+      // 已被删除的文件不再打开
+      if !file_session.path.is_file() {
+        continue;
+      }
+      if let Err(e,) = self.open_file(&file_session.path,) {
+        errors.push(format!("打开 {} 失败: {e}", file_session.path.display()),);
+        continue;
+      }
+      let f = &mut self.files[self.current_index];
+      if let Some(cursor,) = file_session.cursor {
+        f.restore_cursor(cursor,);
+      }
+      if i == session.current_index {
+        current_index = self.current_index;
+      }
+    }
+    if current_index < self.files.len() {
+      self.set_current_index(current_index,);
+    }
+    errors
+  }
+
+  pub fn is_unsaved_dialog_open(&self,) -> bool {
+    self.unsaved_dialog.is_some()
+  }
+
+  /// 退出应用前处理未保存的修改，有未保存的文件时弹出对话框并返回 true
+  pub fn prompt_unsaved_on_exit(&mut self,) -> bool {
+    let unsaved_files = self
+      .files
+      .iter()
+      .filter(|f| f.changed(),)
+      .map(|f| f.path.clone(),)
+      .collect::<Vec<_,>>();
+    if unsaved_files.is_empty() {
+      return false;
+    }
+    self.unsaved_dialog = Some(UnsavedDialog::new(UnsavedIntent::Exit, unsaved_files,),);
+    true
+  }
+
+  /// 保存 save_paths 中的文件，丢弃对话框中其余文件的修改，然后继续关闭/退出
+  fn resolve_unsaved(
+    &mut self,
+    save_paths: &[PathBuf],
+  ) {
+    let Some(mut dialog,) = self.unsaved_dialog.take() else {
+      return;
+    };
+    // 保存失败时保留对话框，列出仍未保存的文件
+    let mut save_failed = false;
+    for f in self
+      .files
+      .iter_mut()
+      .filter(|f| save_paths.contains(&f.path,),)
+    {
+      if let Err(e,) = f.save() {
+        save_failed = true;
+        util::toaster()
+          .error(format!("保存文件失败：{:?}\nErr: {e}", f.path),)
+          .set_duration(Some(Duration::from_secs(5,),),);
+      }
+    }
+    if save_failed {
+      dialog
+        .files
+        .retain(|(path, _,)| self.files.iter().any(|f| &f.path == path && f.changed(),),);
+      self.unsaved_dialog = Some(dialog,);
+      return;
+    }
+
+    // 丢弃修改的文件直接关闭，退出时保留已保存的文件
+    let mut index_list = vec![];
+    for (i, f,) in self.files.iter_mut().enumerate() {
+      if !dialog.contains(&f.path,) {
+        continue;
+      }
+      if f.changed() {
+        f.remove_backup();
+        index_list.push(i,);
+      } else if dialog.intent == UnsavedIntent::Close {
+        index_list.push(i,);
+      }
+    }
+    self.remove_index_list(index_list,);
+
+    if dialog.intent == UnsavedIntent::Exit {
+      let _ = self.tx.send(Action::ExitAppConfirmed,);
+    }
+  }
+
+  /// 是否有文件需要更新备份
+  pub fn need_backup(&self,) -> bool {
+    self.files.iter().any(OpenFile::need_backup,)
+  }
+
+  /// 备份所有未保存的文件，在备份线程中写入
+  pub fn sync_backups(&mut self,) {
+    let Some(dir,) = backup::backup_dir() else {
+      return;
+    };
+    for f in self.files.iter_mut() {
+      f.sync_backup(&dir,);
+    }
+  }
+
+  /// 恢复上次未保存的文件，返回恢复失败的错误信息
+  pub fn restore_backups(&mut self,) -> Vec<String,> {
+    let Some(dir,) = backup::backup_dir() else {
+      return vec![];
+    };
+    let (backups, mut errors,) = backup::load_all(&dir,);
+    for backup in backups {
+      let f = match self.files.iter_mut().find(|f| f.path == backup.path,) {
+        Some(f,) => {
+          f.restore_backup(&backup,);
+          f
+        }
+        None => match OpenFile::from_backup(&backup,) {
+          Ok(f,) => {
+            self.files.push(f,);
+            self.files.last_mut().unwrap()
+          }
+          Err(e,) => {
+            errors.push(format!("恢复 {} 失败: {e}", backup.path.display()),);
+            continue;
+          }
+        },
+      };
+      // 备份与磁盘上的内容一致
+      if !f.changed() {
+        f.remove_backup();
+      }
+    }
+    if self.current_index >= self.files.len() && !self.files.is_empty() {
+      self.set_current_index(0,);
+    }
+    errors
   }
 
   fn get_and_reset_current_index_changed(&mut self,) -> bool {
@@ -158,18 +638,24 @@ impl OpenFiles {
     }
   }
 
-  fn current_file_mut(&mut self,) -> Option<&mut OpenFile,> {
-    if self.current_index >= self.files.len() {
-      None
-    } else {
-      Some(&mut self.files[self.current_index],)
-    }
+  /// 当前文件的编辑器是否获得焦点
+  pub fn has_editor_focus(
+    &self,
+    ctx: &egui::Context,
+  ) -> bool {
+    let focus = ctx.memory(|m| m.focus(),);
+    focus.is_some() && focus == self.current_file().map(|f| f.id(),)
   }
 
   pub fn is_empty(&self,) -> bool {
     self.files.is_empty()
   }
 
+  /// 已打开的文件的路径
+  pub fn paths(&self,) -> impl Iterator<Item = &PathBuf,> {
+    self.files.iter().map(|f| &f.path,)
+  }
+
   pub fn is_current_file(
     &self,
     path: &PathBuf,
@@ -180,6 +666,147 @@ impl OpenFiles {
       .unwrap_or(false,)
   }
 
+  pub fn is_changed(
+    &self,
+    path: &PathBuf,
+  ) -> bool {
+    self.files.iter().any(|f| f.path.eq(path,) && f.changed(),)
+  }
+
+  /// 重新读取已打开的文件（文件未打开时忽略）
+  pub fn reload_file(
+    &mut self,
+    path: &PathBuf,
+  ) -> Result<(), std::io::Error,> {
+    match self.files.iter_mut().find(|f| f.path.eq(path,),) {
+      Some(f,) => f.reload(),
+      None => Ok((),),
+    }
+  }
+
+  /// 当前文件跳转到第 line_number 行（从 1 开始），并选中行内的 range（byte_range）
+  pub fn goto(
+    &mut self,
+    line_number: usize,
+    range_in_line: Option<Range<usize,>,>,
+  ) {
+    if self.current_index >= self.files.len() {
+      return;
+    }
+    let f = &mut self.files[self.current_index];
+    let line_start = f.line_start_index(line_number,);
+    let range = range_in_line.unwrap_or(0..0,);
+    // 文件内容可能与查找时不同，保证索引在 char 边界上
+    let selection = f.content.floor_char_boundary(line_start + range.start,)
+      ..f.content.floor_char_boundary(line_start + range.end,);
+    f.pending_selection = Some(selection,);
+  }
+
+  /// 选中当前文件中 start 到 end 的内容，位置为 (行, 列)，从 0 开始，列为 char 索引
+  pub fn goto_position(
+    &mut self,
+    start: (usize, usize,),
+    end: (usize, usize,),
+  ) {
+    if self.current_index >= self.files.len() {
+      return;
+    }
+    let f = &mut self.files[self.current_index];
+    let start = f
+      .content
+      .char_to_byte(f.content.line_column_to_char(start,),);
+    let end = f.content.char_to_byte(f.content.line_column_to_char(end,),);
+    f.pending_selection = Some(start..end.max(start,),);
+  }
+
+  pub fn set_diagnostics(
+    &mut self,
+    diagnostics: BTreeMap<PathBuf, Vec<Diagnostic,>,>,
+  ) {
+    self.diagnostics = diagnostics;
+  }
+
+  /// 打开的文件的状态，同步到语言服务器
+  pub fn lsp_documents(&self,) -> Vec<DocumentState<'_,>,> {
+    self
+      .files
+      .iter()
+      .map(|f| DocumentState {
+        path: &f.path,
+        language: &f.language,
+        revision: f.history.revision(),
+        save_count: f.save_count,
+        text: &f.content,
+      },)
+      .collect()
+  }
+
+  /// 取出等待发送到语言服务器的请求
+  pub fn take_lsp_requests(&mut self,) -> Vec<LspRequest,> {
+    std::mem::take(&mut self.lsp_requests,)
+  }
+
+  /// 显示补全弹窗，光标已离开请求的位置时忽略
+  pub fn show_completion(
+    &mut self,
+    path: &Path,
+    position: (usize, usize,),
+    items: Vec<CompletionItem,>,
+  ) {
+    let Some(f,) = self.current_file() else {
+      return;
+    };
+    let Some(cr,) = f.cursor_range else {
+      return;
+    };
+    if f.path != path || !matches!(f.editor, Editor::TextEdit(_)) || items.is_empty() {
+      return;
+    }
+    let cursor = cr.primary.index;
+    let request_index = f.content.line_column_to_char(position,);
+    if cursor < request_index || f.content.char_to_line(cursor,) != position.0 {
+      return;
+    }
+    let word_start = completion::word_start(&f.content, request_index,);
+    let mut popup = CompletionPopup::new(path.to_path_buf(), word_start, items,);
+    if popup.update(&f.content, cursor,) {
+      self.completion = Some(popup,);
+    }
+  }
+
+  /// 显示悬停提示，鼠标已离开请求的位置时忽略
+  pub fn set_hover(
+    &mut self,
+    path: &Path,
+    position: (usize, usize,),
+    text: String,
+  ) {
+    let Some(f,) = self.files.iter().find(|f| f.path == path,) else {
+      return;
+    };
+    let index = f.content.line_column_to_char(position,);
+    if let Some(hover,) = &mut self.hover {
+      if hover.path == path && hover.index == index && !text.is_empty() {
+        hover.text = Some(text,);
+      }
+    }
+  }
+
+  /// 对当前文件 cursor 所在的位置发起请求
+  fn request_at_cursor(
+    &mut self,
+    kind: RequestKind,
+  ) {
+    let Some(f,) = self.current_file() else {
+      return;
+    };
+    let Some(cr,) = f.cursor_range else {
+      return;
+    };
+    let request = f.lsp_request(kind, cr.primary.index,);
+    self.lsp_requests.push(request,);
+  }
+
   pub fn open_file(
     &mut self,
     path: &PathBuf,
@@ -221,21 +848,28 @@ impl OpenFiles {
         .files
         .iter()
         .enumerate()
-        .filter_map(|(i, f,)| if f.changed { None } else { Some(i,) },)
+        .filter_map(|(i, f,)| if f.changed() { None } else { Some(i,) },)
         .collect::<Vec<usize,>>(),
       CloseAction::CloseAll => (0..len).collect::<Vec<usize,>>(),
       CloseAction::Close(index,) => vec![index],
     };
     tracing::info!("selected_index_list => {selected_index_list:?}");
+    self.close_index_list(selected_index_list,);
+  }
 
+  /// 关闭 selected_index_list 中已保存的文件，未保存的文件保持打开并弹出对话框
+  fn close_index_list(
+    &mut self,
+    selected_index_list: Vec<usize,>,
+  ) {
     // 从 selected_index_list 根据【是否已保存】推导出 saved_index_list 和 unsaved_files
     let mut saved_index_list: Vec<usize,> = vec![];
-    let mut unsaved_files: Vec<&OpenFile,> = vec![];
+    let mut unsaved_files: Vec<PathBuf,> = vec![];
     for index in selected_index_list.iter() {
       let f: Option<&OpenFile,> = self.files.get(*index,);
       if let Some(f,) = f {
-        if f.changed {
-          unsaved_files.push(f,);
+        if f.changed() {
+          unsaved_files.push(f.path.clone(),);
         } else {
           saved_index_list.push(*index,);
         }
@@ -248,12 +882,20 @@ impl OpenFiles {
 
     // 提示 unsaved_files
     if !unsaved_files.is_empty() {
-      let one = unsaved_files[0];
-      util::toaster()
-        .warning(format!("未保存文件：{:?}", &one.path),)
-        .set_duration(Some(Duration::from_secs(5,),),);
+      self.unsaved_dialog = Some(UnsavedDialog::new(UnsavedIntent::Close, unsaved_files,),);
     }
 
+    self.remove_index_list(saved_index_list,);
+  }
+
+  /// 移除 index_list 中的文件，不检查是否已保存
+  fn remove_index_list(
+    &mut self,
+    saved_index_list: Vec<usize,>,
+  ) {
+    if saved_index_list.is_empty() {
+      return;
+    }
     // 删除 saved_index_list
     let files: Vec<OpenFile,> = std::mem::take(&mut self.files,);
     self.files = files
@@ -293,6 +935,70 @@ impl OpenFiles {
 
     tracing::info!("current_index => {current_index:?}");
   }
+
+  /// 文件在磁盘上被修改或删除：未修改的文件直接重新读取，已修改的文件显示冲突提示
+  pub fn on_disk_change(
+    &mut self,
+    paths: &BTreeSet<PathBuf,>,
+  ) {
+    for f in self.files.iter_mut().filter(|f| paths.contains(&f.path,),) {
+      let modified = modified_time(&f.path,);
+      if modified.is_none() {
+        f.disk_change = Some(DiskChange::Removed,);
+      } else if modified == f.disk_modified {
+        // 自己保存或内容未变化
+      } else if f.changed() {
+        f.disk_change = Some(DiskChange::Modified,);
+      } else if let Err(e,) = f.reload() {
+        util::toaster()
+          .error(format!("重新读取文件失败：{:?}\nErr: {e}", f.path),)
+          .set_duration(Some(Duration::from_secs(5,),),);
+      }
+    }
+  }
+
+  /// 文件被重命名、移动或删除后，更新或关闭对应的已打开文件
+  pub fn on_file_change(
+    &mut self,
+    change: &FileChange,
+  ) {
+    match change {
+      FileChange::Created(_,) => {}
+      FileChange::Renamed {
+        from,
+        to,
+      } => {
+        for f in self.files.iter_mut() {
+          let Ok(relative,) = f.path.strip_prefix(from,) else {
+            continue;
+          };
+          let relative = relative.to_owned();
+          // 下次备份时写入新路径
+          if f.backup_revision.is_some() {
+            f.remove_backup();
+          }
+          f.path = if relative.as_os_str().is_empty() {
+            to.clone()
+          } else {
+            to.join(relative,)
+          };
+          if f.language_detected {
+            f.set_language(None,);
+          }
+        }
+      }
+      FileChange::Removed(path,) => {
+        let index_list = self
+          .files
+          .iter()
+          .enumerate()
+          .filter(|(_, f,)| f.path.starts_with(path,),)
+          .map(|(i, _,)| i,)
+          .collect();
+        self.close_index_list(index_list,);
+      }
+    }
+  }
 }
 
 // ------------------------------------ UI
@@ -336,7 +1042,7 @@ impl OpenFiles {
       return;
     }
     let f = f.unwrap();
-    let name = f.path().file_name().unwrap().to_string_lossy();
+    let name = util::file_name(f.path(),);
     let relative_path = f
       .path
       .strip_prefix(open_dir.unwrap_or(&PathBuf::new(),),)
@@ -345,7 +1051,7 @@ impl OpenFiles {
     let absolute_path = f.path.to_owned();
 
     // UI
-    let tab_title = if f.changed {
+    let tab_title = if f.changed() {
       format!("{name} [+]")
     } else {
       format!("{name}")
@@ -416,6 +1122,16 @@ impl OpenFiles {
     },);
   }
 
+  /// 未保存修改对话框
+  pub fn show_unsaved_dialog(
+    &mut self,
+    ctx: &egui::Context,
+  ) {
+    if let Some(dialog,) = self.unsaved_dialog.as_mut() {
+      dialog.show(ctx, &self.tx,);
+    }
+  }
+
   pub fn show_tab_bar(
     &mut self,
     ui: &mut egui::Ui,
@@ -463,16 +1179,16 @@ impl OpenFiles {
   ) {
     puffin::profile_function!();
 
-    let current_file_mut = self.current_file_mut();
-    if current_file_mut.is_none() {
+    if self.current_index >= self.files.len() {
       return;
     }
     // File info
-    let f = current_file_mut.unwrap();
-    let file_ext = f.extension().to_string();
-    let line_count = f.content.lines().count();
+    let f = &mut self.files[self.current_index];
+    let find_bar = &mut self.find_bar;
+    let language = f.language.clone();
+    let line_count = f.content.len_lines();
     // TextEditor info
-    let hl_line_number = f.cursor_range.map(|v| v.primary.rcursor.row + 1,);
+    let hl_line_number = f.cursor_stat().map(|(row, _, _,)| row,);
     let text_editor_id = f.id();
     let outter_scroll_area_id = egui::Id::new(format!(
       "text_editor_outter_scroll_area_{}",
@@ -483,85 +1199,281 @@ impl OpenFiles {
       f.path.to_string_lossy()
     ),);
 
-    // 竖向滚动
-    egui::ScrollArea::vertical()
-      .id_source(outter_scroll_area_id,)
-      .show(ui, |ui| {
-        // 行号栏
-        show_line_number_bar(ui, line_count, hl_line_number,);
-        // editor UI
-        let response = text_editor_ui(
+    // 磁盘上的变化与未保存的修改冲突
+    if let Some(disk_change,) = f.disk_change {
+      ui::top_panel("disk_change_banner", ui.ctx(),).show_inside(ui, |ui| {
+        disk_change_banner_ui(ui, f, disk_change,);
+      },);
+    }
+
+    // 查找/替换栏
+    let mut selected_match = None;
+    if find_bar.is_show() {
+      find_bar.update_matches(&f.content,);
+      let event = ui::top_panel("find_bar", ui.ctx(),)
+        .show_inside(ui, |ui| find_bar.show(ui,),)
+        .inner;
+      if let Some(event,) = event {
+        selected_match = handle_find_bar_event(f, find_bar, event,);
+      }
+    }
+    // 选中匹配项 或 跳转位置
+    if let Some(range,) = f.pending_selection.take() {
+      selected_match = Some(range,);
+    }
+    let selected_cursor_range = selected_match.as_ref().map(|range| {
+      CCursorRange::two(
+        CCursor::new(f.content.byte_to_char(range.start,),),
+        CCursor::new(f.content.byte_to_char(range.end,),),
+      )
+    },);
+    let highlights = if find_bar.is_show() {
+      find_bar.highlights()
+    } else {
+      vec![]
+    };
+    let marks = self
+      .diagnostics
+      .get(&f.path,)
+      .map(|v| diagnostic::editor_marks(&f.content, v,),)
+      .unwrap_or_default();
+
+    let dark_mode = ui::dark_mode();
+    let theme_name = hl::selected_theme(dark_mode,);
+    let hl_key = hl::HlKey::new(Some(&theme_name,), dark_mode, &language,);
+    // 编辑器的颜色跟随主题
+    hl::apply_theme_colors(ui.visuals_mut(), &hl_key,);
+    ui.painter().rect_filled(
+      ui.available_rect_before_wrap(),
+      0.,
+      ui.visuals().extreme_bg_color,
+    );
+    // 补全弹窗打开时，上下选择、Enter 等按键交给补全弹窗
+    if self.completion.as_ref().is_some_and(|v| v.path != f.path,)
+      || !ui.memory(|m| m.has_focus(text_editor_id,),)
+    {
+      self.completion = None;
+    }
+    let completion_input = self
+      .completion
+      .as_mut()
+      .map(|v| v.consume_keys(ui,),)
+      .unwrap_or_default();
+    let mut feedback = None;
+    let mut buffer = RecordingBuffer::new(&mut f.content,);
+    match &mut f.editor {
+      Editor::Virtual(highlighter,) => {
+        // 大文件：只渲染可见行
+        highlighter.set_key(&hl_key,);
+        if selected_cursor_range.is_some() {
+          f.cursor_range = selected_cursor_range;
+        }
+        virtual_editor::show(
           ui,
-          &file_ext,
-          &mut f.content,
           text_editor_id,
-          inner_scroll_area_id,
+          highlighter,
+          &mut buffer,
+          &mut f.cursor_range,
+          &highlights,
+          &marks,
+          f.line_ending,
+          selected_cursor_range.is_some(),
         );
-        // editor response
-        let mut editor_output = response.inner;
-        let mut cursor_range = editor_output.cursor_range;
-        let galley = editor_output.galley;
-        let mut content_changed = false;
-
-        // 记录 cursor, 给 status_bar 使用
-        f.cursor_range = cursor_range;
-
-        // 如果文本出现变更，设置“未保存”状态
-        if editor_output.response.changed() {
-          f.mark_changed();
+      }
+      Editor::TextEdit(highlighter,) => {
+        highlighter.set_key(&hl_key,);
+        if selected_cursor_range.is_some() {
+          store_text_edit_cursor(ui.ctx(), text_editor_id, selected_cursor_range,);
         }
+        // TextEdit 的 layouter 通过比较文本得到编辑的行，不需要 Buffer 记录的编辑
+        buffer.take_line_edits();
+        // 竖向滚动
+        feedback = egui::ScrollArea::vertical()
+          .id_source(outter_scroll_area_id,)
+          .show(ui, |ui| {
+            // 行号栏
+            show_line_number_bar(ui, line_count, hl_line_number, &marks,);
+            // editor UI
+            let response = text_editor_ui(
+              ui,
+              highlighter,
+              &mut buffer,
+              &highlights,
+              &marks.underlines,
+              text_editor_id,
+              inner_scroll_area_id,
+            );
+            // editor response
+            let editor_output = response.inner;
 
-        // Ctrl+J 换行并缩进
-        ui.ctx().input_mut(|i| {
-          let key = action::parse_shortcut("Ctrl+J",).unwrap();
-          if i.consume_shortcut(&key,) {
-            (cursor_range, content_changed,) =
-              new_line_and_auto_indent(f, cursor_range, galley.clone(),);
-          }
-        },);
-
-        if content_changed {
-          f.mark_changed();
-        }
+            // 记录 cursor, 给 status_bar 使用
+            f.cursor_range = editor_output.cursor_range.map(|v| v.as_ccursor_range(),);
 
-        // 设置 cursor
-        if editor_output.cursor_range != cursor_range {
-          editor_output.state.set_cursor_range(cursor_range,);
-          tracing::info!("==> store state...");
-          editor_output.state.store(ui.ctx(), text_editor_id,);
-          tracing::info!("==> store successfully!");
-        }
+            // 滚动到选中的位置
+            if let Some(cr,) = editor_output
+              .cursor_range
+              .filter(|_| selected_match.is_some(),)
+            {
+              let rect = editor_output
+                .galley
+                .pos_from_cursor(&cr.primary,)
+                .translate(editor_output.text_draw_pos.to_vec2(),);
+              ui.scroll_to_rect(rect, Some(egui::Align::Center,),);
+            }
 
-        // Ctrl+S 保存文件
-        ui.ctx().input_mut(|i| {
-          let key = action::parse_shortcut("Ctrl+S",).unwrap();
-          if i.consume_shortcut(&key,) {
-            match f.save() {
-              Ok(_,) => f.changed = false,
-              Err(e,) => {
-                // toast
-                util::toaster()
-                  .error(e.to_string(),)
-                  .set_duration(Some(Duration::from_secs(5,),),);
-              }
+            let galley = &editor_output.galley;
+            let text_draw_pos = editor_output.text_draw_pos;
+            EditorFeedback {
+              cursor_rect: editor_output.cursor_range.map(|cr| {
+                galley
+                  .pos_from_cursor(&cr.primary,)
+                  .translate(text_draw_pos.to_vec2(),)
+              },),
+              hovered_index: editor_output
+                .response
+                .hover_pos()
+                .and_then(|pos| hovered_char_index(galley, text_draw_pos, pos,),),
+              changed: editor_output.response.changed(),
+              command_clicked: editor_output.response.clicked()
+                && ui.input(|i| i.modifiers.command,),
             }
-          }
-        },);
-        //
-      },);
+          },)
+          .inner
+          .into();
+      }
+    }
+
+    // 如果文本出现变更，记录到编辑历史
+    let edits = buffer.into_edits();
+    if !edits.is_empty() {
+      f.history.record(edits,);
+      find_bar.invalidate();
+    }
+
+    if let Some(feedback,) = feedback {
+      self.lsp_requests.extend(lsp_feedback(
+        ui,
+        f,
+        &feedback,
+        &mut self.completion,
+        completion_input,
+        &mut self.hover,
+      ),);
+      if feedback.changed {
+        find_bar.invalidate();
+      }
+    }
+  }
+
+  fn undo(&mut self,) {
+    if self.current_index >= self.files.len() {
+      return;
+    }
+    self.files[self.current_index].undo();
+    self.find_bar.invalidate();
+  }
+
+  fn redo(&mut self,) {
+    if self.current_index >= self.files.len() {
+      return;
+    }
+    self.files[self.current_index].redo();
+    self.find_bar.invalidate();
+  }
+
+  fn save_current(&mut self,) {
+    let Some(f,) = self.files.get_mut(self.current_index,) else {
+      return;
+    };
+    if let Err(e,) = f.save() {
+      util::toaster()
+        .error(e.to_string(),)
+        .set_duration(Some(Duration::from_secs(5,),),);
+    }
+  }
+
+  /// 换行并缩进，cursor 在下一帧设置到编辑器
+  fn new_line_and_indent(&mut self,) {
+    let Some(f,) = self.files.get_mut(self.current_index,) else {
+      return;
+    };
+    if let Some(cr,) = new_line_and_auto_indent(f,) {
+      let index = f.content.char_to_byte(cr.primary.index,);
+      f.pending_selection = Some(index..index,);
+      self.find_bar.invalidate();
+    }
+  }
+
+  fn open_find_bar(
+    &mut self,
+    show_replace: bool,
+  ) {
+    if self.current_index >= self.files.len() {
+      return;
+    }
+    let selected = self.files[self.current_index].selected_text();
+    self.find_bar.open(show_replace, selected,);
+  }
+}
+
+/// 处理查找栏事件，返回需要选中的匹配项
+fn handle_find_bar_event(
+  f: &mut OpenFile,
+  find_bar: &mut FindBar,
+  event: FindBarEvent,
+) -> Option<Range<usize,>,> {
+  let selected = f.selected_byte_range().unwrap_or(0..0,);
+  match event {
+    FindBarEvent::Close => {
+      find_bar.close();
+      None
+    }
+    FindBarEvent::FindNext => find_bar.select_next(selected.end, true,),
+    FindBarEvent::FindPrevious => find_bar.select_next(selected.start, false,),
+    FindBarEvent::Replace => {
+      // 当前没有选中匹配项时，先选中下一个
+      let current = find_bar.current_match().filter(|v| *v == selected,);
+      let Some(range,) = current else {
+        return find_bar.select_next(selected.start, true,);
+      };
+      let replaced =
+        find_bar
+          .matcher()?
+          .expand_at(f.content.as_str(), range.clone(), find_bar.replacement(),)?;
+      f.edit(range.clone(), &replaced,);
+      find_bar.invalidate();
+      find_bar.update_matches(&f.content,);
+      find_bar.select_next(range.start + replaced.len(), true,)
+    }
+    FindBarEvent::ReplaceAll => {
+      let (content, count,) = find_bar
+        .matcher()?
+        .replace_all(f.content.as_str(), find_bar.replacement(),);
+      if count > 0 {
+        f.set_content(&content,);
+        find_bar.invalidate();
+      }
+      util::toaster()
+        .info(format!("已替换 {count} 处"),)
+        .set_duration(Some(Duration::from_secs(3,),),);
+      None
+    }
   }
 }
 
 fn text_editor_ui(
   ui: &mut egui::Ui,
-  file_ext: &str,
-  content: &mut String,
+  highlighter: &mut hl::FileHighlighter,
+  content: &mut dyn egui::TextBuffer,
+  highlights: &[(Range<usize,>, egui::Color32,)],
+  underlines: &[(Range<usize,>, egui::Color32,)],
   text_editor_id: egui::Id,
   inner_scroll_area_id: egui::Id,
 ) -> egui::scroll_area::ScrollAreaOutput<egui::text_edit::TextEditOutput,> {
   let mut layouter = |ui: &egui::Ui, text: &str, _wrap_width: f32| {
-    let hl_key = hl::HlKey::new(None, ui::dark_mode(), file_ext,);
-    let layout_job = hl::layout::get_layout_job_from_cache(ui.ctx(), &hl_key, text,);
+    let mut layout_job = highlighter.layout_job(text,);
+    hl::layout::highlight_ranges(&mut layout_job, highlights,);
+    hl::layout::underline_ranges(&mut layout_job, underlines,);
     ui.fonts(|f| f.layout_job(layout_job,),)
   };
 
@@ -581,18 +1493,144 @@ fn text_editor_ui(
       // ui.horizonta(|ui| {
       // text editor
       // (*) 获取焦点，保证 cursor 正确显示
-      ui.memory_mut(|m| m.request_focus(text_editor_id,),);
+      // 其他组件（如查找栏）持有焦点时不抢占
+      if ui.memory(|m| m.focus().is_none(),) {
+        ui.memory_mut(|m| m.request_focus(text_editor_id,),);
+      }
       text_editor.show(ui,)
       // },)
       // .inner
     },)
 }
 
-/// 行号栏
+/// 处理补全弹窗、悬停提示和 Ctrl+点击，返回需要发送到语言服务器的请求
+fn lsp_feedback(
+  ui: &mut egui::Ui,
+  f: &mut OpenFile,
+  feedback: &EditorFeedback,
+  completion: &mut Option<CompletionPopup,>,
+  completion_input: CompletionInput,
+  hover: &mut Option<HoverState,>,
+) -> Vec<LspRequest,> {
+  let mut requests = vec![];
+  let text_editor_id = f.id();
+  let Some(cursor,) = f.cursor_range.map(|v| v.primary.index,) else {
+    *completion = None;
+    return requests;
+  };
+
+  // 补全弹窗
+  if completion_input.close
+    || !completion
+      .as_mut()
+      .is_some_and(|v| v.update(&f.content, cursor,),)
+  {
+    *completion = None;
+  }
+  if let Some(popup,) = completion {
+    let clicked = feedback
+      .cursor_rect
+      .and_then(|rect| popup.show(ui, rect.left_bottom(),).cloned(),);
+    let accepted = clicked.or_else(|| {
+      completion_input
+        .accept
+        .then(|| popup.selected_item().cloned(),)
+        .flatten()
+    },);
+    if let Some(item,) = accepted {
+      let (range, cursor,) = popup.edit(&item, &f.content, cursor,);
+      f.edit(range, &item.text,);
+      f.cursor_range = Some(CCursorRange::one(CCursor::new(cursor,),),);
+      store_text_edit_cursor(ui.ctx(), text_editor_id, f.cursor_range,);
+      *completion = None;
+    }
+  } else if feedback.changed && cursor > 0 {
+    // 输入 . 或 :: 后触发补全
+    let (line, column,) = f.content.char_to_line_column(cursor,);
+    let before = f
+      .content
+      .line(line,)
+      .chars()
+      .take(column,)
+      .collect::<String>();
+    if before.ends_with('.',) || before.ends_with("::",) {
+      requests.push(f.lsp_request(RequestKind::Completion, cursor,),);
+    }
+  }
+
+  // Ctrl+点击 跳转到定义
+  if feedback.command_clicked {
+    requests.push(f.lsp_request(RequestKind::Definition, cursor,),);
+  }
+
+  // 悬停提示
+  match feedback.hovered_index {
+    None => *hover = None,
+    Some(index,) => {
+      if !hover
+        .as_ref()
+        .is_some_and(|v| v.path == f.path && v.index == index,)
+      {
+        *hover = Some(HoverState {
+          path: f.path.clone(),
+          index,
+          since: Instant::now(),
+          requested: false,
+          text: None,
+        },);
+      }
+      let Some(state,) = hover else {
+        return requests;
+      };
+      if !state.requested {
+        let elapsed = state.since.elapsed();
+        if elapsed >= HOVER_DELAY {
+          state.requested = true;
+          requests.push(f.lsp_request(RequestKind::Hover, index,),);
+        } else {
+          ui.ctx().request_repaint_after(HOVER_DELAY - elapsed,);
+        }
+      }
+      if let Some(text,) = &state.text {
+        egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("lsp_hover",), |ui| {
+          ui.set_max_width(600.,);
+          ui.label(egui::RichText::new(text,).font(text::search_result_font(),),);
+        },);
+      }
+    }
+  }
+  requests
+}
+
+/// pos 所在的字符（char 索引），不在字符上时返回 None
+fn hovered_char_index(
+  galley: &egui::Galley,
+  text_draw_pos: egui::Pos2,
+  pos: egui::Pos2,
+) -> Option<usize,> {
+  let pos = pos - text_draw_pos;
+  let cursor = galley.cursor_from_pos(pos,);
+  let rect = galley.pos_from_cursor(&cursor,);
+  // cursor 为离 pos 最近的字符边界，pos 在边界左侧时为前一个字符
+  let index = if pos.x < rect.min.x {
+    cursor.ccursor.index.checked_sub(1,)?
+  } else {
+    cursor.ccursor.index
+  };
+  let start = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index,),),);
+  let end = galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index + 1,),),);
+  let on_char = start.min.y == end.min.y
+    && (start.min.x..=end.min.x).contains(&pos.x,)
+    && (start.min.y..=start.max.y).contains(&pos.y,);
+  on_char.then_some(index,)
+}
+
+/// 行号栏，有诊断的行显示为诊断的颜色
 fn show_line_number_bar(
   ui: &mut egui::Ui,
   line_count: usize,
   hl_line_number: Option<usize,>,
+  marks: &EditorMarks,
 ) {
   ui::left_panel("line_number_bar", ui.ctx(),)
     .resizable(false,)
@@ -611,40 +1649,50 @@ fn show_line_number_bar(
                 .strong();
             }
           }
-          ui.label(text,);
+          match marks.lines.get(&(i - 1),) {
+            Some((color, message,),) => {
+              ui.label(text.color(*color,),).on_hover_text(message,);
+            }
+            None => {
+              ui.label(text,);
+            }
+          }
         }
       },);
     },);
 }
 
-fn new_line_and_auto_indent(
-  f: &mut OpenFile,
-  cursor_range: Option<egui::text_edit::CursorRange,>,
-  galley: std::sync::Arc<eframe::epaint::Galley,>,
-) -> (Option<egui::widgets::text_edit::CursorRange,>, bool,) {
-  match cursor_range {
-    None => (None, false,),
-    Some(cr,) => {
-      let row = cr.primary.rcursor.row;
-      match f.content.lines().nth(row,) {
-        None => (None, false,),
-        Some(line,) => {
-          tracing::info!("current line => {line}");
-          let space_count = calc_next_line_indent_space_count(line,);
-          tracing::info!("next line indent space_count => {space_count}");
-          let spaces = " ".repeat(space_count,);
-
-          let row_end_index = galley.cursor_end_of_row(&cr.primary,).ccursor.index;
-          egui::TextBuffer::insert_text(&mut f.content, &format!("\n{spaces}"), row_end_index,);
-
-          let new_cr = Some(egui::widgets::text_edit::CursorRange::one(
-            galley.cursor_end_of_row(&galley.cursor_down_one_row(&cr.primary,),),
-          ),);
-          (new_cr, true,)
-        }
-      }
-    }
-  }
+/// 在 cursor 所在行的行尾换行并缩进，返回新的 cursor
+fn new_line_and_auto_indent(f: &mut OpenFile,) -> Option<CCursorRange,> {
+  let cr = f.cursor_range?;
+  let row = f.content.char_to_line(cr.primary.index,);
+  let line = f.content.line(row,).into_owned();
+  tracing::info!("current line => {line}");
+  let space_count = calc_next_line_indent_space_count(&line,);
+  tracing::info!("next line indent space_count => {space_count}");
+  let spaces = " ".repeat(space_count,);
+
+  let row_end_index = f.content.line_to_char(row,) + f.content.line_len_chars(row,);
+  let row_end_byte_index = f.content.char_to_byte(row_end_index,);
+  f.edit(
+    row_end_byte_index..row_end_byte_index,
+    &format!("\n{spaces}"),
+  );
+
+  Some(CCursorRange::one(CCursor::new(
+    row_end_index + 1 + space_count,
+  ),),)
+}
+
+/// 设置 TextEdit 的 cursor，下一帧生效
+fn store_text_edit_cursor(
+  ctx: &egui::Context,
+  text_editor_id: egui::Id,
+  cursor_range: Option<CCursorRange,>,
+) {
+  let mut state = egui::text_edit::TextEditState::load(ctx, text_editor_id,).unwrap_or_default();
+  state.set_ccursor_range(cursor_range,);
+  state.store(ctx, text_editor_id,);
 }
 
 fn calc_next_line_indent_space_count(cur_line: &str,) -> usize {
@@ -668,6 +1716,35 @@ fn calc_current_line_indent_space_count(cur_line: &str,) -> usize {
   space_count
 }
 
+fn modified_time(path: &Path,) -> Option<SystemTime,> {
+  std::fs::metadata(path,).and_then(|v| v.modified(),).ok()
+}
+
+fn disk_change_banner_ui(
+  ui: &mut egui::Ui,
+  f: &mut OpenFile,
+  disk_change: DiskChange,
+) {
+  ui.horizontal(|ui| {
+    let text = match disk_change {
+      DiskChange::Modified => "文件已在磁盘上被修改，与未保存的修改冲突",
+      DiskChange::Removed => "文件已在磁盘上被删除，保存时将重新创建",
+    };
+    ui.colored_label(ui.visuals().warn_fg_color, text,);
+    if disk_change == DiskChange::Modified && ui.button("重新读取（丢弃修改）",).clicked()
+    {
+      if let Err(e,) = f.reload() {
+        util::toaster()
+          .error(format!("重新读取文件失败：{:?}\nErr: {e}", f.path),)
+          .set_duration(Some(Duration::from_secs(5,),),);
+      }
+    }
+    if ui.button("保留我的修改",).clicked() {
+      f.disk_change = None;
+    }
+  },);
+}
+
 fn index_after_remove(
   index: usize,
   to_remove_index_list: &[usize],
@@ -678,8 +1755,98 @@ fn index_after_remove(
 impl action::Handle for OpenFiles {
   fn handle(
     &mut self,
-    _action: &action::Action,
+    action: &action::Action,
   ) {
-    {}
+    match action {
+      Action::Find => self.open_find_bar(false,),
+      Action::Replace => self.open_find_bar(true,),
+      Action::FindNext => self.find_bar.send(FindBarEvent::FindNext,),
+      Action::FindPrevious => self.find_bar.send(FindBarEvent::FindPrevious,),
+      Action::Undo => self.undo(),
+      Action::Redo => self.redo(),
+      Action::TriggerCompletion => self.request_at_cursor(RequestKind::Completion,),
+      Action::GotoDefinition => self.request_at_cursor(RequestKind::Definition,),
+      Action::FindReferences => self.request_at_cursor(RequestKind::References,),
+      Action::SaveFile => self.save_current(),
+      Action::NewLineAndIndent => self.new_line_and_indent(),
+      Action::SetLanguage(language,) => {
+        if let Some(f,) = self.files.get_mut(self.current_index,) {
+          f.set_language(language.clone(),);
+        }
+      }
+      Action::SaveUnsaved(paths,) => self.resolve_unsaved(paths,),
+      Action::DiscardUnsaved => self.resolve_unsaved(&[],),
+      Action::CancelUnsaved => self.unsaved_dialog = None,
+      Action::ReloadSyntaxes => {
+        // 新的语法可能匹配之前检测为其他语言的文件，手动指定的语言不变
+        for f in self.files.iter_mut().filter(|f| f.language_detected,) {
+          f.set_language(None,);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::mpsc::sync_channel;
+
+  use super::{CloseAction, OpenFiles};
+  use crate::{
+    action::{Action, Handle},
+    test_util::TempDir,
+  };
+
+  #[test]
+  fn test_unsaved_dialog() {
+    let dir = TempDir::new("unsaved",);
+    let paths = ["a.txt", "b.txt", "c.txt",].map(|v| dir.join(v,),);
+    for path in paths.iter() {
+      std::fs::write(path, "old",).unwrap();
+    }
+
+    let (tx, rx,) = sync_channel(10,);
+    let mut open_files = OpenFiles::new(tx,);
+    let open_and_edit = |open_files: &mut OpenFiles| {
+      for path in paths.iter() {
+        open_files.open_file(path,).unwrap();
+      }
+      // 修改 a 和 b，c 未修改
+      for f in open_files.files.iter_mut().take(2,) {
+        f.edit(0..3, "new",);
+      }
+    };
+
+    // 关闭全部：c 直接关闭，a 和 b 弹出对话框
+    open_and_edit(&mut open_files,);
+    open_files.close_files(CloseAction::CloseAll,);
+    assert_eq!(open_files.files.len(), 2);
+    let dialog = open_files.unsaved_dialog.as_ref().unwrap();
+    assert_eq!(dialog.selected_paths(), paths[..2]);
+
+    // 取消：文件保持打开
+    open_files.handle(&Action::CancelUnsaved,);
+    assert!(!open_files.is_unsaved_dialog_open());
+    assert_eq!(open_files.files.len(), 2);
+
+    // 只保存 a，丢弃 b
+    open_files.close_files(CloseAction::CloseAll,);
+    open_files.handle(&Action::SaveUnsaved(vec![paths[0].clone()],),);
+    assert!(!open_files.is_unsaved_dialog_open());
+    assert!(open_files.is_empty());
+    assert_eq!(std::fs::read_to_string(&paths[0]).unwrap(), "new");
+    assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), "old");
+    assert!(rx.try_recv().is_err());
+
+    // 退出：丢弃所有修改，未修改的文件保持打开，然后退出
+    open_and_edit(&mut open_files,);
+    assert!(open_files.prompt_unsaved_on_exit());
+    open_files.handle(&Action::DiscardUnsaved,);
+    assert_eq!(open_files.files.len(), 1);
+    assert_eq!(open_files.current_file().unwrap().path(), &paths[2]);
+    assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), "old");
+    assert_eq!(rx.try_recv(), Ok(Action::ExitAppConfirmed));
+    assert!(!open_files.prompt_unsaved_on_exit());
   }
 }
@@ -0,0 +1,319 @@
+use std::{
+  collections::BTreeSet,
+  path::PathBuf,
+  sync::mpsc::{channel, Receiver},
+  time::{Duration, Instant},
+};
+
+use eframe::egui::{self, collapsing_header::CollapsingState};
+
+use crate::{
+  lsp::{LspRequest, RequestKind},
+  outline::{self, SortOrder, Symbol},
+  text,
+};
+
+use super::open_file::{OpenFile, OpenFiles};
+
+/// 内容变化后多久重新提取符号
+const UPDATE_DELAY: Duration = Duration::from_millis(300,);
+
+/// 超过该大小的文件不根据语法高亮提取符号
+const HEURISTIC_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// 后台提取的符号：(文件, 版本, 符号)
+type Extracted = (PathBuf, u64, Vec<Symbol,>,);
+
+/// 大纲：当前文件的符号树，优先使用语言服务器提供的符号
+pub struct OutlinePanel {
+  path: Option<PathBuf,>,
+  revision: u64,
+  /// 内容变化的时间，None 表示已提取当前版本的符号
+  changed_at: Option<Instant,>,
+  /// 语言服务器提供了符号的文件，不再根据语法高亮提取
+  lsp_paths: BTreeSet<PathBuf,>,
+  symbols: Vec<Symbol,>,
+  sort_order: SortOrder,
+  rx: Option<Receiver<Extracted,>,>,
+  /// 折叠全部时改变，使所有节点使用新的折叠状态
+  generation: u64,
+  default_open: bool,
+  cursor: Option<(usize, usize,),>,
+  /// cursor 所在的符号，从根到该符号的索引
+  cursor_path: Vec<usize,>,
+  /// cursor 所在的符号变化后，展开并滚动到该符号
+  reveal_cursor: bool,
+}
+
+impl Default for OutlinePanel {
+  fn default() -> Self {
+    Self {
+      path: None,
+      revision: 0,
+      changed_at: None,
+      lsp_paths: BTreeSet::new(),
+      symbols: vec![],
+      sort_order: SortOrder::default(),
+      rx: None,
+      generation: 0,
+      default_open: true,
+      cursor: None,
+      cursor_path: vec![],
+      reveal_cursor: false,
+    }
+  }
+}
+
+impl OutlinePanel {
+  pub fn sort_order(&self,) -> SortOrder {
+    self.sort_order
+  }
+
+  /// 切换到下一种排序方式
+  pub fn next_sort_order(&mut self,) {
+    self.sort_order = self.sort_order.next();
+    outline::sort_symbols(&mut self.symbols, self.sort_order,);
+    self.update_cursor_path();
+  }
+
+  /// 折叠所有节点
+  pub fn collapse_all(&mut self,) {
+    self.generation += 1;
+    self.default_open = false;
+  }
+
+  /// 跟随当前文件，需要语言服务器提供符号时返回请求
+  pub fn sync(
+    &mut self,
+    ctx: &egui::Context,
+    file: Option<&OpenFile,>,
+  ) -> Option<LspRequest,> {
+    let Some(file,) = file else {
+      self.path = None;
+      self.symbols.clear();
+      self.cursor_path.clear();
+      return None;
+    };
+    if self.path.as_ref() != Some(file.path(),) {
+      // 切换文件后立即提取
+      self.path = Some(file.path().clone(),);
+      self.revision = file.revision();
+      self.symbols.clear();
+      self.rx = None;
+      self.changed_at = Some(Instant::now() - UPDATE_DELAY,);
+    } else if self.revision != file.revision() {
+      self.revision = file.revision();
+      self.changed_at = Some(Instant::now(),);
+    }
+
+    // 后台提取的符号
+    if let Some(Ok((path, revision, symbols,),),) = self.rx.as_ref().map(|v| v.try_recv(),) {
+      self.rx = None;
+      if self.path.as_ref() == Some(&path,) && !self.lsp_paths.contains(&path,) {
+        self.set_symbols(symbols,);
+        // 提取期间内容已变化
+        if revision != self.revision && self.changed_at.is_none() {
+          self.changed_at = Some(Instant::now(),);
+        }
+      }
+    }
+
+    // 跟随 cursor
+    let cursor = file.cursor_position();
+    if cursor != self.cursor {
+      self.cursor = cursor;
+      self.update_cursor_path();
+    }
+
+    let changed_at = self.changed_at?;
+    let elapsed = changed_at.elapsed();
+    if elapsed < UPDATE_DELAY {
+      ctx.request_repaint_after(UPDATE_DELAY - elapsed,);
+      return None;
+    }
+    // 等待正在进行的提取完成
+    if self.rx.is_some() {
+      return None;
+    }
+    self.changed_at = None;
+    let path = file.path().clone();
+    if !self.lsp_paths.contains(&path,) {
+      let text = file.snapshot();
+      if text.len_bytes() <= HEURISTIC_MAX_BYTES {
+        let language = file.language().to_string();
+        let revision = self.revision;
+        let ctx = ctx.clone();
+        let (tx, rx,) = channel();
+        std::thread::spawn({
+          let path = path.clone();
+          move || {
+            let symbols = outline::extract_symbols(&text, &language,);
+            let _ = tx.send((path, revision, symbols,),);
+            ctx.request_repaint();
+          }
+        },);
+        self.rx = Some(rx,);
+      }
+    }
+    Some(LspRequest {
+      kind: RequestKind::DocumentSymbol,
+      path,
+      position: (0, 0,),
+    },)
+  }
+
+  /// 语言服务器提供的符号，之后该文件不再根据语法高亮提取
+  pub fn set_lsp_symbols(
+    &mut self,
+    path: PathBuf,
+    symbols: Vec<Symbol,>,
+  ) {
+    // 语言服务器可能还未完成索引
+    if symbols.is_empty() && !self.lsp_paths.contains(&path,) {
+      return;
+    }
+    if self.path.as_ref() == Some(&path,) {
+      self.rx = None;
+      self.set_symbols(symbols,);
+    }
+    self.lsp_paths.insert(path,);
+  }
+
+  /// 语言服务器重新启动后重新根据语法高亮提取
+  pub fn clear_lsp_paths(&mut self,) {
+    self.lsp_paths.clear();
+    if self.path.is_some() {
+      self.changed_at = Some(Instant::now() - UPDATE_DELAY,);
+    }
+  }
+
+  fn set_symbols(
+    &mut self,
+    mut symbols: Vec<Symbol,>,
+  ) {
+    outline::sort_symbols(&mut symbols, self.sort_order,);
+    self.symbols = symbols;
+    self.update_cursor_path();
+  }
+
+  fn update_cursor_path(&mut self,) {
+    let cursor_path = self
+      .cursor
+      .map(|v| outline::symbol_path_at(&self.symbols, v,),)
+      .unwrap_or_default();
+    if cursor_path != self.cursor_path {
+      self.cursor_path = cursor_path;
+      self.reveal_cursor = true;
+    }
+  }
+}
+
+// ------------------------------------ UI
+
+impl OutlinePanel {
+  pub fn show(
+    &mut self,
+    ui: &mut egui::Ui,
+    open_files: &mut OpenFiles,
+  ) {
+    if self.path.is_none() {
+      ui.weak("没有打开的文件",);
+      return;
+    }
+    if self.symbols.is_empty() {
+      ui.weak("当前文件没有符号",);
+      return;
+    }
+    let mut to_goto = None;
+    let id = egui::Id::new(("outline", self.generation,),);
+    show_symbols(
+      ui,
+      id,
+      &self.symbols,
+      Some(&self.cursor_path,),
+      self.default_open,
+      self.reveal_cursor,
+      &mut to_goto,
+    );
+    self.reveal_cursor = false;
+
+    // 跳转到符号
+    if let Some(position,) = to_goto {
+      open_files.goto_position(position, position,);
+    }
+  }
+}
+
+fn show_symbols(
+  ui: &mut egui::Ui,
+  id: egui::Id,
+  symbols: &[Symbol],
+  cursor_path: Option<&[usize],>,
+  default_open: bool,
+  reveal_cursor: bool,
+  to_goto: &mut Option<(usize, usize,),>,
+) {
+  for (i, symbol,) in symbols.iter().enumerate() {
+    let id = id.with((&symbol.name, symbol.kind,),);
+    // cursor 所在的符号或其祖先
+    let cursor_path = cursor_path
+      .filter(|v| v.first() == Some(&i,),)
+      .map(|v| &v[1..],);
+    let selected = cursor_path.is_some_and(|v| v.is_empty(),);
+    if symbol.children.is_empty() {
+      ui.horizontal(|ui| {
+        ui.add_space(ui.spacing().indent,);
+        symbol_ui(ui, symbol, selected, reveal_cursor, to_goto,);
+      },);
+      continue;
+    }
+    let mut state = CollapsingState::load_with_default_open(ui.ctx(), id, default_open,);
+    if reveal_cursor && cursor_path.is_some_and(|v| !v.is_empty(),) {
+      state.set_open(true,);
+    }
+    state
+      .show_header(ui, |ui| {
+        symbol_ui(ui, symbol, selected, reveal_cursor, to_goto,);
+      },)
+      .body(|ui| {
+        show_symbols(
+          ui,
+          id,
+          &symbol.children,
+          cursor_path,
+          default_open,
+          reveal_cursor,
+          to_goto,
+        );
+      },);
+  }
+}
+
+/// `类型 名称`，点击时跳转到符号
+fn symbol_ui(
+  ui: &mut egui::Ui,
+  symbol: &Symbol,
+  selected: bool,
+  reveal_cursor: bool,
+  to_goto: &mut Option<(usize, usize,),>,
+) {
+  let format = |color| egui::TextFormat {
+    font_id: text::search_result_font(),
+    color,
+    ..Default::default()
+  };
+  let mut job = egui::text::LayoutJob::default();
+  job.append(
+    &format!("{} ", symbol.kind.label()),
+    0.0,
+    format(symbol.kind.color(),),
+  );
+  job.append(&symbol.name, 0.0, format(ui.visuals().text_color(),),);
+  let response = ui.add(egui::SelectableLabel::new(selected, job,),);
+  if selected && reveal_cursor {
+    response.scroll_to_me(Some(egui::Align::Center,),);
+  }
+  if response.clicked() {
+    *to_goto = Some(symbol.selection,);
+  }
+}
@@ -0,0 +1,267 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  sync::mpsc::{channel, Receiver},
+  time::Duration,
+};
+
+use eframe::egui;
+
+use crate::{
+  diagnostic::{self, Diagnostic, Severity},
+  text, util,
+};
+
+use super::open_file::OpenFiles;
+
+/// 问题面板：运行检查命令（默认 cargo check），按文件列出诊断
+pub struct ProblemsPanel {
+  command: String,
+  diagnostics: BTreeMap<PathBuf, Vec<Diagnostic,>,>,
+  /// 语言服务器发布的诊断
+  lsp_diagnostics: BTreeMap<PathBuf, Vec<Diagnostic,>,>,
+  error: Option<String,>,
+  /// 诊断被 set_diagnostics 替换，下次 sync 时通知
+  changed: bool,
+  /// 后台检查
+  rx: Option<Receiver<Result<Vec<Diagnostic,>, String,>,>,>,
+}
+
+impl Default for ProblemsPanel {
+  fn default() -> Self {
+    Self {
+      command: diagnostic::DEFAULT_CHECK_COMMAND.to_string(),
+      diagnostics: BTreeMap::new(),
+      lsp_diagnostics: BTreeMap::new(),
+      error: None,
+      changed: false,
+      rx: None,
+    }
+  }
+}
+
+impl ProblemsPanel {
+  pub fn command(&self,) -> &str {
+    &self.command
+  }
+
+  pub fn set_command(
+    &mut self,
+    command: &str,
+  ) {
+    self.command = command.to_string();
+  }
+
+  pub fn is_checking(&self,) -> bool {
+    self.rx.is_some()
+  }
+
+  /// 检查命令和语言服务器的诊断，去掉重复的诊断
+  pub fn diagnostics(&self,) -> BTreeMap<PathBuf, Vec<Diagnostic,>,> {
+    let mut diagnostics = self.diagnostics.clone();
+    for (path, lsp_diagnostics,) in self.lsp_diagnostics.iter() {
+      let list = diagnostics.entry(path.clone(),).or_default();
+      let count = list.len();
+      for d in lsp_diagnostics.iter() {
+        if !list[..count].contains(d,) {
+          list.push(d.clone(),);
+        }
+      }
+      list.sort_by_key(|v| (v.start, v.severity,),);
+    }
+    diagnostics.retain(|_, v| !v.is_empty(),);
+    diagnostics
+  }
+
+  /// 替换当前的诊断，如任务输出中匹配的问题
+  pub fn set_diagnostics(
+    &mut self,
+    diagnostics: Vec<Diagnostic,>,
+  ) {
+    self.error = None;
+    self.diagnostics = diagnostic::group_by_file(diagnostics,);
+    self.changed = true;
+  }
+
+  /// 替换语言服务器发布的 path 的诊断
+  pub fn set_lsp_diagnostics(
+    &mut self,
+    path: PathBuf,
+    diagnostics: Vec<Diagnostic,>,
+  ) {
+    if diagnostics.is_empty() {
+      self.lsp_diagnostics.remove(&path,);
+    } else {
+      self.lsp_diagnostics.insert(path, diagnostics,);
+    }
+    self.changed = true;
+  }
+
+  /// 关闭文件夹时清除语言服务器的诊断
+  pub fn clear_lsp_diagnostics(&mut self,) {
+    self.changed |= !self.lsp_diagnostics.is_empty();
+    self.lsp_diagnostics.clear();
+  }
+
+  /// (错误数, 警告数)
+  pub fn counts(&self,) -> (usize, usize,) {
+    let diagnostics = self.diagnostics();
+    let count = |severity| {
+      diagnostics
+        .values()
+        .flatten()
+        .filter(|v| v.severity == severity,)
+        .count()
+    };
+    (count(Severity::Error,), count(Severity::Warning,),)
+  }
+
+  /// 在后台线程中运行检查命令
+  pub fn start_check(
+    &mut self,
+    ctx: &egui::Context,
+    root: Option<&Path,>,
+  ) {
+    let Some(root,) = root.map(|v| v.to_path_buf(),) else {
+      self.error = Some("当前未打开文件夹".to_string(),);
+      return;
+    };
+    self.error = None;
+    let command = self.command.clone();
+    let ctx = ctx.clone();
+    let (tx, rx,) = channel();
+    std::thread::spawn(move || {
+      let _ = tx.send(diagnostic::run_check(&command, &root,),);
+      ctx.request_repaint();
+    },);
+    self.rx = Some(rx,);
+  }
+
+  /// 检查完成时更新诊断，返回诊断是否发生变化
+  pub fn sync(&mut self,) -> bool {
+    let changed = std::mem::take(&mut self.changed,);
+    let Some(result,) = self.rx.as_ref().and_then(|v| v.try_recv().ok(),) else {
+      return changed;
+    };
+    self.rx = None;
+    match result {
+      Ok(diagnostics,) => {
+        self.diagnostics = diagnostic::group_by_file(diagnostics,);
+        true
+      }
+      Err(e,) => {
+        self.error = Some(e,);
+        changed
+      }
+    }
+  }
+}
+
+// ------------------------------------ UI
+
+impl ProblemsPanel {
+  pub fn show(
+    &mut self,
+    ui: &mut egui::Ui,
+    root: Option<&Path,>,
+    open_files: &mut OpenFiles,
+  ) {
+    let spacing_size = ui.spacing().item_spacing;
+    // 检查命令
+    ui.horizontal(|ui| {
+      ui.add_space(spacing_size.x,);
+      let command_edit = egui::TextEdit::singleline(&mut self.command,)
+        .hint_text(diagnostic::DEFAULT_CHECK_COMMAND,)
+        .desired_width(360.,);
+      let response = ui.add(command_edit,);
+      let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter,),);
+      if self.is_checking() {
+        ui.spinner();
+        ui.monospace("正在检查…",);
+      } else if ui.button("检查",).clicked() || enter {
+        self.start_check(ui.ctx(), root,);
+      }
+      if let Some(e,) = &self.error {
+        ui.colored_label(Severity::Error.color(), e,);
+      } else {
+        let (errors, warnings,) = self.counts();
+        ui.monospace(format!("{errors} 个错误，{warnings} 个警告"),);
+      }
+    },);
+    ui.separator();
+
+    let mut to_open = None;
+    egui::ScrollArea::both()
+      .id_source("problems_scroll_area",)
+      .auto_shrink([false, false,],)
+      .show(ui, |ui| {
+        for (path, diagnostics,) in self.diagnostics().iter() {
+          let name = path
+            .file_name()
+            .map(|v| v.to_string_lossy(),)
+            .unwrap_or_default();
+          let relative_path = root
+            .and_then(|v| path.strip_prefix(v,).ok(),)
+            .unwrap_or(path,);
+          let title = format!("{name} ({})", diagnostics.len());
+          egui::CollapsingHeader::new(title,)
+            .id_source(("problems", path,),)
+            .default_open(true,)
+            .show(ui, |ui| {
+              for d in diagnostics.iter() {
+                let job = diagnostic_layout_job(ui, d,);
+                if ui.add(egui::SelectableLabel::new(false, job,),).clicked() {
+                  to_open = Some(d.clone(),);
+                }
+              }
+            },)
+            .header_response
+            .on_hover_text(relative_path.to_string_lossy(),);
+        }
+      },);
+
+    // 打开文件并选中诊断的位置
+    if let Some(d,) = to_open {
+      match open_files.open_file(&d.path,) {
+        Ok(_,) => open_files.goto_position(d.start, d.end,),
+        Err(e,) => {
+          util::toaster()
+            .error(format!("无法读取文件：{:?}\nErr: {e}", d.path),)
+            .set_duration(Some(Duration::from_secs(5,),),);
+        }
+      }
+    }
+  }
+}
+
+/// `图标 消息 [代码] (行:列)`
+fn diagnostic_layout_job(
+  ui: &egui::Ui,
+  d: &Diagnostic,
+) -> egui::text::LayoutJob {
+  let format = |color| egui::TextFormat {
+    font_id: text::search_result_font(),
+    color,
+    ..Default::default()
+  };
+  let mut job = egui::text::LayoutJob::default();
+  job.append(
+    &format!("{} ", d.severity.icon()),
+    0.0,
+    format(d.severity.color(),),
+  );
+  // 多行消息只显示第一行
+  let message = d.message.lines().next().unwrap_or_default();
+  job.append(message, 0.0, format(ui.visuals().text_color(),),);
+  let code = d
+    .code
+    .as_ref()
+    .map(|v| format!(" [{v}]"),)
+    .unwrap_or_default();
+  job.append(
+    &format!("{code} ({}:{})", d.start.0 + 1, d.start.1 + 1),
+    0.0,
+    format(ui.visuals().weak_text_color(),),
+  );
+  job
+}
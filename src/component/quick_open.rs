@@ -0,0 +1,515 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+
+use eframe::egui;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::{hl, path_filter::PathFilter, style, text, util};
+
+use super::open_file::OpenFiles;
+
+/// 最多索引的文件数量
+const MAX_FILE_COUNT: usize = 200000;
+/// 最多显示的匹配项数量
+const MAX_RESULT_COUNT: usize = 100;
+/// 最多记录的最近打开的文件
+const MAX_RECENT_COUNT: usize = 50;
+/// 后台索引每次发回的文件数量
+const INDEX_BATCH_SIZE: usize = 1000;
+/// 索引期间最多每隔多久按新索引的文件重新匹配
+const REMATCH_INTERVAL: Duration = Duration::from_millis(300,);
+
+/// 后台索引线程发回的消息
+enum IndexMessage {
+  Files(Vec<String,>,),
+  Done,
+}
+
+/// 匹配的文件
+#[derive(Debug, Clone, PartialEq, Eq,)]
+struct FileMatch {
+  /// 在索引中的位置
+  index: usize,
+  /// 匹配的字符位置（char index）
+  indices: Vec<usize,>,
+}
+
+/// 快速打开文件（Ctrl+P）：模糊匹配打开的文件夹中的所有文件，`:行号` 跳转到指定行
+#[derive(Default,)]
+pub struct QuickOpen {
+  show: bool,
+  query: String,
+  request_focus: bool,
+  selected_index: usize,
+  /// 打开后在下一帧开始索引
+  need_index: bool,
+
+  // 索引
+  root: Option<PathBuf,>,
+  /// 相对于 root 的路径
+  files: Vec<String,>,
+  rx: Option<Receiver<IndexMessage,>,>,
+  cancel: Arc<AtomicBool,>,
+
+  results: Vec<FileMatch,>,
+  dirty: bool,
+  /// 索引了新的文件，还未重新匹配
+  index_changed: bool,
+  matched_at: Option<Instant,>,
+  /// 最近打开的文件，最近的在前
+  recent: VecDeque<PathBuf,>,
+}
+
+impl QuickOpen {
+  /// 打开时重新索引，保证与磁盘一致
+  pub fn open(
+    &mut self,
+    root: Option<&PathBuf,>,
+  ) {
+    self.show = true;
+    self.query.clear();
+    self.request_focus = true;
+    self.selected_index = 0;
+    self.root = root.cloned();
+    self.need_index = true;
+  }
+
+  pub fn is_show(&self,) -> bool {
+    self.show
+  }
+
+  pub fn close(&mut self,) {
+    self.show = false;
+    self.cancel();
+    self.files.clear();
+    self.results.clear();
+  }
+
+  fn is_indexing(&self,) -> bool {
+    self.rx.is_some()
+  }
+
+  fn cancel(&mut self,) {
+    self.cancel.store(true, Ordering::Relaxed,);
+    self.rx = None;
+  }
+
+  /// 在后台线程中索引 root 下的所有文件（应用文件树的过滤规则）
+  fn start_index(
+    &mut self,
+    ctx: &egui::Context,
+  ) {
+    self.cancel();
+    self.files.clear();
+    self.results.clear();
+    self.dirty = true;
+    let Some(root,) = self.root.clone() else {
+      return;
+    };
+
+    let (tx, rx,) = channel();
+    let cancel = Arc::new(AtomicBool::new(false,),);
+    self.rx = Some(rx,);
+    self.cancel = cancel.clone();
+
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+      puffin::profile_scope!("quick_open_index");
+      let path_filter = PathFilter::load(&root,);
+      let mut batch = Vec::with_capacity(INDEX_BATCH_SIZE,);
+      let files = path_filter
+        .walk_builder(&root,)
+        .build()
+        .filter_map(|v| v.ok(),)
+        .filter(|v| v.file_type().is_some_and(|t| t.is_file(),),)
+        .take(MAX_FILE_COUNT,);
+      for entry in files {
+        if cancel.load(Ordering::Relaxed,) {
+          return;
+        }
+        batch.push(relative_path(&root, entry.path(),),);
+        if batch.len() >= INDEX_BATCH_SIZE {
+          if tx
+            .send(IndexMessage::Files(std::mem::take(&mut batch,),),)
+            .is_err()
+          {
+            return;
+          }
+          ctx.request_repaint();
+        }
+      }
+      let _ = tx.send(IndexMessage::Files(batch,),);
+      let _ = tx.send(IndexMessage::Done,);
+      ctx.request_repaint();
+    },);
+  }
+
+  /// 接收后台索引的结果
+  fn receive_index(&mut self,) {
+    let Some(rx,) = &self.rx else {
+      return;
+    };
+    let mut done = false;
+    for msg in rx.try_iter() {
+      match msg {
+        IndexMessage::Files(files,) => {
+          self.files.extend(files,);
+          self.index_changed = true;
+        }
+        IndexMessage::Done => done = true,
+      }
+    }
+    if done {
+      self.rx = None;
+    }
+  }
+
+  /// 记录最近打开的文件
+  fn record_recent(
+    &mut self,
+    open_files: &OpenFiles,
+  ) {
+    let Some(path,) = open_files.current_file().map(|f| f.path(),) else {
+      return;
+    };
+    if self.recent.front() == Some(path,) {
+      return;
+    }
+    self.recent.retain(|v| v != path,);
+    self.recent.push_front(path.clone(),);
+    self.recent.truncate(MAX_RECENT_COUNT,);
+  }
+
+  /// 索引期间限制重新匹配的频率，索引完成后立即匹配
+  fn throttle_rematch(
+    &mut self,
+    ctx: &egui::Context,
+  ) {
+    if !self.index_changed {
+      return;
+    }
+    let elapsed = self.matched_at.map_or(REMATCH_INTERVAL, |v| v.elapsed(),);
+    if !self.is_indexing() || elapsed >= REMATCH_INTERVAL {
+      self.dirty = true;
+    } else {
+      ctx.request_repaint_after(REMATCH_INTERVAL - elapsed,);
+    }
+  }
+
+  fn update_results(&mut self,) {
+    if !self.dirty {
+      return;
+    }
+    self.dirty = false;
+    self.index_changed = false;
+    self.matched_at = Some(Instant::now(),);
+    let (pattern, _,) = parse_query(&self.query,);
+    let recent = match &self.root {
+      Some(root,) => self
+        .recent
+        .iter()
+        .filter(|v| v.starts_with(root,),)
+        .map(|v| relative_path(root, v,),)
+        .collect(),
+      None => vec![],
+    };
+    self.results = match_files(&self.files, pattern, &recent,);
+    self.selected_index = self
+      .selected_index
+      .min(self.results.len().saturating_sub(1,),);
+  }
+
+  /// 打开选中的文件，并跳转到指定行
+  fn confirm(
+    &mut self,
+    open_files: &mut OpenFiles,
+  ) {
+    let (pattern, line_number,) = parse_query(&self.query,);
+    // 只有 `:行号` 时在当前文件中跳转
+    if let (true, Some(line_number,),) = (pattern.is_empty(), line_number,) {
+      open_files.goto(line_number, None,);
+      self.close();
+      return;
+    }
+    let (Some(root,), Some(m,),) = (&self.root, self.results.get(self.selected_index,),) else {
+      return;
+    };
+    let path = root.join(&self.files[m.index],);
+    if let Err(e,) = open_files.open_file(&path,) {
+      util::toaster()
+        .error(format!("无法读取文件：{path:?}\nErr: {e}"),)
+        .set_duration(Some(Duration::from_secs(5,),),);
+      return;
+    }
+    if let Some(line_number,) = line_number {
+      open_files.goto(line_number, None,);
+    }
+    self.close();
+  }
+}
+
+/// 相对于 root 的路径，统一使用 `/` 分隔
+fn relative_path(
+  root: &Path,
+  path: &Path,
+) -> String {
+  let relative_path = path.strip_prefix(root,).unwrap_or(path,);
+  relative_path
+    .components()
+    .map(|v| v.as_os_str().to_string_lossy(),)
+    .collect::<Vec<_,>>()
+    .join("/",)
+}
+
+/// 拆分查询：`main.rs:12` => ("main.rs", Some(12))
+fn parse_query(query: &str,) -> (&str, Option<usize,>,) {
+  let query = query.trim();
+  match query.rsplit_once(':',) {
+    Some((pattern, line,),) if !line.is_empty() && line.chars().all(|c| c.is_ascii_digit(),) => {
+      (pattern.trim_end(), line.parse().ok(),)
+    }
+    _ => (query, None,),
+  }
+}
+
+/// 模糊匹配 files，最近打开的文件排在最前，其余按匹配分数排序
+fn match_files(
+  files: &[String],
+  pattern: &str,
+  recent: &[String],
+) -> Vec<FileMatch,> {
+  puffin::profile_function!();
+  let recent_rank = recent
+    .iter()
+    .enumerate()
+    .map(|(i, v,)| (v.as_str(), i,),)
+    .collect::<HashMap<_, _,>>();
+  // 分隔符不参与匹配
+  let pattern = pattern.replace(' ', "",);
+  let matcher = SkimMatcherV2::default().smart_case();
+  let mut results = files
+    .iter()
+    .enumerate()
+    .filter_map(|(index, file,)| {
+      let (score, indices,) = if pattern.is_empty() {
+        (0, vec![],)
+      } else {
+        matcher.fuzzy_indices(file, &pattern,)?
+      };
+      let rank = recent_rank
+        .get(file.as_str(),)
+        .copied()
+        .unwrap_or(usize::MAX,);
+      Some((
+        rank,
+        score,
+        FileMatch {
+          index,
+          indices,
+        },
+      ),)
+    },)
+    .collect::<Vec<_,>>();
+  results.sort_by(|a, b| {
+    a.0
+      .cmp(&b.0,)
+      .then_with(|| b.1.cmp(&a.1,),)
+      .then_with(|| files[a.2.index].len().cmp(&files[b.2.index].len(),),)
+  },);
+  results
+    .into_iter()
+    .take(MAX_RESULT_COUNT,)
+    .map(|v| v.2,)
+    .collect()
+}
+
+// ------------------------------------ UI
+
+impl QuickOpen {
+  pub fn show(
+    &mut self,
+    ctx: &egui::Context,
+    open_files: &mut OpenFiles,
+  ) {
+    self.record_recent(open_files,);
+    if !self.show {
+      return;
+    }
+    if std::mem::take(&mut self.need_index,) {
+      self.start_index(ctx,);
+    }
+    self.receive_index();
+    self.throttle_rematch(ctx,);
+    self.update_results();
+
+    let response = egui::Window::new("quick_open",)
+      .title_bar(false,)
+      .collapsible(false,)
+      .resizable(false,)
+      .anchor(egui::Align2::CENTER_TOP, egui::vec2(0., 40.,),)
+      .fixed_size(egui::vec2(500., 0.,),)
+      .show(ctx, |ui| self.show_ui(ui, open_files,),);
+
+    // 按 Esc 或点击外部时关闭
+    let clicked_outside = response.is_some_and(|r| {
+      ctx.input(|i| {
+        i.pointer.any_pressed()
+          && i
+            .pointer
+            .interact_pos()
+            .is_some_and(|pos| !r.response.rect.contains(pos,),)
+      },)
+    },);
+    if clicked_outside || ctx.input(|i| i.key_pressed(egui::Key::Escape,),) {
+      self.close();
+    }
+  }
+
+  fn show_ui(
+    &mut self,
+    ui: &mut egui::Ui,
+    open_files: &mut OpenFiles,
+  ) {
+    // 上下选择，需在 TextEdit 之前处理
+    let (up, down,) = ui.input_mut(|i| {
+      (
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp,),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown,),
+      )
+    },);
+    if up {
+      self.selected_index = self.selected_index.saturating_sub(1,);
+    }
+    if down && self.selected_index + 1 < self.results.len() {
+      self.selected_index += 1;
+    }
+
+    let query_edit = egui::TextEdit::singleline(&mut self.query,)
+      .hint_text("按名称搜索文件，追加 :行号 跳转到行",)
+      .desired_width(f32::INFINITY,);
+    let response = ui.add(query_edit,);
+    if std::mem::take(&mut self.request_focus,) {
+      response.request_focus();
+    }
+    if response.changed() {
+      self.dirty = true;
+      self.selected_index = 0;
+      self.update_results();
+    }
+    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter,),) {
+      self.confirm(open_files,);
+      return;
+    }
+
+    let (pattern, line_number,) = parse_query(&self.query,);
+    if self.root.is_none() {
+      ui.weak("当前未打开文件夹",);
+    } else if self.is_indexing() {
+      ui.weak(format!("正在索引… {} 个文件", self.files.len()),);
+    } else if self.results.is_empty() && !pattern.is_empty() {
+      ui.weak("没有匹配的文件",);
+    }
+    if let (true, Some(line_number,),) = (pattern.is_empty(), line_number,) {
+      ui.weak(format!("跳转到当前文件的第 {line_number} 行"),);
+      return;
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+      .max_height(400.,)
+      .auto_shrink([false, true,],)
+      .show(ui, |ui| {
+        for (i, m,) in self.results.iter().enumerate() {
+          let selected = i == self.selected_index;
+          let job = match_layout_job(ui, &self.files[m.index], &m.indices,);
+          let response = ui.add(egui::SelectableLabel::new(selected, job,),);
+          if selected && (up || down) {
+            response.scroll_to_me(None,);
+          }
+          if response.clicked() {
+            clicked = Some(i,);
+          }
+        }
+      },);
+    if let Some(i,) = clicked {
+      self.selected_index = i;
+      self.confirm(open_files,);
+    }
+  }
+}
+
+/// 模糊匹配的字符高亮显示，indices 为 char index
+pub fn match_layout_job(
+  ui: &egui::Ui,
+  label: &str,
+  indices: &[usize],
+) -> egui::text::LayoutJob {
+  let mut job = egui::text::LayoutJob::default();
+  job.append(
+    label,
+    0.0,
+    egui::TextFormat {
+      font_id: text::search_result_font(),
+      color: ui.visuals().text_color(),
+      ..Default::default()
+    },
+  );
+  let highlights = label
+    .char_indices()
+    .enumerate()
+    .filter(|(i, _,)| indices.contains(i,),)
+    .map(|(_, (start, c,),)| (start..start + c.len_utf8(), style::FIND_CURRENT_MATCH_COLOR,),)
+    .collect::<Vec<_,>>();
+  hl::layout::highlight_ranges(&mut job, &highlights,);
+  job
+}
+
+#[cfg(test)]
+mod test {
+  use super::{match_files, parse_query};
+
+  #[test]
+  fn test_match_files() {
+    assert_eq!(parse_query("main.rs:12"), ("main.rs", Some(12)));
+    assert_eq!(parse_query(" :7 "), ("", Some(7)));
+    assert_eq!(parse_query("a:b"), ("a:b", None));
+    assert_eq!(parse_query("main.rs:"), ("main.rs:", None));
+
+    let files = [
+      "src/main.rs",
+      "src/component/menu_bar.rs",
+      "README.md",
+      "src/app.rs",
+    ]
+    .map(String::from,);
+    let paths = |pattern: &str, recent: &[String]| {
+      match_files(&files, pattern, recent,)
+        .into_iter()
+        .map(|v| files[v.index].as_str(),)
+        .collect::<Vec<_,>>()
+    };
+    assert_eq!(paths("main", &[]), vec!["src/main.rs"]);
+    assert_eq!(
+      paths("mrs", &[]),
+      vec!["src/main.rs", "src/component/menu_bar.rs"]
+    );
+    // 最近打开的文件排在最前
+    assert_eq!(
+      paths("mrs", &["src/component/menu_bar.rs".into()]),
+      vec!["src/component/menu_bar.rs", "src/main.rs"]
+    );
+    assert_eq!(paths("", &["src/app.rs".into()])[0], "src/app.rs");
+    assert!(paths("xyz", &[]).is_empty());
+
+    let m = &match_files(&files, "main", &[],)[0];
+    assert_eq!(m.indices, vec![4, 5, 6, 7]);
+  }
+}
@@ -0,0 +1,140 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use eframe::egui;
+
+use crate::{buffer::Buffer, lsp::Location, search::LineMatch, util};
+
+use super::{open_file::OpenFiles, search_panel::preview_layout_job};
+
+/// 引用所在的行
+struct Reference {
+  location: Location,
+  preview: LineMatch,
+}
+
+/// 引用面板：按文件列出语言服务器找到的引用
+#[derive(Default,)]
+pub struct ReferencesPanel {
+  references: BTreeMap<PathBuf, Vec<Reference,>,>,
+}
+
+impl ReferencesPanel {
+  pub fn count(&self,) -> usize {
+    self.references.values().map(|v| v.len(),).sum()
+  }
+
+  /// 替换显示的引用，从磁盘读取引用所在的行
+  pub fn set_locations(
+    &mut self,
+    locations: Vec<Location,>,
+  ) {
+    self.references.clear();
+    let mut contents = BTreeMap::<PathBuf, Option<Buffer,>,>::new();
+    for location in locations {
+      let content = contents
+        .entry(location.path.clone(),)
+        .or_insert_with(|| {
+          std::fs::read_to_string(&location.path,)
+            .ok()
+            .map(|v| Buffer::from(v.as_str(),),)
+        },)
+        .as_ref();
+      let line = content.map_or(String::new(), |v| {
+        let line = location.start.0;
+        let len = v.line_len_chars(line,);
+        v.line(line,).chars().take(len,).collect()
+      },);
+      // 列为 char 索引，预览中的范围为 byte 索引
+      let byte_index = |column: usize| {
+        line
+          .char_indices()
+          .nth(column,)
+          .map_or(line.len(), |(i, _,)| i,)
+      };
+      let start = byte_index(location.start.1,);
+      let end = if location.end.0 == location.start.0 {
+        byte_index(location.end.1,)
+      } else {
+        line.len()
+      };
+      let range = start..end.max(start,);
+      let preview = LineMatch {
+        line_number: location.start.0 + 1,
+        ranges: vec![range],
+        line,
+      };
+      self
+        .references
+        .entry(location.path.clone(),)
+        .or_default()
+        .push(Reference {
+          location,
+          preview,
+        },);
+    }
+    for references in self.references.values_mut() {
+      references.sort_by_key(|v| v.location.start,);
+    }
+  }
+}
+
+// ------------------------------------ UI
+
+impl ReferencesPanel {
+  pub fn show(
+    &mut self,
+    ui: &mut egui::Ui,
+    root: Option<&Path,>,
+    open_files: &mut OpenFiles,
+  ) {
+    if self.references.is_empty() {
+      ui.weak("没有找到引用",);
+      return;
+    }
+    let mut to_open = None;
+    egui::ScrollArea::both()
+      .id_source("references_scroll_area",)
+      .auto_shrink([false, false,],)
+      .show(ui, |ui| {
+        for (path, references,) in self.references.iter() {
+          let name = path
+            .file_name()
+            .map(|v| v.to_string_lossy(),)
+            .unwrap_or_default();
+          let relative_path = root
+            .and_then(|v| path.strip_prefix(v,).ok(),)
+            .unwrap_or(path,);
+          let title = format!("{name} ({})", references.len());
+          egui::CollapsingHeader::new(title,)
+            .id_source(("references", path,),)
+            .default_open(true,)
+            .show(ui, |ui| {
+              for r in references.iter() {
+                let job = preview_layout_job(ui, &r.preview,);
+                if ui.add(egui::SelectableLabel::new(false, job,),).clicked() {
+                  to_open = Some(r.location.clone(),);
+                }
+              }
+            },)
+            .header_response
+            .on_hover_text(relative_path.to_string_lossy(),);
+        }
+      },);
+
+    // 打开文件并选中引用
+    if let Some(location,) = to_open {
+      match open_files.open_file(&location.path,) {
+        Ok(_,) => open_files.goto_position(location.start, location.end,),
+        Err(e,) => {
+          util::toaster()
+            .error(format!("无法读取文件：{:?}\nErr: {e}", location.path),)
+            .set_duration(Some(Duration::from_secs(5,),),);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,504 @@
+use std::{
+  path::PathBuf,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver},
+    Arc,
+  },
+  time::Duration,
+};
+
+use eframe::egui;
+
+use crate::{
+  hl,
+  path_filter::PathFilter,
+  search::{self, FileFilter, FileMatches, Matcher, SearchOptions},
+  style, text, ui, util,
+};
+
+use super::open_file::OpenFiles;
+
+/// 最多显示的匹配项数量，超出后停止查找
+const MAX_MATCH_COUNT: usize = 20000;
+
+/// 后台查找线程发回的消息
+enum SearchMessage {
+  File(FileMatches,),
+  /// complete 为 false 表示匹配项过多，提前停止了查找
+  Done {
+    searched: usize,
+    complete: bool,
+  },
+}
+
+/// 文件夹内 查找/替换 面板
+pub struct SearchPanel {
+  query: String,
+  replacement: String,
+  include: String,
+  exclude: String,
+  options: SearchOptions,
+  show_replace: bool,
+  show_filter: bool,
+
+  // 查找结果
+  root: Option<PathBuf,>,
+  matcher: Option<Matcher,>,
+  results: Vec<FileMatches,>,
+  match_count: usize,
+  searched: Option<usize,>,
+  /// 查找已完成且没有提前停止，此时才能全部替换
+  complete: bool,
+  error: Option<String,>,
+
+  // 后台查找
+  rx: Option<Receiver<SearchMessage,>,>,
+  cancel: Arc<AtomicBool,>,
+  dirty: bool,
+}
+
+impl Default for SearchPanel {
+  fn default() -> Self {
+    Self {
+      query: String::new(),
+      replacement: String::new(),
+      include: String::new(),
+      exclude: ".git, target, node_modules".into(),
+      options: SearchOptions::default(),
+      show_replace: false,
+      show_filter: false,
+      root: None,
+      matcher: None,
+      results: vec![],
+      match_count: 0,
+      searched: None,
+      complete: false,
+      error: None,
+      rx: None,
+      cancel: Arc::new(AtomicBool::new(false,),),
+      dirty: false,
+    }
+  }
+}
+
+impl SearchPanel {
+  fn is_searching(&self,) -> bool {
+    self.rx.is_some()
+  }
+
+  fn cancel(&mut self,) {
+    self.cancel.store(true, Ordering::Relaxed,);
+    self.rx = None;
+    self.complete = false;
+  }
+
+  /// 在后台线程中查找 root 下的所有文件
+  fn start_search(
+    &mut self,
+    ctx: &egui::Context,
+    root: Option<&PathBuf,>,
+  ) {
+    self.cancel();
+    self.results.clear();
+    self.match_count = 0;
+    self.searched = None;
+    self.error = None;
+    self.matcher = None;
+    self.root = root.cloned();
+
+    let Some(root,) = root.cloned() else {
+      return;
+    };
+    if self.query.is_empty() {
+      return;
+    }
+    let matcher = match Matcher::new(&self.query, self.options,) {
+      Ok(v,) => v,
+      Err(e,) => {
+        self.error = Some(e,);
+        return;
+      }
+    };
+    let filter = match FileFilter::new(&self.include, &self.exclude,) {
+      Ok(v,) => v,
+      Err(e,) => {
+        self.error = Some(e,);
+        return;
+      }
+    };
+    self.matcher = Some(matcher.clone(),);
+
+    let (tx, rx,) = channel();
+    let cancel = Arc::new(AtomicBool::new(false,),);
+    self.rx = Some(rx,);
+    self.cancel = cancel.clone();
+
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+      puffin::profile_scope!("search_dir");
+      let mut match_count = 0;
+      let path_filter = PathFilter::load(&root,);
+      let searched = search::search_dir(
+        &root,
+        &matcher,
+        &filter,
+        &path_filter,
+        &cancel,
+        |file_matches| {
+          match_count += file_matches.match_count();
+          let ok = tx.send(SearchMessage::File(file_matches,),).is_ok();
+          ctx.request_repaint();
+          ok && match_count < MAX_MATCH_COUNT
+        },
+      );
+      let _ = tx.send(SearchMessage::Done {
+        searched,
+        complete: match_count < MAX_MATCH_COUNT,
+      },);
+      ctx.request_repaint();
+    },);
+  }
+
+  /// 接收后台查找结果
+  fn poll_results(&mut self,) {
+    let Some(rx,) = self.rx.as_ref() else {
+      return;
+    };
+    let mut done = false;
+    while let Ok(msg,) = rx.try_recv() {
+      match msg {
+        SearchMessage::File(file_matches,) => {
+          self.match_count += file_matches.match_count();
+          self.results.push(file_matches,);
+        }
+        SearchMessage::Done {
+          searched,
+          complete,
+        } => {
+          self.searched = Some(searched,);
+          self.complete = complete;
+          done = true;
+        }
+      }
+    }
+    if done {
+      self.rx = None;
+    }
+  }
+
+  /// 替换所有文件中的匹配项
+  ///
+  /// 已打开且未保存的文件会被跳过，已打开且已保存的文件替换后重新读取；
+  /// 查找未完成时结果不完整，不替换
+  fn replace_all(
+    &mut self,
+    open_files: &mut OpenFiles,
+  ) {
+    if !self.complete || self.dirty {
+      return;
+    }
+    let Some(matcher,) = self.matcher.as_ref() else {
+      return;
+    };
+    let mut replaced_files = 0;
+    let mut replaced = 0;
+    let mut skipped: Vec<&PathBuf,> = vec![];
+    for file_matches in self.results.iter() {
+      let path = &file_matches.path;
+      if open_files.is_changed(path,) {
+        skipped.push(path,);
+        continue;
+      }
+      match search::replace_in_file(path, matcher, &self.replacement,) {
+        Ok(count,) => {
+          replaced += count;
+          replaced_files += 1;
+          if let Err(e,) = open_files.reload_file(path,) {
+            util::toaster()
+              .error(format!("重新读取文件失败：{path:?}\nErr: {e}"),)
+              .set_duration(Some(Duration::from_secs(5,),),);
+          }
+        }
+        Err(e,) => {
+          util::toaster()
+            .error(format!("替换失败：{path:?}\nErr: {e}"),)
+            .set_duration(Some(Duration::from_secs(5,),),);
+        }
+      }
+    }
+
+    util::toaster()
+      .info(format!("已在 {replaced_files} 个文件中替换 {replaced} 处"),)
+      .set_duration(Some(Duration::from_secs(3,),),);
+    if !skipped.is_empty() {
+      util::toaster()
+        .warning(format!("未保存文件已跳过：{skipped:?}"),)
+        .set_duration(Some(Duration::from_secs(5,),),);
+    }
+    self.dirty = true;
+  }
+}
+
+// ------------------------------------ UI
+
+impl SearchPanel {
+  pub fn show(
+    &mut self,
+    ui: &mut egui::Ui,
+    root: Option<&PathBuf,>,
+    open_files: &mut OpenFiles,
+  ) {
+    puffin::profile_function!();
+
+    // 打开的文件夹发生变更，重新查找
+    if self.root.as_ref() != root {
+      self.dirty = true;
+    }
+
+    ui.style_mut().wrap = Some(false,);
+    // 标题
+    ui::top_panel("search_top", ui.ctx(),)
+      .exact_height(style::EXPLORER_TOP_HEIGHT,)
+      .show_inside(ui, |ui| {
+        ui.horizontal_centered(|ui| {
+          let spacing_size = ui.spacing().item_spacing;
+          ui.add_space(spacing_size.x,);
+          ui.heading("搜索",);
+        },);
+      },);
+    // 查找条件
+    ui::top_panel("search_inputs", ui.ctx(),).show_inside(ui, |ui| {
+      self.show_inputs(ui, root.is_some(), open_files,);
+    },);
+
+    if self.dirty {
+      self.dirty = false;
+      self.start_search(ui.ctx(), root,);
+    }
+    self.poll_results();
+
+    // 查找结果
+    ui::central_panel(ui.ctx(),).show_inside(ui, |ui| {
+      self.show_results(ui, root, open_files,);
+    },);
+  }
+
+  fn show_inputs(
+    &mut self,
+    ui: &mut egui::Ui,
+    has_root: bool,
+    open_files: &mut OpenFiles,
+  ) {
+    let spacing_size = ui.spacing().item_spacing;
+    let input_width = ui.available_width() - spacing_size.x * 4.;
+    ui.add_space(spacing_size.y,);
+    // 查找
+    ui.horizontal(|ui| {
+      ui.add_space(spacing_size.x,);
+      let toggle_text = if self.show_replace { "▼" } else { "▶" };
+      if ui.small_button(toggle_text,).clicked() {
+        self.show_replace = !self.show_replace;
+      }
+      let query_edit = egui::TextEdit::singleline(&mut self.query,)
+        .hint_text("搜索",)
+        .desired_width(input_width - 120.,);
+      if ui.add(query_edit,).changed() {
+        self.dirty = true;
+      }
+      let mut options = self.options;
+      ui.toggle_value(&mut options.case_sensitive, "Aa",)
+        .on_hover_text("区分大小写",);
+      ui.toggle_value(&mut options.whole_word, "W",)
+        .on_hover_text("全字匹配",);
+      ui.toggle_value(&mut options.regex, ".*",)
+        .on_hover_text("使用正则表达式",);
+      if options != self.options {
+        self.options = options;
+        self.dirty = true;
+      }
+    },);
+    // 替换
+    if self.show_replace {
+      ui.horizontal(|ui| {
+        ui.add_space(spacing_size.x,);
+        ui.add_visible(false, egui::Button::new("▶",).small(),);
+        let replace_edit = egui::TextEdit::singleline(&mut self.replacement,)
+          .hint_text("替换",)
+          .desired_width(input_width - 120.,);
+        ui.add(replace_edit,);
+        let enabled = self.complete && !self.dirty && !self.results.is_empty();
+        if ui
+          .add_enabled(enabled, egui::Button::new("全部替换",),)
+          .on_hover_text("替换所有文件中的匹配项",)
+          .on_disabled_hover_text("查找完成后才能全部替换，匹配项过多或停止查找时不可用",)
+          .clicked()
+        {
+          self.replace_all(open_files,);
+        }
+      },);
+    }
+    // 过滤
+    ui.horizontal(|ui| {
+      ui.add_space(spacing_size.x,);
+      ui.toggle_value(&mut self.show_filter, "…",)
+        .on_hover_text("切换搜索详细信息",);
+    },);
+    if self.show_filter {
+      ui.horizontal(|ui| {
+        ui.add_space(spacing_size.x,);
+        let include_edit = egui::TextEdit::singleline(&mut self.include,)
+          .hint_text("包含的文件，例如 *.rs, src/**",)
+          .desired_width(input_width,);
+        if ui.add(include_edit,).changed() {
+          self.dirty = true;
+        }
+      },);
+      ui.horizontal(|ui| {
+        ui.add_space(spacing_size.x,);
+        let exclude_edit = egui::TextEdit::singleline(&mut self.exclude,)
+          .hint_text("排除的文件，例如 target, *.lock",)
+          .desired_width(input_width,);
+        if ui.add(exclude_edit,).changed() {
+          self.dirty = true;
+        }
+      },);
+    }
+    // 状态
+    ui.horizontal(|ui| {
+      ui.add_space(spacing_size.x,);
+      if !has_root {
+        ui.monospace("当前未打开文件夹",);
+      } else if let Some(e,) = &self.error {
+        ui.monospace(e,);
+      } else if self.is_searching() {
+        ui.spinner();
+        ui.monospace(format!(
+          "{} 个文件中有 {} 个结果",
+          self.results.len(),
+          self.match_count
+        ),);
+        if ui.small_button("停止",).clicked() {
+          self.cancel();
+        }
+      } else if let Some(searched,) = self.searched {
+        ui.monospace(format!(
+          "已搜索 {searched} 个文件，{} 个文件中有 {} 个结果{}",
+          self.results.len(),
+          self.match_count,
+          if self.complete {
+            ""
+          } else {
+            "（结果过多，已停止）"
+          },
+        ),);
+      }
+    },);
+    ui.add_space(spacing_size.y,);
+  }
+
+  fn show_results(
+    &mut self,
+    ui: &mut egui::Ui,
+    root: Option<&PathBuf,>,
+    open_files: &mut OpenFiles,
+  ) {
+    let mut to_open = None;
+    egui::ScrollArea::both()
+      .auto_shrink([false, false,],)
+      .show(ui, |ui| {
+        for file_matches in self.results.iter() {
+          let path = &file_matches.path;
+          let name = path
+            .file_name()
+            .map(|v| v.to_string_lossy(),)
+            .unwrap_or_default();
+          let relative_path = root
+            .and_then(|v| path.strip_prefix(v,).ok(),)
+            .unwrap_or(path,);
+          let title = format!("{name} ({})", file_matches.match_count());
+          egui::CollapsingHeader::new(title,)
+            .id_source(path,)
+            .default_open(true,)
+            .show(ui, |ui| {
+              for line_match in file_matches.lines.iter() {
+                let job = preview_layout_job(ui, line_match,);
+                let response = ui.add(egui::SelectableLabel::new(false, job,),);
+                if response.clicked() {
+                  to_open = Some((
+                    path.clone(),
+                    line_match.line_number,
+                    line_match.ranges[0].clone(),
+                  ),);
+                }
+              }
+            },)
+            .header_response
+            .on_hover_text(relative_path.to_string_lossy(),);
+        }
+      },);
+
+    // 打开文件并跳转到匹配项
+    if let Some((path, line_number, range,),) = to_open {
+      match open_files.open_file(&path,) {
+        Ok(_,) => open_files.goto(line_number, Some(range,),),
+        Err(e,) => {
+          util::toaster()
+            .error(format!("无法读取文件：{path:?}\nErr: {e}"),)
+            .set_duration(Some(Duration::from_secs(5,),),);
+        }
+      }
+    }
+  }
+}
+
+/// 匹配行预览：`行号  行内容`，匹配项高亮显示
+pub fn preview_layout_job(
+  ui: &egui::Ui,
+  line_match: &search::LineMatch,
+) -> egui::text::LayoutJob {
+  // 去掉行首空白，并限制预览长度
+  const MAX_PREVIEW_LEN: usize = 200;
+  let line = &line_match.line;
+  let trimmed = line.trim_start();
+  let trim_offset = line.len() - trimmed.len();
+  let mut preview_len = trimmed.len().min(MAX_PREVIEW_LEN,);
+  while !trimmed.is_char_boundary(preview_len,) {
+    preview_len -= 1;
+  }
+  let preview = &trimmed[..preview_len];
+
+  let prefix = format!("{:>4}  ", line_match.line_number);
+  let mut job = egui::text::LayoutJob::default();
+  job.append(
+    &prefix,
+    0.0,
+    egui::TextFormat {
+      font_id: text::search_result_font(),
+      color: ui.visuals().weak_text_color(),
+      ..Default::default()
+    },
+  );
+  job.append(
+    preview,
+    0.0,
+    egui::TextFormat {
+      font_id: text::search_result_font(),
+      color: ui.visuals().text_color(),
+      ..Default::default()
+    },
+  );
+
+  let highlights = line_match
+    .ranges
+    .iter()
+    .filter(|r| r.start >= trim_offset,)
+    .map(|r| {
+      let start = r.start - trim_offset + prefix.len();
+      let end = (r.end - trim_offset).min(preview_len,) + prefix.len();
+      (start..end, style::FIND_CURRENT_MATCH_COLOR,)
+    },)
+    .collect::<Vec<_,>>();
+  hl::layout::highlight_ranges(&mut job, &highlights,);
+  job
+}
@@ -2,7 +2,7 @@ use std::sync::mpsc::SyncSender;
 
 use crate::{
   action::{self, Action},
-  frame_history, id, style, ui,
+  frame_history, hl, id, style, ui,
 };
 
 use super::open_file::OpenFile;
@@ -14,8 +14,11 @@ pub enum ItemId {
   FilePath,
   FileEncoding,
   FileLineEnding,
+  FileLanguage,
   CursorStat,
   Fps,
+  /// 组合键中已按下的按键
+  PendingChord,
 }
 
 #[derive(Debug, Clone, Copy,)]
@@ -31,6 +34,8 @@ impl ItemId {
     ui: &mut egui::Ui,
     region: Region,
     file: Option<&OpenFile,>,
+    pending_chord: Option<&str,>,
+    tx: &SyncSender<Action,>,
   ) {
     let rich_text = match self {
       ItemId::FilePath => {
@@ -49,6 +54,7 @@ impl ItemId {
       }
       ItemId::FileEncoding => file.map(|f| egui::RichText::new(f.encoding().name(),),),
       ItemId::FileLineEnding => file.map(|f| egui::RichText::new(f.line_ending().as_str(),),),
+      ItemId::FileLanguage => file.map(|f| egui::RichText::new(f.language(),),),
       ItemId::CursorStat => {
         if let Some(f,) = file {
           if let Some((row, col, selected,),) = f.cursor_stat() {
@@ -70,6 +76,9 @@ impl ItemId {
         ),);
         Some(rich_text,)
       }
+      ItemId::PendingChord => {
+        pending_chord.map(|v| egui::RichText::new(format!("({v}) 等待下一个按键…"),),)
+      }
     };
 
     if rich_text.is_none() {
@@ -84,7 +93,43 @@ impl ItemId {
       Region::Right => rich_text.strong(),
     };
 
-    ui.monospace(rich_text,);
+    match (self, file,) {
+      // 点击选择语言
+      (ItemId::FileLanguage, Some(f,),) => {
+        let response = ui
+          .add(egui::Label::new(rich_text.monospace(),).sense(egui::Sense::click(),),)
+          .on_hover_text("选择语言",);
+        let popup_id = ui.make_persistent_id("status_bar_language_popup",);
+        if response.clicked() {
+          ui.memory_mut(|m| m.toggle_popup(popup_id,),);
+        }
+        egui::popup::popup_above_or_below_widget(
+          ui,
+          popup_id,
+          &response,
+          egui::AboveOrBelow::Above,
+          |ui| {
+            ui.set_min_width(style::LANGUAGE_POPUP_WIDTH,);
+            egui::ScrollArea::vertical()
+              .max_height(style::LANGUAGE_POPUP_MAX_HEIGHT,)
+              .show(ui, |ui| {
+                if ui.selectable_label(false, "自动检测",).clicked() {
+                  let _ = tx.send(Action::SetLanguage(None,),);
+                }
+                ui.separator();
+                for name in hl::language_names() {
+                  if ui.selectable_label(name == f.language(), &name,).clicked() {
+                    let _ = tx.send(Action::SetLanguage(Some(name,),),);
+                  }
+                }
+              },);
+          },
+        );
+      }
+      _ => {
+        ui.monospace(rich_text,);
+      }
+    }
   }
 }
 
@@ -129,6 +174,8 @@ impl StatusBar {
     };
     slf
       .push_left(ItemId::Fps,)
+      .push_left(ItemId::PendingChord,)
+      .push_right(ItemId::FileLanguage,)
       .push_right(ItemId::FileLineEnding,)
       .push_right(ItemId::FileEncoding,)
       .push_right(ItemId::CursorStat,)
@@ -144,6 +191,7 @@ impl StatusBar {
     ui: &mut egui::Ui,
     show: bool,
     file: Option<&OpenFile,>,
+    pending_chord: Option<&str,>,
   ) {
     ui.style_mut().wrap = Some(false,);
     ui::bottom_panel(id::STATUS_BAR, ui.ctx(),)
@@ -160,7 +208,7 @@ impl StatusBar {
             ui.add_space(spacing_size.x,);
 
             for item in self.left.iter() {
-              item.show(ui, Region::Left, file,);
+              item.show(ui, Region::Left, file, pending_chord, &self.tx,);
             }
           },);
 
@@ -172,7 +220,7 @@ impl StatusBar {
                 // 右侧填充
                 ui.add_space(spacing_size.x,);
                 for item in self.right.iter() {
-                  item.show(ui, Region::Right, file,);
+                  item.show(ui, Region::Right, file, pending_chord, &self.tx,);
                 }
               },);
             },);
@@ -184,7 +232,7 @@ impl StatusBar {
               .show(ui, |ui| {
                 ui.horizontal_centered(|ui| {
                   for item in self.center.iter() {
-                    item.show(ui, Region::Center, file,);
+                    item.show(ui, Region::Center, file, pending_chord, &self.tx,);
                   }
                 },);
               },);
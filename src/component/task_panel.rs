@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+
+use crate::{
+  diagnostic::{Diagnostic, Severity},
+  task::{self, ProblemMatcher, TaskDef, TaskRun},
+};
+
+use super::terminal_panel::{self, TerminalView};
+
+/// 输出的初始大小，显示时按面板大小调整
+const INITIAL_SIZE: (usize, usize,) = (24, 80,);
+
+/// 任务的运行状态
+enum TaskStatus {
+  Idle,
+  Running,
+  Cancelled,
+  Exited(std::process::ExitStatus,),
+  Failed(String,),
+}
+
+/// 任务面板：运行 .egui_code/tasks.toml 中定义的任务，显示任务的输出
+pub struct TaskPanel {
+  tasks: Vec<TaskDef,>,
+  /// 最后运行的任务，任务定义重新加载后也可以重新运行
+  last_task: Option<TaskDef,>,
+  /// 任务的工作目录，用于匹配问题中的相对路径
+  cwd: PathBuf,
+  run: Option<TaskRun,>,
+  status: TaskStatus,
+  view: TerminalView,
+  /// 全部输出，任务结束后用于匹配问题
+  output: Vec<u8,>,
+}
+
+impl Default for TaskPanel {
+  fn default() -> Self {
+    Self {
+      tasks: vec![],
+      last_task: None,
+      cwd: PathBuf::new(),
+      run: None,
+      status: TaskStatus::Idle,
+      view: TerminalView::new(INITIAL_SIZE.0, INITIAL_SIZE.1,),
+      output: vec![],
+    }
+  }
+}
+
+impl TaskPanel {
+  pub fn task_names(&self,) -> Vec<String,> {
+    self.tasks.iter().map(|v| v.name.clone(),).collect()
+  }
+
+  pub fn is_running(&self,) -> bool {
+    matches!(self.status, TaskStatus::Running)
+  }
+
+  /// 重新读取打开的文件夹中的任务定义
+  pub fn load_tasks(
+    &mut self,
+    root: Option<&Path,>,
+  ) -> Result<(), String,> {
+    self.tasks = vec![];
+    if let Some(root,) = root {
+      self.tasks = task::load_tasks(root,)?;
+    }
+    Ok((),)
+  }
+
+  /// 运行名为 name 的任务，正在运行的任务会被取消
+  pub fn run_task(
+    &mut self,
+    ctx: &egui::Context,
+    root: Option<&Path,>,
+    name: &str,
+  ) -> Result<(), String,> {
+    let task = self
+      .tasks
+      .iter()
+      .find(|v| v.name == name,)
+      .cloned()
+      .ok_or_else(|| format!("任务 {name} 不存在"),)?;
+    self.start(ctx, root, task,)
+  }
+
+  /// 重新运行最后运行的任务
+  pub fn rerun_task(
+    &mut self,
+    ctx: &egui::Context,
+    root: Option<&Path,>,
+  ) -> Result<(), String,> {
+    let Some(last_task,) = &self.last_task else {
+      return Err("还没有运行过任务".to_string(),);
+    };
+    // 优先使用重新加载后的任务定义
+    let task = self
+      .tasks
+      .iter()
+      .find(|v| v.name == last_task.name,)
+      .unwrap_or(last_task,)
+      .clone();
+    self.start(ctx, root, task,)
+  }
+
+  fn start(
+    &mut self,
+    ctx: &egui::Context,
+    root: Option<&Path,>,
+    task: TaskDef,
+  ) -> Result<(), String,> {
+    let Some(root,) = root else {
+      return Err("当前未打开文件夹".to_string(),);
+    };
+    self.cancel_task();
+    self.run = None;
+    self.output.clear();
+    let (rows, cols,) = self.view.term().screen().size();
+    self.view = TerminalView::new(rows, cols,);
+    self.cwd = task.cwd(root,);
+    self
+      .view
+      .advance(format!("\x1b[2m> {}\x1b[0m\r\n", task.command_line()).as_bytes(),);
+    let result = TaskRun::spawn(&task, root, ctx.clone(),);
+    self.last_task = Some(task,);
+    match result {
+      Ok(run,) => {
+        self.run = Some(run,);
+        self.status = TaskStatus::Running;
+        Ok((),)
+      }
+      Err(e,) => {
+        let e = format!("无法运行任务：{e}");
+        self.status = TaskStatus::Failed(e.clone(),);
+        Err(e,)
+      }
+    }
+  }
+
+  /// 取消正在运行的任务
+  pub fn cancel_task(&mut self,) {
+    if !self.is_running() {
+      return;
+    }
+    if let Some(run,) = &mut self.run {
+      run.cancel();
+      self.status = TaskStatus::Cancelled;
+    }
+  }
+
+  /// 读取任务的输出，任务结束且定义了 problem_matcher 时返回匹配的问题
+  pub fn sync(&mut self,) -> Option<Vec<Diagnostic,>,> {
+    let run = self.run.as_mut()?;
+    let (bytes, status,) = run.read();
+    if !bytes.is_empty() {
+      // 输出不是终端，换行时需要回到行首
+      let mut converted = Vec::with_capacity(bytes.len(),);
+      for &b in bytes.iter() {
+        if b == b'\n' {
+          converted.push(b'\r',);
+        }
+        converted.push(b,);
+      }
+      self.view.advance(&converted,);
+      self.output.extend(bytes,);
+    }
+    let status = status?;
+    self.run = None;
+    // 取消的任务不匹配问题
+    if !self.is_running() {
+      return None;
+    }
+    self.status = TaskStatus::Exited(status,);
+    let matcher = self.last_task.as_ref()?.problem_matcher.as_ref()?;
+    let matcher = ProblemMatcher::new(matcher,).ok()?;
+    Some(matcher.find_all(&String::from_utf8_lossy(&self.output,), &self.cwd,),)
+  }
+}
+
+// ------------------------------------ UI
+
+impl TaskPanel {
+  /// 显示在底部面板的标签栏中：任务名称、状态和操作按钮
+  pub fn show_tab_bar(
+    &mut self,
+    ui: &mut egui::Ui,
+    root: Option<&Path,>,
+  ) {
+    let Some(task,) = &self.last_task else {
+      ui.weak("没有运行的任务",);
+      return;
+    };
+    ui.monospace(&task.name,);
+    match &self.status {
+      TaskStatus::Idle => {}
+      TaskStatus::Running => {
+        ui.spinner();
+      }
+      TaskStatus::Cancelled => {
+        ui.weak("已取消",);
+      }
+      TaskStatus::Exited(status,) if status.success() => {
+        ui.weak("已完成",);
+      }
+      TaskStatus::Exited(status,) => {
+        let text = status
+          .code()
+          .map_or("已退出".to_string(), |v| format!("已退出，退出码 {v}"),);
+        ui.colored_label(Severity::Error.color(), text,);
+      }
+      TaskStatus::Failed(e,) => {
+        ui.colored_label(Severity::Error.color(), e,);
+      }
+    }
+    if self.is_running() {
+      if ui.button("停止",).clicked() {
+        self.cancel_task();
+      }
+    } else if ui.button("重新运行",).clicked() {
+      let ctx = ui.ctx().clone();
+      if let Err(e,) = self.rerun_task(&ctx, root,) {
+        self.status = TaskStatus::Failed(e,);
+      }
+    }
+  }
+
+  /// 显示任务的输出
+  pub fn show_output(
+    &mut self,
+    ui: &mut egui::Ui,
+  ) {
+    let layout = terminal_panel::allocate_grid(ui, egui::Id::new("task_output",),);
+    self.view.resize(layout.rows, layout.cols,);
+    self.view.interact(ui, &layout,);
+    self.view.paint(ui, &layout, None,);
+  }
+}
@@ -0,0 +1,774 @@
+use std::{path::Path, time::Duration};
+
+use eframe::egui::{self, Color32, Key, Modifiers, Sense};
+
+use crate::{
+  font::NerdFont,
+  pty::{self, Pty},
+  style,
+  terminal::{self, Cell, CellStyle, Terminal, WIDE_CHAR_SPACER},
+  text, util,
+};
+
+/// 新建终端时的初始大小，显示时按面板大小调整
+const INITIAL_SIZE: (usize, usize,) = (24, 80,);
+
+/// 终端内容的显示状态：回滚缓冲的滚动位置和选区
+pub struct TerminalView {
+  term: Terminal,
+  /// 向上滚动的行数，0 表示显示最新的内容
+  scroll_offset: usize,
+  /// 选区 (起点, 终点)，位置为 (行, 列)
+  selection: Option<((usize, usize,), (usize, usize,),),>,
+}
+
+/// 终端内容占用的区域和网格大小
+pub struct GridLayout {
+  pub response: egui::Response,
+  /// 去掉留白后的区域
+  pub rect: egui::Rect,
+  pub cell_width: f32,
+  pub row_height: f32,
+  pub rows: usize,
+  pub cols: usize,
+}
+
+/// 分配 ui 中剩余的全部区域，按区域大小计算网格的行列数
+pub fn allocate_grid(
+  ui: &mut egui::Ui,
+  id: egui::Id,
+) -> GridLayout {
+  let font_id = text::text_editor_font();
+  let (cell_width, row_height,) =
+    ui.fonts(|f| (f.glyph_width(&font_id, 'M',), f.row_height(&font_id,),),);
+  let rect = ui.available_rect_before_wrap();
+  ui.allocate_rect(rect, Sense::hover(),);
+  let response = ui.interact(rect, id, Sense::click_and_drag(),);
+  let content_rect = rect.shrink(style::TERMINAL_PADDING,);
+  GridLayout {
+    response,
+    rect: content_rect,
+    cell_width,
+    row_height,
+    rows: ((content_rect.height() / row_height) as usize).max(1,),
+    cols: ((content_rect.width() / cell_width) as usize).max(2,),
+  }
+}
+
+impl TerminalView {
+  pub fn new(
+    rows: usize,
+    cols: usize,
+  ) -> Self {
+    Self {
+      term: Terminal::new(rows, cols,),
+      scroll_offset: 0,
+      selection: None,
+    }
+  }
+
+  pub fn term(&self,) -> &Terminal {
+    &self.term
+  }
+
+  /// 解析输出，向上滚动时保持显示的内容不变
+  pub fn advance(
+    &mut self,
+    bytes: &[u8],
+  ) {
+    let scrollback_len = self.term.screen().scrollback_len();
+    self.term.advance(bytes,);
+    if self.scroll_offset > 0 {
+      let scrollback_len_now = self.term.screen().scrollback_len();
+      let added = scrollback_len_now.saturating_sub(scrollback_len,);
+      self.scroll_offset = (self.scroll_offset + added).min(scrollback_len_now,);
+    }
+  }
+
+  pub fn take_responses(&mut self,) -> Vec<u8,> {
+    self.term.take_responses()
+  }
+
+  /// 滚动到最新的内容并清除选区
+  pub fn scroll_to_bottom(&mut self,) {
+    self.scroll_offset = 0;
+    self.selection = None;
+  }
+
+  /// 滚动 lines 行，正数向上
+  fn scroll(
+    &mut self,
+    lines: isize,
+  ) {
+    let scrollback_len = self.term.screen().scrollback_len() as isize;
+    self.scroll_offset = (self.scroll_offset as isize + lines).clamp(0, scrollback_len,) as usize;
+  }
+
+  /// 调整大小，返回大小是否发生变化
+  pub fn resize(
+    &mut self,
+    rows: usize,
+    cols: usize,
+  ) -> bool {
+    if self.term.screen().size() == (rows, cols,) {
+      return false;
+    }
+    self.term.resize(rows, cols,);
+    self.scroll_offset = self.scroll_offset.min(self.term.screen().scrollback_len(),);
+    self.selection = None;
+    true
+  }
+
+  /// 选中的文本
+  pub fn selected_text(&self,) -> Option<String,> {
+    let (start, end,) = self.selection?;
+    let (start, end,) = if start <= end {
+      (start, end,)
+    } else {
+      (end, start,)
+    };
+    let text = self.term.screen().text(start, end,);
+    (!text.is_empty()).then_some(text,)
+  }
+
+  /// 可见的第一行
+  fn first_line(&self,) -> usize {
+    let scrollback_len = self.term.screen().scrollback_len();
+    scrollback_len - self.scroll_offset.min(scrollback_len,)
+  }
+
+  /// 滚轮滚动，拖动选择，右键菜单复制
+  pub fn interact(
+    &mut self,
+    ui: &egui::Ui,
+    layout: &GridLayout,
+  ) {
+    let response = &layout.response;
+    if response.hovered() {
+      let scroll_delta = ui.input(|i| i.scroll_delta.y,);
+      let lines = (scroll_delta / layout.row_height).round() as isize;
+      if lines != 0 {
+        self.scroll(lines,);
+      }
+    }
+
+    let first_line = self.first_line();
+    let pos_at = |pos: egui::Pos2| {
+      let row = ((pos.y - layout.rect.top()) / layout.row_height)
+        .floor()
+        .max(0.,) as usize;
+      let col = ((pos.x - layout.rect.left()) / layout.cell_width)
+        .round()
+        .max(0.,) as usize;
+      (
+        first_line + row.min(layout.rows - 1,),
+        col.min(layout.cols,),
+      )
+    };
+    if let Some(pos,) = response.interact_pointer_pos() {
+      if response.drag_started() {
+        self.selection = Some((pos_at(pos,), pos_at(pos,),),);
+      } else if response.dragged() {
+        if let Some((_, end,),) = self.selection.as_mut() {
+          *end = pos_at(pos,);
+        }
+      }
+    }
+    if response.clicked() {
+      self.selection = None;
+    }
+
+    let selected_text = self.selected_text();
+    response.clone().context_menu(|ui| {
+      if ui
+        .add_enabled(selected_text.is_some(), egui::Button::new("复制",),)
+        .clicked()
+      {
+        ui.close_menu();
+        if let Some(text,) = selected_text.clone() {
+          ui.output_mut(|o| o.copied_text = text,);
+        }
+      }
+    },);
+  }
+}
+
+/// 一个终端标签
+struct TerminalTab {
+  id: usize,
+  view: TerminalView,
+  /// 子进程退出后为 None
+  pty: Option<Pty,>,
+}
+
+impl TerminalTab {
+  fn focus_id(&self,) -> egui::Id {
+    egui::Id::new(("terminal", self.id,),)
+  }
+
+  fn title(&self,) -> String {
+    let title = self
+      .view
+      .term()
+      .screen()
+      .title()
+      .filter(|v| !v.is_empty(),)
+      .map_or_else(|| format!("终端 {}", self.id), |v| v.to_string(),);
+    if self.pty.is_none() {
+      format!("{title}（已退出）")
+    } else {
+      title
+    }
+  }
+
+  /// 写入输入，滚动到最新的内容
+  fn write(
+    &mut self,
+    bytes: &[u8],
+  ) {
+    if bytes.is_empty() {
+      return;
+    }
+    if let Some(pty,) = self.pty.as_mut() {
+      pty.write(bytes,);
+    }
+    self.view.scroll_to_bottom();
+  }
+
+  /// 读取子进程的输出
+  fn sync(&mut self,) {
+    let Some(pty,) = self.pty.as_mut() else {
+      return;
+    };
+    match pty.read() {
+      Some(bytes,) => {
+        if bytes.is_empty() {
+          return;
+        }
+        self.view.advance(&bytes,);
+        let responses = self.view.take_responses();
+        if !responses.is_empty() {
+          pty.write(&responses,);
+        }
+      }
+      None => self.pty = None,
+    }
+  }
+
+  fn resize(
+    &mut self,
+    rows: usize,
+    cols: usize,
+  ) {
+    if self.view.resize(rows, cols,) {
+      if let Some(pty,) = self.pty.as_mut() {
+        pty.resize(rows, cols,);
+      }
+    }
+  }
+
+  /// 粘贴，开启 bracketed paste 模式时用控制序列包裹
+  fn paste(
+    &mut self,
+    text: &str,
+  ) {
+    let text = text.replace("\r\n", "\r",).replace('\n', "\r",);
+    if self.view.term().screen().bracketed_paste() {
+      self.write(format!("\x1b[200~{text}\x1b[201~").as_bytes(),);
+    } else {
+      self.write(text.as_bytes(),);
+    }
+  }
+}
+
+/// 终端面板，每个终端一个标签
+#[derive(Default,)]
+pub struct TerminalPanel {
+  tabs: Vec<TerminalTab,>,
+  current_index: usize,
+  next_id: usize,
+  /// 上一帧终端是否有焦点，egui 按下 Esc 时会清除焦点
+  had_focus: bool,
+}
+
+impl TerminalPanel {
+  pub fn is_empty(&self,) -> bool {
+    self.tabs.is_empty()
+  }
+
+  /// 当前终端是否有焦点，有焦点时按键发送给终端而不触发快捷键
+  pub fn has_focus(
+    &self,
+    ctx: &egui::Context,
+  ) -> bool {
+    let focus = ctx.memory(|m| m.focus(),);
+    focus.is_some() && focus == self.tabs.get(self.current_index,).map(|v| v.focus_id(),)
+  }
+
+  /// 新建终端，在 cwd 中启动用户的 shell
+  pub fn new_terminal(
+    &mut self,
+    ctx: &egui::Context,
+    cwd: Option<&Path,>,
+  ) {
+    let (rows, cols,) = INITIAL_SIZE;
+    match Pty::spawn(pty::default_shell(), cwd, (rows, cols,), ctx.clone(),) {
+      Ok(pty,) => {
+        self.next_id += 1;
+        self.tabs.push(TerminalTab {
+          id: self.next_id,
+          view: TerminalView::new(rows, cols,),
+          pty: Some(pty,),
+        },);
+        self.current_index = self.tabs.len() - 1;
+      }
+      Err(e,) => {
+        util::toaster()
+          .error(format!("启动终端失败\nErr: {e}"),)
+          .set_duration(Some(Duration::from_secs(5,),),);
+      }
+    }
+  }
+
+  /// 关闭终端，结束其子进程
+  pub fn close_terminal(
+    &mut self,
+    index: usize,
+  ) {
+    if index >= self.tabs.len() {
+      return;
+    }
+    self.tabs.remove(index,);
+    if self.current_index > index || self.current_index >= self.tabs.len() {
+      self.current_index = self.current_index.saturating_sub(1,);
+    }
+  }
+
+  /// 读取所有终端的输出，每帧调用
+  pub fn sync(&mut self,) {
+    for tab in self.tabs.iter_mut() {
+      tab.sync();
+    }
+  }
+}
+
+// ------------------------------------ UI
+
+impl TerminalPanel {
+  /// 标签栏
+  pub fn show_tab_bar(
+    &mut self,
+    ui: &mut egui::Ui,
+    cwd: Option<&Path,>,
+  ) {
+    let mut close_index = None;
+    egui::ScrollArea::horizontal()
+      .id_source("terminal_tab_bar_scroll",)
+      .show(ui, |ui| {
+        ui.horizontal_centered(|ui| {
+          let spacing_size = ui.spacing().item_spacing;
+          ui.add_space(spacing_size.x,);
+          for (i, tab,) in self.tabs.iter().enumerate() {
+            let title = format!("{} {}", NerdFont::TERMINAL.utf(), tab.title());
+            let response = ui.selectable_label(i == self.current_index, title,);
+            if response.clicked() {
+              self.current_index = i;
+            }
+            // 鼠标中键点击
+            if response.middle_clicked() {
+              close_index = Some(i,);
+            }
+            response.context_menu(|ui| {
+              if ui.button("关闭",).clicked() {
+                ui.close_menu();
+                close_index = Some(i,);
+              }
+            },);
+            if i == self.current_index
+              && ui
+                .small_button(NerdFont::CLOSE.utf().to_string(),)
+                .on_hover_text("关闭终端",)
+                .clicked()
+            {
+              close_index = Some(i,);
+            }
+          }
+          if ui
+            .small_button(NerdFont::ADD.utf().to_string(),)
+            .on_hover_text("新建终端",)
+            .clicked()
+          {
+            self.new_terminal(ui.ctx(), cwd,);
+          }
+        },);
+      },);
+    if let Some(i,) = close_index {
+      self.close_terminal(i,);
+    }
+  }
+
+  /// 当前终端
+  pub fn show_terminal(
+    &mut self,
+    ui: &mut egui::Ui,
+  ) {
+    let Some(tab,) = self.tabs.get_mut(self.current_index,) else {
+      self.had_focus = false;
+      ui.centered_and_justified(|ui| {
+        ui.weak("没有打开的终端",);
+      },);
+      return;
+    };
+
+    let id = tab.focus_id();
+    let layout = allocate_grid(ui, id,);
+    // 按面板大小调整终端大小
+    tab.resize(layout.rows, layout.cols,);
+
+    // 焦点
+    let response = &layout.response;
+    if response.clicked() || response.drag_started() {
+      response.request_focus();
+    }
+    if self.had_focus && ui.input(|i| i.key_pressed(Key::Escape,),) {
+      response.request_focus();
+    }
+    let has_focus = response.has_focus() && ui.is_enabled();
+    self.had_focus = has_focus;
+    if has_focus {
+      ui.memory_mut(|m| m.lock_focus(id, true,),);
+      handle_input(ui, tab,);
+    }
+
+    // 右键菜单只有复制，粘贴使用 Ctrl+V
+    tab.view.interact(ui, &layout,);
+    let cursor = tab.pty.is_some().then_some(has_focus,);
+    tab.view.paint(ui, &layout, cursor,);
+  }
+}
+
+/// 按键转换为发送给终端的字节
+fn handle_input(
+  ui: &egui::Ui,
+  tab: &mut TerminalTab,
+) {
+  let events = ui.input(|i| i.events.clone(),);
+  let app_cursor_keys = tab.view.term().screen().app_cursor_keys();
+  let rows = tab.view.term().screen().size().0 as isize;
+  for event in events {
+    match event {
+      egui::Event::Text(text,) => tab.write(text.as_bytes(),),
+      egui::Event::Paste(text,) => tab.paste(&text,),
+      egui::Event::Key {
+        key,
+        pressed: true,
+        modifiers,
+        ..
+      } => {
+        // Ctrl+Shift+C 复制选中的文本
+        if modifiers.ctrl && modifiers.shift && key == Key::C {
+          if let Some(text,) = tab.view.selected_text() {
+            ui.ctx().output_mut(|o| o.copied_text = text,);
+          }
+          continue;
+        }
+        // 粘贴的内容由 Event::Paste 处理
+        if modifiers.command && key == Key::V {
+          continue;
+        }
+        // Shift+PageUp/PageDown 滚动回滚缓冲
+        if modifiers.shift && matches!(key, Key::PageUp | Key::PageDown) {
+          tab
+            .view
+            .scroll(if key == Key::PageUp { rows } else { -rows },);
+          continue;
+        }
+        if let Some(bytes,) = key_bytes(key, modifiers, app_cursor_keys,) {
+          tab.write(&bytes,);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// 特殊按键和 Ctrl 组合键对应的字节，可打印字符由 Event::Text 处理
+fn key_bytes(
+  key: Key,
+  modifiers: Modifiers,
+  app_cursor_keys: bool,
+) -> Option<Vec<u8,>,> {
+  let cursor_key = |c: u8| {
+    if app_cursor_keys {
+      vec![0x1b, b'O', c]
+    } else {
+      vec![0x1b, b'[', c]
+    }
+  };
+  let bytes = match key {
+    Key::Enter => vec![b'\r'],
+    Key::Backspace => vec![0x7f],
+    Key::Tab if modifiers.shift => b"\x1b[Z".to_vec(),
+    Key::Tab => vec![b'\t'],
+    Key::Escape => vec![0x1b],
+    Key::ArrowUp => cursor_key(b'A',),
+    Key::ArrowDown => cursor_key(b'B',),
+    Key::ArrowRight => cursor_key(b'C',),
+    Key::ArrowLeft => cursor_key(b'D',),
+    Key::Home => cursor_key(b'H',),
+    Key::End => cursor_key(b'F',),
+    Key::Insert => b"\x1b[2~".to_vec(),
+    Key::Delete => b"\x1b[3~".to_vec(),
+    Key::PageUp => b"\x1b[5~".to_vec(),
+    Key::PageDown => b"\x1b[6~".to_vec(),
+    Key::Space if modifiers.ctrl => vec![0],
+    _ if modifiers.ctrl && !modifiers.shift => {
+      // Ctrl+A..Z => 0x01..0x1a
+      let name = key.name().as_bytes();
+      match name {
+        [c] if c.is_ascii_alphabetic() => vec![c.to_ascii_uppercase() & 0x1f],
+        _ => return None,
+      }
+    }
+    _ => return None,
+  };
+  Some(bytes,)
+}
+
+impl TerminalView {
+  /// 绘制可见的内容，cursor 为 Some(是否有焦点) 时绘制光标
+  pub fn paint(
+    &self,
+    ui: &egui::Ui,
+    layout: &GridLayout,
+    cursor: Option<bool,>,
+  ) {
+    let GridLayout {
+      rect,
+      cell_width,
+      row_height,
+      ..
+    } = *layout;
+    let painter = ui.painter_at(rect.expand(style::TERMINAL_PADDING,),);
+    let visuals = ui.visuals();
+    let default_fg = visuals.text_color();
+    let default_bg = visuals.extreme_bg_color;
+    painter.rect_filled(rect.expand(style::TERMINAL_PADDING,), 0., default_bg,);
+
+    let font_id = text::text_editor_font();
+    let screen = self.term.screen();
+    let (rows, _,) = screen.size();
+    let first_line = self.first_line();
+    let selection = self
+      .selection
+      .map(|(a, b,)| if a <= b { (a, b,) } else { (b, a,) },);
+    let cell_rect = |row: usize, col: usize, width: usize| {
+      egui::Rect::from_min_size(
+        rect.min + egui::vec2(col as f32 * cell_width, row as f32 * row_height,),
+        egui::vec2(width as f32 * cell_width, row_height,),
+      )
+    };
+
+    for row in 0..rows {
+      let index = first_line + row;
+      let line = screen.line(index,);
+      // 选区
+      if let Some((start, end,),) = selection {
+        if start.0 <= index && index <= end.0 {
+          let from = if index == start.0 { start.1 } else { 0 };
+          let to = if index == end.0 { end.1 } else { line.len() };
+          if to > from {
+            painter.rect_filled(
+              cell_rect(row, from, to - from,),
+              0.,
+              visuals.selection.bg_fill,
+            );
+          }
+        }
+      }
+      // 样式相同的连续字符合并绘制，宽字符单独绘制以对齐网格
+      let mut col = 0;
+      while col < line.len() {
+        let cell = line[col];
+        if cell.c == WIDE_CHAR_SPACER {
+          col += 1;
+          continue;
+        }
+        let wide = line.get(col + 1,).is_some_and(|v| v.c == WIDE_CHAR_SPACER,);
+        let mut end = col + 1;
+        let mut run = String::from(cell.c,);
+        if !wide {
+          while let Some(next,) = line.get(end,) {
+            let next_wide = line.get(end + 1,).is_some_and(|v| v.c == WIDE_CHAR_SPACER,);
+            if next.style != cell.style || next.c == WIDE_CHAR_SPACER || next_wide {
+              break;
+            }
+            run.push(next.c,);
+            end += 1;
+          }
+        } else {
+          end += 1;
+        }
+        paint_run(
+          &painter,
+          &font_id,
+          cell_rect(row, col, end - col,),
+          &run,
+          cell,
+          default_fg,
+        );
+        col = end;
+      }
+    }
+
+    // 光标
+    if let Some(has_focus,) = cursor.filter(|_| self.scroll_offset == 0 && screen.cursor_visible(),)
+    {
+      let (row, col,) = screen.cursor();
+      let cursor_rect = cell_rect(row, col, 1,);
+      if has_focus {
+        painter.rect_filled(cursor_rect, 0., default_fg.gamma_multiply(0.5,),);
+      } else {
+        painter.rect_stroke(cursor_rect, 0., egui::Stroke::new(1., default_fg,),);
+      }
+    }
+  }
+}
+
+fn paint_run(
+  painter: &egui::Painter,
+  font_id: &egui::FontId,
+  rect: egui::Rect,
+  run: &str,
+  cell: Cell,
+  default_fg: Color32,
+) {
+  let CellStyle {
+    fg,
+    bg,
+    bold,
+    italic,
+    underline,
+    inverse,
+  } = cell.style;
+  let mut fg = color32(fg, bold,).unwrap_or(default_fg,);
+  let mut bg = color32(bg, false,);
+  if inverse {
+    let old_fg = fg;
+    fg = bg.unwrap_or(painter.ctx().style().visuals.extreme_bg_color,);
+    bg = Some(old_fg,);
+  }
+  if let Some(bg,) = bg {
+    painter.rect_filled(rect, 0., bg,);
+  }
+  if run.trim().is_empty() {
+    return;
+  }
+  let mut job = egui::text::LayoutJob::single_section(
+    run.to_string(),
+    egui::TextFormat {
+      font_id: font_id.clone(),
+      color: fg,
+      italics: italic,
+      underline: if underline {
+        egui::Stroke::new(1., fg,)
+      } else {
+        egui::Stroke::NONE
+      },
+      ..Default::default()
+    },
+  );
+  job.wrap.max_width = f32::INFINITY;
+  let galley = painter.ctx().fonts(|f| f.layout_job(job,),);
+  painter.galley(rect.min, galley,);
+}
+
+/// 终端颜色转换为 egui 颜色，默认颜色返回 None；粗体使用亮色
+fn color32(
+  color: terminal::Color,
+  bold: bool,
+) -> Option<Color32,> {
+  match color {
+    terminal::Color::Default => None,
+    terminal::Color::Indexed(i,) if bold && i < 8 => Some(indexed_color(i + 8,),),
+    terminal::Color::Indexed(i,) => Some(indexed_color(i,),),
+    terminal::Color::Rgb(r, g, b,) => Some(Color32::from_rgb(r, g, b,),),
+  }
+}
+
+/// xterm 256 色
+fn indexed_color(i: u8,) -> Color32 {
+  const ANSI: [(u8, u8, u8,); 16] = [
+    (0, 0, 0,),
+    (205, 0, 0,),
+    (0, 205, 0,),
+    (205, 205, 0,),
+    (0, 0, 238,),
+    (205, 0, 205,),
+    (0, 205, 205,),
+    (229, 229, 229,),
+    (127, 127, 127,),
+    (255, 0, 0,),
+    (0, 255, 0,),
+    (255, 255, 0,),
+    (92, 92, 255,),
+    (255, 0, 255,),
+    (0, 255, 255,),
+    (255, 255, 255,),
+  ];
+  match i {
+    0..=15 => {
+      let (r, g, b,) = ANSI[i as usize];
+      Color32::from_rgb(r, g, b,)
+    }
+    // 6x6x6 色块
+    16..=231 => {
+      let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+      let i = i - 16;
+      Color32::from_rgb(level(i / 36,), level(i / 6 % 6,), level(i % 6,),)
+    }
+    // 灰度
+    _ => {
+      let v = 8 + (i - 232) * 10;
+      Color32::from_rgb(v, v, v,)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use eframe::egui::{Color32, Key, Modifiers};
+
+  use super::{indexed_color, key_bytes};
+
+  #[test]
+  fn test_key_bytes() {
+    let ctrl = Modifiers::CTRL;
+    assert_eq!(
+      key_bytes(Key::Enter, Modifiers::NONE, false),
+      Some(b"\r".to_vec())
+    );
+    assert_eq!(
+      key_bytes(Key::ArrowUp, Modifiers::NONE, false),
+      Some(b"\x1b[A".to_vec())
+    );
+    assert_eq!(
+      key_bytes(Key::ArrowUp, Modifiers::NONE, true),
+      Some(b"\x1bOA".to_vec())
+    );
+    assert_eq!(key_bytes(Key::C, ctrl, false), Some(vec![0x03]));
+    assert_eq!(key_bytes(Key::D, ctrl, false), Some(vec![0x04]));
+    assert_eq!(key_bytes(Key::A, Modifiers::NONE, false), None);
+    assert_eq!(key_bytes(Key::Num1, ctrl, false), None);
+  }
+
+  #[test]
+  fn test_indexed_color() {
+    assert_eq!(indexed_color(1), Color32::from_rgb(205, 0, 0));
+    assert_eq!(indexed_color(16), Color32::from_rgb(0, 0, 0));
+    assert_eq!(indexed_color(196), Color32::from_rgb(255, 0, 0));
+    assert_eq!(indexed_color(231), Color32::from_rgb(255, 255, 255));
+    assert_eq!(indexed_color(232), Color32::from_rgb(8, 8, 8));
+    assert_eq!(indexed_color(255), Color32::from_rgb(238, 238, 238));
+  }
+}
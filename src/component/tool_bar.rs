@@ -1,5 +1,7 @@
 use std::sync::mpsc::SyncSender;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
   action::{self, Action},
   id,
@@ -7,7 +9,7 @@ use crate::{
   style, text, ui,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq,)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize,)]
 pub enum ToolId {
   Explorer,
   Search,
@@ -87,16 +89,14 @@ impl ToolBar {
     }
   }
 
-  fn select_item(
+  /// 选中工具，None 表示不选中
+  pub fn select_item(
     &mut self,
-    item_id: ToolId,
+    item_id: Option<&ToolId,>,
   ) {
-    for (i, item,) in self.top.iter().enumerate() {
-      if item.id == item_id {
-        self.current_index = i;
-        break;
-      }
-    }
+    self.current_index = item_id
+      .and_then(|id| self.top.iter().position(|item| &item.id == id,),)
+      .unwrap_or(usize::MAX,);
   }
 }
 
@@ -123,7 +123,7 @@ impl ToolBar {
       },)
       .push_top(Item {
         id: ToolId::Search,
-        action: Action::NoOp,
+        action: Action::ToggleSearch,
         image: search_image,
       },)
       .push_top(Item {
@@ -216,6 +216,7 @@ impl action::Handle for ToolBar {
   ) {
     match action {
       Action::ToggleExplorer => self.toggle_item(ToolId::Explorer,),
+      Action::ToggleSearch => self.toggle_item(ToolId::Search,),
       _ => {}
     }
   }
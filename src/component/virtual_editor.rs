@@ -0,0 +1,538 @@
+use std::{ops::Range, sync::Arc};
+
+use eframe::{egui, epaint::Galley};
+use egui::{text::CCursor, text_edit::CCursorRange};
+
+use crate::{
+  buffer::Buffer, diagnostic::EditorMarks, history::RecordingBuffer, hl, style, text,
+  util::LineEnding,
+};
+
+/// 跨帧保存的状态
+#[derive(Debug, Clone, Copy, Default,)]
+struct State {
+  /// 已布局过的行中最长的行宽，用于横向滚动
+  max_line_width: f32,
+}
+
+/// 只布局和绘制可见行（及上下 overscan 行）的编辑器，用于大文件
+///
+/// 每行单独布局为一个 Galley，行号、高亮、cursor 定位都按行计算，每帧的开销与文件大小无关
+#[allow(clippy::too_many_arguments)]
+pub fn show(
+  ui: &mut egui::Ui,
+  id: egui::Id,
+  highlighter: &mut hl::BackgroundHighlighter,
+  buffer: &mut RecordingBuffer,
+  cursor_range: &mut Option<CCursorRange,>,
+  highlights: &[(Range<usize,>, egui::Color32,)],
+  marks: &EditorMarks,
+  line_ending: LineEnding,
+  scroll_to_cursor: bool,
+) -> egui::Response {
+  puffin::profile_function!();
+
+  let font_id = text::text_editor_font();
+  let (row_height, char_width,) =
+    ui.fonts(|f| (f.row_height(&font_id,), f.glyph_width(&font_id, '0',),),);
+  let line_count = buffer.buffer().len_lines();
+  let gutter_width =
+    line_count.to_string().len() as f32 * char_width + style::LINE_NUMBER_BAR_PADDING * 2.;
+  let mut state = ui
+    .data_mut(|d| d.get_temp::<State>(id,),)
+    .unwrap_or_default();
+
+  // 文本可能被外部修改（如撤销），保证 cursor 不越界
+  if let Some(cr,) = cursor_range.as_mut() {
+    let len_chars = buffer.buffer().len_chars();
+    cr.primary.index = cr.primary.index.min(len_chars,);
+    cr.secondary.index = cr.secondary.index.min(len_chars,);
+  }
+
+  // (*) 获取焦点，其他组件（如查找栏）持有焦点时不抢占
+  if ui.is_enabled() && ui.memory(|m| m.focus().is_none(),) {
+    ui.memory_mut(|m| m.request_focus(id,),);
+  }
+  // 有模态窗口时主界面不可交互，不处理键盘输入
+  let has_focus = ui.is_enabled() && ui.memory(|m| m.has_focus(id,),);
+  if has_focus {
+    // Tab 用于输入，不切换焦点
+    ui.memory_mut(|m| m.lock_focus(id, true,),);
+  }
+
+  // 键盘输入
+  let mut cursor_changed = false;
+  if has_focus {
+    let page_rows = ((ui.available_height() / row_height) as usize).max(1,);
+    cursor_changed = handle_events(ui, buffer, cursor_range, line_ending, page_rows,);
+  }
+  highlighter.apply_edits(buffer.take_line_edits(),);
+  let line_count = buffer.buffer().len_lines();
+
+  let output = egui::ScrollArea::both()
+    .id_source(id.with("scroll_area",),)
+    .auto_shrink([false, false,],)
+    .show_viewport(ui, |ui, viewport| {
+      let desired_size = egui::vec2(
+        (gutter_width + state.max_line_width + char_width).max(viewport.width(),),
+        (row_height * line_count as f32).max(viewport.height(),),
+      );
+      let (rect, _,) = ui.allocate_exact_size(desired_size, egui::Sense::hover(),);
+      let response = ui.interact(rect, id, egui::Sense::click_and_drag(),);
+      let visible_rect =
+        egui::Rect::from_min_size(rect.min + viewport.min.to_vec2(), viewport.size(),);
+      let text_left = rect.left() + gutter_width;
+      let row_top = |row: usize| rect.top() + row as f32 * row_height;
+
+      // 可见行
+      let overscan = style::VIRTUAL_EDITOR_OVERSCAN_ROWS;
+      let first_row = ((viewport.min.y / row_height).max(0.,) as usize)
+        .saturating_sub(overscan,)
+        .min(line_count,);
+      let last_row =
+        ((viewport.max.y / row_height).ceil().max(0.,) as usize + overscan).min(line_count,);
+      let galleys = layout_rows(
+        ui,
+        highlighter,
+        buffer.buffer(),
+        first_row..last_row,
+        highlights,
+        &marks.underlines,
+      );
+      for g in galleys.iter() {
+        state.max_line_width = state.max_line_width.max(g.size().x,);
+      }
+      let galley_of_row = |ui: &egui::Ui, row: usize| match row
+        .checked_sub(first_row,)
+        .and_then(|i| galleys.get(i,),)
+      {
+        Some(g,) => g.clone(),
+        None => layout_plain_row(ui, buffer.buffer(), row,),
+      };
+
+      // 鼠标 点击/拖拽 定位 cursor
+      if let Some(pos,) = response.interact_pointer_pos() {
+        let row = (((pos.y - rect.top()) / row_height).max(0.,) as usize).min(line_count - 1,);
+        let galley = galley_of_row(ui, row,);
+        let column = galley
+          .cursor_from_pos(egui::vec2(pos.x - text_left, row_height / 2.,),)
+          .ccursor
+          .index;
+        let ccursor = CCursor::new(buffer.buffer().line_to_char(row,) + column,);
+        if response.hovered() && ui.input(|i| i.pointer.any_pressed(),) {
+          ui.memory_mut(|m| m.request_focus(id,),);
+          let shift = ui.input(|i| i.modifiers.shift,);
+          *cursor_range = Some(match *cursor_range {
+            Some(cr,) if shift => CCursorRange::two(cr.secondary, ccursor,),
+            _ => CCursorRange::one(ccursor,),
+          },);
+        } else if response.dragged() {
+          if let Some(cr,) = cursor_range.as_mut() {
+            cr.primary = ccursor;
+          }
+        }
+      }
+
+      let painter = ui.painter();
+      let visuals = ui.visuals();
+      let selection = cursor_range.map(|cr| {
+        let [start, end] = cr.sorted();
+        start.index..end.index
+      },);
+      let cursor_row = cursor_range.map(|cr| buffer.buffer().char_to_line(cr.primary.index,),);
+
+      // 文本，选中区域
+      for (i, galley,) in galleys.iter().enumerate() {
+        let row = first_row + i;
+        let top = row_top(row,);
+        let line_start = buffer.buffer().line_to_char(row,);
+        let line_chars = buffer.buffer().line_len_chars(row,);
+        if let Some(sel,) = selection.as_ref().filter(|sel| {
+          !sel.is_empty() && sel.start <= line_start + line_chars && sel.end > line_start
+        },)
+        {
+          let start_column = sel.start.saturating_sub(line_start,);
+          let end_column = (sel.end - line_start).min(line_chars,);
+          let x0 = text_left + x_of_column(galley, start_column,);
+          let mut x1 = text_left + x_of_column(galley, end_column,);
+          // 选中了换行符
+          if sel.end > line_start + line_chars {
+            x1 += char_width;
+          }
+          painter.rect_filled(
+            egui::Rect::from_x_y_ranges(x0..=x1, top..=top + row_height,),
+            0.,
+            visuals.selection.bg_fill,
+          );
+        }
+        painter.galley(egui::pos2(text_left, top,), galley.clone(),);
+      }
+
+      // cursor
+      if let (Some(cr,), Some(row,),) = (cursor_range.as_ref(), cursor_row,) {
+        let galley = galley_of_row(ui, row,);
+        let column = cr.primary.index - buffer.buffer().line_to_char(row,);
+        let x = text_left + x_of_column(&galley, column,);
+        let cursor_rect =
+          egui::Rect::from_x_y_ranges(x..=x, row_top(row,)..=row_top(row,) + row_height,);
+        if has_focus {
+          painter.line_segment(
+            [cursor_rect.center_top(), cursor_rect.center_bottom(),],
+            (visuals.text_cursor_width, visuals.selection.stroke.color,),
+          );
+          // 输入法候选框位置
+          ui.ctx()
+            .output_mut(|o| o.text_cursor_pos = Some(cursor_rect.left_bottom(),),);
+        }
+        if scroll_to_cursor {
+          ui.scroll_to_rect(cursor_rect, Some(egui::Align::Center,),);
+        } else if cursor_changed {
+          ui.scroll_to_rect(cursor_rect.expand2(egui::vec2(char_width, 0.,),), None,);
+        }
+      }
+
+      // 行号栏，固定在可见区域左侧，遮挡横向滚动的文本
+      let gutter_rect = egui::Rect::from_min_size(
+        visible_rect.min,
+        egui::vec2(gutter_width, visible_rect.height(),),
+      );
+      painter.rect_filled(gutter_rect, 0., visuals.panel_fill,);
+      for row in first_row..last_row {
+        let color = match marks.lines.get(&row,) {
+          Some((color, _,),) => *color,
+          None if cursor_row == Some(row,) => visuals.strong_text_color(),
+          None => visuals.weak_text_color(),
+        };
+        painter.text(
+          egui::pos2(
+            gutter_rect.right() - style::LINE_NUMBER_BAR_PADDING,
+            row_top(row,),
+          ),
+          egui::Align2::RIGHT_TOP,
+          row + 1,
+          font_id.clone(),
+          color,
+        );
+      }
+      // 鼠标悬停在有诊断的行号上时显示诊断的消息
+      if let Some(pos,) = ui
+        .input(|i| i.pointer.hover_pos(),)
+        .filter(|v| gutter_rect.contains(*v,),)
+      {
+        let row = ((pos.y - rect.top()) / row_height).max(0.,) as usize;
+        if let Some((_, message,),) = marks.lines.get(&row,) {
+          egui::show_tooltip_at_pointer(ui.ctx(), id.with("diagnostic",), |ui| {
+            ui.label(message,);
+          },);
+        }
+      }
+
+      response
+    },);
+
+  ui.data_mut(|d| d.insert_temp(id, state,),);
+  output.inner
+}
+
+/// 布局并高亮 rows 中的行
+fn layout_rows(
+  ui: &egui::Ui,
+  highlighter: &mut hl::BackgroundHighlighter,
+  buffer: &Buffer,
+  rows: Range<usize,>,
+  highlights: &[(Range<usize,>, egui::Color32,)],
+  underlines: &[(Range<usize,>, egui::Color32,)],
+) -> Vec<Arc<Galley,>,> {
+  puffin::profile_function!();
+
+  // 后台高亮还未完成的行显示为普通文本
+  highlighter.update(ui.ctx(), buffer, rows.clone(),);
+  let color = ui.visuals().text_color();
+  rows
+    .map(|row| {
+      let line = buffer.line(row,);
+      let mut job = hl::layout::get_line_layout_job(&line, highlighter.line_sections(row,), color,);
+
+      // 与本行相交的查找匹配项和诊断
+      let line_start = buffer.line_to_byte(row,);
+      let line_end = line_start + job.text.len();
+      hl::layout::highlight_ranges(&mut job, &ranges_in_line(highlights, line_start..line_end,),);
+      hl::layout::underline_ranges(&mut job, &ranges_in_line(underlines, line_start..line_end,),);
+
+      ui.fonts(|f| f.layout_job(job,),)
+    },)
+    .collect()
+}
+
+/// 与 line 相交的 range，转换为行内的 byte_range
+fn ranges_in_line(
+  ranges: &[(Range<usize,>, egui::Color32,)],
+  line: Range<usize,>,
+) -> Vec<(Range<usize,>, egui::Color32,),> {
+  let i = ranges.partition_point(|(r, _,)| r.end <= line.start,);
+  ranges[i..]
+    .iter()
+    .take_while(|(r, _,)| r.start < line.end,)
+    .map(|(r, color,)| {
+      let start = r.start.max(line.start,) - line.start;
+      let end = r.end.min(line.end,) - line.start;
+      (start..end, *color,)
+    },)
+    .collect()
+}
+
+/// 不高亮，只用于定位不可见行中的 cursor
+fn layout_plain_row(
+  ui: &egui::Ui,
+  buffer: &Buffer,
+  row: usize,
+) -> Arc<Galley,> {
+  let job = hl::layout::get_line_layout_job(&buffer.line(row,), None, ui.visuals().text_color(),);
+  ui.fonts(|f| f.layout_job(job,),)
+}
+
+fn x_of_column(
+  galley: &Galley,
+  column: usize,
+) -> f32 {
+  galley
+    .pos_from_cursor(&galley.from_ccursor(CCursor::new(column,),),)
+    .min
+    .x
+}
+
+/// 处理键盘输入，返回 cursor 是否发生变化
+fn handle_events(
+  ui: &egui::Ui,
+  buffer: &mut RecordingBuffer,
+  cursor_range: &mut Option<CCursorRange,>,
+  line_ending: LineEnding,
+  page_rows: usize,
+) -> bool {
+  let events = ui.input(|i| i.events.clone(),);
+  let mut changed = false;
+  for event in events {
+    let cr = cursor_range.unwrap_or(CCursorRange::one(CCursor::new(0,),),);
+    let [start, end] = cr.sorted();
+    let selection = start.index..end.index;
+    let new_cr = match event {
+      egui::Event::Text(text,) | egui::Event::Paste(text,) | egui::Event::CompositionEnd(text,) => {
+        Some(replace(buffer, selection, &text,),)
+      }
+      egui::Event::Copy | egui::Event::Cut if !selection.is_empty() => {
+        let b = buffer.buffer();
+        let byte_range = b.char_to_byte(selection.start,)..b.char_to_byte(selection.end,);
+        let copied_text = b.byte_slice(byte_range,).into_owned();
+        ui.ctx().output_mut(|o| o.copied_text = copied_text,);
+        if event == egui::Event::Cut {
+          Some(replace(buffer, selection, "",),)
+        } else {
+          None
+        }
+      }
+      egui::Event::Key {
+        key,
+        pressed: true,
+        modifiers,
+        ..
+      } => on_key_press(
+        buffer,
+        cr,
+        selection,
+        key,
+        modifiers,
+        line_ending,
+        page_rows,
+      ),
+      _ => None,
+    };
+    if let Some(new_cr,) = new_cr {
+      *cursor_range = Some(new_cr,);
+      changed = true;
+    }
+  }
+  changed
+}
+
+/// 用 text 替换 char_range 中的内容，返回替换后的 cursor
+fn replace(
+  buffer: &mut RecordingBuffer,
+  char_range: Range<usize,>,
+  text: &str,
+) -> CCursorRange {
+  use egui::TextBuffer;
+
+  if !char_range.is_empty() {
+    buffer.delete_char_range(char_range.clone(),);
+  }
+  let char_count = buffer.insert_text(text, char_range.start,);
+  CCursorRange::one(CCursor::new(char_range.start + char_count,),)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn on_key_press(
+  buffer: &mut RecordingBuffer,
+  cr: CCursorRange,
+  selection: Range<usize,>,
+  key: egui::Key,
+  modifiers: egui::Modifiers,
+  line_ending: LineEnding,
+  page_rows: usize,
+) -> Option<CCursorRange,> {
+  let b = buffer.buffer();
+  let c = cr.primary.index;
+  let (line, column,) = b.char_to_line_column(c,);
+  let line_end = |line: usize| b.line_to_char(line,) + b.line_len_chars(line,);
+  match key {
+    egui::Key::Backspace => {
+      let range = if !selection.is_empty() {
+        selection
+      } else if column == 0 && line > 0 {
+        // 删除整个换行符（包括 \r\n）
+        line_end(line - 1,)..c
+      } else if c > 0 {
+        c - 1..c
+      } else {
+        return None;
+      };
+      Some(replace(buffer, range, "",),)
+    }
+    egui::Key::Delete => {
+      let range = if !selection.is_empty() {
+        selection
+      } else if c >= line_end(line,) && line + 1 < b.len_lines() {
+        c..b.line_to_char(line + 1,)
+      } else if c < b.len_chars() {
+        c..c + 1
+      } else {
+        return None;
+      };
+      Some(replace(buffer, range, "",),)
+    }
+    // 换行符跟随文件的 line_ending
+    egui::Key::Enter => Some(replace(buffer, selection, line_ending.as_text(),),),
+    egui::Key::Tab => Some(replace(buffer, selection, "\t",),),
+    egui::Key::A if modifiers.command => Some(CCursorRange::two(
+      CCursor::new(0,),
+      CCursor::new(b.len_chars(),),
+    ),),
+    _ => {
+      let to = CCursor::new(move_cursor(b, c, selection, key, modifiers, page_rows,)?,);
+      Some(
+        if modifiers.shift {
+          CCursorRange {
+            primary: to,
+            secondary: cr.secondary,
+          }
+        } else {
+          CCursorRange::one(to,)
+        },
+      )
+    }
+  }
+}
+
+/// 方向键等移动 cursor，返回新的 char 索引
+fn move_cursor(
+  b: &Buffer,
+  c: usize,
+  selection: Range<usize,>,
+  key: egui::Key,
+  modifiers: egui::Modifiers,
+  page_rows: usize,
+) -> Option<usize,> {
+  let (line, column,) = b.char_to_line_column(c,);
+  let last_line = b.len_lines() - 1;
+  let line_end = |line: usize| b.line_to_char(line,) + b.line_len_chars(line,);
+  // 上下移动时保持列
+  let vertical = |line: usize| b.line_to_char(line,) + column.min(b.line_len_chars(line,),);
+
+  let to = match key {
+    egui::Key::ArrowLeft if !modifiers.shift && !selection.is_empty() => selection.start,
+    egui::Key::ArrowRight if !modifiers.shift && !selection.is_empty() => selection.end,
+    egui::Key::ArrowLeft if column == 0 => {
+      if line == 0 {
+        0
+      } else {
+        line_end(line - 1,)
+      }
+    }
+    egui::Key::ArrowLeft => c - 1,
+    // 跳过换行符（包括 \r\n）
+    egui::Key::ArrowRight if c >= line_end(line,) => {
+      if line == last_line {
+        c
+      } else {
+        b.line_to_char(line + 1,)
+      }
+    }
+    egui::Key::ArrowRight => c + 1,
+    egui::Key::ArrowUp if line == 0 => 0,
+    egui::Key::ArrowUp => vertical(line - 1,),
+    egui::Key::ArrowDown if line == last_line => line_end(line,),
+    egui::Key::ArrowDown => vertical(line + 1,),
+    egui::Key::PageUp => vertical(line.saturating_sub(page_rows,),),
+    egui::Key::PageDown => vertical((line + page_rows).min(last_line,),),
+    egui::Key::Home if modifiers.command => 0,
+    egui::Key::End if modifiers.command => b.len_chars(),
+    egui::Key::Home => b.line_to_char(line,),
+    egui::Key::End => line_end(line,),
+    _ => return None,
+  };
+  Some(to,)
+}
+
+#[cfg(test)]
+mod test {
+  use eframe::egui::{Key, Modifiers};
+
+  use eframe::egui::{text::CCursor, text_edit::CCursorRange};
+
+  use super::{move_cursor, on_key_press};
+  use crate::{buffer::Buffer, history::RecordingBuffer, util::LineEnding};
+
+  #[test]
+  fn test_move_cursor() {
+    let b = Buffer::from("abc\r\nde\n\nfghij",);
+    let none = Modifiers::NONE;
+    let move_to = |c: usize, key: Key| move_cursor(&b, c, c..c, key, none, 2,).unwrap();
+
+    // 跳过 \r\n
+    assert_eq!(move_to(3, Key::ArrowRight), 5);
+    assert_eq!(move_to(5, Key::ArrowLeft), 3);
+    // 上下移动保持列，超出行尾时到行尾
+    assert_eq!(move_to(2, Key::ArrowDown), 7);
+    assert_eq!(move_to(7, Key::ArrowDown), 8);
+    assert_eq!(move_to(14, Key::ArrowUp), 8);
+    assert_eq!(move_to(1, Key::PageDown), 8);
+    assert_eq!(move_to(6, Key::End), 7);
+    assert_eq!(move_to(6, Key::Home), 5);
+    assert_eq!(move_to(0, Key::ArrowUp), 0);
+    assert_eq!(move_to(14, Key::ArrowDown), 14);
+    // 有选中内容时，左右方向键移动到选中区域的两端
+    assert_eq!(move_cursor(&b, 1, 1..3, Key::ArrowLeft, none, 2), Some(1));
+    assert_eq!(move_cursor(&b, 1, 1..3, Key::ArrowRight, none, 2), Some(3));
+    assert_eq!(move_cursor(&b, 1, 1..1, Key::A, none, 2), None);
+  }
+  #[test]
+  fn test_line_ending_edit() {
+    let none = Modifiers::NONE;
+    let press = |text: &str, c: usize, key: Key| {
+      let mut b = Buffer::from(text,);
+      let mut buffer = RecordingBuffer::new(&mut b,);
+      let cr = CCursorRange::one(CCursor::new(c,),);
+      let new_cr = on_key_press(&mut buffer, cr, c..c, key, none, LineEnding::Crlf, 2,).unwrap();
+      (b.as_str().to_string(), new_cr.primary.index,)
+    };
+
+    // 行首退格删除整个 \r\n
+    assert_eq!(press("ab\r\ncd", 4, Key::Backspace), ("abcd".into(), 2));
+    assert_eq!(press("ab\n\ncd", 3, Key::Backspace), ("ab\ncd".into(), 2));
+    assert_eq!(press("ab\r\ncd", 2, Key::Backspace), ("a\r\ncd".into(), 1));
+    // 行尾删除整个 \r\n
+    assert_eq!(press("ab\r\ncd", 2, Key::Delete), ("abcd".into(), 2));
+    assert_eq!(press("ab\r\ncd", 4, Key::Delete), ("ab\r\nd".into(), 4));
+    // 换行插入文件的 line_ending
+    assert_eq!(press("abcd", 2, Key::Enter), ("ab\r\ncd".into(), 4));
+  }
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// 用户配置目录，如 ~/.config/egui_code
+pub fn config_dir() -> Option<PathBuf,> {
+  dirs::config_dir().map(|v| v.join("egui_code",),)
+}
+
+/// 读取配置目录中的 toml 文件，文件不存在时返回默认值
+pub fn load_toml<T: DeserializeOwned + Default,>(file_name: &str,) -> Result<T, String,> {
+  let Some(path,) = config_dir().map(|v| v.join(file_name,),) else {
+    return Ok(T::default(),);
+  };
+  match std::fs::read_to_string(&path,) {
+    Ok(text,) => toml::from_str(&text,).map_err(|e| format!("解析 {} 失败: {e}", path.display()),),
+    Err(e,) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default(),),
+    Err(e,) => Err(format!("读取 {} 失败: {e}", path.display()),),
+  }
+}
+
+/// 将 value 保存为配置目录中的 toml 文件
+pub fn save_toml<T: Serialize,>(
+  file_name: &str,
+  value: &T,
+) -> Result<(), String,> {
+  let Some(dir,) = config_dir() else {
+    return Err("找不到配置目录".to_string(),);
+  };
+  let path = dir.join(file_name,);
+  let text = toml::to_string(value,).map_err(|e| format!("序列化 {} 失败: {e}", path.display()),)?;
+  std::fs::create_dir_all(&dir,)
+    .and_then(|_| std::fs::write(&path, text,),)
+    .map_err(|e| format!("写入 {} 失败: {e}", path.display()),)
+}
@@ -0,0 +1,361 @@
+use std::{
+  collections::BTreeMap,
+  ops::Range,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+use eframe::egui;
+use serde::Deserialize;
+
+use crate::{buffer::Buffer, font::NerdFont, style};
+
+/// 默认的检查命令
+pub const DEFAULT_CHECK_COMMAND: &str = "cargo check --message-format=json";
+
+/// 诊断的严重程度，按严重程度排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+pub enum Severity {
+  Error,
+  Warning,
+  Note,
+  Help,
+}
+
+impl Severity {
+  pub fn color(&self,) -> egui::Color32 {
+    match self {
+      Self::Error => style::DIAGNOSTIC_ERROR_COLOR,
+      Self::Warning => style::DIAGNOSTIC_WARNING_COLOR,
+      Self::Note | Self::Help => style::DIAGNOSTIC_INFO_COLOR,
+    }
+  }
+
+  pub fn icon(&self,) -> char {
+    match self {
+      Self::Error => NerdFont::ERROR.utf(),
+      Self::Warning => NerdFont::WARN.utf(),
+      Self::Note | Self::Help => NerdFont::INFO.utf(),
+    }
+  }
+
+  fn from_level(level: &str,) -> Option<Self,> {
+    match level {
+      "warning" => Some(Self::Warning,),
+      "note" => Some(Self::Note,),
+      "help" => Some(Self::Help,),
+      // "error", "error: internal compiler error"
+      _ if level.starts_with("error",) => Some(Self::Error,),
+      _ => None,
+    }
+  }
+}
+
+/// 编译器诊断，位置为主 span
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Diagnostic {
+  pub path: PathBuf,
+  pub severity: Severity,
+  pub message: String,
+  /// 如 unused_variables, E0308
+  pub code: Option<String,>,
+  /// 起点 (行, 列)，从 0 开始，列为 char 索引
+  pub start: (usize, usize,),
+  /// 终点 (行, 列)，从 0 开始，列为 char 索引
+  pub end: (usize, usize,),
+}
+
+// ------------------------------------ cargo --message-format=json
+
+#[derive(Deserialize,)]
+struct CargoMessage {
+  reason: String,
+  message: Option<RustcDiagnostic,>,
+}
+
+#[derive(Deserialize,)]
+struct RustcDiagnostic {
+  message: String,
+  code: Option<RustcCode,>,
+  level: String,
+  spans: Vec<RustcSpan,>,
+}
+
+#[derive(Deserialize,)]
+struct RustcCode {
+  code: String,
+}
+
+#[derive(Deserialize,)]
+struct RustcSpan {
+  file_name: String,
+  is_primary: bool,
+  /// 以下行列均从 1 开始
+  line_start: usize,
+  line_end: usize,
+  column_start: usize,
+  column_end: usize,
+}
+
+/// 解析 cargo 输出的 JSON 消息，忽略非诊断消息和没有位置的诊断（如 "aborting due to ..."）
+///
+/// span 的路径相对于 workspace 根目录，从 root 向上查找存在的文件
+pub fn parse_cargo_messages(
+  output: &str,
+  root: &Path,
+) -> Vec<Diagnostic,> {
+  let mut diagnostics = vec![];
+  for line in output.lines().filter(|v| v.starts_with('{',),) {
+    let Ok(message,) = serde_json::from_str::<CargoMessage,>(line,) else {
+      continue;
+    };
+    if message.reason != "compiler-message" {
+      continue;
+    }
+    let Some(rustc,) = message.message else {
+      continue;
+    };
+    let Some(severity,) = Severity::from_level(&rustc.level,) else {
+      continue;
+    };
+    let Some(span,) = rustc.spans.iter().find(|v| v.is_primary,) else {
+      continue;
+    };
+    let diagnostic = Diagnostic {
+      path: resolve_path(root, &span.file_name,),
+      severity,
+      message: rustc.message,
+      code: rustc.code.map(|v| v.code,),
+      start: (
+        span.line_start.saturating_sub(1,),
+        span.column_start.saturating_sub(1,),
+      ),
+      end: (
+        span.line_end.saturating_sub(1,),
+        span.column_end.saturating_sub(1,),
+      ),
+    };
+    // 同一文件属于多个 target 时会重复输出
+    if !diagnostics.contains(&diagnostic,) {
+      diagnostics.push(diagnostic,);
+    }
+  }
+  diagnostics
+}
+
+fn resolve_path(
+  root: &Path,
+  file_name: &str,
+) -> PathBuf {
+  root
+    .ancestors()
+    .map(|v| v.join(file_name,),)
+    .find(|v| v.exists(),)
+    .unwrap_or_else(|| root.join(file_name,),)
+}
+
+/// 在 root 中运行检查命令，解析输出的诊断
+pub fn run_check(
+  command: &str,
+  root: &Path,
+) -> Result<Vec<Diagnostic,>, String,> {
+  let args = shlex::split(command,).filter(|v| !v.is_empty(),);
+  let Some((program, args,),) = args.as_ref().and_then(|v| v.split_first(),) else {
+    return Err(format!("无效的检查命令: {command}"),);
+  };
+  let output = Command::new(program,)
+    .args(args,)
+    .current_dir(root,)
+    .output()
+    .map_err(|e| format!("运行 {command} 失败: {e}"),)?;
+  let diagnostics = parse_cargo_messages(&String::from_utf8_lossy(&output.stdout,), root,);
+  // 没有诊断却失败，如找不到 Cargo.toml
+  if !output.status.success() && diagnostics.is_empty() {
+    let stderr = String::from_utf8_lossy(&output.stderr,);
+    let error = stderr
+      .lines()
+      .rev()
+      .find(|v| !v.trim().is_empty(),)
+      .unwrap_or_default();
+    return Err(format!("运行 {command} 失败: {} {error}", output.status),);
+  }
+  Ok(diagnostics,)
+}
+
+/// 按文件分组，组内按位置排序
+pub fn group_by_file(diagnostics: Vec<Diagnostic,>,) -> BTreeMap<PathBuf, Vec<Diagnostic,>,> {
+  let mut groups = BTreeMap::<PathBuf, Vec<Diagnostic,>,>::new();
+  for diagnostic in diagnostics {
+    groups
+      .entry(diagnostic.path.clone(),)
+      .or_default()
+      .push(diagnostic,);
+  }
+  for diagnostics in groups.values_mut() {
+    diagnostics.sort_by_key(|v| (v.start, v.severity,),);
+  }
+  groups
+}
+
+/// 编辑器中显示的诊断
+#[derive(Debug, Default, PartialEq,)]
+pub struct EditorMarks {
+  /// 下划线，byte_range 按 start 升序排列且互不重叠
+  pub underlines: Vec<(Range<usize,>, egui::Color32,),>,
+  /// 行号栏标记：行 => (最严重的诊断的颜色, 所有诊断的消息)
+  pub lines: BTreeMap<usize, (egui::Color32, String,),>,
+}
+
+/// 将诊断的位置转换为 buffer 中的 byte_range，文件修改后位置可能越界，按 buffer 截断
+pub fn editor_marks(
+  buffer: &Buffer,
+  diagnostics: &[Diagnostic],
+) -> EditorMarks {
+  let last_line = buffer.len_lines().saturating_sub(1,);
+  let mut marks = EditorMarks::default();
+  let mut diagnostics = diagnostics.iter().collect::<Vec<_,>>();
+  diagnostics.sort_by_key(|v| (v.severity, v.start,),);
+  let mut ranges = vec![];
+  for d in diagnostics {
+    let mut start = buffer.line_column_to_char(d.start,);
+    let mut end = buffer.line_column_to_char(d.end,).max(start,);
+    // 空的 span（如缺少分号）扩展为一个字符
+    if start == end {
+      let (line, _,) = buffer.char_to_line_column(start,);
+      if end < buffer.line_to_char(line,) + buffer.line_len_chars(line,) {
+        end += 1;
+      } else if start > buffer.line_to_char(line,) {
+        start -= 1;
+      }
+    }
+    if start < end {
+      let range = buffer.char_to_byte(start,)..buffer.char_to_byte(end,);
+      ranges.push((range, d.severity.color(),),);
+    }
+    let line = d.start.0.min(last_line,);
+    let message = d.message.lines().next().unwrap_or_default();
+    marks
+      .lines
+      .entry(line,)
+      .and_modify(|(_, v,)| {
+        v.push('\n',);
+        v.push_str(message,);
+      },)
+      .or_insert_with(|| (d.severity.color(), message.to_string(),),);
+  }
+  // 严重的诊断在前，与其重叠的诊断不显示下划线
+  for (range, color,) in ranges {
+    let i = marks
+      .underlines
+      .partition_point(|(r, _,)| r.start < range.start,);
+    let overlap_prev = i > 0 && marks.underlines[i - 1].0.end > range.start;
+    let overlap_next = marks
+      .underlines
+      .get(i,)
+      .is_some_and(|(r, _,)| r.start < range.end,);
+    if !overlap_prev && !overlap_next {
+      marks.underlines.insert(i, (range, color,),);
+    }
+  }
+  marks
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::{Path, PathBuf};
+
+  use super::{editor_marks, group_by_file, parse_cargo_messages, Diagnostic, Severity};
+  use crate::buffer::Buffer;
+
+  /// cargo check --message-format=json 的输出，省略了部分字段
+  const OUTPUT: &str = r#"{"reason":"compiler-artifact","package_id":"libc 0.2.190","manifest_path":"/tmp/project/Cargo.toml","target":{"kind":["lib"],"name":"libc"},"profile":{},"features":[],"filenames":[],"executable":null,"fresh":true}
+{"reason":"compiler-message","package_id":"project 0.1.0","manifest_path":"/tmp/project/Cargo.toml","target":{"kind":["bin"],"name":"project"},"message":{"rendered":"warning: unused variable: `x`\n --> src/main.rs:2:7\n","$message_type":"diagnostic","children":[{"children":[],"code":null,"level":"help","message":"if this is intentional, prefix it with an underscore","rendered":null,"spans":[]}],"code":{"code":"unused_variables","explanation":null},"level":"warning","message":"unused variable: `x`","spans":[{"byte_end":24,"byte_start":23,"column_end":8,"column_start":7,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}
+{"reason":"compiler-message","package_id":"project 0.1.0","manifest_path":"/tmp/project/Cargo.toml","target":{"kind":["bin"],"name":"project"},"message":{"rendered":"error[E0308]: mismatched types\n","$message_type":"diagnostic","children":[],"code":{"code":"E0308","explanation":"..."},"level":"error","message":"mismatched types","spans":[{"byte_end":70,"byte_start":65,"column_end":17,"column_start":12,"expansion":null,"file_name":"src/lib.rs","is_primary":false,"label":"expected due to this","line_end":4,"line_start":4,"suggested_replacement":null,"suggestion_applicability":null,"text":[]},{"byte_end":90,"byte_start":80,"column_end":3,"column_start":20,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":"expected `i32`, found `&str`","line_end":6,"line_start":5,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}
+{"reason":"compiler-message","package_id":"project 0.1.0","manifest_path":"/tmp/project/Cargo.toml","target":{"kind":["test"],"name":"project"},"message":{"rendered":"warning: unused variable: `x`\n --> src/main.rs:2:7\n","$message_type":"diagnostic","children":[],"code":{"code":"unused_variables","explanation":null},"level":"warning","message":"unused variable: `x`","spans":[{"byte_end":24,"byte_start":23,"column_end":8,"column_start":7,"expansion":null,"file_name":"src/main.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}]}}
+{"reason":"compiler-message","package_id":"project 0.1.0","manifest_path":"/tmp/project/Cargo.toml","target":{"kind":["bin"],"name":"project"},"message":{"rendered":"error: aborting due to 1 previous error\n","$message_type":"diagnostic","children":[],"code":null,"level":"error","message":"aborting due to 1 previous error","spans":[]}}
+{"reason":"compiler-message","package_id":"project 0.1.0","manifest_path":"/tmp/project/Cargo.toml","target":{"kind":["bin"],"name":"project"},"message":{"rendered":"warning: 1 warning emitted\n","$message_type":"diagnostic","children":[],"code":null,"level":"warning","message":"1 warning emitted","spans":[]}}
+{"reason":"build-finished","success":false}
+"#;
+
+  #[test]
+  fn test_parse_cargo_messages() {
+    let root = Path::new("/tmp/egui_code_no_such_project",);
+    let diagnostics = parse_cargo_messages(OUTPUT, root,);
+    assert_eq!(
+      diagnostics,
+      vec![
+        Diagnostic {
+          path: root.join("src/main.rs"),
+          severity: Severity::Warning,
+          message: "unused variable: `x`".into(),
+          code: Some("unused_variables".into()),
+          start: (1, 6),
+          end: (1, 7),
+        },
+        Diagnostic {
+          path: root.join("src/lib.rs"),
+          severity: Severity::Error,
+          message: "mismatched types".into(),
+          code: Some("E0308".into()),
+          start: (4, 19),
+          end: (5, 2),
+        },
+      ]
+    );
+
+    let groups = group_by_file(diagnostics,);
+    assert_eq!(
+      groups.keys().collect::<Vec<_,>>(),
+      vec![&root.join("src/lib.rs"), &root.join("src/main.rs")]
+    );
+
+    // 非 JSON 行和无法解析的行被忽略
+    assert!(parse_cargo_messages("   Compiling project\n{\"reason\":1}\n", root).is_empty());
+  }
+
+  #[test]
+  fn test_resolve_path() {
+    // 在子目录中打开时，路径相对于上层的 workspace 根目录
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"),).join("src",);
+    let output = OUTPUT.replace("src/lib.rs", "src/main.rs",);
+    let diagnostics = parse_cargo_messages(&output, &root,);
+    assert_eq!(diagnostics[0].path, root.join("main.rs"));
+  }
+
+  #[test]
+  fn test_editor_marks() {
+    let diagnostic = |severity, start, end| Diagnostic {
+      path: PathBuf::from("/tmp/project/src/main.rs",),
+      severity,
+      message: format!("{severity:?}"),
+      code: None,
+      start,
+      end,
+    };
+    let buffer = Buffer::from("fn main() {\n  let 变量 = 1\n}\n".to_string(),);
+    let marks = editor_marks(
+      &buffer,
+      &[
+        // 变量
+        diagnostic(Severity::Warning, (1, 6,), (1, 8,),),
+        // 与警告重叠
+        diagnostic(Severity::Error, (1, 2,), (1, 7,),),
+        // 空的 span 位于行尾
+        diagnostic(Severity::Error, (1, 12,), (1, 12,),),
+        // 越界
+        diagnostic(Severity::Note, (100, 0,), (100, 3,),),
+      ],
+    );
+    let red = Severity::Error.color();
+    // "let 变" 的 byte_range 为 14..21，"1" 为 27..28，越界的诊断位于空行，没有下划线
+    assert_eq!(marks.underlines, vec![(14..21, red), (27..28, red)]);
+    assert_eq!(
+      marks.lines.get(&1),
+      Some(&(red, "Error\nError\nWarning".to_string()))
+    );
+    assert_eq!(
+      marks.lines.get(&3).map(|v| v.0),
+      Some(Severity::Note.color())
+    );
+  }
+}
@@ -0,0 +1,284 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+/// 最多保留的可撤销文件操作
+const MAX_UNDO_COUNT: usize = 100;
+
+/// 文件树中执行过的文件操作
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub enum FileOp {
+  /// 新建文件或文件夹
+  Create(PathBuf,),
+  /// 重命名或移动
+  Rename { from: PathBuf, to: PathBuf, },
+  /// 移到回收站
+  Delete(PathBuf,),
+  /// 创建副本
+  Duplicate { from: PathBuf, to: PathBuf, },
+}
+
+/// 文件操作（或撤销）对文件系统的影响，用于更新文件树和已打开的文件
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub enum FileChange {
+  Created(PathBuf,),
+  Removed(PathBuf,),
+  Renamed { from: PathBuf, to: PathBuf, },
+}
+
+impl FileOp {
+  /// 执行操作后的影响
+  pub fn change(&self,) -> FileChange {
+    match self {
+      FileOp::Create(path,) => FileChange::Created(path.clone(),),
+      FileOp::Rename {
+        from,
+        to,
+      } => FileChange::Renamed {
+        from: from.clone(),
+        to: to.clone(),
+      },
+      FileOp::Delete(path,) => FileChange::Removed(path.clone(),),
+      FileOp::Duplicate {
+        to, ..
+      } => FileChange::Created(to.clone(),),
+    }
+  }
+
+  /// 撤销操作，返回撤销后的影响
+  ///
+  /// 新建和副本会被移到回收站而不是直接删除，其中可能已经写入了内容
+  pub fn undo(&self,) -> Result<FileChange, String,> {
+    match self {
+      FileOp::Create(path,)
+      | FileOp::Duplicate {
+        to: path, ..
+      } => {
+        trash::delete(path,).map_err(|e| e.to_string(),)?;
+        Ok(FileChange::Removed(path.clone(),),)
+      }
+      FileOp::Rename {
+        from,
+        to,
+      } => {
+        rename(to, from,).map_err(|e| e.to_string(),)?;
+        Ok(FileChange::Renamed {
+          from: to.clone(),
+          to: from.clone(),
+        },)
+      }
+      FileOp::Delete(path,) => {
+        restore(path,)?;
+        Ok(FileChange::Created(path.clone(),),)
+      }
+    }
+  }
+}
+
+/// 新建空文件，文件已存在时失败
+pub fn create_file(path: &Path,) -> std::io::Result<FileOp,> {
+  fs::OpenOptions::new()
+    .write(true,)
+    .create_new(true,)
+    .open(path,)?;
+  Ok(FileOp::Create(path.to_owned(),),)
+}
+
+/// 新建文件夹，文件夹已存在时失败
+pub fn create_dir(path: &Path,) -> std::io::Result<FileOp,> {
+  fs::create_dir(path,)?;
+  Ok(FileOp::Create(path.to_owned(),),)
+}
+
+/// 重命名或移动，不覆盖已存在的文件
+pub fn rename(
+  from: &Path,
+  to: &Path,
+) -> std::io::Result<FileOp,> {
+  if to.exists() {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::AlreadyExists,
+      format!("{} 已存在", to.display()),
+    ),);
+  }
+  if to.starts_with(from,) {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      "不能移动到自身的子文件夹中",
+    ),);
+  }
+  fs::rename(from, to,)?;
+  Ok(FileOp::Rename {
+    from: from.to_owned(),
+    to: to.to_owned(),
+  },)
+}
+
+/// 移到回收站
+pub fn delete(path: &Path,) -> Result<FileOp, String,> {
+  trash::delete(path,).map_err(|e| e.to_string(),)?;
+  Ok(FileOp::Delete(path.to_owned(),),)
+}
+
+/// 在同一文件夹中创建副本，如 a.rs => a copy.rs
+pub fn duplicate(path: &Path,) -> std::io::Result<FileOp,> {
+  let to = copy_path(path,);
+  if path.is_dir() {
+    copy_dir(path, &to,)?;
+  } else {
+    fs::copy(path, &to,)?;
+  }
+  Ok(FileOp::Duplicate {
+    from: path.to_owned(),
+    to,
+  },)
+}
+
+/// 副本的路径：a.rs => a copy.rs => a copy 2.rs ...
+fn copy_path(path: &Path,) -> PathBuf {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  let (stem, extension,) = match path.extension() {
+    Some(extension,) if path.is_file() => (
+      path.file_stem().unwrap_or_default().to_string_lossy(),
+      format!(".{}", extension.to_string_lossy()),
+    ),
+    _ => (name, String::new(),),
+  };
+  (1..)
+    .map(|i| {
+      let suffix = if i == 1 {
+        " copy".to_string()
+      } else {
+        format!(" copy {i}")
+      };
+      path.with_file_name(format!("{stem}{suffix}{extension}"),)
+    },)
+    .find(|v| !v.exists(),)
+    .unwrap()
+}
+
+fn copy_dir(
+  from: &Path,
+  to: &Path,
+) -> std::io::Result<(),> {
+  fs::create_dir(to,)?;
+  for entry in fs::read_dir(from,)? {
+    let entry = entry?;
+    let target = to.join(entry.file_name(),);
+    if entry.file_type()?.is_dir() {
+      copy_dir(&entry.path(), &target,)?;
+    } else {
+      fs::copy(entry.path(), target,)?;
+    }
+  }
+  Ok((),)
+}
+
+/// 从回收站恢复最近一次删除的 path
+#[cfg(any(
+  target_os = "windows",
+  all(
+    unix,
+    not(target_os = "macos"),
+    not(target_os = "ios"),
+    not(target_os = "android")
+  )
+))]
+fn restore(path: &Path,) -> Result<(), String,> {
+  let item = trash::os_limited::list()
+    .map_err(|e| e.to_string(),)?
+    .into_iter()
+    .filter(|v| v.original_path() == path,)
+    .max_by_key(|v| v.time_deleted,)
+    .ok_or(format!("回收站中找不到 {}", path.display()),)?;
+  trash::os_limited::restore_all([item,],).map_err(|e| e.to_string(),)
+}
+
+#[cfg(not(any(
+  target_os = "windows",
+  all(
+    unix,
+    not(target_os = "macos"),
+    not(target_os = "ios"),
+    not(target_os = "android")
+  )
+)))]
+fn restore(path: &Path,) -> Result<(), String,> {
+  Err(format!("当前系统不支持从回收站恢复 {}", path.display()),)
+}
+
+/// 可撤销的文件操作记录
+#[derive(Debug, Default,)]
+pub struct FileOpHistory {
+  ops: Vec<FileOp,>,
+}
+
+impl FileOpHistory {
+  pub fn record(
+    &mut self,
+    op: FileOp,
+  ) {
+    self.ops.push(op,);
+    if self.ops.len() > MAX_UNDO_COUNT {
+      self.ops.remove(0,);
+    }
+  }
+
+  /// 撤销最近一次文件操作，没有可撤销的操作时返回 None
+  pub fn undo(&mut self,) -> Option<Result<FileChange, String,>,> {
+    let op = self.ops.pop()?;
+    let result = op.undo();
+    if result.is_err() {
+      // 撤销失败时保留，解决问题后可以再次撤销
+      self.ops.push(op,);
+    }
+    Some(result,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs;
+
+  use super::{create_dir, create_file, duplicate, rename, FileChange, FileOp};
+  use crate::test_util::TempDir;
+
+  #[test]
+  fn test_file_ops() {
+    let dir = TempDir::new("file_op",);
+
+    let src = dir.join("src",);
+    assert_eq!(create_dir(&src,).unwrap(), FileOp::Create(src.clone()));
+    let main = src.join("main.rs",);
+    create_file(&main,).unwrap();
+    assert!(create_file(&main,).is_err());
+    fs::write(&main, "fn main() {}",).unwrap();
+
+    // 副本不覆盖已存在的文件
+    let op = duplicate(&main,).unwrap();
+    assert_eq!(op.change(), FileChange::Created(src.join("main copy.rs")));
+    duplicate(&main,).unwrap();
+    assert!(src.join("main copy 2.rs").is_file());
+    let op = duplicate(&src,).unwrap();
+    assert_eq!(
+      fs::read_to_string(dir.join("src copy/main.rs")).unwrap(),
+      "fn main() {}"
+    );
+    assert_eq!(op.change(), FileChange::Created(dir.join("src copy")));
+
+    // 重命名和撤销
+    let lib = src.join("lib.rs",);
+    let op = rename(&main, &lib,).unwrap();
+    assert!(rename(&lib, &src.join("main copy.rs"),).is_err());
+    assert!(rename(&src, &src.join("sub"),).is_err());
+    assert_eq!(
+      op.undo(),
+      Ok(FileChange::Renamed {
+        from: lib.clone(),
+        to: main.clone(),
+      })
+    );
+    assert!(main.is_file() && !lib.exists());
+  }
+}
@@ -51,6 +51,8 @@ impl NerdFont<'static,> {
 impl NerdFont<'static,> {
   // 
   pub const WARN: NerdFont<'static,> = NerdFont('\u{ea6c}', "nf-cod-warning",);
+  // 
+  pub const ERROR: NerdFont<'static,> = NerdFont('\u{ea87}', "nf-cod-error",);
   // 
   pub const INFO: NerdFont<'static,> = NerdFont('\u{ea74}', "nf-cod-info",);
   // 
@@ -63,4 +65,14 @@ impl NerdFont<'static,> {
   pub const FOLDER_OPEN: NerdFont<'static,> = NerdFont('\u{f07c}', "nf-fa-folder_open",);
   // 
   pub const RUST: NerdFont<'static,> = NerdFont('\u{e7a8}', "nf-dev-rust",);
+  // 
+  pub const EYE: NerdFont<'static,> = NerdFont('\u{ea70}', "nf-cod-eye",);
+  // 
+  pub const EYE_CLOSED: NerdFont<'static,> = NerdFont('\u{eae7}', "nf-cod-eye_closed",);
+  //
+  pub const TERMINAL: NerdFont<'static,> = NerdFont('\u{ea85}', "nf-cod-terminal",);
+  //
+  pub const ADD: NerdFont<'static,> = NerdFont('\u{ea60}', "nf-cod-add",);
+  //
+  pub const CLOSE: NerdFont<'static,> = NerdFont('\u{ea76}', "nf-cod-close",);
 }
@@ -0,0 +1,241 @@
+use std::{
+  collections::BTreeSet,
+  path::{Path, PathBuf},
+  sync::mpsc::{channel, Receiver, Sender},
+  time::Duration,
+};
+
+use eframe::egui;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{path_filter::PathFilter, util};
+
+/// 发送给监视线程的命令
+enum WatchCommand {
+  Watch(PathBuf, RecursiveMode,),
+  Unwatch(PathBuf,),
+}
+
+/// 监视打开的文件夹，收集在磁盘上发生变化的路径
+///
+/// notify 的 watcher 在后台线程中创建和修改，添加大量监视时不阻塞 UI
+pub struct FsWatcher {
+  ctx: egui::Context,
+  /// 监视线程的命令，None 表示未监视
+  commands: Option<Sender<WatchCommand,>,>,
+  rx: Receiver<PathBuf,>,
+  /// set_dirs 监视的文件夹（不递归）
+  dirs: BTreeSet<PathBuf,>,
+}
+
+impl FsWatcher {
+  pub fn new(ctx: egui::Context,) -> Self {
+    let (_, rx,) = channel();
+    Self {
+      ctx,
+      commands: None,
+      rx,
+      dirs: BTreeSet::new(),
+    }
+  }
+
+  /// 递归监视 dir，用于配置目录等较小的文件夹；之前的监视会被停止，None 表示不再监视
+  pub fn watch(
+    &mut self,
+    dir: Option<&Path,>,
+  ) {
+    self.stop();
+    if let Some(dir,) = dir {
+      let commands = self.start(None,);
+      let _ = commands.send(WatchCommand::Watch(
+        dir.to_owned(),
+        RecursiveMode::Recursive,
+      ),);
+    }
+  }
+
+  /// 开始监视，之后只监视 set_dirs 指定的文件夹，被 filter 排除的路径的变化会被忽略
+  pub fn watch_filtered(
+    &mut self,
+    filter: PathFilter,
+  ) {
+    self.stop();
+    self.start(Some(filter,),);
+  }
+
+  /// 停止监视
+  ///
+  /// 每次监视使用新的 channel，之前的监视线程中延迟发出的变化会被丢弃
+  pub fn stop(&mut self,) {
+    self.commands = None;
+    self.dirs.clear();
+    let (_, rx,) = channel();
+    self.rx = rx;
+  }
+
+  fn start(
+    &mut self,
+    filter: Option<PathFilter,>,
+  ) -> Sender<WatchCommand,> {
+    let (tx, rx,) = channel();
+    self.rx = rx;
+    let (commands, commands_rx,) = channel::<WatchCommand,>();
+    self.commands = Some(commands.clone(),);
+    let ctx = self.ctx.clone();
+    std::thread::spawn(move || {
+      let handler_ctx = ctx.clone();
+      let watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event,>| match res {
+          // 读取文件也会产生 Access 事件
+          Ok(event,) if !matches!(event.kind, EventKind::Access(_,)) => {
+            let mut changed = false;
+            for path in event.paths {
+              if filter.as_ref().is_some_and(|v| v.is_excluded(&path,),) {
+                continue;
+              }
+              changed = true;
+              let _ = tx.send(path,);
+            }
+            if changed {
+              handler_ctx.request_repaint();
+            }
+          }
+          Ok(_,) => {}
+          Err(e,) => tracing::warn!("监视文件失败: {e}"),
+        },);
+      let mut watcher = match watcher {
+        Ok(watcher,) => watcher,
+        Err(e,) => {
+          util::toaster()
+            .error(format!("监视文件失败: {e}"),)
+            .set_duration(Some(Duration::from_secs(5,),),);
+          ctx.request_repaint();
+          return;
+        }
+      };
+      // FsWatcher 停止监视后 channel 断开，线程结束时 watcher 被 drop
+      for command in commands_rx {
+        match command {
+          WatchCommand::Watch(dir, mode,) => {
+            if let Err(e,) = watcher.watch(&dir, mode,) {
+              // 文件夹可能已被删除，只有递归监视的文件夹需要提示
+              if mode == RecursiveMode::Recursive {
+                util::toaster()
+                  .error(format!("监视文件夹 {} 失败: {e}", dir.display()),)
+                  .set_duration(Some(Duration::from_secs(5,),),);
+                ctx.request_repaint();
+              } else {
+                tracing::warn!("监视文件夹 {} 失败: {e}", dir.display());
+              }
+            }
+          }
+          WatchCommand::Unwatch(dir,) => {
+            let _ = watcher.unwatch(&dir,);
+          }
+        }
+      }
+    },);
+    commands
+  }
+
+  /// 更新监视的文件夹（不递归），只监视新增的、取消移除的
+  pub fn set_dirs(
+    &mut self,
+    dirs: BTreeSet<PathBuf,>,
+  ) {
+    let Some(commands,) = &self.commands else {
+      return;
+    };
+    if dirs == self.dirs {
+      return;
+    }
+    for dir in self.dirs.difference(&dirs,) {
+      let _ = commands.send(WatchCommand::Unwatch(dir.clone(),),);
+    }
+    for dir in dirs.difference(&self.dirs,) {
+      let _ = commands.send(WatchCommand::Watch(
+        dir.clone(),
+        RecursiveMode::NonRecursive,
+      ),);
+    }
+    self.dirs = dirs;
+  }
+
+  /// 取走上次调用以来发生变化的路径
+  pub fn take_changed_paths(&self,) -> BTreeSet<PathBuf,> {
+    self.rx.try_iter().collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+  };
+
+  use eframe::egui;
+
+  use super::FsWatcher;
+  use crate::{path_filter::PathFilter, test_util::TempDir};
+
+  /// 等待 file 的变化
+  fn wait_change(
+    watcher: &FsWatcher,
+    file: &Path,
+  ) -> BTreeSet<PathBuf,> {
+    let deadline = Instant::now() + Duration::from_secs(10,);
+    let mut paths = watcher.take_changed_paths();
+    while !paths.contains(file,) {
+      assert!(Instant::now() < deadline);
+      std::thread::sleep(Duration::from_millis(10,),);
+      paths.extend(watcher.take_changed_paths(),);
+    }
+    paths
+  }
+
+  #[test]
+  fn test_fs_watcher() {
+    let tmp = TempDir::new("fs_watcher",);
+    fs::create_dir_all(tmp.join("src",),).unwrap();
+    let dir = tmp.canonicalize().unwrap();
+
+    let mut watcher = FsWatcher::new(egui::Context::default(),);
+    watcher.watch(Some(&dir,),);
+    // 等待监视线程添加监视
+    std::thread::sleep(Duration::from_millis(100,),);
+    let file = dir.join("src/main.rs",);
+    fs::write(&file, "fn main() {}",).unwrap();
+    wait_change(&watcher, &file,);
+
+    // 停止监视后不再收到变化
+    watcher.watch(None,);
+    watcher.take_changed_paths();
+    fs::write(&file, "",).unwrap();
+    std::thread::sleep(Duration::from_millis(100,),);
+    assert!(watcher.take_changed_paths().is_empty());
+
+    // 只监视指定的文件夹，忽略被排除的路径
+    fs::create_dir_all(dir.join("target",),).unwrap();
+    watcher.watch_filtered(PathFilter::new(&dir, ["*.log",],).unwrap(),);
+    watcher.set_dirs(BTreeSet::from([dir.clone(), dir.join("src",),],),);
+    std::thread::sleep(Duration::from_millis(100,),);
+    fs::write(dir.join("target/a.rs",), "",).unwrap();
+    fs::write(dir.join("a.log",), "",).unwrap();
+    let file = dir.join("b.rs",);
+    fs::write(&file, "",).unwrap();
+    let paths = wait_change(&watcher, &file,);
+    assert!(!paths.contains(&dir.join("target/a.rs")));
+    assert!(!paths.contains(&dir.join("a.log")));
+
+    // 取消监视移除的文件夹
+    watcher.set_dirs(BTreeSet::from([dir.clone(),],),);
+    std::thread::sleep(Duration::from_millis(100,),);
+    watcher.take_changed_paths();
+    fs::write(dir.join("src/main.rs",), "x",).unwrap();
+    std::thread::sleep(Duration::from_millis(100,),);
+    assert!(watcher.take_changed_paths().is_empty());
+  }
+}
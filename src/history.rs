@@ -0,0 +1,460 @@
+use std::{
+  ops::Range,
+  time::{Duration, Instant},
+};
+
+use eframe::egui;
+
+use crate::buffer::{Buffer, LineEdit};
+
+/// 连续输入合并为一个撤销步骤的最大时间间隔
+const COALESCE_INTERVAL: Duration = Duration::from_secs(1,);
+
+/// 最多保留的撤销步骤
+const MAX_UNDO_COUNT: usize = 1000;
+
+/// 一次编辑：把 text 中从 byte 索引 index 开始的 deleted 替换为 inserted
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Edit {
+  pub index: usize,
+  pub deleted: String,
+  pub inserted: String,
+}
+
+impl Edit {
+  /// 根据公共 前缀/后缀 计算 old => new 的最小编辑
+  pub fn diff(
+    old: &str,
+    new: &str,
+  ) -> Option<Self,> {
+    if old == new {
+      return None;
+    }
+    let mut prefix = old
+      .bytes()
+      .zip(new.bytes(),)
+      .take_while(|(a, b,)| a == b,)
+      .count();
+    while !old.is_char_boundary(prefix,) || !new.is_char_boundary(prefix,) {
+      prefix -= 1;
+    }
+    let mut suffix = old[prefix..]
+      .bytes()
+      .rev()
+      .zip(new[prefix..].bytes().rev(),)
+      .take_while(|(a, b,)| a == b,)
+      .count();
+    while !old.is_char_boundary(old.len() - suffix,) || !new.is_char_boundary(new.len() - suffix,) {
+      suffix -= 1;
+    }
+    Some(Self {
+      index: prefix,
+      deleted: old[prefix..old.len() - suffix].to_string(),
+      inserted: new[prefix..new.len() - suffix].to_string(),
+    },)
+  }
+
+  pub fn apply(
+    &self,
+    buffer: &mut Buffer,
+  ) {
+    buffer.replace_range(self.index..self.index + self.deleted.len(), &self.inserted,);
+  }
+
+  pub fn invert(&self,) -> Self {
+    Self {
+      index: self.index,
+      deleted: self.inserted.clone(),
+      inserted: self.deleted.clone(),
+    }
+  }
+
+  /// 编辑后 inserted 的 byte_range
+  fn inserted_range(&self,) -> Range<usize,> {
+    self.index..self.index + self.inserted.len()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+enum EditKind {
+  /// 输入单个字符
+  Typing,
+  /// 删除单个字符（Backspace / Delete）
+  Deleting,
+  /// 粘贴、替换、自动缩进等，不与其他步骤合并
+  Other,
+}
+
+impl EditKind {
+  fn of(edits: &[Edit],) -> Self {
+    let is_single_char = |s: &str| {
+      let mut chars = s.chars();
+      matches!((chars.next(), chars.next()), (Some(c,), None,) if c != '\n')
+    };
+    match edits {
+      [e] if e.deleted.is_empty() && is_single_char(&e.inserted,) => Self::Typing,
+      [e] if e.inserted.is_empty() && is_single_char(&e.deleted,) => Self::Deleting,
+      _ => Self::Other,
+    }
+  }
+}
+
+/// 一个撤销步骤，包含按顺序执行的多次编辑
+#[derive(Debug,)]
+struct Transaction {
+  revision: u64,
+  kind: EditKind,
+  edits: Vec<Edit,>,
+  time: Instant,
+}
+
+impl Transaction {
+  /// 新的编辑能否合并到当前步骤
+  fn can_coalesce(
+    &self,
+    kind: EditKind,
+    edit: &Edit,
+    time: Instant,
+  ) -> bool {
+    if kind != self.kind || time.duration_since(self.time,) > COALESCE_INTERVAL {
+      return false;
+    }
+    let Some(last,) = self.edits.last() else {
+      return false;
+    };
+    match kind {
+      EditKind::Typing => last.index + last.inserted.len() == edit.index,
+      // Backspace 向前删除，Delete 原地删除
+      EditKind::Deleting => {
+        edit.index + edit.deleted.len() == last.index || edit.index == last.index
+      }
+      EditKind::Other => false,
+    }
+  }
+}
+
+/// 文件的编辑历史，支持 撤销/重做
+#[derive(Debug,)]
+pub struct History {
+  undo_stack: Vec<Transaction,>,
+  redo_stack: Vec<Transaction,>,
+  next_revision: u64,
+  saved_revision: u64,
+  /// 撤销全部步骤后的版本，超出 MAX_UNDO_COUNT 丢弃最早的步骤时改变
+  base_revision: u64,
+}
+
+impl Default for History {
+  fn default() -> Self {
+    Self {
+      undo_stack: vec![],
+      redo_stack: vec![],
+      next_revision: 1,
+      saved_revision: 0,
+      base_revision: 0,
+    }
+  }
+}
+
+impl History {
+  /// 当前文本对应的版本，0 表示打开时的版本
+  pub fn revision(&self,) -> u64 {
+    self
+      .undo_stack
+      .last()
+      .map_or(self.base_revision, |t| t.revision,)
+  }
+
+  /// 当前文本是否与最近一次保存时一致
+  pub fn is_clean(&self,) -> bool {
+    self.revision() == self.saved_revision
+  }
+
+  pub fn mark_saved(&mut self,) {
+    self.saved_revision = self.revision();
+  }
+
+  /// 记录已执行的编辑
+  pub fn record(
+    &mut self,
+    edits: Vec<Edit,>,
+  ) {
+    self.record_at(edits, Instant::now(),);
+  }
+
+  fn record_at(
+    &mut self,
+    edits: Vec<Edit,>,
+    time: Instant,
+  ) {
+    if edits.is_empty() {
+      return;
+    }
+    self.redo_stack.clear();
+
+    let kind = EditKind::of(&edits,);
+    // 已保存的版本不参与合并，保证撤销后能回到保存时的状态
+    if let Some(last,) = self
+      .undo_stack
+      .last_mut()
+      .filter(|t| t.revision != self.saved_revision && t.can_coalesce(kind, &edits[0], time,),)
+    {
+      last.edits.extend(edits,);
+      last.time = time;
+      // 合并后文本已变化，需要新的版本号
+      last.revision = self.next_revision;
+      self.next_revision += 1;
+      return;
+    }
+
+    self.undo_stack.push(Transaction {
+      revision: self.next_revision,
+      kind,
+      edits,
+      time,
+    },);
+    self.next_revision += 1;
+    if self.undo_stack.len() > MAX_UNDO_COUNT {
+      let t = self.undo_stack.remove(0,);
+      self.base_revision = t.revision;
+    }
+  }
+
+  /// 撤销，返回撤销后需要选中的 byte_range
+  pub fn undo(
+    &mut self,
+    buffer: &mut Buffer,
+  ) -> Option<Range<usize,>,> {
+    let t = self.undo_stack.pop()?;
+    let mut selection = None;
+    for e in t.edits.iter().rev() {
+      let inverted = e.invert();
+      inverted.apply(buffer,);
+      selection = Some(inverted.inserted_range(),);
+    }
+    self.redo_stack.push(t,);
+    selection
+  }
+
+  /// 重做，返回重做后 cursor 所在的 byte_range
+  pub fn redo(
+    &mut self,
+    buffer: &mut Buffer,
+  ) -> Option<Range<usize,>,> {
+    let t = self.redo_stack.pop()?;
+    let mut selection = None;
+    for e in t.edits.iter() {
+      e.apply(buffer,);
+      let end = e.inserted_range().end;
+      selection = Some(end..end,);
+    }
+    self.undo_stack.push(t,);
+    selection
+  }
+}
+
+/// 包装 Buffer，记录 TextEdit 对文本的所有修改
+pub struct RecordingBuffer<'a,> {
+  buffer: &'a mut Buffer,
+  edits: Vec<Edit,>,
+}
+
+impl<'a,> RecordingBuffer<'a,> {
+  pub fn new(buffer: &'a mut Buffer,) -> Self {
+    Self {
+      buffer,
+      edits: vec![],
+    }
+  }
+
+  pub fn buffer(&self,) -> &Buffer {
+    self.buffer
+  }
+
+  pub fn take_line_edits(&mut self,) -> Vec<LineEdit,> {
+    self.buffer.take_line_edits()
+  }
+
+  pub fn into_edits(self,) -> Vec<Edit,> {
+    self.edits
+  }
+}
+
+impl egui::TextBuffer for RecordingBuffer<'_,> {
+  fn is_mutable(&self,) -> bool {
+    true
+  }
+
+  fn as_str(&self,) -> &str {
+    self.buffer.as_str()
+  }
+
+  fn byte_index_from_char_index(
+    &self,
+    char_index: usize,
+  ) -> usize {
+    self.buffer.char_to_byte(char_index,)
+  }
+
+  fn insert_text(
+    &mut self,
+    text: &str,
+    char_index: usize,
+  ) -> usize {
+    let index = self.byte_index_from_char_index(char_index,);
+    let char_count = self.buffer.insert_text(text, char_index,);
+    if char_count > 0 {
+      self.edits.push(Edit {
+        index,
+        deleted: String::new(),
+        inserted: text.to_string(),
+      },);
+    }
+    char_count
+  }
+
+  fn delete_char_range(
+    &mut self,
+    char_range: Range<usize,>,
+  ) {
+    let start = self.byte_index_from_char_index(char_range.start,);
+    let end = self.byte_index_from_char_index(char_range.end,);
+    if start < end {
+      self.edits.push(Edit {
+        index: start,
+        deleted: self.buffer.byte_slice(start..end,).into_owned(),
+        inserted: String::new(),
+      },);
+    }
+    self.buffer.delete_char_range(char_range,);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, Instant};
+
+  use eframe::egui::TextBuffer;
+
+  use super::{Edit, History, RecordingBuffer, MAX_UNDO_COUNT};
+  use crate::buffer::Buffer;
+
+  fn typing(
+    history: &mut History,
+    text: &mut Buffer,
+    s: &str,
+    time: Instant,
+  ) {
+    for c in s.chars() {
+      let mut buffer = RecordingBuffer::new(text,);
+      let char_count = buffer.as_str().chars().count();
+      buffer.insert_text(&c.to_string(), char_count,);
+      history.record_at(buffer.into_edits(), time,);
+    }
+  }
+
+  #[test]
+  fn test_edit_diff() {
+    let e = Edit::diff("hello world", "hello rust world",).unwrap();
+    assert_eq!(e.index, 6);
+    assert_eq!(e.deleted, "");
+    assert_eq!(e.inserted, "rust ");
+
+    let e = Edit::diff("中文字", "中字",).unwrap();
+    assert_eq!(
+      (e.index, e.deleted.as_str(), e.inserted.as_str()),
+      (3, "文", "")
+    );
+
+    assert!(Edit::diff("same", "same").is_none());
+  }
+
+  #[test]
+  fn test_recording_buffer() {
+    let mut text = Buffer::from("中文abc",);
+    let mut buffer = RecordingBuffer::new(&mut text,);
+    buffer.delete_char_range(1..3,);
+    buffer.insert_text("x", 1,);
+    let edits = buffer.into_edits();
+    assert_eq!(text.as_str(), "中xbc");
+
+    let mut undo = text.clone();
+    for e in edits.iter().rev() {
+      e.invert().apply(&mut undo,);
+    }
+    assert_eq!(undo.as_str(), "中文abc");
+  }
+
+  #[test]
+  fn test_undo_redo_coalesce() {
+    let mut history = History::default();
+    let mut text = Buffer::default();
+    let now = Instant::now();
+
+    typing(&mut history, &mut text, "abc", now,);
+    // 超过合并间隔，新的撤销步骤
+    typing(&mut history, &mut text, "de", now + Duration::from_secs(5,),);
+    assert_eq!(text.as_str(), "abcde");
+    assert!(!history.is_clean());
+
+    assert_eq!(history.undo(&mut text), Some(3..3));
+    assert_eq!(text.as_str(), "abc");
+    assert_eq!(history.undo(&mut text), Some(0..0));
+    assert_eq!(text.as_str(), "");
+    assert!(history.is_clean());
+    assert_eq!(history.undo(&mut text), None);
+
+    assert_eq!(history.redo(&mut text), Some(3..3));
+    assert_eq!(history.redo(&mut text), Some(5..5));
+    assert_eq!(text.as_str(), "abcde");
+    assert_eq!(history.redo(&mut text), None);
+  }
+
+  #[test]
+  fn test_saved_revision() {
+    let mut history = History::default();
+    let mut text = Buffer::default();
+    let now = Instant::now();
+
+    typing(&mut history, &mut text, "ab", now,);
+    history.mark_saved();
+    assert!(history.is_clean());
+
+    // 保存后的输入不合并到已保存的版本中
+    typing(&mut history, &mut text, "c", now,);
+    assert!(!history.is_clean());
+    history.undo(&mut text,);
+    assert_eq!(text.as_str(), "ab");
+    assert!(history.is_clean());
+
+    // 新的编辑使 redo 中的版本失效
+    history.undo(&mut text,);
+    typing(&mut history, &mut text, "x", now,);
+    assert_eq!(history.redo(&mut text), None);
+    assert!(!history.is_clean());
+  }
+
+  #[test]
+  fn test_undo_count_limit() {
+    let mut history = History::default();
+    let mut text = Buffer::default();
+    let now = Instant::now();
+    for i in 0..=MAX_UNDO_COUNT {
+      typing(
+        &mut history,
+        &mut text,
+        "a",
+        now + Duration::from_secs(i as u64 * 5,),
+      );
+    }
+
+    // 最早的步骤已丢弃，撤销全部后仍与打开时不同
+    while history.undo(&mut text,).is_some() {}
+    assert_eq!(text.as_str(), "a");
+    assert!(!history.is_clean());
+    history.redo(&mut text,);
+    history.mark_saved();
+    history.undo(&mut text,);
+    assert!(!history.is_clean());
+  }
+}
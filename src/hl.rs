@@ -1,24 +1,290 @@
+use std::{
+  borrow::Cow,
+  collections::{BTreeMap, BTreeSet},
+  ops::Range,
+  path::Path,
+  sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc,
+  },
+};
+
+use eframe::egui;
+use globset::{Glob, GlobMatcher};
+use ropey::Rope;
+use serde::Deserialize;
 use syntect::{
-  easy::HighlightLines,
-  highlighting::{Theme, ThemeSet},
-  parsing::{SyntaxReference, SyntaxSet},
+  highlighting::{HighlightState, RangedHighlightIterator, Style, Theme, ThemeSet},
+  parsing::{ParseState, ScopeStack, SyntaxDefinition, SyntaxSet},
+};
+
+use crate::{
+  buffer::{Buffer, LineEdit},
+  config,
+  history::Edit,
+  util,
 };
-//
-static SYNTAX_SET: once_cell::sync::Lazy<SyntaxSet,> =
-  once_cell::sync::Lazy::new(SyntaxSet::load_defaults_newlines,);
 
-static THEME_SET: once_cell::sync::Lazy<ThemeSet,> =
-  once_cell::sync::Lazy::new(ThemeSet::load_defaults,);
+/// 增量高亮每隔多少行保存一次解析状态
+const CHECKPOINT_INTERVAL: usize = 32;
+
+/// 后台高亮每完成多少行发送一次结果
+const BACKGROUND_PUBLISH_ROWS: usize = 64;
+
+/// 后台高亮每高亮多少行检查一次是否有新的请求
+const BACKGROUND_STEP_LINES: usize = 1024;
+
+/// 后台高亮的结果在可见行上下各保留多少行
+const BACKGROUND_KEEP_ROWS: usize = 256;
+
+/// 重新加载语法时整体替换，正在使用旧语法的高亮器不受影响
+static SYNTAX_SET: once_cell::sync::Lazy<egui::mutex::RwLock<Arc<SyntaxSet,>,>,> =
+  once_cell::sync::Lazy::new(|| egui::mutex::RwLock::new(Arc::new(load_syntax_set(),),),);
+
+static THEME_SET: once_cell::sync::Lazy<ThemeSet,> = once_cell::sync::Lazy::new(|| {
+  let mut theme_set = ThemeSet::load_defaults();
+  load_user_themes(&mut theme_set,);
+  theme_set
+},);
+
+/// 黑夜模式默认主题
+pub const DEFAULT_DARK_THEME: &str = "Solarized (dark)";
+/// 白天模式默认主题
+pub const DEFAULT_LIGHT_THEME: &str = "Solarized (light)";
+
+/// 黑夜/白天 模式下分别选择的主题
+struct SelectedThemes {
+  dark: String,
+  light: String,
+}
+
+static SELECTED_THEMES: once_cell::sync::Lazy<egui::mutex::Mutex<SelectedThemes,>,> =
+  once_cell::sync::Lazy::new(|| {
+    egui::mutex::Mutex::new(SelectedThemes {
+      dark: DEFAULT_DARK_THEME.to_string(),
+      light: DEFAULT_LIGHT_THEME.to_string(),
+    },)
+  },);
+
+/// 加载配置目录 themes 中的 .tmTheme 文件，文件名（不含扩展名）为主题名称
+fn load_user_themes(theme_set: &mut ThemeSet,) {
+  let Some(dir,) = config::config_dir().map(|v| v.join("themes",),) else {
+    return;
+  };
+  let Ok(entries,) = std::fs::read_dir(&dir,) else {
+    return;
+  };
+  for path in entries.flatten().map(|v| v.path(),) {
+    if !path
+      .extension()
+      .is_some_and(|v| v.eq_ignore_ascii_case("tmTheme",),)
+    {
+      continue;
+    }
+    match ThemeSet::get_theme(&path,) {
+      Ok(theme,) => {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        theme_set.themes.insert(name.to_string(), theme,);
+      }
+      Err(e,) => {
+        util::toaster()
+          .error(format!("加载主题 {} 失败: {e}", path.display()),)
+          .set_duration(Some(std::time::Duration::from_secs(5,),),);
+      }
+    }
+  }
+}
+
+/// 内置的语法，加上配置目录 syntaxes 中的 .sublime-syntax 文件
+fn load_syntax_set() -> SyntaxSet {
+  let dir = config::config_dir().map(|v| v.join("syntaxes",),);
+  build_syntax_set(dir.as_deref(),)
+}
+
+/// 与内置语法同名的用户语法优先，加载失败的文件以 toast 提示
+fn build_syntax_set(dir: Option<&Path,>,) -> SyntaxSet {
+  let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+  let Some(Ok(entries,),) = dir.map(std::fs::read_dir,) else {
+    return builder.build();
+  };
+  for path in entries.flatten().map(|v| v.path(),) {
+    if !path
+      .extension()
+      .is_some_and(|v| v.eq_ignore_ascii_case("sublime-syntax",),)
+    {
+      continue;
+    }
+    let fallback_name = path.file_stem().map(|v| v.to_string_lossy(),);
+    let syntax = std::fs::read_to_string(&path,)
+      .map_err(|e| e.to_string(),)
+      .and_then(|v| {
+        SyntaxDefinition::load_from_str(&v, true, fallback_name.as_deref(),)
+          .map_err(|e| e.to_string(),)
+      },);
+    match syntax {
+      Ok(syntax,) => builder.add(syntax,),
+      Err(e,) => {
+        util::toaster()
+          .error(format!("加载语法 {} 失败: {e}", path.display()),)
+          .set_duration(Some(std::time::Duration::from_secs(5,),),);
+      }
+    }
+  }
+  builder.build()
+}
+
+/// 当前的语法集合
+pub fn syntax_set() -> Arc<SyntaxSet,> {
+  SYNTAX_SET.read().clone()
+}
+
+/// 重新加载内置和配置目录中的语法，返回语法的数量
+pub fn reload_syntax_set() -> usize {
+  let syntax_set = load_syntax_set();
+  let count = syntax_set.syntaxes().len();
+  *SYNTAX_SET.write() = Arc::new(syntax_set,);
+  count
+}
+
+/// 用户配置的语言，见配置目录中的 languages.toml
+///
+/// ```toml
+/// [globs]
+/// "*.conf" = "Bourne Again Shell (bash)"
+/// "**/templates/*.html" = "HTML"
+/// ```
+#[derive(Debug, Default, Deserialize,)]
+struct LanguageConfig {
+  /// 文件名或路径的 glob => 语言（syntax 的名称）
+  #[serde(default)]
+  globs: BTreeMap<String, String,>,
+}
+
+static LANGUAGE_GLOBS: once_cell::sync::Lazy<Vec<(GlobMatcher, String,),>,> =
+  once_cell::sync::Lazy::new(|| {
+    let config = config::load_toml::<LanguageConfig,>("languages.toml",).unwrap_or_else(|e| {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(std::time::Duration::from_secs(5,),),);
+      LanguageConfig::default()
+    },);
+    config
+      .globs
+      .into_iter()
+      .filter_map(|(glob, language,)| match Glob::new(&glob,) {
+        Ok(v,) => Some((v.compile_matcher(), language,),),
+        Err(e,) => {
+          util::toaster()
+            .error(format!("languages.toml: {e}"),)
+            .set_duration(Some(std::time::Duration::from_secs(5,),),);
+          None
+        }
+      },)
+      .collect()
+  },);
+
+/// 确定文件的语言，返回 syntax 的名称
+///
+/// 依次根据 完整文件名（如 Makefile）、扩展名、第一行（如 shebang）、用户配置的 glob 查找，都找不到时为纯文本
+pub fn detect_language(
+  path: &Path,
+  first_line: &str,
+) -> String {
+  detect_language_with_globs(path, first_line, &LANGUAGE_GLOBS,)
+}
+
+fn detect_language_with_globs(
+  path: &Path,
+  first_line: &str,
+  globs: &[(GlobMatcher, String,)],
+) -> String {
+  let syntax_set = syntax_set();
+  let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+  let extension = path.extension().unwrap_or_default().to_string_lossy();
+  let by_extension = |v: &str| {
+    Some(v,)
+      .filter(|v| !v.is_empty(),)
+      .and_then(|v| syntax_set.find_syntax_by_extension(v,),)
+  };
+  let by_glob = || {
+    globs
+      .iter()
+      .find(|(m, _,)| m.is_match(file_name.as_ref(),) || m.is_match(path,),)
+      .and_then(|(_, language,)| syntax_set.find_syntax_by_name(language,),)
+  };
+  by_extension(&file_name,)
+    .or_else(|| by_extension(&extension,),)
+    .or_else(|| syntax_set.find_syntax_by_first_line(first_line,),)
+    .or_else(by_glob,)
+    .unwrap_or_else(|| syntax_set.find_syntax_plain_text(),)
+    .name
+    .clone()
+}
 
-pub fn syntax_set() -> &'static SyntaxSet {
-  &SYNTAX_SET
+/// 可以手动选择的语言，按名称排序
+pub fn language_names() -> Vec<String,> {
+  let mut names = syntax_set()
+    .syntaxes()
+    .iter()
+    .filter(|v| !v.hidden,)
+    .map(|v| v.name.clone(),)
+    .collect::<Vec<_,>>();
+  names.sort_by_key(|v| v.to_lowercase(),);
+  names.dedup();
+  names
 }
 
-pub fn get_syntax(file_ext: &str,) -> &SyntaxReference {
-  let fallback_syntax = || SYNTAX_SET.find_syntax_plain_text();
-  SYNTAX_SET
-    .find_syntax_by_extension(file_ext,)
-    .unwrap_or_else(fallback_syntax,)
+/// 所有主题的名称，包括配置目录中的主题
+pub fn theme_names() -> Vec<&'static str,> {
+  THEME_SET.themes.keys().map(|v| v.as_str(),).collect()
+}
+
+/// 黑夜/白天 模式下选择的主题
+pub fn selected_theme(dark_mode: bool,) -> String {
+  let themes = SELECTED_THEMES.lock();
+  if dark_mode {
+    themes.dark.clone()
+  } else {
+    themes.light.clone()
+  }
+}
+
+pub fn select_theme(
+  dark_mode: bool,
+  theme_name: &str,
+) {
+  let mut themes = SELECTED_THEMES.lock();
+  if dark_mode {
+    themes.dark = theme_name.to_string();
+  } else {
+    themes.light = theme_name.to_string();
+  }
+}
+
+/// 用主题中定义的颜色设置编辑器的 背景、行号栏、选中、光标 的颜色，主题未定义的颜色保持不变
+pub fn apply_theme_colors(
+  visuals: &mut egui::Visuals,
+  key: &HlKey,
+) {
+  let settings = &get_theme(key.theme_name, key.dark_mode,).settings;
+  let color =
+    |v: syntect::highlighting::Color| egui::Color32::from_rgba_unmultiplied(v.r, v.g, v.b, v.a,);
+  if let Some(v,) = settings.background.map(color,) {
+    visuals.extreme_bg_color = v;
+    visuals.panel_fill = v;
+  }
+  if let Some(v,) = settings.gutter.map(color,) {
+    visuals.panel_fill = v;
+  }
+  if let Some(v,) = settings.foreground.map(color,) {
+    visuals.widgets.noninteractive.fg_stroke.color = v;
+  }
+  if let Some(v,) = settings.selection.map(color,) {
+    visuals.selection.bg_fill = v;
+  }
+  if let Some(v,) = settings.caret.map(color,) {
+    visuals.selection.stroke.color = v;
+  }
 }
 
 pub fn get_theme(
@@ -27,10 +293,10 @@ pub fn get_theme(
 ) -> &Theme {
   let fallback_theme = || {
     if dark_mode {
-      THEME_SET.themes.get("Solarized (dark)",).unwrap()
+      THEME_SET.themes.get(DEFAULT_DARK_THEME,).unwrap()
       // THEME_SET.themes.get("base16-ocean.dark",).unwrap()
     } else {
-      THEME_SET.themes.get("Solarized (light)",).unwrap()
+      THEME_SET.themes.get(DEFAULT_LIGHT_THEME,).unwrap()
       // THEME_SET.themes.get("base16-ocean.light",).unwrap()
     }
   };
@@ -45,93 +311,681 @@ pub fn get_theme(
 pub struct HlKey<'a,> {
   theme_name: Option<&'a str,>,
   dark_mode: bool,
-  file_ext: &'a str,
+  language: &'a str,
 }
 
 impl<'a,> HlKey<'a,> {
   pub fn new(
     theme_name: Option<&'a str,>,
     dark_mode: bool,
-    file_ext: &'a str,
+    language: &'a str,
   ) -> Self {
     Self {
       theme_name,
       dark_mode,
-      file_ext,
+      language,
     }
   }
-  pub fn new_hl(&self,) -> HighlightLines {
-    let syntax = get_syntax(self.file_ext,);
-    let theme = get_theme(self.theme_name, self.dark_mode,);
-    HighlightLines::new(syntax, theme,)
+}
+
+/// 可以保存和发送到其他线程的 HlKey
+///
+/// 同时记录创建时的语法集合，解析状态只在同一个语法集合中有效，重新加载语法后 key 也随之变化
+#[derive(Debug, Clone,)]
+struct OwnedHlKey {
+  theme_name: Option<String,>,
+  dark_mode: bool,
+  language: String,
+  syntax_set: Arc<SyntaxSet,>,
+}
+
+impl Default for OwnedHlKey {
+  fn default() -> Self {
+    Self::from(&HlKey::new(None, false, "",),)
   }
 }
 
-pub mod layout {
-  use eframe::egui;
-  use syntect::{highlighting::FontStyle, util::LinesWithEndings};
+impl PartialEq for OwnedHlKey {
+  fn eq(
+    &self,
+    other: &Self,
+  ) -> bool {
+    self.theme_name == other.theme_name
+      && self.dark_mode == other.dark_mode
+      && self.language == other.language
+      && Arc::ptr_eq(&self.syntax_set, &other.syntax_set,)
+  }
+}
 
-  use crate::{hl::syntax_set, text};
+impl From<&HlKey<'_,>,> for OwnedHlKey {
+  fn from(key: &HlKey<'_,>,) -> Self {
+    Self {
+      theme_name: key.theme_name.map(str::to_string,),
+      dark_mode: key.dark_mode,
+      language: key.language.to_string(),
+      syntax_set: syntax_set(),
+    }
+  }
+}
 
-  use super::HlKey;
+/// 一行开始时的解析状态
+#[derive(Debug, Clone, PartialEq, Eq,)]
+struct LineState {
+  parse: ParseState,
+  highlight: HighlightState,
+}
 
-  #[derive(Clone, Default,)]
-  struct LayoutJobLoader {}
+#[derive(Debug, Clone,)]
+struct Checkpoint {
+  line: usize,
+  state: LineState,
+}
 
-  impl egui::util::cache::ComputerMut<(&HlKey<'_,>, &str,), egui::text::LayoutJob,>
-    for LayoutJobLoader
-  {
-    fn compute(
-      &mut self,
-      (hl_key, text,): (&HlKey<'_,>, &str,),
-    ) -> egui::text::LayoutJob {
-      get_layout_job(hl_key, text,)
+/// 一行的高亮结果，byte_range 相对于行首
+type LineRuns = Box<[(Style, Range<usize,>,)],>;
+
+/// 单个文件的增量语法高亮
+///
+/// 每 CHECKPOINT_INTERVAL 行保存一次行开始时的解析状态，并缓存每行的高亮结果。
+/// 编辑后从编辑行之前最近的 checkpoint 开始重新高亮，
+/// 经过编辑前保存的 checkpoint 时如果状态与之前一致，之后的结果仍然有效，不再继续高亮
+#[derive(Debug, Default,)]
+pub struct FileHighlighter {
+  key: OwnedHlKey,
+  /// 按 line 升序排列，line > stale_from 的是编辑前的旧状态（已按编辑调整行号），用于判断是否收敛
+  checkpoints: Vec<Checkpoint,>,
+  /// 每行的高亮结果，None 表示还未高亮
+  lines: Vec<Option<LineRuns,>,>,
+  /// 从这一行开始的高亮结果不可信，这一行开始时的状态是可信的
+  stale_from: usize,
+  /// 内容被修改后还未重新高亮的行
+  dirty: BTreeSet<usize,>,
+  /// TextEdit 模式下最近一次布局的文本及其 LayoutJob
+  text: String,
+  job: Option<egui::text::LayoutJob,>,
+}
+
+impl FileHighlighter {
+  /// 语法或主题变化时清空所有状态
+  pub fn set_key(
+    &mut self,
+    key: &HlKey,
+  ) {
+    self.set_owned_key(OwnedHlKey::from(key,),);
+  }
+
+  fn set_owned_key(
+    &mut self,
+    key: OwnedHlKey,
+  ) {
+    if self.key != key {
+      *self = Self {
+        key,
+        ..Default::default()
+      };
     }
   }
 
-  type LayoutJobCache = egui::util::cache::FrameCache<egui::text::LayoutJob, LayoutJobLoader,>;
+  /// 文本被编辑，保留编辑行之前的结果，之后的结果等到重新高亮时判断是否仍然有效
+  pub fn edit(
+    &mut self,
+    edit: LineEdit,
+  ) {
+    let LineEdit {
+      first_line,
+      old_last_line,
+      new_last_line,
+    } = edit;
+    let shift = |line: usize| {
+      if line > old_last_line && line != usize::MAX {
+        line - old_last_line + new_last_line
+      } else {
+        line
+      }
+    };
+    let removed = |line: usize| line > first_line && line <= old_last_line;
+
+    if first_line < self.lines.len() {
+      let end = (old_last_line + 1).min(self.lines.len(),);
+      let inserted = std::iter::repeat_n(None, new_last_line - first_line + 1,);
+      self.lines.splice(first_line..end, inserted,);
+    }
+    self.checkpoints.retain(|c| !removed(c.line,),);
+    for c in self.checkpoints.iter_mut() {
+      c.line = shift(c.line,);
+    }
+    self.dirty = std::mem::take(&mut self.dirty,)
+      .into_iter()
+      .filter(|&line| !removed(line,),)
+      .map(shift,)
+      .chain([first_line,],)
+      .collect();
+    self.stale_from = shift(self.stale_from,).min(first_line,);
+    self.job = None;
+  }
 
-  pub fn get_layout_job_from_cache(
-    ctx: &egui::Context,
-    hl_key: &HlKey,
-    text: &str,
-  ) -> egui::text::LayoutJob {
+  /// 依次应用 Buffer::take_line_edits 取走的编辑
+  pub fn apply_edits(
+    &mut self,
+    line_edits: Vec<LineEdit,>,
+  ) {
+    for e in line_edits {
+      self.edit(e,);
+    }
+  }
+
+  /// 第 row 行的高亮结果可以直接使用
+  fn is_valid(
+    &self,
+    row: usize,
+  ) -> bool {
+    row < self.stale_from && self.lines[row].is_some()
+  }
+
+  /// 保证 rows 中的行都已高亮，line(i) 返回第 i 行的内容（包含换行符）
+  ///
+  /// 只保存 rows 中各行的高亮结果。最多高亮 max_lines 行，返回 rows 是否已全部高亮，
+  /// 未完成时再次调用会从已保存的 checkpoint 继续
+  pub fn highlight<'a,>(
+    &mut self,
+    rows: Range<usize,>,
+    line_count: usize,
+    line: impl Fn(usize,) -> Cow<'a, str,>,
+    max_lines: usize,
+  ) -> bool {
     puffin::profile_function!();
-    ctx.memory_mut(|mem| mem.caches.cache::<LayoutJobCache>().get((hl_key, text,),),)
+
+    self.lines.resize(line_count, None,);
+    let rows = rows.start.min(line_count,)..rows.end.min(line_count,);
+    let syntax_set = self.key.syntax_set.clone();
+    let syntax = syntax_set
+      .find_syntax_by_name(&self.key.language,)
+      .unwrap_or_else(|| syntax_set.find_syntax_plain_text(),);
+    let theme = get_theme(self.key.theme_name.as_deref(), self.key.dark_mode,);
+    let highlighter = syntect::highlighting::Highlighter::new(theme,);
+
+    let mut highlighted_lines = 0;
+    'restart: loop {
+      let Some(first,) = rows.clone().find(|&row| !self.is_valid(row,),) else {
+        return true;
+      };
+      // 从 first 之前最近的可信 checkpoint 开始
+      let limit = first.min(self.stale_from,);
+      let (mut row, mut state,) = match self.checkpoints.partition_point(|c| c.line <= limit,) {
+        0 => (
+          0,
+          LineState {
+            parse: ParseState::new(syntax,),
+            highlight: HighlightState::new(&highlighter, ScopeStack::new(),),
+          },
+        ),
+        i => (
+          self.checkpoints[i - 1].line,
+          self.checkpoints[i - 1].state.clone(),
+        ),
+      };
+
+      while row < rows.end {
+        if highlighted_lines >= max_lines {
+          return false;
+        }
+        highlighted_lines += 1;
+        let text = line(row,);
+        let ops = state
+          .parse
+          .parse_line(&text, &syntax_set,)
+          .unwrap_or_default();
+        let runs = RangedHighlightIterator::new(&mut state.highlight, &ops, &text, &highlighter,)
+          .map(|(style, _, range,)| (style, range,),)
+          .collect();
+        self.lines[row] = rows.contains(&row,).then_some(runs,);
+        self.dirty.remove(&row,);
+        let was_stale = row >= self.stale_from;
+        row += 1;
+
+        // 此时 state 是第 row 行开始时的状态
+        let checkpoint = self.checkpoints.binary_search_by_key(&row, |c| c.line,);
+        if !was_stale {
+          match checkpoint {
+            Err(i,) if row % CHECKPOINT_INTERVAL == 0 => self.checkpoints.insert(
+              i,
+              Checkpoint {
+                line: row,
+                state: state.clone(),
+              },
+            ),
+            _ => {}
+          }
+          continue;
+        }
+        self.stale_from = row;
+        match checkpoint {
+          Ok(i,) if self.checkpoints[i].state == state => {
+            // 与编辑前的状态一致：直到下一个被修改的行，之后的结果都仍然有效
+            self.stale_from = self
+              .dirty
+              .range(row..,)
+              .next()
+              .copied()
+              .unwrap_or(usize::MAX,);
+            continue 'restart;
+          }
+          Ok(i,) => self.checkpoints[i].state = state.clone(),
+          Err(i,) if row % CHECKPOINT_INTERVAL == 0 => {
+            self.checkpoints.insert(
+              i,
+              Checkpoint {
+                line: row,
+                state: state.clone(),
+              },
+            );
+          }
+          Err(_,) => {}
+        }
+      }
+      return true;
+    }
   }
 
-  fn get_layout_job(
-    hl_key: &HlKey,
+  /// 第 row 行的高亮结果，需先调用 highlight
+  fn line_runs(
+    &self,
+    row: usize,
+  ) -> &[(Style, Range<usize,>,)] {
+    self
+      .lines
+      .get(row,)
+      .and_then(|v| v.as_deref(),)
+      .unwrap_or_default()
+  }
+
+  /// 整个文本的 LayoutJob，用于 TextEdit 的 layouter
+  ///
+  /// 与上次的文本比较得到编辑的行，只有文本变化时才重新生成
+  pub fn layout_job(
+    &mut self,
     text: &str,
   ) -> egui::text::LayoutJob {
     puffin::profile_function!();
 
-    let mut hl = hl_key.new_hl();
+    if self.text != text {
+      if let Some(e,) = Edit::diff(&self.text, text,) {
+        let first_line = count_lines(&self.text[..e.index],);
+        self.edit(LineEdit {
+          first_line,
+          old_last_line: first_line + count_lines(&e.deleted,),
+          new_last_line: first_line + count_lines(&e.inserted,),
+        },);
+      }
+      self.text = text.to_string();
+    }
+    if let Some(job,) = &self.job {
+      return job.clone();
+    }
+
+    let mut lines = text.split_inclusive('\n',).collect::<Vec<_,>>();
+    if text.is_empty() || text.ends_with('\n',) {
+      lines.push("",);
+    }
+    self.highlight(0..lines.len(), lines.len(), |i| lines[i].into(), usize::MAX,);
 
     let mut job = egui::text::LayoutJob {
       text: text.into(),
       ..Default::default()
     };
-
     job.wrap.max_width = f32::INFINITY; // no wrap
-
-    for line in LinesWithEndings::from(text,) {
-      for (style, range,) in hl.highlight_line(line, syntax_set(),).unwrap() {
-        let format = convert_to_text_format(style,);
-        //
+    let mut offset = 0;
+    for (i, line,) in lines.iter().enumerate() {
+      for (style, range,) in self.line_runs(i,) {
         job.sections.push(egui::text::LayoutSection {
           leading_space: 0.0,
-          byte_range: as_byte_range(text, range,),
-          format,
+          byte_range: offset + range.start..offset + range.end,
+          format: layout::convert_to_text_format(*style,),
         },);
       }
+      offset += line.len();
+    }
+    self.job = Some(job.clone(),);
+    job
+  }
+}
+
+fn count_lines(text: &str,) -> usize {
+  text.bytes().filter(|&b| b == b'\n',).count()
+}
+
+/// 发送给后台高亮线程的请求
+struct Request {
+  ctx: egui::Context,
+  revision: u64,
+  key: OwnedHlKey,
+  text: Rope,
+  /// 上一个请求之后的编辑
+  edits: Vec<LineEdit,>,
+  rows: Range<usize,>,
+}
+
+/// 后台高亮线程完成的一段行
+struct Highlighted {
+  revision: u64,
+  first_row: usize,
+  lines: Vec<Arc<[egui::text::LayoutSection],>,>,
+}
+
+/// 在后台线程中高亮大文件，UI 线程只使用已完成的结果，不会因为高亮而阻塞
+///
+/// 每次编辑都会产生新的版本，旧版本的结果到达时直接丢弃；
+/// 已有的结果按编辑调整行号后继续显示，直到新的结果到达
+pub struct BackgroundHighlighter {
+  tx: Sender<Request,>,
+  rx: Receiver<Highlighted,>,
+  key: OwnedHlKey,
+  revision: u64,
+  /// 行 => (版本, 高亮结果)
+  lines: BTreeMap<usize, (u64, Arc<[egui::text::LayoutSection],>,),>,
+  /// 还未发送给后台线程的编辑
+  pending_edits: Vec<LineEdit,>,
+  /// 最近一次请求的 (版本, 行)
+  requested: Option<(u64, Range<usize,>,),>,
+}
+
+impl Default for BackgroundHighlighter {
+  fn default() -> Self {
+    let (tx, request_rx,) = channel();
+    let (result_tx, rx,) = channel();
+    std::thread::spawn(move || run_background_highlighter(request_rx, result_tx,),);
+    Self {
+      tx,
+      rx,
+      key: OwnedHlKey::default(),
+      revision: 0,
+      lines: BTreeMap::new(),
+      pending_edits: vec![],
+      requested: None,
+    }
+  }
+}
+
+impl BackgroundHighlighter {
+  /// 语法或主题变化时丢弃所有结果
+  pub fn set_key(
+    &mut self,
+    key: &HlKey,
+  ) {
+    let key = OwnedHlKey::from(key,);
+    if self.key != key {
+      self.key = key;
+      self.lines.clear();
+      self.revision += 1;
+    }
+  }
+
+  /// 依次应用 Buffer::take_line_edits 取走的编辑，被编辑的行不再显示旧的结果
+  pub fn apply_edits(
+    &mut self,
+    edits: Vec<LineEdit,>,
+  ) {
+    if edits.is_empty() {
+      return;
+    }
+    for e in edits.iter() {
+      self.lines = std::mem::take(&mut self.lines,)
+        .into_iter()
+        .filter_map(|(row, v,)| {
+          if row < e.first_line {
+            Some((row, v,),)
+          } else if row > e.old_last_line {
+            Some((row - e.old_last_line + e.new_last_line, v,),)
+          } else {
+            None
+          }
+        },)
+        .collect();
+    }
+    self.pending_edits.extend(edits,);
+    self.revision += 1;
+  }
+
+  /// 接收后台线程的结果，rows 中有未高亮或已过期的行时请求后台高亮
+  pub fn update(
+    &mut self,
+    ctx: &egui::Context,
+    buffer: &Buffer,
+    rows: Range<usize,>,
+  ) {
+    puffin::profile_function!();
+
+    while let Ok(result,) = self.rx.try_recv() {
+      // 过期版本的行号可能已经变化
+      if result.revision != self.revision {
+        continue;
+      }
+      for (i, sections,) in result.lines.into_iter().enumerate() {
+        self
+          .lines
+          .insert(result.first_row + i, (result.revision, sections,),);
+      }
+    }
+
+    let keep = rows.start.saturating_sub(BACKGROUND_KEEP_ROWS,)..rows.end + BACKGROUND_KEEP_ROWS;
+    self.lines.retain(|row, _| keep.contains(row,),);
+
+    let ready =
+      rows.clone().all(
+        |row| matches!(self.lines.get(&row), Some((revision, _)) if *revision == self.revision),
+      );
+    let request = (self.revision, rows.clone(),);
+    if ready || self.requested.as_ref() == Some(&request,) {
+      return;
+    }
+    let _ = self.tx.send(Request {
+      ctx: ctx.clone(),
+      revision: self.revision,
+      key: self.key.clone(),
+      text: buffer.snapshot(),
+      edits: std::mem::take(&mut self.pending_edits,),
+      rows,
+    },);
+    self.requested = Some(request,);
+  }
+
+  /// 第 row 行已完成的高亮结果，可能来自编辑前的版本
+  pub fn line_sections(
+    &self,
+    row: usize,
+  ) -> Option<&[egui::text::LayoutSection],> {
+    self.lines.get(&row,).map(|(_, sections,)| &sections[..],)
+  }
+}
+
+/// 后台高亮线程：只处理最新的请求，每完成一段行就发送结果，UI 关闭 channel 后退出
+fn run_background_highlighter(
+  rx: Receiver<Request,>,
+  tx: Sender<Highlighted,>,
+) {
+  let mut hl = FileHighlighter::default();
+  let absorb = |hl: &mut FileHighlighter, req: &mut Request| {
+    hl.set_owned_key(req.key.clone(),);
+    hl.apply_edits(std::mem::take(&mut req.edits,),);
+  };
+  let mut next = None;
+  'request: loop {
+    let Some(mut req,) = next.take().or_else(|| rx.recv().ok(),) else {
+      return;
+    };
+    absorb(&mut hl, &mut req,);
+    // 积压的请求中的编辑仍需按顺序应用
+    while let Ok(mut newer,) = rx.try_recv() {
+      absorb(&mut hl, &mut newer,);
+      req = newer;
+    }
+
+    let line_count = req.text.len_lines();
+    let rows = req.rows.start.min(line_count,)..req.rows.end.min(line_count,);
+    let line = |i: usize| Cow::from(req.text.line(i,),);
+    for start in rows.clone().step_by(BACKGROUND_PUBLISH_ROWS,) {
+      let chunk = start..(start + BACKGROUND_PUBLISH_ROWS).min(rows.end,);
+      loop {
+        if let Ok(newer,) = rx.try_recv() {
+          next = Some(newer,);
+          continue 'request;
+        }
+        if hl.highlight(chunk.clone(), line_count, line, BACKGROUND_STEP_LINES,) {
+          break;
+        }
+      }
+      let lines = chunk
+        .clone()
+        .map(|row| layout::line_sections(&line(row,), hl.line_runs(row,),).into(),)
+        .collect();
+      let result = Highlighted {
+        revision: req.revision,
+        first_row: chunk.start,
+        lines,
+      };
+      if tx.send(result,).is_err() {
+        return;
+      }
+      req.ctx.request_repaint();
     }
+  }
+}
+
+pub mod layout {
+  use eframe::egui;
+  use syntect::highlighting::FontStyle;
+
+  use crate::text;
 
+  /// 单行文本的 LayoutJob，line 包含换行符，生成的 LayoutJob 不包含换行符
+  ///
+  /// 还没有高亮结果（sections 为 None）时用 color 显示为普通文本
+  pub fn get_line_layout_job(
+    line: &str,
+    sections: Option<&[egui::text::LayoutSection],>,
+    color: egui::Color32,
+  ) -> egui::text::LayoutJob {
+    let text = strip_line_ending(line,).to_string();
+    let mut job = match sections {
+      Some(sections,) => egui::text::LayoutJob {
+        text,
+        sections: sections.to_vec(),
+        ..Default::default()
+      },
+      None => egui::text::LayoutJob::simple_singleline(text, text::text_editor_font(), color,),
+    };
+    job.wrap.max_width = f32::INFINITY; // no wrap
     job
   }
 
+  /// 把 FileHighlighter 的单行高亮结果转换为 LayoutSection，不包含换行符
+  pub(super) fn line_sections(
+    line: &str,
+    runs: &[(syntect::highlighting::Style, std::ops::Range<usize,>,)],
+  ) -> Vec<egui::text::LayoutSection,> {
+    let len = strip_line_ending(line,).len();
+    runs
+      .iter()
+      .map(|(style, range,)| (style, range.start.min(len,)..range.end.min(len,),),)
+      .filter(|(_, byte_range,)| !byte_range.is_empty(),)
+      .map(|(style, byte_range,)| egui::text::LayoutSection {
+        leading_space: 0.0,
+        byte_range,
+        format: convert_to_text_format(*style,),
+      },)
+      .collect()
+  }
+
+  fn strip_line_ending(line: &str,) -> &str {
+    let line = line.strip_suffix('\n',).unwrap_or(line,);
+    line.strip_suffix('\r',).unwrap_or(line,)
+  }
+
+  /// 给 LayoutJob 中指定的 byte_range 设置背景色（如查找的匹配项）
+  ///
+  /// ranges 需按 start 升序排列且互不重叠，越界或不在 char 边界上的 range 会被忽略
+  pub fn highlight_ranges(
+    job: &mut egui::text::LayoutJob,
+    ranges: &[(std::ops::Range<usize,>, egui::Color32,)],
+  ) {
+    puffin::profile_function!();
+
+    apply_ranges(job, ranges, |format, color| format.background = color,);
+  }
+
+  /// 给 LayoutJob 中指定的 byte_range 加下划线（如编译器诊断），要求同 highlight_ranges
+  pub fn underline_ranges(
+    job: &mut egui::text::LayoutJob,
+    ranges: &[(std::ops::Range<usize,>, egui::Color32,)],
+  ) {
+    puffin::profile_function!();
+
+    apply_ranges(job, ranges, |format, color| {
+      format.underline = egui::Stroke::new(2.0, color,)
+    },);
+  }
+
+  /// 按 ranges 拆分 section，对 range 内的部分调用 apply 修改格式
+  fn apply_ranges(
+    job: &mut egui::text::LayoutJob,
+    ranges: &[(std::ops::Range<usize,>, egui::Color32,)],
+    apply: impl Fn(&mut egui::TextFormat, egui::Color32,),
+  ) {
+    let text = &job.text;
+    let ranges = ranges
+      .iter()
+      .filter(|(r, _,)| {
+        r.start < r.end
+          && r.end <= text.len()
+          && text.is_char_boundary(r.start,)
+          && text.is_char_boundary(r.end,)
+      },)
+      .collect::<Vec<_,>>();
+    if ranges.is_empty() {
+      return;
+    }
+
+    let sections = std::mem::take(&mut job.sections,);
+    let mut ri = 0;
+    for section in sections {
+      let mut leading_space = section.leading_space;
+      let mut push = |byte_range: std::ops::Range<usize,>, format: egui::TextFormat| {
+        job.sections.push(egui::text::LayoutSection {
+          leading_space,
+          byte_range,
+          format,
+        },);
+        leading_space = 0.0;
+      };
+
+      let (mut start, end,) = (section.byte_range.start, section.byte_range.end,);
+      while start < end {
+        while ri < ranges.len() && ranges[ri].0.end <= start {
+          ri += 1;
+        }
+        match ranges.get(ri,) {
+          Some((r, color,),) if r.start < end => {
+            if r.start > start {
+              push(start..r.start, section.format.clone(),);
+              start = r.start;
+            }
+            let to = r.end.min(end,);
+            let mut format = section.format.clone();
+            apply(&mut format, *color,);
+            push(start..to, format,);
+            start = to;
+          }
+          _ => {
+            push(start..end, section.format.clone(),);
+            start = end;
+          }
+        }
+      }
+    }
+  }
+
   /// syntect style => egui TextFormat
-  fn convert_to_text_format(style: syntect::highlighting::Style,) -> egui::TextFormat {
+  pub(super) fn convert_to_text_format(style: syntect::highlighting::Style,) -> egui::TextFormat {
     let fg = style.foreground;
     let text_color = egui::Color32::from_rgb(fg.r, fg.g, fg.b,);
     let italics = style.font_style.contains(FontStyle::ITALIC,);
@@ -150,16 +1004,271 @@ pub mod layout {
       ..Default::default()
     }
   }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::BTreeMap,
+    path::Path,
+    sync::mpsc::channel,
+    time::{Duration, Instant},
+  };
+
+  use eframe::egui;
+
+  use super::{
+    apply_theme_colors, build_syntax_set, detect_language_with_globs,
+    layout::{highlight_ranges, underline_ranges},
+    BackgroundHighlighter, FileHighlighter, Highlighted, HlKey, OwnedHlKey,
+  };
+  use crate::{
+    buffer::{Buffer, LineEdit},
+    test_util::TempDir,
+  };
+
+  fn new_highlighter() -> FileHighlighter {
+    let mut hl = FileHighlighter::default();
+    hl.set_key(&HlKey::new(None, true, "Rust",),);
+    hl
+  }
+
+  /// 增量高亮的结果与从头高亮一致
+  fn assert_same_as_full(
+    hl: &mut FileHighlighter,
+    text: &str,
+  ) {
+    assert_eq!(hl.layout_job(text,), new_highlighter().layout_job(text,));
+  }
+
+  #[test]
+  fn test_incremental_highlight() {
+    let lines = (0..200)
+      .map(|i| format!("let a{i} = {i};\n"),)
+      .collect::<Vec<_,>>();
+    let mut hl = new_highlighter();
+    let calls = Cell::new(0,);
+    let line = |i: usize| {
+      calls.set(calls.get() + 1,);
+      Cow::from(lines[i].as_str(),)
+    };
+    assert!(!hl.highlight(190..200, 200, line, 100,));
+    assert!(hl.highlight(190..200, 200, line, usize::MAX,));
+    // 第二次从第 96 行的 checkpoint 继续
+    assert_eq!(calls.take(), 100 + 200 - 96);
+
+    // 第 150 行的编辑：从第 128 行的 checkpoint 开始，到第 160 行的 checkpoint 收敛
+    hl.edit(LineEdit {
+      first_line: 150,
+      old_last_line: 150,
+      new_last_line: 150,
+    },);
+    hl.highlight(190..200, 200, line, usize::MAX,);
+    assert_eq!(calls.take(), 160 - 128);
+    hl.highlight(190..200, 200, line, usize::MAX,);
+    assert_eq!(calls.take(), 0);
+
+    // 多行注释改变之后所有行的状态
+    let mut text = lines.concat();
+    assert_same_as_full(&mut hl, &text,);
+    text.insert_str(lines[0].len() * 10, "/* ",);
+    assert_same_as_full(&mut hl, &text,);
+    text.insert_str(lines[0].len() * 100, "*/\n",);
+    assert_same_as_full(&mut hl, &text,);
+    text.replace_range(0..lines[0].len() * 20, "",);
+    assert_same_as_full(&mut hl, &text,);
+  }
+
+  #[test]
+  fn test_highlight_ranges() {
+    let mut job = egui::text::LayoutJob::default();
+    job.append("hello ", 0.0, Default::default(),);
+    job.append("world", 0.0, Default::default(),);
+
+    let red = egui::Color32::RED;
+    highlight_ranges(&mut job, &[(4..8, red,), (100..200, red,),],);
+
+    let sections = job
+      .sections
+      .iter()
+      .map(|v| (v.byte_range.clone(), v.format.background,),)
+      .collect::<Vec<_,>>();
+    assert_eq!(
+      sections,
+      vec![
+        (0..4, egui::Color32::TRANSPARENT),
+        (4..6, red),
+        (6..8, red),
+        (8..11, egui::Color32::TRANSPARENT),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_underline_ranges() {
+    let mut job = egui::text::LayoutJob::default();
+    job.append("let x = 1;", 0.0, Default::default(),);
+
+    let red = egui::Color32::RED;
+    underline_ranges(&mut job, &[(4..5, red,),],);
+
+    let sections = job
+      .sections
+      .iter()
+      .map(|v| (v.byte_range.clone(), v.format.underline,),)
+      .collect::<Vec<_,>>();
+    assert_eq!(
+      sections,
+      vec![
+        (0..4, egui::Stroke::NONE),
+        (4..5, egui::Stroke::new(2.0, red)),
+        (5..10, egui::Stroke::NONE),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_background_highlighter() {
+    let ctx = egui::Context::default();
+    let mut buffer = Buffer::from("fn main() {}\n".repeat(100,),);
+    let mut hl = BackgroundHighlighter::default();
+    hl.set_key(&HlKey::new(None, true, "Rust",),);
+
+    let deadline = Instant::now() + Duration::from_secs(10,);
+    while (0..10).any(|row| hl.line_sections(row,).is_none(),) {
+      assert!(Instant::now() < deadline);
+      hl.update(&ctx, &buffer, 0..10,);
+      std::thread::sleep(Duration::from_millis(10,),);
+    }
+    let sections = hl.line_sections(0,).unwrap().to_vec();
+    assert!(sections.len() > 1);
+
+    // 编辑后旧的结果按行号调整后继续显示，被编辑的行显示为普通文本
+    buffer.replace_range(0..0, "//\n",);
+    hl.apply_edits(buffer.take_line_edits(),);
+    assert!(hl.line_sections(1,).is_none());
+    assert_eq!(hl.line_sections(2,), Some(&sections[..]));
+  }
+
+  #[test]
+  fn test_background_stale_results() {
+    let (tx, requests,) = channel();
+    let (results, rx,) = channel();
+    let mut hl = BackgroundHighlighter {
+      tx,
+      rx,
+      key: OwnedHlKey::default(),
+      revision: 0,
+      lines: BTreeMap::new(),
+      pending_edits: vec![],
+      requested: None,
+    };
+    let ctx = egui::Context::default();
+    let buffer = Buffer::from("a\nb\n",);
+    let result = |revision| Highlighted {
+      revision,
+      first_row: 0,
+      lines: vec![vec![].into()],
+    };
+
+    hl.update(&ctx, &buffer, 0..2,);
+    assert_eq!(requests.try_recv().unwrap().revision, 0);
+    // 相同的请求只发送一次
+    hl.update(&ctx, &buffer, 0..2,);
+    assert!(requests.try_recv().is_err());
+
+    let edit = LineEdit {
+      first_line: 0,
+      old_last_line: 0,
+      new_last_line: 1,
+    };
+    hl.apply_edits(vec![edit],);
+    results.send(result(0,),).unwrap();
+    hl.update(&ctx, &buffer, 0..2,);
+    assert!(hl.line_sections(0,).is_none());
+    let request = requests.try_recv().unwrap();
+    assert_eq!((request.revision, request.edits), (1, vec![edit]));
+
+    results.send(result(1,),).unwrap();
+    hl.update(&ctx, &buffer, 0..2,);
+    assert!(hl.line_sections(0,).is_some());
+  }
+
+  #[test]
+  fn test_detect_language() {
+    let globs = [(
+      globset::Glob::new("**/conf/*",).unwrap().compile_matcher(),
+      "Bourne Again Shell (bash)".to_string(),
+    ),];
+    let detect = |path: &str, first_line: &str| {
+      detect_language_with_globs(Path::new(path,), first_line, &globs,)
+    };
+    assert_eq!(detect("/src/main.rs", ""), "Rust");
+    assert_eq!(detect("/Makefile", ""), "Makefile");
+    // 扩展名优先于第一行
+    assert_eq!(detect("/a.py", "#!/bin/bash"), "Python");
+    assert_eq!(detect("/bin/run", "#!/usr/bin/env python3"), "Python");
+    assert_eq!(detect("/app/conf/env", ""), "Bourne Again Shell (bash)");
+    assert_eq!(detect("/LICENSE", "MIT License"), "Plain Text");
+    assert_eq!(detect("/", ""), "Plain Text");
+  }
+
+  #[test]
+  fn test_user_syntaxes() {
+    let dir = TempDir::new("syntaxes",);
+    std::fs::write(
+      dir.join("toy.sublime-syntax",),
+      "%YAML 1.2\n---\nfile_extensions: [toy]\nscope: source.toy\ncontexts:\n  main:\n    - match: \\bfn\\b\n      scope: keyword.toy\n",
+    )
+    .unwrap();
+    // 加载失败的文件不影响其他语法
+    std::fs::write(dir.join("broken.sublime-syntax",), "contexts: [",).unwrap();
+    let syntax_set = std::sync::Arc::new(build_syntax_set(Some(&dir,),),);
+    drop(dir,);
+
+    let toy = syntax_set.find_syntax_by_extension("toy",).unwrap();
+    assert_eq!(toy.name, "toy");
+    assert!(syntax_set.find_syntax_by_name("Rust",).is_some());
+
+    // 语法集合变化后清空旧的解析状态
+    let mut hl = new_highlighter();
+    assert!(hl.highlight(0..1, 1, |_| Cow::from("fn a() {}\n",), usize::MAX,));
+    hl.set_owned_key(OwnedHlKey {
+      language: "toy".to_string(),
+      syntax_set,
+      ..hl.key.clone()
+    },);
+    assert!(hl.lines.is_empty());
+    assert!(hl.highlight(0..1, 1, |_| Cow::from("fn a\n",), usize::MAX,));
+    let styles = hl
+      .line_runs(0,)
+      .iter()
+      .map(|(style, _,)| style.foreground,)
+      .collect::<Vec<_,>>();
+    assert_ne!(styles[0], styles[1]);
+  }
+
+  #[test]
+  fn test_apply_theme_colors() {
+    let mut visuals = egui::Visuals::dark();
+    let selection = visuals.selection.bg_fill;
+    apply_theme_colors(
+      &mut visuals,
+      &HlKey::new(Some("base16-ocean.dark",), true, "Rust",),
+    );
+    assert_eq!(
+      visuals.extreme_bg_color,
+      egui::Color32::from_rgb(0x2b, 0x30, 0x3b)
+    );
+    assert_ne!(visuals.selection.bg_fill, selection);
 
-  fn as_byte_range(
-    whole: &str,
-    range: &str,
-  ) -> std::ops::Range<usize,> {
-    let whole_start = whole.as_ptr() as usize;
-    let range_start = range.as_ptr() as usize;
-    assert!(whole_start <= range_start);
-    assert!(range_start + range.len() <= whole_start + whole.len());
-    let offset = range_start - whole_start;
-    offset..(offset + range.len())
+    // 找不到的主题使用默认主题
+    apply_theme_colors(&mut visuals, &HlKey::new(Some("?",), true, "Rust",),);
+    assert_eq!(
+      visuals.extreme_bg_color,
+      egui::Color32::from_rgb(0x00, 0x2b, 0x36)
+    );
   }
 }
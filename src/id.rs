@@ -2,3 +2,4 @@ pub const TOOL_BAR: &str = "tool_bar";
 pub const STATUS_BAR: &str = "status_bar";
 pub const TAB_BAR: &str = "tab_bar";
 pub const EXPLORER: &str = "explorer";
+pub const SEARCH: &str = "search";
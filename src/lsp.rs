@@ -0,0 +1,1406 @@
+use std::{
+  borrow::Cow,
+  collections::{BTreeMap, BTreeSet, HashMap},
+  io::{BufRead, BufReader, Write},
+  path::{Path, PathBuf},
+  process::{Child, Command, Stdio},
+  sync::mpsc::{Receiver, Sender, TryRecvError},
+  time::{Duration, Instant},
+};
+
+use eframe::egui;
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+  buffer::Buffer,
+  diagnostic::{Diagnostic, Severity},
+  outline::{Symbol, SymbolKind},
+};
+
+/// 语言服务器的启动命令
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize,)]
+pub struct ServerConfig {
+  pub command: String,
+  #[serde(default)]
+  pub args: Vec<String,>,
+}
+
+/// 默认的语言服务器，key 为语言（syntect syntax 的名称）
+pub fn default_servers() -> BTreeMap<String, ServerConfig,> {
+  BTreeMap::from([(
+    "Rust".to_string(),
+    ServerConfig {
+      command: "rust-analyzer".to_string(),
+      args: vec![],
+    },
+  ),],)
+}
+
+/// 语言对应的 LSP languageId
+pub fn language_id(language: &str,) -> String {
+  match language {
+    "C++" => "cpp".to_string(),
+    "C#" => "csharp".to_string(),
+    "Bourne Again Shell (bash)" => "shellscript".to_string(),
+    _ => language.to_lowercase().replace(' ', "",),
+  }
+}
+
+// ------------------------------------ 请求和事件
+
+/// 编辑器发起的请求
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum RequestKind {
+  Completion,
+  Hover,
+  Definition,
+  References,
+  /// 文件的符号，忽略请求的位置
+  DocumentSymbol,
+}
+
+impl RequestKind {
+  fn method(&self,) -> &'static str {
+    match self {
+      RequestKind::Completion => "textDocument/completion",
+      RequestKind::Hover => "textDocument/hover",
+      RequestKind::Definition => "textDocument/definition",
+      RequestKind::References => "textDocument/references",
+      RequestKind::DocumentSymbol => "textDocument/documentSymbol",
+    }
+  }
+}
+
+/// 对文件中某个位置的请求，位置为 (行, 列)，从 0 开始，列为 char 索引
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct LspRequest {
+  pub kind: RequestKind,
+  pub path: PathBuf,
+  pub position: (usize, usize,),
+}
+
+/// 文件中的一段范围，位置为 (行, 列)，从 0 开始，列为 char 索引
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Location {
+  pub path: PathBuf,
+  pub start: (usize, usize,),
+  pub end: (usize, usize,),
+}
+
+/// 补全项
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct CompletionItem {
+  pub label: String,
+  pub detail: Option<String,>,
+  /// 用于匹配输入的文本
+  pub filter_text: String,
+  /// 插入的文本
+  pub text: String,
+  /// 替换的范围，None 表示替换光标前的单词
+  pub range: Option<((usize, usize,), (usize, usize,),),>,
+}
+
+/// 语言服务器的响应和通知
+#[derive(Debug, Clone, PartialEq,)]
+pub enum LspEvent {
+  /// 文件的全部诊断
+  Diagnostics {
+    path: PathBuf,
+    diagnostics: Vec<Diagnostic,>,
+  },
+  Completion {
+    path: PathBuf,
+    position: (usize, usize,),
+    items: Vec<CompletionItem,>,
+  },
+  Hover {
+    path: PathBuf,
+    position: (usize, usize,),
+    text: String,
+  },
+  Definition(Vec<Location,>,),
+  References(Vec<Location,>,),
+  /// 文件的符号树
+  Symbols {
+    path: PathBuf,
+    symbols: Vec<Symbol,>,
+  },
+  /// 语言服务器启动失败或退出
+  Error(String,),
+}
+
+// ------------------------------------ 消息
+
+/// 写入一条消息：Content-Length 头部 + JSON
+fn write_message(
+  writer: &mut impl Write,
+  message: &Value,
+) -> std::io::Result<(),> {
+  let body = message.to_string();
+  write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+  writer.flush()
+}
+
+/// 发送给语言服务器的消息，由写入线程序列化
+enum Outgoing {
+  Message(Value,),
+  /// 包含文件全部内容的消息，在写入线程中将 text 写入 message 中 pointer 处
+  Text {
+    message: Value,
+    pointer: &'static str,
+    text: Rope,
+  },
+}
+
+impl Outgoing {
+  fn into_message(self,) -> Value {
+    match self {
+      Outgoing::Message(message,) => message,
+      Outgoing::Text {
+        mut message,
+        pointer,
+        text,
+      } => {
+        if let Some(v,) = message.pointer_mut(pointer,) {
+          *v = Value::String(text.to_string(),);
+        }
+        message
+      }
+    }
+  }
+}
+
+/// 读取一条消息，输入结束时返回 None
+fn read_message(reader: &mut impl BufRead,) -> std::io::Result<Option<Value,>,> {
+  let mut content_length = None;
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line,)? == 0 {
+      return Ok(None,);
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((name, value,),) = line.split_once(':',) {
+      if name.eq_ignore_ascii_case("Content-Length",) {
+        content_length = value.trim().parse::<usize>().ok();
+      }
+    }
+  }
+  let Some(len,) = content_length else {
+    return Err(std::io::Error::other("缺少 Content-Length",),);
+  };
+  let mut body = vec![0; len];
+  reader.read_exact(&mut body,)?;
+  serde_json::from_slice(&body,)
+    .map(Some,)
+    .map_err(std::io::Error::other,)
+}
+
+// ------------------------------------ URI 和位置
+
+/// 绝对路径转换为 file:// URI
+pub fn path_to_uri(path: &Path,) -> String {
+  let path = path.to_string_lossy().replace('\\', "/",);
+  let mut uri = String::from("file://",);
+  // Windows 路径 C:/a => /C:/a
+  if !path.starts_with('/',) {
+    uri.push('/',);
+  }
+  for b in path.bytes() {
+    match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+        uri.push(b as char,)
+      }
+      // 盘符后的冒号
+      b':' if uri.len() == "file:///C".len() => uri.push(':',),
+      _ => uri.push_str(&format!("%{b:02X}"),),
+    }
+  }
+  uri
+}
+
+/// file:// URI 转换为路径，不是 file:// URI 时返回 None
+pub fn uri_to_path(uri: &str,) -> Option<PathBuf,> {
+  let path = uri.strip_prefix("file://",)?;
+  let bytes = path.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len(),);
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] == b'%' && i + 2 < bytes.len() {
+      let hex = std::str::from_utf8(&bytes[i + 1..i + 3],).ok()?;
+      decoded.push(u8::from_str_radix(hex, 16,).ok()?,);
+      i += 3;
+    } else {
+      decoded.push(bytes[i],);
+      i += 1;
+    }
+  }
+  let path = String::from_utf8(decoded,).ok()?;
+  // /C:/a => C:/a
+  let path = match path.as_bytes() {
+    [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+    _ => path,
+  };
+  Some(PathBuf::from(path,),)
+}
+
+/// 行内 UTF-16 偏移转换为 char 索引
+fn utf16_to_char(
+  line: &str,
+  offset: usize,
+) -> usize {
+  let mut units = 0;
+  for (i, c,) in line.chars().enumerate() {
+    if units >= offset || c == '\n' {
+      return i;
+    }
+    units += c.len_utf16();
+  }
+  line.chars().count()
+}
+
+/// 行内 char 索引转换为 UTF-16 偏移
+fn char_to_utf16(
+  line: &str,
+  column: usize,
+) -> usize {
+  line.chars().take(column,).map(char::len_utf16,).sum()
+}
+
+/// LSP 的位置编码，默认为 UTF-16
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+enum PositionEncoding {
+  Utf16,
+  Utf32,
+}
+
+/// 文件的文本，用于转换位置
+struct TextLines<'a,> {
+  text: &'a Rope,
+}
+
+impl<'a,> TextLines<'a,> {
+  fn new(text: &'a Rope,) -> Self {
+    Self {
+      text,
+    }
+  }
+
+  fn line(
+    &self,
+    line: usize,
+  ) -> Cow<'a, str,> {
+    if line < self.text.len_lines() {
+      self.text.line(line,).into()
+    } else {
+      Cow::Borrowed("",)
+    }
+  }
+}
+
+// ------------------------------------ LspClient
+
+/// 内容变化后最多每隔多久发送一次 didChange
+const CHANGE_INTERVAL: Duration = Duration::from_millis(200,);
+
+/// 已打开的文件
+struct Document {
+  version: i32,
+  text: Rope,
+  /// 第一个未发送的修改的时间，None 表示已发送全部修改
+  changed_at: Option<Instant,>,
+}
+
+/// 语言服务器客户端，通过 stdio 与语言服务器通信
+pub struct LspClient {
+  child: Option<Child,>,
+  /// 写入线程，避免语言服务器读取缓慢时阻塞 UI
+  writer: Sender<Outgoing,>,
+  /// 读取线程解析的消息，语言服务器退出后断开
+  rx: Receiver<Value,>,
+  next_id: i64,
+  initialize_id: i64,
+  /// 等待响应的请求
+  pending: HashMap<i64, LspRequest,>,
+  /// 收到 initialize 的响应前，其他消息暂存在 queue 中
+  initialized: bool,
+  queue: Vec<Outgoing,>,
+  encoding: PositionEncoding,
+  documents: HashMap<PathBuf, Document,>,
+}
+
+impl LspClient {
+  /// 启动语言服务器并发送 initialize，收到消息时重绘 ctx
+  pub fn start(
+    config: &ServerConfig,
+    root: &Path,
+    ctx: egui::Context,
+  ) -> std::io::Result<Self,> {
+    let mut child = Command::new(&config.command,)
+      .args(&config.args,)
+      .current_dir(root,)
+      .stdin(Stdio::piped(),)
+      .stdout(Stdio::piped(),)
+      .stderr(Stdio::null(),)
+      .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let (writer, writer_rx,) = std::sync::mpsc::channel::<Outgoing,>();
+    std::thread::spawn(move || {
+      for outgoing in writer_rx {
+        if let Err(e,) = write_message(&mut stdin, &outgoing.into_message(),) {
+          tracing::error!("发送语言服务器消息失败 => {e}");
+          break;
+        }
+      }
+    },);
+    let mut stdout = BufReader::new(child.stdout.take().unwrap(),);
+    let (tx, rx,) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+      loop {
+        match read_message(&mut stdout,) {
+          Ok(Some(message,),) => {
+            if tx.send(message,).is_err() {
+              break;
+            }
+          }
+          Ok(None,) => break,
+          Err(e,) => {
+            tracing::error!("读取语言服务器消息失败 => {e}");
+            break;
+          }
+        }
+        ctx.request_repaint();
+      }
+      ctx.request_repaint();
+    },);
+
+    let mut client = Self {
+      child: Some(child,),
+      writer,
+      rx,
+      next_id: 0,
+      initialize_id: 0,
+      pending: HashMap::new(),
+      initialized: false,
+      queue: vec![],
+      encoding: PositionEncoding::Utf16,
+      documents: HashMap::new(),
+    };
+    let root_uri = path_to_uri(root,);
+    let name = root
+      .file_name()
+      .map(|v| v.to_string_lossy().into_owned(),)
+      .unwrap_or_default();
+    let params = json!({
+      "processId": std::process::id(),
+      "clientInfo": { "name": "egui_code" },
+      "rootUri": root_uri,
+      "workspaceFolders": [{ "uri": root_uri, "name": name }],
+      "capabilities": {
+        "general": { "positionEncodings": ["utf-32", "utf-16"] },
+        "textDocument": {
+          "synchronization": { "didSave": true },
+          "completion": { "completionItem": { "snippetSupport": false } },
+          "hover": { "contentFormat": ["plaintext", "markdown"] },
+          "definition": {},
+          "references": {},
+          "documentSymbol": { "hierarchicalDocumentSymbolSupport": true },
+          "publishDiagnostics": {},
+        },
+      },
+    });
+    client.next_id += 1;
+    client.initialize_id = client.next_id;
+    client.write(Outgoing::Message(
+      json!({ "jsonrpc": "2.0", "id": client.initialize_id, "method": "initialize", "params": params }),
+    ),);
+    Ok(client,)
+  }
+
+  /// 交给写入线程，写入线程已退出时语言服务器也已退出，由 poll 报告
+  fn write(
+    &self,
+    outgoing: Outgoing,
+  ) {
+    let _ = self.writer.send(outgoing,);
+  }
+
+  /// 发送消息，未初始化时暂存
+  fn send_outgoing(
+    &mut self,
+    outgoing: Outgoing,
+  ) {
+    if self.initialized {
+      self.write(outgoing,);
+    } else {
+      self.queue.push(outgoing,);
+    }
+  }
+
+  fn send(
+    &mut self,
+    message: Value,
+  ) {
+    self.send_outgoing(Outgoing::Message(message,),);
+  }
+
+  fn notify(
+    &mut self,
+    method: &str,
+    params: Value,
+  ) {
+    self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }),);
+  }
+
+  pub fn did_open(
+    &mut self,
+    path: &Path,
+    language: &str,
+    text: Rope,
+  ) {
+    self.documents.insert(
+      path.to_path_buf(),
+      Document {
+        version: 1,
+        text: text.clone(),
+        changed_at: None,
+      },
+    );
+    self.send_outgoing(Outgoing::Text {
+      message: json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+        "textDocument": {
+          "uri": path_to_uri(path),
+          "languageId": language_id(language),
+          "version": 1,
+          "text": null,
+        },
+      }}),
+      pointer: "/params/textDocument/text",
+      text,
+    },);
+  }
+
+  /// 记录修改后的内容，由 flush_changes 发送
+  pub fn did_change(
+    &mut self,
+    path: &Path,
+    text: Rope,
+  ) {
+    let Some(doc,) = self.documents.get_mut(path,) else {
+      return;
+    };
+    doc.text = text;
+    doc.changed_at.get_or_insert_with(Instant::now,);
+  }
+
+  /// 发送修改的全部内容；path 为 None 时只发送超过 CHANGE_INTERVAL 的修改
+  fn flush_changes(
+    &mut self,
+    path: Option<&Path,>,
+  ) {
+    let mut changes = vec![];
+    for (doc_path, doc,) in self.documents.iter_mut() {
+      let Some(changed_at,) = doc.changed_at else {
+        continue;
+      };
+      let due = match path {
+        Some(path,) => path == doc_path,
+        None => changed_at.elapsed() >= CHANGE_INTERVAL,
+      };
+      if !due {
+        continue;
+      }
+      doc.version += 1;
+      doc.changed_at = None;
+      changes.push(Outgoing::Text {
+        message: json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+          "textDocument": { "uri": path_to_uri(doc_path), "version": doc.version },
+          "contentChanges": [{ "text": null }],
+        }}),
+        pointer: "/params/contentChanges/0/text",
+        text: doc.text.clone(),
+      },);
+    }
+    for change in changes {
+      self.send_outgoing(change,);
+    }
+  }
+
+  pub fn did_save(
+    &mut self,
+    path: &Path,
+  ) {
+    self.flush_changes(Some(path,),);
+    self.notify(
+      "textDocument/didSave",
+      json!({ "textDocument": { "uri": path_to_uri(path) } }),
+    );
+  }
+
+  pub fn did_close(
+    &mut self,
+    path: &Path,
+  ) {
+    self.flush_changes(Some(path,),);
+    if self.documents.remove(path,).is_some() {
+      self.notify(
+        "textDocument/didClose",
+        json!({ "textDocument": { "uri": path_to_uri(path) } }),
+      );
+    }
+  }
+
+  /// 发送请求，响应通过 poll 返回
+  pub fn request(
+    &mut self,
+    request: LspRequest,
+  ) {
+    // 请求之前发送未发送的修改
+    self.flush_changes(Some(&request.path,),);
+    let (line, column,) = request.position;
+    let character = match self.encoding {
+      PositionEncoding::Utf32 => column,
+      PositionEncoding::Utf16 => match self.documents.get(&request.path,) {
+        Some(doc,) => char_to_utf16(&TextLines::new(&doc.text,).line(line,), column,),
+        None => column,
+      },
+    };
+    let mut params = json!({
+      "textDocument": { "uri": path_to_uri(&request.path) },
+      "position": { "line": line, "character": character },
+    });
+    match request.kind {
+      RequestKind::References => params["context"] = json!({ "includeDeclaration": true }),
+      RequestKind::DocumentSymbol => params = json!({ "textDocument": params["textDocument"] }),
+      _ => {}
+    }
+    self.next_id += 1;
+    let id = self.next_id;
+    self.send(
+      json!({ "jsonrpc": "2.0", "id": id, "method": request.kind.method(), "params": params }),
+    );
+    self.pending.insert(id, request,);
+  }
+
+  /// 处理收到的消息，语言服务器退出时返回 Err
+  pub fn poll(&mut self,) -> Result<Vec<LspEvent,>, String,> {
+    self.flush_changes(None,);
+    let mut events = vec![];
+    loop {
+      match self.rx.try_recv() {
+        Ok(message,) => {
+          if let Some(event,) = self.handle_message(message,) {
+            events.push(event,);
+          }
+        }
+        Err(TryRecvError::Empty,) => return Ok(events,),
+        Err(TryRecvError::Disconnected,) => return Err("语言服务器已退出".to_string(),),
+      }
+    }
+  }
+
+  fn handle_message(
+    &mut self,
+    message: Value,
+  ) -> Option<LspEvent,> {
+    let method = message["method"].as_str();
+    match (message.get("id",), method,) {
+      // 响应
+      (Some(id,), None,) => {
+        let id = id.as_i64()?;
+        if id == self.initialize_id && !self.initialized {
+          self.on_initialized(&message["result"],);
+          return None;
+        }
+        let request = self.pending.remove(&id,)?;
+        if let Some(error,) = message.get("error",) {
+          tracing::warn!("{} => {error}", request.kind.method());
+          return None;
+        }
+        self.on_response(request, &message["result"],)
+      }
+      // 语言服务器发起的请求
+      (Some(id,), Some(method,),) => {
+        let result = match method {
+          // 每一项都使用默认配置
+          "workspace/configuration" => {
+            let len = message["params"]["items"].as_array().map_or(0, Vec::len,);
+            Value::Array(vec![Value::Null; len],)
+          }
+          _ => Value::Null,
+        };
+        let id = id.clone();
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }),);
+        None
+      }
+      // 通知
+      (None, Some("textDocument/publishDiagnostics",),) => self.on_diagnostics(&message["params"],),
+      (None, Some(method,),) => {
+        tracing::debug!("{method} => {}", message["params"]);
+        None
+      }
+      (None, None,) => None,
+    }
+  }
+
+  fn on_initialized(
+    &mut self,
+    result: &Value,
+  ) {
+    if result["capabilities"]["positionEncoding"].as_str() == Some("utf-32",) {
+      self.encoding = PositionEncoding::Utf32;
+    }
+    self.initialized = true;
+    self.notify("initialized", json!({}),);
+    for outgoing in std::mem::take(&mut self.queue,) {
+      self.write(outgoing,);
+    }
+  }
+
+  /// 读取文件的文本用于转换位置，UTF-32 编码时不需要
+  fn text_of(
+    &self,
+    path: &Path,
+  ) -> Option<Rope,> {
+    if self.encoding == PositionEncoding::Utf32 {
+      return None;
+    }
+    match self.documents.get(path,) {
+      Some(doc,) => Some(doc.text.clone(),),
+      None => std::fs::read_to_string(path,).ok().map(Rope::from,),
+    }
+  }
+
+  /// LSP 的 Position 转换为 (行, 列)
+  fn to_position(
+    &self,
+    lines: Option<&TextLines,>,
+    position: &Value,
+  ) -> (usize, usize,) {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    match lines {
+      Some(lines,) => (line, utf16_to_char(&lines.line(line,), character,),),
+      None => (line, character,),
+    }
+  }
+
+  fn to_range(
+    &self,
+    lines: Option<&TextLines,>,
+    range: &Value,
+  ) -> ((usize, usize,), (usize, usize,),) {
+    (
+      self.to_position(lines, &range["start"],),
+      self.to_position(lines, &range["end"],),
+    )
+  }
+
+  fn on_diagnostics(
+    &self,
+    params: &Value,
+  ) -> Option<LspEvent,> {
+    let path = uri_to_path(params["uri"].as_str()?,)?;
+    let text = self.text_of(&path,);
+    let lines = text.as_ref().map(TextLines::new,);
+    let diagnostics = params["diagnostics"]
+      .as_array()
+      .map(Vec::as_slice,)
+      .unwrap_or_default()
+      .iter()
+      .map(|d| {
+        let (start, end,) = self.to_range(lines.as_ref(), &d["range"],);
+        Diagnostic {
+          path: path.clone(),
+          severity: match d["severity"].as_u64() {
+            Some(2,) => Severity::Warning,
+            Some(3,) => Severity::Note,
+            Some(4,) => Severity::Help,
+            _ => Severity::Error,
+          },
+          message: d["message"].as_str().unwrap_or_default().to_string(),
+          code: match &d["code"] {
+            Value::String(v,) => Some(v.clone(),),
+            Value::Number(v,) => Some(v.to_string(),),
+            _ => None,
+          },
+          start,
+          end,
+        }
+      },)
+      .collect();
+    Some(LspEvent::Diagnostics {
+      path,
+      diagnostics,
+    },)
+  }
+
+  fn on_response(
+    &self,
+    request: LspRequest,
+    result: &Value,
+  ) -> Option<LspEvent,> {
+    match request.kind {
+      RequestKind::Completion => {
+        // CompletionItem[] | CompletionList
+        let items = result.as_array().or_else(|| result["items"].as_array(),)?;
+        let text = self.text_of(&request.path,);
+        let lines = text.as_ref().map(TextLines::new,);
+        let mut items = items
+          .iter()
+          .filter_map(|item| {
+            let label = item["label"].as_str()?.to_string();
+            let edit = &item["textEdit"];
+            let text = edit["newText"]
+              .as_str()
+              .or_else(|| item["insertText"].as_str(),)
+              .unwrap_or(&label,)
+              .to_string();
+            let sort_text = item["sortText"].as_str().unwrap_or(&label,).to_string();
+            let completion = CompletionItem {
+              detail: item["detail"].as_str().map(str::to_string,),
+              filter_text: item["filterText"].as_str().unwrap_or(&label,).to_string(),
+              range: edit
+                .get("range",)
+                .map(|range| self.to_range(lines.as_ref(), range,),),
+              label,
+              text,
+            };
+            Some((sort_text, completion,),)
+          },)
+          .collect::<Vec<_,>>();
+        items.sort_by(|a, b| a.0.cmp(&b.0,).then_with(|| a.1.label.cmp(&b.1.label,),),);
+        Some(LspEvent::Completion {
+          path: request.path,
+          position: request.position,
+          items: items.into_iter().map(|v| v.1,).collect(),
+        },)
+      }
+      RequestKind::Hover => {
+        let text = hover_text(&result["contents"],);
+        (!text.trim().is_empty()).then(|| LspEvent::Hover {
+          path: request.path,
+          position: request.position,
+          text: text.trim().to_string(),
+        },)
+      }
+      RequestKind::Definition => Some(LspEvent::Definition(self.to_locations(result,),),),
+      RequestKind::References => Some(LspEvent::References(self.to_locations(result,),),),
+      RequestKind::DocumentSymbol => {
+        let text = self.text_of(&request.path,);
+        let lines = text.as_ref().map(TextLines::new,);
+        let values = result.as_array().map(Vec::as_slice,).unwrap_or_default();
+        let mut symbols = self.to_symbols(lines.as_ref(), values,);
+        // SymbolInformation[] 没有层级，按范围嵌套
+        if values.iter().all(|v| v.get("location",).is_some(),) {
+          symbols = nest_symbols(symbols,);
+        }
+        Some(LspEvent::Symbols {
+          path: request.path,
+          symbols,
+        },)
+      }
+    }
+  }
+
+  /// DocumentSymbol[] | SymbolInformation[]
+  fn to_symbols(
+    &self,
+    lines: Option<&TextLines,>,
+    values: &[Value],
+  ) -> Vec<Symbol,> {
+    values
+      .iter()
+      .filter_map(|v| {
+        let range = v.get("range",).or_else(|| v["location"].get("range",),)?;
+        let (start, end,) = self.to_range(lines, range,);
+        let selection = v
+          .get("selectionRange",)
+          .map_or(start, |v| self.to_position(lines, &v["start"],),);
+        let children = v["children"]
+          .as_array()
+          .map(Vec::as_slice,)
+          .unwrap_or_default();
+        Some(Symbol {
+          name: v["name"].as_str()?.to_string(),
+          kind: SymbolKind::from_lsp(v["kind"].as_u64().unwrap_or_default(),),
+          start,
+          end,
+          selection,
+          children: self.to_symbols(lines, children,),
+        },)
+      },)
+      .collect()
+  }
+
+  /// Location | Location[] | LocationLink[] | null
+  fn to_locations(
+    &self,
+    result: &Value,
+  ) -> Vec<Location,> {
+    let values = match result {
+      Value::Array(v,) => v.as_slice(),
+      Value::Object(_,) => std::slice::from_ref(result,),
+      _ => &[],
+    };
+    let mut texts = HashMap::<PathBuf, Option<Rope,>,>::new();
+    values
+      .iter()
+      .filter_map(|v| {
+        let uri = v["uri"].as_str().or_else(|| v["targetUri"].as_str(),)?;
+        let range = v
+          .get("range",)
+          .or_else(|| v.get("targetSelectionRange",),)?;
+        let path = uri_to_path(uri,)?;
+        let text = texts
+          .entry(path.clone(),)
+          .or_insert_with(|| self.text_of(&path,),);
+        let lines = text.as_ref().map(TextLines::new,);
+        let (start, end,) = self.to_range(lines.as_ref(), range,);
+        Some(Location {
+          path,
+          start,
+          end,
+        },)
+      },)
+      .collect()
+  }
+}
+
+/// 没有层级的符号按范围嵌套，范围在前一个符号之内的为其子符号
+fn nest_symbols(mut symbols: Vec<Symbol,>,) -> Vec<Symbol,> {
+  // 起点相同时范围大的在前
+  symbols.sort_by(|a, b| a.start.cmp(&b.start,).then(b.end.cmp(&a.end,),),);
+  let mut roots: Vec<Symbol,> = vec![];
+  for symbol in symbols {
+    let mut list = &mut roots;
+    while list
+      .last()
+      .is_some_and(|v| v.start <= symbol.start && symbol.end <= v.end,)
+    {
+      list = &mut list.last_mut().unwrap().children;
+    }
+    list.push(symbol,);
+  }
+  roots
+}
+
+/// MarkedString | MarkedString[] | MarkupContent 转换为文本
+fn hover_text(contents: &Value,) -> String {
+  match contents {
+    Value::String(v,) => v.clone(),
+    Value::Array(v,) => v.iter().map(hover_text,).collect::<Vec<_,>>().join("\n\n",),
+    Value::Object(v,) => v
+      .get("value",)
+      .and_then(Value::as_str,)
+      .unwrap_or_default()
+      .to_string(),
+    _ => String::new(),
+  }
+}
+
+impl Drop for LspClient {
+  fn drop(&mut self,) {
+    // 通知语言服务器退出，超时后结束进程
+    if self.initialized {
+      self.next_id += 1;
+      self.write(Outgoing::Message(
+        json!({ "jsonrpc": "2.0", "id": self.next_id, "method": "shutdown" }),
+      ),);
+      self.write(Outgoing::Message(
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+      ),);
+    }
+    if let Some(mut child,) = self.child.take() {
+      std::thread::spawn(move || {
+        let start = std::time::Instant::now();
+        while start.elapsed() < Duration::from_secs(2,) {
+          if !matches!(child.try_wait(), Ok(None)) {
+            return;
+          }
+          std::thread::sleep(Duration::from_millis(50,),);
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+      },);
+    }
+  }
+}
+
+// ------------------------------------ LspManager
+
+/// 打开的文件的状态，用于同步到语言服务器
+pub struct DocumentState<'a,> {
+  pub path: &'a Path,
+  pub language: &'a str,
+  /// 编辑历史的版本，内容变化时改变
+  pub revision: u64,
+  /// 保存到磁盘的次数，保存后发送 didSave，撤销到已保存的版本不算保存
+  pub save_count: u64,
+  /// 内容变化时才读取
+  pub text: &'a Buffer,
+}
+
+/// 已同步的文件
+struct SyncedDocument {
+  language: String,
+  revision: u64,
+  save_count: u64,
+}
+
+/// 按语言启动语言服务器，同步打开的文件
+pub struct LspManager {
+  ctx: egui::Context,
+  servers: BTreeMap<String, ServerConfig,>,
+  root: Option<PathBuf,>,
+  clients: BTreeMap<String, LspClient,>,
+  /// 启动失败或已退出的语言，重新打开文件夹前不再启动
+  failed: BTreeSet<String,>,
+  documents: BTreeMap<PathBuf, SyncedDocument,>,
+}
+
+impl LspManager {
+  pub fn new(ctx: egui::Context,) -> Self {
+    Self {
+      ctx,
+      servers: default_servers(),
+      root: None,
+      clients: BTreeMap::new(),
+      failed: BTreeSet::new(),
+      documents: BTreeMap::new(),
+    }
+  }
+
+  pub fn servers(&self,) -> &BTreeMap<String, ServerConfig,> {
+    &self.servers
+  }
+
+  /// 修改配置后重新启动语言服务器
+  pub fn set_servers(
+    &mut self,
+    servers: BTreeMap<String, ServerConfig,>,
+  ) {
+    if self.servers != servers {
+      self.servers = servers;
+      self.restart();
+    }
+  }
+
+  /// 语言服务器的工作区为打开的文件夹，未打开文件夹时不启动
+  pub fn set_root(
+    &mut self,
+    root: Option<PathBuf,>,
+  ) {
+    if self.root != root {
+      self.root = root;
+      self.restart();
+    }
+  }
+
+  fn restart(&mut self,) {
+    self.clients.clear();
+    self.failed.clear();
+    self.documents.clear();
+  }
+
+  /// 同步打开的文件，返回启动语言服务器的错误
+  pub fn sync_documents(
+    &mut self,
+    docs: &[DocumentState],
+  ) -> Vec<String,> {
+    let mut errors = vec![];
+    // 关闭的文件
+    let closed = self
+      .documents
+      .keys()
+      .filter(|path| !docs.iter().any(|d| d.path == path.as_path(),),)
+      .cloned()
+      .collect::<Vec<_,>>();
+    for path in closed {
+      if let Some(synced,) = self.documents.remove(&path,) {
+        if let Some(client,) = self.clients.get_mut(&synced.language,) {
+          client.did_close(&path,);
+        }
+      }
+    }
+
+    for doc in docs.iter() {
+      // 修改了语言
+      if let Some(synced,) = self.documents.get(doc.path,) {
+        if synced.language != doc.language {
+          if let Some(client,) = self.clients.get_mut(&synced.language,) {
+            client.did_close(doc.path,);
+          }
+          self.documents.remove(doc.path,);
+        }
+      }
+      if let Err(e,) = self.ensure_client(doc.language,) {
+        errors.extend(e,);
+        continue;
+      }
+      let Some(client,) = self.clients.get_mut(doc.language,) else {
+        continue;
+      };
+      match self.documents.get_mut(doc.path,) {
+        None => {
+          client.did_open(doc.path, doc.language, doc.text.snapshot(),);
+          self.documents.insert(
+            doc.path.to_path_buf(),
+            SyncedDocument {
+              language: doc.language.to_string(),
+              revision: doc.revision,
+              save_count: doc.save_count,
+            },
+          );
+        }
+        Some(synced,) => {
+          if synced.revision != doc.revision {
+            client.did_change(doc.path, doc.text.snapshot(),);
+            synced.revision = doc.revision;
+            // 稍后发送修改
+            self.ctx.request_repaint_after(CHANGE_INTERVAL,);
+          }
+          if synced.save_count != doc.save_count {
+            client.did_save(doc.path,);
+            synced.save_count = doc.save_count;
+          }
+        }
+      }
+    }
+    errors
+  }
+
+  /// 需要时启动语言的服务器，启动失败时返回错误
+  fn ensure_client(
+    &mut self,
+    language: &str,
+  ) -> Result<(), Option<String,>,> {
+    if self.clients.contains_key(language,) {
+      return Ok((),);
+    }
+    let (Some(root,), Some(config,),) = (&self.root, self.servers.get(language,),) else {
+      return Err(None,);
+    };
+    if self.failed.contains(language,) {
+      return Err(None,);
+    }
+    match LspClient::start(config, root, self.ctx.clone(),) {
+      Ok(client,) => {
+        self.clients.insert(language.to_string(), client,);
+        Ok((),)
+      }
+      Err(e,) => {
+        self.failed.insert(language.to_string(),);
+        Err(Some(format!("启动语言服务器 {} 失败：{e}", config.command),),)
+      }
+    }
+  }
+
+  /// 发送请求，文件没有对应的语言服务器时忽略
+  pub fn request(
+    &mut self,
+    request: LspRequest,
+  ) {
+    let Some(synced,) = self.documents.get(&request.path,) else {
+      return;
+    };
+    if let Some(client,) = self.clients.get_mut(&synced.language,) {
+      client.request(request,);
+    }
+  }
+
+  /// 收到的响应和通知
+  pub fn poll(&mut self,) -> Vec<LspEvent,> {
+    let mut events = vec![];
+    let mut exited = vec![];
+    for (language, client,) in self.clients.iter_mut() {
+      match client.poll() {
+        Ok(v,) => events.extend(v,),
+        Err(e,) => {
+          events.push(LspEvent::Error(format!("{language}: {e}"),),);
+          exited.push(language.clone(),);
+        }
+      }
+    }
+    for language in exited {
+      self.clients.remove(&language,);
+      self.failed.insert(language.clone(),);
+      self.documents.retain(|_, v| v.language != language,);
+    }
+    events
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+  };
+
+  use super::{
+    char_to_utf16, path_to_uri, read_message, uri_to_path, utf16_to_char, write_message,
+    DocumentState, LspEvent, LspManager, LspRequest, Outgoing, RequestKind, ServerConfig,
+  };
+  use crate::{buffer::Buffer, diagnostic::Severity, outline::SymbolKind, test_util::TempDir};
+
+  #[test]
+  fn test_message() {
+    let mut buf = vec![];
+    let message = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "好" });
+    write_message(&mut buf, &message,).unwrap();
+    write_message(&mut buf, &message,).unwrap();
+    let text = String::from_utf8(buf.clone(),).unwrap();
+    assert!(text.starts_with("Content-Length: 39\r\n\r\n{"));
+    let mut reader = std::io::BufReader::new(buf.as_slice(),);
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+    assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+    assert_eq!(read_message(&mut reader).unwrap(), None);
+
+    // 文件内容在写入线程中填入
+    let outgoing = Outgoing::Text {
+      message: serde_json::json!({ "params": { "contentChanges": [{ "text": null }] } }),
+      pointer: "/params/contentChanges/0/text",
+      text: ropey::Rope::from("a\n中",),
+    };
+    assert_eq!(
+      outgoing.into_message(),
+      serde_json::json!({ "params": { "contentChanges": [{ "text": "a\n中" }] } })
+    );
+  }
+
+  #[test]
+  fn test_uri() {
+    let path = Path::new("/tmp/a b/中.rs",);
+    let uri = path_to_uri(path,);
+    assert_eq!(uri, "file:///tmp/a%20b/%E4%B8%AD.rs");
+    assert_eq!(uri_to_path(&uri), Some(path.to_path_buf()));
+    assert_eq!(path_to_uri(Path::new("C:\\a\\b.rs")), "file:///C:/a/b.rs");
+    assert_eq!(
+      uri_to_path("file:///C:/a/b.rs"),
+      Some(PathBuf::from("C:/a/b.rs"))
+    );
+    assert_eq!(uri_to_path("untitled:1"), None);
+  }
+
+  #[test]
+  fn test_utf16() {
+    let line = "a😀b中c\n";
+    assert_eq!(char_to_utf16(line, 2), 3);
+    assert_eq!(char_to_utf16(line, 4), 5);
+    assert_eq!(utf16_to_char(line, 3), 2);
+    assert_eq!(utf16_to_char(line, 5), 4);
+    assert_eq!(utf16_to_char(line, 100), 5);
+  }
+
+  /// 按脚本回复的语言服务器：收到请求时回复固定的结果，位置使用 UTF-16 编码
+  const FAKE_SERVER: &str = r#"
+reply() { printf 'Content-Length: %s\r\n\r\n%s' "${#1}" "$1"; }
+cr=$(printf '\r')
+while IFS= read -r header; do
+  case "$header" in
+    Content-Length:*) len=$(printf '%s' "${header#Content-Length: }" | tr -d '\r') ;;
+    "$cr")
+      body=$(dd bs=1 count="$len" 2>/dev/null)
+      id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      method=$(printf '%s' "$body" | sed -n 's/.*"method":"\([^"]*\)".*/\1/p')
+      uri=$(printf '%s' "$body" | sed -n 's/.*"uri":"\([^"]*\)".*/\1/p')
+      loc='{"uri":"'$uri'","range":{"start":{"line":0,"character":7},"end":{"line":0,"character":10}}}'
+      case "$method" in
+        initialize)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":{"capabilities":{"textDocumentSync":1}}}' ;;
+        initialized)
+          reply '{"jsonrpc":"2.0","id":"c1","method":"workspace/configuration","params":{"items":[{},{}]}}' ;;
+        textDocument/didOpen)
+          reply '{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"'$uri'","diagnostics":[{"range":{"start":{"line":0,"character":7},"end":{"line":0,"character":10}},"severity":1,"code":"E0425","message":"cannot find foo"}]}}' ;;
+        textDocument/didChange)
+          reply '{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"'$uri'","diagnostics":[]}}' ;;
+        textDocument/didSave)
+          reply '{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"'$uri'","diagnostics":[{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":1}},"severity":2,"message":"saved"}]}}' ;;
+        textDocument/completion)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":{"isIncomplete":false,"items":[{"label":"foo","detail":"fn foo()","sortText":"1"},{"label":"bar","sortText":"0","textEdit":{"range":{"start":{"line":0,"character":7},"end":{"line":0,"character":8}},"newText":"bar()"}}]}}' ;;
+        textDocument/hover)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":{"contents":{"kind":"markdown","value":"fn foo()"}}}' ;;
+        textDocument/definition)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":'$loc'}' ;;
+        textDocument/references)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":['$loc','$loc']}' ;;
+        textDocument/documentSymbol)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":[{"name":"main","kind":12,"location":{"uri":"'$uri'","range":{"start":{"line":1,"character":0},"end":{"line":1,"character":12}}}},{"name":"foo","kind":13,"location":{"uri":"'$uri'","range":{"start":{"line":1,"character":11},"end":{"line":1,"character":11}}}},{"name":"doc","kind":2,"location":{"uri":"'$uri'","range":{"start":{"line":0,"character":0},"end":{"line":1,"character":12}}}}]}' ;;
+        shutdown)
+          reply '{"jsonrpc":"2.0","id":'$id',"result":null}' ;;
+        exit)
+          exit 0 ;;
+      esac
+      ;;
+  esac
+done
+"#;
+
+  /// 等待语言服务器的事件
+  fn wait_event(lsp: &mut LspManager,) -> LspEvent {
+    let start = Instant::now();
+    loop {
+      if let Some(event,) = lsp.poll().into_iter().next() {
+        return event;
+      }
+      assert!(start.elapsed() < Duration::from_secs(10), "等待事件超时");
+      std::thread::sleep(Duration::from_millis(10,),);
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_lsp_manager() {
+    let root = TempDir::new("lsp",);
+    let path = root.join("main.rs",);
+    // 😀 在 UTF-16 中占两个单位，foo 的 char 索引为 6，UTF-16 偏移为 7
+    let text = "/*😀*/ foo()\nfn main() {}\n";
+    std::fs::write(&path, text,).unwrap();
+    let buffer = Buffer::from(text,);
+
+    let mut lsp = LspManager::new(Default::default(),);
+    lsp.set_servers(BTreeMap::from([(
+      "Rust".to_string(),
+      ServerConfig {
+        command: "sh".into(),
+        args: vec!["-c".into(), FAKE_SERVER.into()],
+      },
+    ),],),);
+    let doc = |revision, save_count| DocumentState {
+      path: &path,
+      language: "Rust",
+      revision,
+      save_count,
+      text: &buffer,
+    };
+    // 未打开文件夹时不启动
+    assert!(lsp.sync_documents(&[doc(0, 0,)]).is_empty());
+    assert!(lsp.clients.is_empty());
+    lsp.set_root(Some(root.to_path_buf(),),);
+
+    // didOpen => 诊断
+    assert!(lsp.sync_documents(&[doc(0, 0,)]).is_empty());
+    let LspEvent::Diagnostics {
+      path: p,
+      diagnostics,
+    } = wait_event(&mut lsp,)
+    else {
+      panic!("expected diagnostics");
+    };
+    assert_eq!(p, path);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].code.as_deref(), Some("E0425"));
+    assert_eq!((diagnostics[0].start, diagnostics[0].end), ((0, 6), (0, 9)));
+
+    // didChange => 清除诊断，撤销到保存的版本不发送 didSave
+    let cleared = LspEvent::Diagnostics {
+      path: path.clone(),
+      diagnostics: vec![],
+    };
+    lsp.sync_documents(&[doc(1, 0,),],);
+    assert_eq!(wait_event(&mut lsp), cleared);
+    lsp.sync_documents(&[doc(0, 0,),],);
+    assert_eq!(wait_event(&mut lsp), cleared);
+    // didSave => 新的诊断
+    lsp.sync_documents(&[doc(0, 1,),],);
+    let LspEvent::Diagnostics {
+      diagnostics, ..
+    } = wait_event(&mut lsp,)
+    else {
+      panic!("expected diagnostics");
+    };
+    assert_eq!(diagnostics[0].message, "saved");
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+
+    let request = |kind| LspRequest {
+      kind,
+      path: path.clone(),
+      position: (0, 7,),
+    };
+    // 补全，按 sortText 排序
+    lsp.request(request(RequestKind::Completion,),);
+    let LspEvent::Completion {
+      position,
+      items,
+      ..
+    } = wait_event(&mut lsp,)
+    else {
+      panic!("expected completion");
+    };
+    assert_eq!(position, (0, 7));
+    assert_eq!(
+      items.iter().map(|v| v.label.as_str()).collect::<Vec<_,>>(),
+      ["bar", "foo"]
+    );
+    assert_eq!(items[0].text, "bar()");
+    assert_eq!(items[0].range, Some(((0, 6), (0, 7))));
+    assert_eq!(items[1].text, "foo");
+    assert_eq!(items[1].detail.as_deref(), Some("fn foo()"));
+
+    lsp.request(request(RequestKind::Hover,),);
+    assert_eq!(
+      wait_event(&mut lsp),
+      LspEvent::Hover {
+        path: path.clone(),
+        position: (0, 7),
+        text: "fn foo()".into()
+      }
+    );
+
+    let location = super::Location {
+      path: path.clone(),
+      start: (0, 6,),
+      end: (0, 9,),
+    };
+    lsp.request(request(RequestKind::Definition,),);
+    assert_eq!(
+      wait_event(&mut lsp),
+      LspEvent::Definition(vec![location.clone()])
+    );
+    lsp.request(request(RequestKind::References,),);
+    assert_eq!(
+      wait_event(&mut lsp),
+      LspEvent::References(vec![location.clone(), location])
+    );
+
+    // SymbolInformation[] 按范围嵌套
+    lsp.request(request(RequestKind::DocumentSymbol,),);
+    let LspEvent::Symbols {
+      symbols, ..
+    } = wait_event(&mut lsp,)
+    else {
+      panic!("expected symbols");
+    };
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0].name, "doc");
+    assert_eq!(symbols[0].kind, SymbolKind::Module);
+    assert_eq!(symbols[0].children[0].name, "main");
+    assert_eq!(symbols[0].children[0].children[0].name, "foo");
+
+    // 关闭文件后不再发送请求
+    lsp.sync_documents(&[],);
+    lsp.request(request(RequestKind::Hover,),);
+    std::thread::sleep(Duration::from_millis(200,),);
+    assert!(lsp.poll().is_empty());
+
+    // 语言服务器启动失败时只报告一次
+    let mut lsp = LspManager::new(Default::default(),);
+    lsp.set_servers(BTreeMap::from([(
+      "Rust".to_string(),
+      ServerConfig {
+        command: "egui_code_no_such_server".into(),
+        args: vec![],
+      },
+    ),],),);
+    lsp.set_root(Some(root.to_path_buf(),),);
+    assert_eq!(lsp.sync_documents(&[doc(0, 0,)]).len(), 1);
+    assert!(lsp.sync_documents(&[doc(0, 0,)]).is_empty());
+  }
+}
@@ -4,15 +4,32 @@
 
 mod action;
 mod app;
+mod backup;
+mod buffer;
 mod component;
+mod config;
 mod dev_tool;
+mod diagnostic;
+mod file_op;
 mod font;
 mod frame_history;
+mod fs_watcher;
+mod history;
 mod hl;
 mod id;
 #[allow(unused)]
 mod images;
+mod lsp;
+mod outline;
+mod path_filter;
+mod pty;
+mod search;
+mod settings;
 mod style;
+mod task;
+mod terminal;
+#[cfg(test)]
+mod test_util;
 mod text;
 mod ui;
 mod util;
@@ -0,0 +1,444 @@
+use std::cmp::Ordering;
+
+use eframe::egui;
+use ropey::Rope;
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxSet};
+
+use crate::hl;
+
+/// 符号的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,)]
+pub enum SymbolKind {
+  Module,
+  Heading,
+  Class,
+  Struct,
+  Enum,
+  Trait,
+  Impl,
+  Type,
+  Function,
+  Method,
+  Field,
+  Constant,
+  Variable,
+  Other,
+}
+
+impl SymbolKind {
+  /// LSP 的 SymbolKind
+  pub fn from_lsp(kind: u64,) -> Self {
+    match kind {
+      2..=4 => SymbolKind::Module,
+      5 => SymbolKind::Class,
+      6 | 9 => SymbolKind::Method,
+      7 | 8 | 22 => SymbolKind::Field,
+      10 => SymbolKind::Enum,
+      11 => SymbolKind::Trait,
+      12 => SymbolKind::Function,
+      13 => SymbolKind::Variable,
+      14 => SymbolKind::Constant,
+      15 => SymbolKind::Heading,
+      // rust-analyzer 中 impl 为 Object
+      19 => SymbolKind::Impl,
+      23 => SymbolKind::Struct,
+      26 => SymbolKind::Type,
+      _ => SymbolKind::Other,
+    }
+  }
+
+  /// syntect 的 entity.name.* scope
+  fn from_scope(name: &str,) -> Option<Self,> {
+    let kind = match name.split('.',).next()? {
+      "namespace" | "module" => SymbolKind::Module,
+      "section" => SymbolKind::Heading,
+      "class" => SymbolKind::Class,
+      "struct" => SymbolKind::Struct,
+      "enum" | "union" => SymbolKind::Enum,
+      "trait" | "interface" => SymbolKind::Trait,
+      "impl" => SymbolKind::Impl,
+      "type" => SymbolKind::Type,
+      "function" | "macro" => SymbolKind::Function,
+      "constant" => SymbolKind::Constant,
+      _ => return None,
+    };
+    Some(kind,)
+  }
+
+  /// 在大纲中显示的标记
+  pub fn label(&self,) -> &'static str {
+    match self {
+      SymbolKind::Module => "mod",
+      SymbolKind::Heading => "#",
+      SymbolKind::Class => "class",
+      SymbolKind::Struct => "struct",
+      SymbolKind::Enum => "enum",
+      SymbolKind::Trait => "trait",
+      SymbolKind::Impl => "impl",
+      SymbolKind::Type => "type",
+      SymbolKind::Function => "fn",
+      SymbolKind::Method => "method",
+      SymbolKind::Field => "field",
+      SymbolKind::Constant => "const",
+      SymbolKind::Variable => "var",
+      SymbolKind::Other => "•",
+    }
+  }
+
+  pub fn color(&self,) -> egui::Color32 {
+    match self {
+      SymbolKind::Module | SymbolKind::Heading => egui::Color32::from_rgb(0x4f, 0xc1, 0xff,),
+      SymbolKind::Class | SymbolKind::Struct | SymbolKind::Type => {
+        egui::Color32::from_rgb(0xee, 0x9d, 0x28,)
+      }
+      SymbolKind::Enum | SymbolKind::Trait | SymbolKind::Impl => {
+        egui::Color32::from_rgb(0x4e, 0xc9, 0xb0,)
+      }
+      SymbolKind::Function | SymbolKind::Method => egui::Color32::from_rgb(0xb1, 0x80, 0xd7,),
+      SymbolKind::Field | SymbolKind::Constant | SymbolKind::Variable | SymbolKind::Other => {
+        egui::Color32::from_rgb(0x75, 0xbe, 0xff,)
+      }
+    }
+  }
+}
+
+/// 大纲中的符号，位置为 (行, 列)，从 0 开始，列为 char 索引
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct Symbol {
+  pub name: String,
+  pub kind: SymbolKind,
+  /// 符号的范围，如函数的整个函数体
+  pub start: (usize, usize,),
+  pub end: (usize, usize,),
+  /// 符号名称的位置，点击时跳转到此处
+  pub selection: (usize, usize,),
+  pub children: Vec<Symbol,>,
+}
+
+impl Symbol {
+  fn contains(
+    &self,
+    position: (usize, usize,),
+  ) -> bool {
+    self.start <= position && position <= self.end
+  }
+}
+
+/// 大纲的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq,)]
+pub enum SortOrder {
+  /// 按在文件中的位置
+  #[default]
+  Position,
+  Name,
+  Kind,
+}
+
+impl SortOrder {
+  pub fn next(&self,) -> Self {
+    match self {
+      SortOrder::Position => SortOrder::Name,
+      SortOrder::Name => SortOrder::Kind,
+      SortOrder::Kind => SortOrder::Position,
+    }
+  }
+
+  pub fn name(&self,) -> &'static str {
+    match self {
+      SortOrder::Position => "按位置排序",
+      SortOrder::Name => "按名称排序",
+      SortOrder::Kind => "按类型排序",
+    }
+  }
+
+  fn compare(
+    &self,
+    a: &Symbol,
+    b: &Symbol,
+  ) -> Ordering {
+    let by_position = a.start.cmp(&b.start,);
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase(),);
+    match self {
+      SortOrder::Position => by_position,
+      SortOrder::Name => by_name().then(by_position,),
+      SortOrder::Kind => a.kind.cmp(&b.kind,).then_with(by_name,).then(by_position,),
+    }
+  }
+}
+
+/// 按 order 递归排序
+pub fn sort_symbols(
+  symbols: &mut [Symbol],
+  order: SortOrder,
+) {
+  symbols.sort_by(|a, b| order.compare(a, b,),);
+  for symbol in symbols.iter_mut() {
+    sort_symbols(&mut symbol.children, order,);
+  }
+}
+
+/// 包含 position 的最内层符号，返回从根到该符号的索引
+pub fn symbol_path_at(
+  symbols: &[Symbol],
+  position: (usize, usize,),
+) -> Vec<usize,> {
+  let mut path = vec![];
+  let mut symbols = symbols;
+  while let Some(i,) = symbols.iter().position(|v| v.contains(position,),) {
+    path.push(i,);
+    symbols = &symbols[i].children;
+  }
+  path
+}
+
+/// 没有语言服务器时，根据 Markdown 标题或语法高亮的 scope 提取符号
+pub fn extract_symbols(
+  text: &Rope,
+  language: &str,
+) -> Vec<Symbol,> {
+  let flat = if language == "Markdown" {
+    markdown_headings(text,)
+  } else {
+    scope_symbols(text, language, &hl::syntax_set(),)
+  };
+  build_tree(flat, text.len_lines(),)
+}
+
+/// (层级, 符号)，层级为标题级别或缩进
+type FlatSymbol = (usize, Symbol,);
+
+fn line_text(
+  text: &Rope,
+  line: usize,
+) -> String {
+  let line = text.line(line,).to_string();
+  line.trim_end_matches(['\r', '\n',],).to_string()
+}
+
+/// Markdown 的标题，忽略代码块中的 #
+fn markdown_headings(text: &Rope,) -> Vec<FlatSymbol,> {
+  let mut symbols = vec![];
+  let mut fence: Option<&str,> = None;
+  for line_index in 0..text.len_lines() {
+    let line = line_text(text, line_index,);
+    let trimmed = line.trim_start();
+    if let Some(marker,) = ["```", "~~~",]
+      .into_iter()
+      .find(|v| trimmed.starts_with(v,),)
+    {
+      fence = match fence {
+        Some(v,) if v == marker => None,
+        Some(v,) => Some(v,),
+        None => Some(marker,),
+      };
+      continue;
+    }
+    if fence.is_some() {
+      continue;
+    }
+    let level = trimmed.chars().take_while(|c| *c == '#',).count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level,) || !(rest.is_empty() || rest.starts_with([' ', '\t',],)) {
+      continue;
+    }
+    let name = rest.trim().trim_end_matches('#',).trim_end();
+    let column = line.chars().count() - trimmed.chars().count();
+    symbols.push((
+      level,
+      Symbol {
+        name: name.to_string(),
+        kind: SymbolKind::Heading,
+        start: (line_index, 0,),
+        end: (line_index, 0,),
+        selection: (line_index, column,),
+        children: vec![],
+      },
+    ),);
+  }
+  symbols
+}
+
+/// entity.name.* scope 标记的名称，层级为所在行的缩进
+fn scope_symbols(
+  text: &Rope,
+  language: &str,
+  syntax_set: &SyntaxSet,
+) -> Vec<FlatSymbol,> {
+  let Some(syntax,) = syntax_set.find_syntax_by_name(language,) else {
+    return vec![];
+  };
+  let entity_name = Scope::new("entity.name",).unwrap();
+  let mut parse_state = ParseState::new(syntax,);
+  let mut stack = ScopeStack::new();
+  let mut symbols = vec![];
+  for line_index in 0..text.len_lines() {
+    // syntect 的语法需要包含换行符
+    let line = text.line(line_index,).to_string();
+    let Ok(ops,) = parse_state.parse_line(&line, syntax_set,) else {
+      break;
+    };
+    let indent = line
+      .chars()
+      .take_while(|c| c.is_whitespace() && *c != '\n',)
+      .map(|c| if c == '\t' { 4 } else { 1 },)
+      .sum::<usize>();
+    // 行内每一段文本所在的 entity.name.* scope
+    let mut current: Option<(SymbolKind, usize, String,),> = None;
+    let mut flush = |current: &mut Option<(SymbolKind, usize, String,),>| {
+      if let Some((kind, byte_index, name,),) = current.take() {
+        let name = name.trim();
+        if !name.is_empty() {
+          let column = line[..byte_index].chars().count();
+          symbols.push((
+            indent,
+            Symbol {
+              name: name.to_string(),
+              kind,
+              start: (line_index, 0,),
+              end: (line_index, 0,),
+              selection: (line_index, column,),
+              children: vec![],
+            },
+          ),);
+        }
+      }
+    };
+    let mut segment_start = 0;
+    let segment_ends = ops
+      .iter()
+      .map(|(i, _,)| *i,)
+      .chain([line.len(),],)
+      .collect::<Vec<_,>>();
+    let mut ops = ops.into_iter();
+    for segment_end in segment_ends {
+      if segment_end > segment_start {
+        let kind = stack
+          .as_slice()
+          .iter()
+          .rev()
+          .find(|v| entity_name.is_prefix_of(**v,),)
+          .and_then(|v| {
+            let name = v.build_string();
+            SymbolKind::from_scope(name.strip_prefix("entity.name.",)?,)
+          },);
+        let segment = &line[segment_start..segment_end];
+        match (kind, &mut current,) {
+          (Some(kind,), Some((current_kind, _, name,),),) if kind == *current_kind => {
+            name.push_str(segment,);
+          }
+          (Some(kind,), _,) => {
+            flush(&mut current,);
+            current = Some((kind, segment_start, segment.to_string(),),);
+          }
+          (None, _,) => flush(&mut current,),
+        }
+      }
+      segment_start = segment_end;
+      if let Some((_, op,),) = ops.next() {
+        let _ = stack.apply(&op,);
+      }
+    }
+    flush(&mut current,);
+  }
+  symbols
+}
+
+/// 按层级构建符号树，符号的范围到下一个同级或更高级的符号之前
+fn build_tree(
+  flat: Vec<FlatSymbol,>,
+  line_count: usize,
+) -> Vec<Symbol,> {
+  let last_line = line_count.saturating_sub(1,);
+  let levels = flat.iter().map(|v| v.0,).collect::<Vec<_,>>();
+  let mut symbols = flat.into_iter().map(|v| v.1,).collect::<Vec<_,>>();
+  for i in 0..symbols.len() {
+    let end_line = (i + 1..symbols.len())
+      .find(|j| levels[*j] <= levels[i],)
+      .map_or(last_line, |j| symbols[j].start.0.saturating_sub(1,),);
+    symbols[i].end = (end_line.max(symbols[i].start.0,), usize::MAX,);
+  }
+
+  // 栈中为尚未结束的祖先
+  let mut roots = vec![];
+  let mut stack: Vec<(usize, Symbol,),> = vec![];
+  let pop = |stack: &mut Vec<(usize, Symbol,),>, roots: &mut Vec<Symbol,>| {
+    let (_, symbol,) = stack.pop().unwrap();
+    match stack.last_mut() {
+      Some((_, parent,),) => parent.children.push(symbol,),
+      None => roots.push(symbol,),
+    }
+  };
+  for (level, symbol,) in levels.into_iter().zip(symbols,) {
+    while stack.last().is_some_and(|(v, _,)| *v >= level,) {
+      pop(&mut stack, &mut roots,);
+    }
+    stack.push((level, symbol,),);
+  }
+  while !stack.is_empty() {
+    pop(&mut stack, &mut roots,);
+  }
+  roots
+}
+
+#[cfg(test)]
+mod test {
+  use ropey::Rope;
+
+  use super::{extract_symbols, sort_symbols, symbol_path_at, SortOrder, SymbolKind};
+
+  #[test]
+  fn test_markdown_headings() {
+    let text = Rope::from_str("# A\ntext\n```\n# code\n```\n## B\n### C\n## D #\n# E\n",);
+    let symbols = extract_symbols(&text, "Markdown",);
+    let names = |v: &[super::Symbol]| v.iter().map(|v| v.name.clone(),).collect::<Vec<_,>>();
+    assert_eq!(names(&symbols), ["A", "E"]);
+    assert_eq!(names(&symbols[0].children), ["B", "D"]);
+    assert_eq!(names(&symbols[0].children[0].children), ["C"]);
+    assert_eq!(symbols[0].children[0].start.0, 5);
+    assert_eq!(symbols[0].children[0].end.0, 6);
+    assert_eq!(symbols[0].kind, SymbolKind::Heading);
+    assert_eq!(symbol_path_at(&symbols, (6, 1)), [0, 0, 0]);
+    assert_eq!(symbol_path_at(&symbols, (1, 0)), [0]);
+  }
+
+  #[test]
+  fn test_scope_symbols() {
+    let text = Rope::from_str(
+      "mod m {\n  struct Foo;\n  impl Foo {\n    fn bar() {}\n  }\n}\n\nenum Baz {}\nfn 中文() {}\n",
+    );
+    let symbols = extract_symbols(&text, "Rust",);
+    let summary = |v: &[super::Symbol]| {
+      v.iter()
+        .map(|v| (v.kind, v.name.clone(),),)
+        .collect::<Vec<_,>>()
+    };
+    assert_eq!(
+      summary(&symbols),
+      [
+        (SymbolKind::Module, "m".into()),
+        (SymbolKind::Enum, "Baz".into()),
+        (SymbolKind::Function, "中文".into())
+      ]
+    );
+    assert_eq!(
+      summary(&symbols[0].children),
+      [
+        (SymbolKind::Struct, "Foo".into()),
+        (SymbolKind::Impl, "Foo".into())
+      ]
+    );
+    assert_eq!(
+      summary(&symbols[0].children[1].children),
+      [(SymbolKind::Function, "bar".into())]
+    );
+    assert_eq!(symbols[0].children[1].children[0].selection, (3, 7));
+    assert_eq!(symbol_path_at(&symbols, (3, 0)), [0, 1, 0]);
+
+    let mut sorted = symbols.clone();
+    sort_symbols(&mut sorted, SortOrder::Name,);
+    assert_eq!(sorted[0].name, "Baz");
+    sort_symbols(&mut sorted, SortOrder::Kind,);
+    assert_eq!(sorted[0].name, "m");
+    assert_eq!(sorted[1].name, "Baz");
+  }
+}
@@ -0,0 +1,216 @@
+use std::{
+  collections::HashSet,
+  fs,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use crate::{config, util};
+
+/// 用户配置的文件过滤，见配置目录中的 files.toml
+///
+/// ```toml
+/// exclude = ["*.log", "docs/build"]
+/// ```
+#[derive(Debug, Default, Deserialize,)]
+struct FilesConfig {
+  /// 排除的 glob，匹配相对于打开的文件夹的路径
+  #[serde(default)]
+  exclude: Vec<String,>,
+}
+
+/// 目录遍历共用的过滤规则：.gitignore、.ignore、全局 git excludes、隐藏文件，以及用户配置的排除 glob
+///
+/// Default 不含用户配置的排除 glob
+#[derive(Debug, Clone, Default,)]
+pub struct PathFilter {
+  root: PathBuf,
+  exclude: Option<GlobSet,>,
+}
+
+/// 目录中的一项
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct DirEntry {
+  pub path: PathBuf,
+  pub is_dir: bool,
+  /// 被过滤规则排除
+  pub filtered: bool,
+}
+
+impl PathFilter {
+  /// root 为打开的文件夹，exclude 中的 glob 匹配相对于 root 的路径
+  pub fn new<'a,>(
+    root: &Path,
+    exclude: impl IntoIterator<Item = &'a str,>,
+  ) -> Result<Self, String,> {
+    Ok(Self {
+      root: root.to_owned(),
+      exclude: build_glob_set(exclude,)?,
+    },)
+  }
+
+  /// 使用配置目录中的 files.toml，配置有误时提示并忽略
+  pub fn load(root: &Path,) -> Self {
+    let config = config::load_toml::<FilesConfig,>("files.toml",).unwrap_or_else(|e| {
+      util::toaster()
+        .error(e,)
+        .set_duration(Some(Duration::from_secs(5,),),);
+      FilesConfig::default()
+    },);
+    Self::new(root, config.exclude.iter().map(String::as_str,),).unwrap_or_else(|e| {
+      util::toaster()
+        .error(format!("files.toml: {e}"),)
+        .set_duration(Some(Duration::from_secs(5,),),);
+      Self::new(root, [],).unwrap()
+    },)
+  }
+
+  /// 是否被用户配置的 glob 排除
+  pub fn is_excluded(
+    &self,
+    path: &Path,
+  ) -> bool {
+    let relative_path = path.strip_prefix(&self.root,).unwrap_or(path,);
+    self
+      .exclude
+      .as_ref()
+      .is_some_and(|v| v.is_match(relative_path,),)
+  }
+
+  /// 应用了所有过滤规则的 WalkBuilder，从 dir 开始遍历，会读取 dir 的上级目录中的 .gitignore
+  pub fn walk_builder(
+    &self,
+    dir: &Path,
+  ) -> ignore::WalkBuilder {
+    let mut builder = ignore::WalkBuilder::new(dir,);
+    builder.hidden(true,);
+    if self.exclude.is_some() {
+      let filter = self.clone();
+      builder.filter_entry(move |e| !filter.is_excluded(e.path(),),);
+    }
+    builder
+  }
+
+  /// 读取文件夹中的所有 文件/文件夹，并标记被过滤的项
+  pub fn read_dir(
+    &self,
+    dir: &Path,
+  ) -> std::io::Result<Vec<DirEntry,>,> {
+    let rd = fs::read_dir(dir,)?;
+    let visible = self
+      .walk_builder(dir,)
+      .max_depth(Some(1,),)
+      .build()
+      .filter_map(|v| v.ok(),)
+      .filter(|v| v.depth() == 1,)
+      .map(|v| v.into_path(),)
+      .collect::<HashSet<_,>>();
+    let entries = rd
+      .filter_map(|v| v.ok(),)
+      .filter_map(|v| {
+        let ft = v.file_type().ok()?;
+        let path = v.path();
+        (ft.is_dir() || ft.is_file()).then(|| DirEntry {
+          filtered: !visible.contains(&path,),
+          path,
+          is_dir: ft.is_dir(),
+        },)
+      },)
+      .collect();
+    Ok(entries,)
+  }
+}
+
+/// 不含 `/` 的 glob（如 `target`、`*.rs`）可匹配任意层级，匹配文件夹时也匹配其中的所有文件
+pub fn build_glob_set<'a,>(
+  patterns: impl IntoIterator<Item = &'a str,>,
+) -> Result<Option<GlobSet,>, String,> {
+  let patterns = patterns
+    .into_iter()
+    .map(|v| v.trim().trim_matches('/',),)
+    .filter(|v| !v.is_empty(),)
+    .collect::<Vec<&str,>>();
+  if patterns.is_empty() {
+    return Ok(None,);
+  }
+
+  let mut builder = GlobSetBuilder::new();
+  for p in patterns {
+    let globs = if p.contains('/',) {
+      vec![p.to_string(), format!("{p}/**")]
+    } else {
+      vec![
+        p.to_string(),
+        format!("**/{p}"),
+        format!("{p}/**"),
+        format!("**/{p}/**"),
+      ]
+    };
+    for g in globs {
+      builder.add(Glob::new(&g,).map_err(|e| format!("无效的 glob => {e}"),)?,);
+    }
+  }
+  builder
+    .build()
+    .map(Some,)
+    .map_err(|e| format!("无效的 glob => {e}"),)
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs;
+
+  use super::{DirEntry, PathFilter};
+  use crate::test_util::TempDir;
+
+  #[test]
+  fn test_read_dir() {
+    let root = TempDir::new("path_filter",);
+    fs::create_dir_all(root.join(".git",),).unwrap();
+    fs::create_dir_all(root.join("target/debug",),).unwrap();
+    fs::create_dir_all(root.join("docs/build",),).unwrap();
+    fs::write(root.join(".gitignore",), "/target\n*.log\n",).unwrap();
+    fs::write(root.join("a.log",), "",).unwrap();
+    fs::write(root.join("main.rs",), "",).unwrap();
+    fs::write(root.join("docs/index.md",), "",).unwrap();
+
+    let filter = PathFilter::new(&root, ["docs/build",],).unwrap();
+    let mut entries = filter.read_dir(&root,).unwrap();
+    entries.sort_by(|a, b| a.path.cmp(&b.path,),);
+    let entries = entries
+      .iter()
+      .map(|v| {
+        (
+          v.path.strip_prefix(&root,).unwrap().to_str().unwrap(),
+          v.filtered,
+        )
+      },)
+      .collect::<Vec<_,>>();
+    assert_eq!(
+      entries,
+      vec![
+        (".git", true),
+        (".gitignore", true),
+        ("a.log", true),
+        ("docs", false),
+        ("main.rs", false),
+        ("target", true),
+      ]
+    );
+
+    // 上级目录中的规则同样生效
+    assert_eq!(filter.read_dir(&root.join("docs")).unwrap().len(), 2);
+    assert!(filter
+      .read_dir(&root.join("docs"),)
+      .unwrap()
+      .contains(&DirEntry {
+        path: root.join("docs/build"),
+        is_dir: true,
+        filtered: true,
+      }));
+    assert!(PathFilter::new(&root, ["a[",],).is_err());
+  }
+}
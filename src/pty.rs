@@ -0,0 +1,245 @@
+use std::{
+  path::Path,
+  process::Command,
+  sync::mpsc::{Receiver, Sender, TryRecvError},
+};
+
+use eframe::egui;
+
+/// 伪终端，子进程的输入输出连接到伪终端的从设备
+pub struct Pty {
+  #[cfg(target_os = "linux")]
+  master: std::fs::File,
+  child: std::process::Child,
+  rx: Receiver<Vec<u8,>,>,
+  /// 写入线程，子进程不读取输入时写入会阻塞，不能在 UI 线程中写入
+  writer: Sender<Vec<u8,>,>,
+}
+
+/// 用户的 shell
+pub fn default_shell() -> Command {
+  let shell = std::env::var("SHELL",).unwrap_or_else(|_| "/bin/sh".to_string(),);
+  Command::new(shell,)
+}
+
+impl Pty {
+  /// 在伪终端中启动 cmd，读取到输出时重绘 ctx
+  #[cfg(target_os = "linux")]
+  pub fn spawn(
+    mut cmd: Command,
+    cwd: Option<&Path,>,
+    (rows, cols,): (usize, usize,),
+    ctx: egui::Context,
+  ) -> std::io::Result<Self,> {
+    use std::{
+      ffi::CStr,
+      io::{Read, Write},
+      os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, FromRawFd},
+        process::CommandExt,
+      },
+    };
+
+    // 打开主设备
+    let master = unsafe {
+      let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,);
+      if fd < 0 {
+        return Err(std::io::Error::last_os_error(),);
+      }
+      std::fs::File::from_raw_fd(fd,)
+    };
+    let mut name = [0 as libc::c_char; 128];
+    unsafe {
+      let fd = master.as_raw_fd();
+      if libc::grantpt(fd,) != 0
+        || libc::unlockpt(fd,) != 0
+        || libc::ptsname_r(fd, name.as_mut_ptr(), name.len(),) != 0
+      {
+        return Err(std::io::Error::last_os_error(),);
+      }
+    }
+    // 打开从设备
+    let slave_path = unsafe { CStr::from_ptr(name.as_ptr(),) }
+      .to_string_lossy()
+      .into_owned();
+    let slave = std::fs::OpenOptions::new()
+      .read(true,)
+      .write(true,)
+      .custom_flags(libc::O_NOCTTY,)
+      .open(slave_path,)?;
+    set_size(&master, rows, cols,)?;
+
+    cmd
+      .stdin(slave.try_clone()?,)
+      .stdout(slave.try_clone()?,)
+      .stderr(slave,)
+      .env("TERM", "xterm-256color",)
+      .env("COLORTERM", "truecolor",);
+    if let Some(cwd,) = cwd {
+      cmd.current_dir(cwd,);
+    }
+    // 子进程成为新会话的首进程，从设备作为控制终端
+    unsafe {
+      cmd.pre_exec(|| {
+        if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0,) < 0 {
+          return Err(std::io::Error::last_os_error(),);
+        }
+        Ok((),)
+      },);
+    }
+    let child = cmd.spawn()?;
+
+    // 后台读取输出，子进程退出后读取失败，channel 随之关闭
+    let mut reader = master.try_clone()?;
+    let (tx, rx,) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+      let mut buf = [0_u8; 8192];
+      loop {
+        match reader.read(&mut buf,) {
+          Ok(0,) | Err(_,) => break,
+          Ok(n,) => {
+            if tx.send(buf[..n].to_vec(),).is_err() {
+              break;
+            }
+            ctx.request_repaint();
+          }
+        }
+      }
+      ctx.request_repaint();
+    },);
+
+    // 后台按顺序写入输入，Pty 被 drop 后 channel 关闭，线程结束
+    let mut writer = master.try_clone()?;
+    let (writer_tx, writer_rx,) = std::sync::mpsc::channel::<Vec<u8,>,>();
+    std::thread::spawn(move || {
+      for bytes in writer_rx {
+        if let Err(e,) = writer.write_all(&bytes,) {
+          tracing::error!("写入终端失败 => {e}");
+          break;
+        }
+      }
+    },);
+
+    Ok(Self {
+      master,
+      child,
+      rx,
+      writer: writer_tx,
+    },)
+  }
+
+  #[cfg(not(target_os = "linux"))]
+  pub fn spawn(
+    _cmd: Command,
+    _cwd: Option<&Path,>,
+    _size: (usize, usize,),
+    _ctx: egui::Context,
+  ) -> std::io::Result<Self,> {
+    Err(std::io::Error::new(
+      std::io::ErrorKind::Unsupported,
+      "当前平台不支持终端",
+    ),)
+  }
+
+  /// 读取已输出的内容，子进程已退出且输出已读完时返回 None
+  pub fn read(&mut self,) -> Option<Vec<u8,>,> {
+    let mut output = vec![];
+    loop {
+      match self.rx.try_recv() {
+        Ok(bytes,) => output.extend(bytes,),
+        Err(TryRecvError::Empty,) => return Some(output,),
+        Err(TryRecvError::Disconnected,) if output.is_empty() => return None,
+        Err(TryRecvError::Disconnected,) => return Some(output,),
+      }
+    }
+  }
+
+  /// 写入子进程的输入，在写入线程中执行，不会阻塞
+  pub fn write(
+    &mut self,
+    bytes: &[u8],
+  ) {
+    let _ = self.writer.send(bytes.to_vec(),);
+  }
+
+  /// 调整终端大小，子进程收到 SIGWINCH
+  pub fn resize(
+    &mut self,
+    rows: usize,
+    cols: usize,
+  ) {
+    #[cfg(target_os = "linux")]
+    if let Err(e,) = set_size(&self.master, rows, cols,) {
+      tracing::error!("调整终端大小失败 => {e}");
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (rows, cols,);
+  }
+}
+
+impl Drop for Pty {
+  fn drop(&mut self,) {
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn set_size(
+  master: &std::fs::File,
+  rows: usize,
+  cols: usize,
+) -> std::io::Result<(),> {
+  use std::os::unix::io::AsRawFd;
+
+  let size = libc::winsize {
+    ws_row: rows as u16,
+    ws_col: cols as u16,
+    ws_xpixel: 0,
+    ws_ypixel: 0,
+  };
+  if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size,) } < 0 {
+    return Err(std::io::Error::last_os_error(),);
+  }
+  Ok((),)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+  use std::{
+    process::Command,
+    time::{Duration, Instant},
+  };
+
+  use super::Pty;
+
+  #[test]
+  fn test_pty() {
+    let mut cmd = Command::new("sh",);
+    cmd.args(["-c", "stty size; read line; echo \"got $line\"",],);
+    let mut pty = Pty::spawn(cmd, None, (24, 100,), Default::default(),).unwrap();
+    pty.write(b"hello\r",);
+
+    let mut output = vec![];
+    let start = Instant::now();
+    while let Some(bytes,) = pty.read() {
+      output.extend(bytes,);
+      assert!(start.elapsed() < Duration::from_secs(10));
+      std::thread::sleep(Duration::from_millis(10,),);
+    }
+    let output = String::from_utf8_lossy(&output,);
+    assert!(output.contains("24 100"), "{output}");
+    assert!(output.contains("got hello"), "{output}");
+  }
+  #[test]
+  fn test_pty_write_not_blocking() {
+    // 子进程不读取输入，写入超过缓冲区大小的内容也不阻塞
+    let mut cmd = Command::new("sh",);
+    cmd.args(["-c", "sleep 2",],);
+    let mut pty = Pty::spawn(cmd, None, (24, 100,), Default::default(),).unwrap();
+    let start = Instant::now();
+    pty.write(&[b'a'; 1 << 20],);
+    assert!(start.elapsed() < Duration::from_secs(1));
+  }
+}
@@ -0,0 +1,546 @@
+use std::{
+  fs,
+  ops::Range,
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicBool, Ordering},
+};
+
+use globset::GlobSet;
+
+use crate::{
+  path_filter::{build_glob_set, PathFilter},
+  util,
+};
+
+/// 查找选项
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash,)]
+pub struct SearchOptions {
+  pub case_sensitive: bool,
+  pub whole_word: bool,
+  pub regex: bool,
+}
+
+/// 由 查找内容 + 查找选项 编译得到的匹配器
+#[derive(Debug, Clone,)]
+pub struct Matcher {
+  regex: regex::Regex,
+  options: SearchOptions,
+}
+
+impl Matcher {
+  pub fn new(
+    query: &str,
+    options: SearchOptions,
+  ) -> Result<Self, String,> {
+    if query.is_empty() {
+      return Err("查找内容为空".into(),);
+    }
+
+    let pattern = if options.regex {
+      query.to_string()
+    } else {
+      regex::escape(query,)
+    };
+    let pattern = if options.whole_word {
+      format!(r"\b(?:{pattern})\b")
+    } else {
+      pattern
+    };
+
+    let regex = regex::RegexBuilder::new(&pattern,)
+      .case_insensitive(!options.case_sensitive,)
+      .multi_line(true,)
+      .build()
+      .map_err(|e| format!("无效的正则表达式 => {e}"),)?;
+
+    Ok(Self {
+      regex,
+      options,
+    },)
+  }
+
+  /// 查找所有匹配项，返回 byte_range 列表（忽略空匹配）
+  pub fn find_all(
+    &self,
+    text: &str,
+  ) -> Vec<Range<usize,>,> {
+    self
+      .regex
+      .find_iter(text,)
+      .filter(|m| !m.is_empty(),)
+      .map(|m| m.range(),)
+      .collect()
+  }
+
+  /// 计算 range 处匹配项的替换文本
+  ///
+  /// 正则模式下支持 `$1` `${name}` 引用捕获组；range 不是一个匹配项时返回 None
+  pub fn expand_at(
+    &self,
+    text: &str,
+    range: Range<usize,>,
+    replacement: &str,
+  ) -> Option<String,> {
+    if range.end > text.len() || !text.is_char_boundary(range.start,) {
+      return None;
+    }
+    let caps = self.regex.captures_at(text, range.start,)?;
+    if caps.get(0,)?.range() != range {
+      return None;
+    }
+    let mut dst = String::new();
+    self.expand(&caps, replacement, &mut dst,);
+    Some(dst,)
+  }
+
+  /// 替换所有匹配项，返回 (替换后的文本, 替换数量)
+  pub fn replace_all(
+    &self,
+    text: &str,
+    replacement: &str,
+  ) -> (String, usize,) {
+    let mut ret = String::with_capacity(text.len(),);
+    let mut last = 0;
+    let mut count = 0;
+    for caps in self.regex.captures_iter(text,) {
+      let m = caps.get(0,).unwrap();
+      if m.is_empty() {
+        continue;
+      }
+      ret.push_str(&text[last..m.start()],);
+      self.expand(&caps, replacement, &mut ret,);
+      last = m.end();
+      count += 1;
+    }
+    ret.push_str(&text[last..],);
+    (ret, count,)
+  }
+
+  fn expand(
+    &self,
+    caps: &regex::Captures,
+    replacement: &str,
+    dst: &mut String,
+  ) {
+    if self.options.regex {
+      caps.expand(replacement, dst,);
+    } else {
+      dst.push_str(replacement,);
+    }
+  }
+}
+
+/// 根据 byte 位置查找 下一个/上一个 匹配项的索引，到达末尾/开头时回绕
+///
+/// - forward: 第一个 start >= from 的匹配项
+/// - backward: 最后一个 end <= from 的匹配项
+pub fn next_match_index(
+  matches: &[Range<usize,>],
+  from: usize,
+  forward: bool,
+) -> Option<usize,> {
+  if matches.is_empty() {
+    return None;
+  }
+  if forward {
+    let i = matches.partition_point(|m| m.start < from,);
+    Some(if i == matches.len() { 0 } else { i },)
+  } else {
+    let i = matches.partition_point(|m| m.end <= from,);
+    Some(if i == 0 { matches.len() - 1 } else { i - 1 },)
+  }
+}
+
+// ------------------------------------ 文件夹内查找
+
+/// 单个文件超过该大小时不查找
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// 文件过滤：包含/排除 glob，多个 glob 之间以 `,` 分隔
+///
+/// glob 匹配相对于根目录的路径，不含 `/` 的 glob（如 `target`、`*.rs`）可匹配任意层级
+#[derive(Debug, Clone,)]
+pub struct FileFilter {
+  include: Option<GlobSet,>,
+  exclude: Option<GlobSet,>,
+}
+
+impl FileFilter {
+  pub fn new(
+    include: &str,
+    exclude: &str,
+  ) -> Result<Self, String,> {
+    Ok(Self {
+      include: build_glob_set(include.split(',',),)?,
+      exclude: build_glob_set(exclude.split(',',),)?,
+    },)
+  }
+
+  /// 目录是否被排除
+  pub fn is_excluded(
+    &self,
+    relative_path: &Path,
+  ) -> bool {
+    self
+      .exclude
+      .as_ref()
+      .map(|v| v.is_match(relative_path,),)
+      .unwrap_or(false,)
+  }
+
+  /// 文件是否需要查找
+  pub fn is_match(
+    &self,
+    relative_path: &Path,
+  ) -> bool {
+    !self.is_excluded(relative_path,)
+      && self
+        .include
+        .as_ref()
+        .map(|v| v.is_match(relative_path,),)
+        .unwrap_or(true,)
+  }
+}
+
+/// 某一行中的匹配项
+#[derive(Debug, Clone, PartialEq, Eq,)]
+pub struct LineMatch {
+  /// 行号，从 1 开始
+  pub line_number: usize,
+  /// 行内容（不含换行符）
+  pub line: String,
+  /// 匹配项在行内的 byte_range
+  pub ranges: Vec<Range<usize,>,>,
+}
+
+/// 某个文件中的匹配项
+#[derive(Debug, Clone,)]
+pub struct FileMatches {
+  pub path: PathBuf,
+  pub lines: Vec<LineMatch,>,
+}
+
+impl FileMatches {
+  pub fn match_count(&self,) -> usize {
+    self.lines.iter().map(|v| v.ranges.len(),).sum()
+  }
+}
+
+/// 查找文本，按行汇总匹配项
+///
+/// 跨行的匹配项只显示在其起始行
+pub fn search_text(
+  matcher: &Matcher,
+  text: &str,
+) -> Vec<LineMatch,> {
+  let matches = matcher.find_all(text,);
+  let mut ret = vec![];
+  let mut mi = 0;
+  let mut line_start = 0;
+  for (line_index, line,) in text.split_inclusive('\n',).enumerate() {
+    if mi >= matches.len() {
+      break;
+    }
+    let line_end = line_start + line.len();
+    let line = line.trim_end_matches(['\r', '\n',],);
+    let mut ranges = vec![];
+    while mi < matches.len() && matches[mi].start < line_end {
+      let m = &matches[mi];
+      let start = m.start - line_start;
+      let end = (m.end - line_start).min(line.len(),);
+      if start < end {
+        ranges.push(start..end,);
+      }
+      mi += 1;
+    }
+    if !ranges.is_empty() {
+      ret.push(LineMatch {
+        line_number: line_index + 1,
+        line: line.to_string(),
+        ranges,
+      },);
+    }
+    line_start = line_end;
+  }
+  ret
+}
+
+/// 查找单个文件，跳过二进制文件及过大的文件
+pub fn search_file(
+  path: &Path,
+  matcher: &Matcher,
+) -> Option<FileMatches,> {
+  if fs::metadata(path,).ok()?.len() > MAX_FILE_SIZE {
+    return None;
+  }
+  let content_bytes = fs::read(path,).ok()?;
+  if util::is_binary(&content_bytes,) {
+    return None;
+  }
+  let (content, _,) = util::decode_text(&content_bytes,).ok()?;
+  let lines = search_text(matcher, &content,);
+  if lines.is_empty() {
+    None
+  } else {
+    Some(FileMatches {
+      path: path.to_owned(),
+      lines,
+    },)
+  }
+}
+
+/// 查找 root 下的所有文件，每找到一个包含匹配项的文件回调一次 on_file
+///
+/// 跳过被 path_filter 过滤（.gitignore 等）的 文件/文件夹。
+/// on_file 返回 false 或 cancel 被设置时停止查找，返回已查找的文件数
+pub fn search_dir(
+  root: &Path,
+  matcher: &Matcher,
+  filter: &FileFilter,
+  path_filter: &PathFilter,
+  cancel: &AtomicBool,
+  mut on_file: impl FnMut(FileMatches,) -> bool,
+) -> usize {
+  let mut searched = 0;
+  let mut dirs = vec![root.to_path_buf()];
+  while let Some(dir,) = dirs.pop() {
+    let Ok(mut entries,) = path_filter.read_dir(&dir,) else {
+      continue;
+    };
+    entries.retain(|v| !v.filtered,);
+    entries.sort_by(|a, b| a.path.cmp(&b.path,),);
+
+    let mut sub_dirs = vec![];
+    for entry in entries {
+      if cancel.load(Ordering::Relaxed,) {
+        return searched;
+      }
+      let path = entry.path;
+      let relative_path = path.strip_prefix(root,).unwrap_or(&path,);
+      if entry.is_dir {
+        if !filter.is_excluded(relative_path,) {
+          sub_dirs.push(path,);
+        }
+        continue;
+      }
+      if !filter.is_match(relative_path,) {
+        continue;
+      }
+      searched += 1;
+      if let Some(file_matches,) = search_file(&path, matcher,) {
+        if !on_file(file_matches,) {
+          return searched;
+        }
+      }
+    }
+    // 保证子目录按顺序出栈
+    sub_dirs.reverse();
+    dirs.append(&mut sub_dirs,);
+  }
+  searched
+}
+
+/// 替换文件中的所有匹配项，保持文件原有的编码和换行符，返回替换数量
+pub fn replace_in_file(
+  path: &Path,
+  matcher: &Matcher,
+  replacement: &str,
+) -> std::io::Result<usize,> {
+  let (content, encoding,) = util::read_text_file(path,)?;
+  let line_ending = util::guess_line_ending(&content,);
+  let replacement = line_ending.normalize(replacement,);
+  let (content, count,) = matcher.replace_all(&content, &replacement,);
+  if count > 0 {
+    let (content_bytes, _, _,) = encoding.encode(&content,);
+    fs::write(path, content_bytes,)?;
+  }
+  Ok(count,)
+}
+
+#[cfg(test)]
+mod test {
+  use std::{path::Path, sync::atomic::AtomicBool};
+
+  use super::{
+    next_match_index, replace_in_file, search_dir, search_text, FileFilter, LineMatch, Matcher,
+    PathFilter, SearchOptions,
+  };
+  use crate::test_util::TempDir;
+
+  fn opts(
+    case_sensitive: bool,
+    whole_word: bool,
+    regex: bool,
+  ) -> SearchOptions {
+    SearchOptions {
+      case_sensitive,
+      whole_word,
+      regex,
+    }
+  }
+
+  #[test]
+  fn test_find_all() {
+    let text = "foo Foo food foo.bar";
+
+    let m = Matcher::new("foo", opts(false, false, false,),).unwrap();
+    assert_eq!(m.find_all(text), vec![0..3, 4..7, 8..11, 13..16]);
+
+    let m = Matcher::new("foo", opts(true, false, false,),).unwrap();
+    assert_eq!(m.find_all(text), vec![0..3, 8..11, 13..16]);
+
+    let m = Matcher::new("foo", opts(false, true, false,),).unwrap();
+    assert_eq!(m.find_all(text), vec![0..3, 4..7, 13..16]);
+
+    // 非正则模式下 `.` 按字面匹配
+    let m = Matcher::new("o.b", opts(false, false, false,),).unwrap();
+    assert_eq!(m.find_all(text), vec![15..18]);
+
+    let m = Matcher::new(r"f\w+d", opts(true, false, true,),).unwrap();
+    assert_eq!(m.find_all(text), vec![8..12]);
+  }
+
+  #[test]
+  fn test_invalid_query() {
+    assert!(Matcher::new("", SearchOptions::default(),).is_err());
+    assert!(Matcher::new("(", opts(false, false, true,),).is_err());
+    assert!(Matcher::new("(", opts(false, false, false,),).is_ok());
+  }
+
+  #[test]
+  fn test_replace_all() {
+    let m = Matcher::new("foo", opts(false, true, false,),).unwrap();
+    let (text, count,) = m.replace_all("foo Foo food", "$1bar",);
+    assert_eq!(text, "$1bar $1bar food");
+    assert_eq!(count, 2);
+
+    let m = Matcher::new(r"(\w+)=(\w+)", opts(true, false, true,),).unwrap();
+    let (text, count,) = m.replace_all("a=1, b=2", "$2=$1",);
+    assert_eq!(text, "1=a, 2=b");
+    assert_eq!(count, 2);
+
+    // 空匹配不替换
+    let m = Matcher::new("x*", opts(true, false, true,),).unwrap();
+    let (text, count,) = m.replace_all("abxxc", "-",);
+    assert_eq!(text, "ab-c");
+    assert_eq!(count, 1);
+  }
+
+  #[test]
+  fn test_expand_at() {
+    let text = "let 变量 = 1;\nlet y = 2;";
+    let m = Matcher::new(r"let (\S+)", opts(true, false, true,),).unwrap();
+    let matches = m.find_all(text,);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(
+      m.expand_at(text, matches[0].clone(), "const $1").as_deref(),
+      Some("const 变量")
+    );
+    assert_eq!(
+      m.expand_at(text, matches[1].clone(), "const $1").as_deref(),
+      Some("const y")
+    );
+    // 不是匹配项
+    assert_eq!(m.expand_at(text, 1..3, "x"), None);
+  }
+
+  #[test]
+  fn test_next_match_index() {
+    let matches = vec![2..4, 6..8, 10..12];
+    assert_eq!(next_match_index(&matches, 0, true), Some(0));
+    assert_eq!(next_match_index(&matches, 4, true), Some(1));
+    assert_eq!(next_match_index(&matches, 11, true), Some(0));
+    assert_eq!(next_match_index(&matches, 10, false), Some(1));
+    assert_eq!(next_match_index(&matches, 2, false), Some(2));
+    assert_eq!(next_match_index(&[], 2, false), None);
+  }
+
+  #[test]
+  #[allow(clippy::single_range_in_vec_init)]
+  fn test_search_text() {
+    let m = Matcher::new("ab", SearchOptions::default(),).unwrap();
+    let lines = search_text(&m, "xab ab\r\n\nno\nab",);
+    assert_eq!(
+      lines,
+      vec![
+        LineMatch {
+          line_number: 1,
+          line: "xab ab".into(),
+          ranges: vec![1..3, 4..6],
+        },
+        LineMatch {
+          line_number: 4,
+          line: "ab".into(),
+          ranges: vec![0..2],
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_file_filter() {
+    let filter = FileFilter::new("*.rs, docs/*.md", "target, .git",).unwrap();
+    assert!(filter.is_match(Path::new("src/main.rs")));
+    assert!(filter.is_match(Path::new("main.rs")));
+    assert!(filter.is_match(Path::new("docs/a.md")));
+    assert!(!filter.is_match(Path::new("README.md")));
+    assert!(!filter.is_match(Path::new("target/debug/build.rs")));
+    assert!(filter.is_excluded(Path::new("a/target")));
+    assert!(filter.is_excluded(Path::new(".git")));
+
+    let filter = FileFilter::new("", "",).unwrap();
+    assert!(filter.is_match(Path::new("a/b/c")));
+
+    assert!(FileFilter::new("a[", "",).is_err());
+  }
+
+  #[test]
+  fn test_search_dir_and_replace_in_file() {
+    let root = TempDir::new("search",);
+    std::fs::create_dir_all(root.join("src",),).unwrap();
+    std::fs::create_dir_all(root.join("target",),).unwrap();
+    std::fs::write(root.join("a.txt",), "hello\r\nworld\r\n",).unwrap();
+    std::fs::write(root.join("src/b.txt",), "hello hello\n",).unwrap();
+    std::fs::write(root.join("target/c.txt",), "hello\n",).unwrap();
+    std::fs::write(root.join("d.bin",), b"hello\0",).unwrap();
+
+    let m = Matcher::new("hello", SearchOptions::default(),).unwrap();
+    let filter = FileFilter::new("", "target",).unwrap();
+    let path_filter = PathFilter::new(&root, [],).unwrap();
+    let mut found = vec![];
+    let searched = search_dir(
+      &root,
+      &m,
+      &filter,
+      &path_filter,
+      &AtomicBool::new(false,),
+      |v| {
+        found.push((
+          v.path.strip_prefix(&root,).unwrap().to_owned(),
+          v.match_count(),
+        ),);
+        true
+      },
+    );
+    assert_eq!(searched, 3);
+    assert_eq!(found, vec![("a.txt".into(), 1), ("src/b.txt".into(), 2)]);
+
+    // 替换文本中的换行符跟随文件的 line_ending
+    assert_eq!(
+      replace_in_file(&root.join("a.txt"), &m, "hi\nthere").unwrap(),
+      1
+    );
+    assert_eq!(
+      std::fs::read_to_string(root.join("a.txt")).unwrap(),
+      "hi\r\nthere\r\nworld\r\n"
+    );
+    assert_eq!(
+      replace_in_file(&root.join("src/b.txt"), &m, "hi").unwrap(),
+      2
+    );
+    assert_eq!(
+      std::fs::read_to_string(root.join("src/b.txt")).unwrap(),
+      "hi hi\n"
+    );
+  }
+}
@@ -0,0 +1,186 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{component::tool_bar::ToolId, config, diagnostic, hl, lsp, style};
+
+/// 设置文件名，位于配置目录
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+/// 检查设置变化并保存的间隔，进程意外退出时也只丢失最近的修改
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(5,);
+
+/// 重启后需要恢复的设置：界面偏好、布局、会话
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize,)]
+#[serde(default)]
+pub struct Settings {
+  pub ui: UiSettings,
+  pub layout: LayoutSettings,
+  pub problems: ProblemsSettings,
+  pub lsp: LspSettings,
+  pub session: Session,
+}
+
+/// 界面偏好
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize,)]
+#[serde(default)]
+pub struct UiSettings {
+  /// 缩放比例，None 表示使用系统默认值
+  pub zoom: Option<f32,>,
+  pub transparency: f32,
+  pub dark_mode: bool,
+  /// 黑夜模式编辑器主题
+  pub dark_theme: String,
+  /// 白天模式编辑器主题
+  pub light_theme: String,
+}
+
+impl Default for UiSettings {
+  fn default() -> Self {
+    Self {
+      zoom: None,
+      transparency: style::DEFAULT_TRANSPARENCY,
+      dark_mode: style::DEFAULT_THEME == eframe::Theme::Dark,
+      dark_theme: hl::DEFAULT_DARK_THEME.to_string(),
+      light_theme: hl::DEFAULT_LIGHT_THEME.to_string(),
+    }
+  }
+}
+
+/// 布局
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize,)]
+#[serde(default)]
+pub struct LayoutSettings {
+  pub vertical_tab_bar: bool,
+  pub show_tool_bar: bool,
+  pub show_status_bar: bool,
+  pub show_terminal: bool,
+  /// 探索面板宽
+  pub explorer_width: f32,
+  /// 工具栏中选中的工具
+  pub tool: Option<ToolId,>,
+}
+
+impl Default for LayoutSettings {
+  fn default() -> Self {
+    Self {
+      vertical_tab_bar: false,
+      show_tool_bar: true,
+      show_status_bar: true,
+      show_terminal: false,
+      explorer_width: style::EXPLORER_MIN_WIDTH,
+      tool: None,
+    }
+  }
+}
+
+/// 问题面板
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize,)]
+#[serde(default)]
+pub struct ProblemsSettings {
+  /// 检查命令，输出 cargo 的 JSON 消息
+  pub check_command: String,
+}
+
+impl Default for ProblemsSettings {
+  fn default() -> Self {
+    Self {
+      check_command: diagnostic::DEFAULT_CHECK_COMMAND.to_string(),
+    }
+  }
+}
+
+/// 语言服务器
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize,)]
+#[serde(default)]
+pub struct LspSettings {
+  /// 语言名称 => 语言服务器的启动命令
+  pub servers: BTreeMap<String, lsp::ServerConfig,>,
+}
+
+impl Default for LspSettings {
+  fn default() -> Self {
+    Self {
+      servers: lsp::default_servers(),
+    }
+  }
+}
+
+/// 会话：打开的文件夹和文件
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize,)]
+#[serde(default)]
+pub struct Session {
+  pub open_dir: Option<PathBuf,>,
+  pub open_files: Vec<FileSession,>,
+  /// 当前文件在 open_files 中的索引
+  pub current_index: usize,
+}
+
+/// 打开的文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize,)]
+pub struct FileSession {
+  pub path: PathBuf,
+  /// 选区 (起点, 光标) 的 char 索引
+  #[serde(default)]
+  pub cursor: Option<(usize, usize,),>,
+}
+
+impl Settings {
+  /// 读取配置目录中的设置，文件不存在时返回默认值
+  pub fn load() -> Result<Self, String,> {
+    config::load_toml(SETTINGS_FILE_NAME,)
+  }
+
+  /// 保存到配置目录
+  pub fn save(&self,) -> Result<(), String,> {
+    config::save_toml(SETTINGS_FILE_NAME, self,)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use super::{FileSession, Settings};
+  use crate::{component::tool_bar::ToolId, lsp};
+
+  #[test]
+  fn test_settings_toml() {
+    let mut settings = Settings::default();
+    settings.ui.zoom = Some(1.5,);
+    settings.ui.dark_mode = false;
+    settings.layout.vertical_tab_bar = true;
+    settings.layout.explorer_width = 320.;
+    settings.layout.tool = Some(ToolId::Explorer,);
+    settings.problems.check_command = "cargo clippy --message-format=json".into();
+    settings.session.open_dir = Some(PathBuf::from("/tmp/project",),);
+    settings.session.open_files = vec![
+      FileSession {
+        path: PathBuf::from("/tmp/project/a.rs",),
+        cursor: Some((3, 10,),),
+      },
+      FileSession {
+        path: PathBuf::from("/tmp/project/b.rs",),
+        cursor: None,
+      },
+    ];
+    settings.session.current_index = 1;
+
+    let text = toml::to_string(&settings,).unwrap();
+    assert_eq!(toml::from_str::<Settings,>(&text).unwrap(), settings);
+
+    // 缺少的字段使用默认值
+    let settings = toml::from_str::<Settings,>(
+      r#"
+      [layout]
+      show_tool_bar = false
+      "#,
+    )
+    .unwrap();
+    assert!(!settings.layout.show_tool_bar);
+    assert!(settings.layout.show_status_bar);
+    assert_eq!(settings.ui, Default::default());
+    assert!(settings.session.open_files.is_empty());
+    assert_eq!(settings.lsp.servers, lsp::default_servers());
+  }
+}
@@ -8,6 +8,12 @@ pub const MAIN_Y: f32 = 100.; // 主窗口位置y
 pub const MENU_BAR_HEIGHT: f32 = 40.; //顶部菜单栏高
 
 pub const STATUS_BAR_HEIGHT: f32 = 50.; //底部状态栏高
+pub const LANGUAGE_POPUP_WIDTH: f32 = 220.; // 状态栏选择语言弹出框宽
+pub const LANGUAGE_POPUP_MAX_HEIGHT: f32 = 400.; // 状态栏选择语言弹出框最大高
+
+pub const THEME_COMBO_BOX_WIDTH: f32 = 220.; // 设置窗口编辑器主题下拉框宽
+pub const KEYBINDINGS_MAX_HEIGHT: f32 = 300.; // 设置窗口快捷键列表最大高
+pub const UNSAVED_FILES_MAX_HEIGHT: f32 = 300.; // 未保存文件对话框文件列表最大高
 
 pub const TOOL_BAR_WIDTH: f32 = 66.; //左侧工具栏宽
 pub const TOOL_BUTTON_SIZE: u32 = 36; // 左侧工具栏工具按钮大小
@@ -20,3 +26,21 @@ pub const TAB_BAR_HEIGHT: f32 = 50.; // tab bar 高
 
 pub const TERMINAL_PANEL_DEFAULT_HEIGHT: f32 = 200.; // 终端面板高
 pub const TERMINAL_PANEL_MAX_HEIGHT: f32 = 600.; // 终端面板最大高
+pub const TERMINAL_SCROLLBACK_LINES: usize = 10000; // 终端回滚缓冲最大行数
+pub const TERMINAL_PADDING: f32 = 4.; // 终端内容四周留白
+
+pub const LINE_NUMBER_BAR_PADDING: f32 = 8.; // 大文件编辑器行号栏左右留白
+pub const VIRTUAL_EDITOR_OVERSCAN_ROWS: usize = 8; // 大文件编辑器可见区域外额外布局的行数
+
+pub const FIND_BAR_INPUT_WIDTH: f32 = 240.; // 查找栏输入框宽
+pub const FIND_MATCH_COLOR: eframe::egui::Color32 =
+  eframe::egui::Color32::from_rgba_premultiplied(90, 70, 0, 90,); // 查找匹配项背景色
+pub const FIND_CURRENT_MATCH_COLOR: eframe::egui::Color32 =
+  eframe::egui::Color32::from_rgba_premultiplied(180, 120, 0, 160,); // 当前匹配项背景色
+
+pub const DIAGNOSTIC_ERROR_COLOR: eframe::egui::Color32 =
+  eframe::egui::Color32::from_rgb(230, 70, 70,); // 错误诊断颜色
+pub const DIAGNOSTIC_WARNING_COLOR: eframe::egui::Color32 =
+  eframe::egui::Color32::from_rgb(220, 170, 40,); // 警告诊断颜色
+pub const DIAGNOSTIC_INFO_COLOR: eframe::egui::Color32 =
+  eframe::egui::Color32::from_rgb(80, 150, 230,); // 提示诊断颜色
@@ -0,0 +1,416 @@
+use std::{
+  collections::BTreeMap,
+  io::Read,
+  path::{Path, PathBuf},
+  process::{Child, Command, ExitStatus, Stdio},
+  sync::mpsc::{Receiver, TryRecvError},
+  time::Duration,
+};
+
+use eframe::egui;
+use serde::Deserialize;
+
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// 任务定义文件，位于打开的文件夹中
+pub const TASKS_FILE: &str = ".egui_code/tasks.toml";
+
+/// 任务定义文件
+///
+/// ```toml
+/// [[tasks]]
+/// name = "build"
+/// command = "cargo"
+/// args = ["build"]
+/// # 相对于打开的文件夹，默认为打开的文件夹
+/// cwd = "crates/app"
+/// env = { RUST_BACKTRACE = "1" }
+/// # 从输出中匹配问题，命名分组 file, line 必须，column, severity, message 可选
+/// problem_matcher = '^(?P<file>[^:\s]+):(?P<line>\d+):(?P<column>\d+): (?P<severity>error|warning): (?P<message>.*)$'
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize,)]
+#[serde(default)]
+struct TasksFile {
+  tasks: Vec<TaskDef,>,
+}
+
+/// 任务
+#[derive(Debug, Clone, PartialEq, Deserialize,)]
+pub struct TaskDef {
+  pub name: String,
+  pub command: String,
+  #[serde(default)]
+  pub args: Vec<String,>,
+  #[serde(default)]
+  pub cwd: Option<PathBuf,>,
+  #[serde(default)]
+  pub env: BTreeMap<String, String,>,
+  #[serde(default)]
+  pub problem_matcher: Option<String,>,
+}
+
+impl TaskDef {
+  /// 命令行，用于显示
+  pub fn command_line(&self,) -> String {
+    std::iter::once(self.command.as_str(),)
+      .chain(self.args.iter().map(String::as_str,),)
+      .collect::<Vec<_,>>()
+      .join(" ",)
+  }
+
+  /// 工作目录，相对路径基于 root
+  pub fn cwd(
+    &self,
+    root: &Path,
+  ) -> PathBuf {
+    self
+      .cwd
+      .as_ref()
+      .map_or(root.to_path_buf(), |v| root.join(v,),)
+  }
+}
+
+/// 读取 root 中的任务定义，文件不存在时返回空列表
+pub fn load_tasks(root: &Path,) -> Result<Vec<TaskDef,>, String,> {
+  let path = root.join(TASKS_FILE,);
+  let text = match std::fs::read_to_string(&path,) {
+    Ok(v,) => v,
+    Err(e,) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![],),
+    Err(e,) => return Err(format!("读取 {} 失败: {e}", path.display()),),
+  };
+  let tasks = toml::from_str::<TasksFile,>(&text,)
+    .map_err(|e| format!("解析 {} 失败: {e}", path.display()),)?
+    .tasks;
+  for task in tasks.iter() {
+    if let Some(pattern,) = &task.problem_matcher {
+      ProblemMatcher::new(pattern,).map_err(|e| format!("任务 {}: {e}", task.name),)?;
+    }
+  }
+  Ok(tasks,)
+}
+
+/// 去掉输出中的 ANSI 控制序列
+pub fn strip_ansi(text: &str,) -> String {
+  static ANSI: once_cell::sync::Lazy<regex::Regex,> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[@-Z\\-_]",)
+      .unwrap()
+  },);
+  ANSI.replace_all(text, "",).into_owned()
+}
+
+/// 用正则表达式从任务输出中匹配问题
+#[derive(Debug, Clone,)]
+pub struct ProblemMatcher {
+  regex: regex::Regex,
+}
+
+impl ProblemMatcher {
+  pub fn new(pattern: &str,) -> Result<Self, String,> {
+    let regex = regex::RegexBuilder::new(pattern,)
+      .multi_line(true,)
+      .build()
+      .map_err(|e| format!("无效的 problem_matcher => {e}"),)?;
+    let names = regex.capture_names().flatten().collect::<Vec<_,>>();
+    if !names.contains(&"file",) || !names.contains(&"line",) {
+      return Err("problem_matcher 需要命名分组 file 和 line".to_string(),);
+    }
+    Ok(Self {
+      regex,
+    },)
+  }
+
+  /// 匹配输出中的问题，相对路径基于 cwd
+  pub fn find_all(
+    &self,
+    output: &str,
+    cwd: &Path,
+  ) -> Vec<Diagnostic,> {
+    let output = strip_ansi(output,);
+    self
+      .regex
+      .captures_iter(&output,)
+      .filter_map(|caps| {
+        let file = caps.name("file",)?.as_str().trim();
+        let line = caps.name("line",)?.as_str().parse::<usize>().ok()?;
+        let column = caps
+          .name("column",)
+          .and_then(|v| v.as_str().parse::<usize>().ok(),)
+          .unwrap_or(1,);
+        let severity = match caps.name("severity",).map(|v| v.as_str().to_lowercase(),) {
+          Some(v,) if v.starts_with("warn",) => Severity::Warning,
+          Some(v,) if v.starts_with("note",) || v.starts_with("info",) => Severity::Note,
+          Some(v,) if v.starts_with("help",) => Severity::Help,
+          _ => Severity::Error,
+        };
+        let position = (line.saturating_sub(1,), column.saturating_sub(1,),);
+        Some(Diagnostic {
+          path: cwd.join(file,),
+          severity,
+          message: caps
+            .name("message",)
+            .map_or("", |v| v.as_str(),)
+            .trim()
+            .to_string(),
+          code: None,
+          start: position,
+          end: position,
+        },)
+      },)
+      .collect()
+  }
+}
+
+/// 输出读完后检查进程是否退出的间隔
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100,);
+
+/// 运行中的任务，stdout 和 stderr 合并读取
+pub struct TaskRun {
+  child: Child,
+  rx: Receiver<Vec<u8,>,>,
+  status: Option<ExitStatus,>,
+  ctx: egui::Context,
+}
+
+impl TaskRun {
+  /// 在 root 中启动任务，读取到输出时重绘 ctx
+  pub fn spawn(
+    task: &TaskDef,
+    root: &Path,
+    ctx: egui::Context,
+  ) -> std::io::Result<Self,> {
+    let mut cmd = Command::new(&task.command,);
+    cmd
+      .args(&task.args,)
+      .current_dir(task.cwd(root,),)
+      // 输出不是终端，让 cargo 等保留颜色
+      .env("CARGO_TERM_COLOR", "always",)
+      .envs(&task.env,)
+      .stdin(Stdio::null(),)
+      .stdout(Stdio::piped(),)
+      .stderr(Stdio::piped(),);
+    // 单独的进程组，取消时结束所有子进程
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0,);
+    let mut child = cmd.spawn()?;
+
+    let (tx, rx,) = std::sync::mpsc::channel();
+    let readers: [Box<dyn Read + Send,>; 2] = [
+      Box::new(child.stdout.take().unwrap(),),
+      Box::new(child.stderr.take().unwrap(),),
+    ];
+    for mut reader in readers {
+      let tx = tx.clone();
+      let ctx = ctx.clone();
+      std::thread::spawn(move || {
+        let mut buf = [0_u8; 8192];
+        loop {
+          match reader.read(&mut buf,) {
+            Ok(0,) | Err(_,) => break,
+            Ok(n,) => {
+              if tx.send(buf[..n].to_vec(),).is_err() {
+                break;
+              }
+              ctx.request_repaint();
+            }
+          }
+        }
+        ctx.request_repaint();
+      },);
+    }
+
+    Ok(Self {
+      child,
+      rx,
+      status: None,
+      ctx,
+    },)
+  }
+
+  /// 读取已输出的内容，输出读完且进程退出后返回退出状态，返回 (输出, 退出状态)
+  pub fn read(&mut self,) -> (Vec<u8,>, Option<ExitStatus,>,) {
+    let mut output = vec![];
+    loop {
+      match self.rx.try_recv() {
+        Ok(bytes,) => output.extend(bytes,),
+        Err(TryRecvError::Empty,) => break,
+        Err(TryRecvError::Disconnected,) => {
+          // 关闭了输出的进程可能仍在运行，不阻塞等待，之后再检查
+          if self.status.is_none() {
+            match self.child.try_wait() {
+              Ok(Some(status,),) => self.status = Some(status,),
+              Ok(None,) => self.ctx.request_repaint_after(EXIT_POLL_INTERVAL,),
+              Err(e,) => tracing::warn!("等待任务退出失败 => {e}"),
+            }
+          }
+          break;
+        }
+      }
+    }
+    (output, self.status,)
+  }
+
+  /// 结束任务及其子进程
+  pub fn cancel(&mut self,) {
+    if self.status.is_some() {
+      return;
+    }
+    #[cfg(target_os = "linux")]
+    unsafe {
+      libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL,);
+    }
+    let _ = self.child.kill();
+  }
+}
+
+impl Drop for TaskRun {
+  fn drop(&mut self,) {
+    self.cancel();
+    let _ = self.child.wait();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{
+    path::Path,
+    time::{Duration, Instant},
+  };
+
+  use super::{load_tasks, strip_ansi, ProblemMatcher, TaskDef, TaskRun};
+  use crate::{diagnostic::Severity, test_util::TempDir};
+
+  #[test]
+  fn test_load_tasks() {
+    let root = TempDir::new("tasks",);
+    assert_eq!(load_tasks(&root), Ok(vec![]));
+
+    std::fs::create_dir_all(root.join(".egui_code",),).unwrap();
+    std::fs::write(
+      root.join(super::TASKS_FILE,),
+      r#"
+      [[tasks]]
+      name = "build"
+      command = "cargo"
+      args = ["build", "--release"]
+      env = { RUST_BACKTRACE = "1" }
+
+      [[tasks]]
+      name = "lint"
+      command = "eslint"
+      cwd = "web"
+      problem_matcher = '^(?P<file>\S+):(?P<line>\d+)'
+      "#,
+    )
+    .unwrap();
+    let tasks = load_tasks(&root,).unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].command_line(), "cargo build --release");
+    assert_eq!(tasks[0].env.get("RUST_BACKTRACE").unwrap(), "1");
+    assert_eq!(tasks[0].cwd(&root), root.to_path_buf());
+    assert_eq!(tasks[1].cwd(&root), root.join("web"));
+
+    // problem_matcher 缺少命名分组
+    std::fs::write(
+      root.join(super::TASKS_FILE,),
+      r#"
+      [[tasks]]
+      name = "lint"
+      command = "eslint"
+      problem_matcher = '^(\S+):(\d+)'
+      "#,
+    )
+    .unwrap();
+    assert!(load_tasks(&root).unwrap_err().contains("lint"));
+  }
+
+  #[test]
+  fn test_problem_matcher() {
+    let matcher = ProblemMatcher::new(
+      r"^(?P<file>[^:\s]+):(?P<line>\d+):(?P<column>\d+): (?P<severity>\w+): (?P<message>.*)$",
+    )
+    .unwrap();
+    let output = "\x1b[1mbuilding\x1b[0m\nsrc/a.c:3:5: \x1b[31merror\x1b[0m: expected ';'\r\n/abs/b.h:10:1: warning: unused\nok\n";
+    let diagnostics = matcher.find_all(output, Path::new("/tmp/project",),);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].path, Path::new("/tmp/project/src/a.c"));
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].message, "expected ';'");
+    assert_eq!(diagnostics[0].start, (2, 4));
+    assert_eq!(diagnostics[1].path, Path::new("/abs/b.h"));
+    assert_eq!(diagnostics[1].severity, Severity::Warning);
+
+    assert!(ProblemMatcher::new(r"(?P<file>\S+)").is_err());
+    assert_eq!(
+      strip_ansi("\x1b[1;31merror\x1b[0m\x1b]0;title\x07!"),
+      "error!"
+    );
+  }
+
+  fn task(args: &[&str],) -> TaskDef {
+    TaskDef {
+      name: "test".into(),
+      command: "sh".into(),
+      args: args.iter().map(|v| v.to_string(),).collect(),
+      cwd: None,
+      env: [("GREETING".to_string(), "hello".to_string(),),].into(),
+      problem_matcher: None,
+    }
+  }
+
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn test_task_run() {
+    let root = std::env::temp_dir();
+    let mut run = TaskRun::spawn(
+      &task(&["-c", "echo $GREETING; echo oops >&2; exit 3",],),
+      &root,
+      Default::default(),
+    )
+    .unwrap();
+    let mut output = vec![];
+    let start = Instant::now();
+    let status = loop {
+      let (bytes, status,) = run.read();
+      output.extend(bytes,);
+      if let Some(status,) = status {
+        break status;
+      }
+      assert!(start.elapsed() < Duration::from_secs(10));
+      std::thread::sleep(Duration::from_millis(10,),);
+    };
+    let output = String::from_utf8_lossy(&output,);
+    assert!(output.contains("hello\n"), "{output}");
+    assert!(output.contains("oops\n"), "{output}");
+    assert_eq!(status.code(), Some(3));
+
+    // 取消时结束子进程
+    let mut run = TaskRun::spawn(
+      &task(&["-c", "sleep 30 & wait",],),
+      &root,
+      Default::default(),
+    )
+    .unwrap();
+    let start = Instant::now();
+    run.cancel();
+    while run.read().1.is_none() {
+      assert!(start.elapsed() < Duration::from_secs(10));
+      std::thread::sleep(Duration::from_millis(10,),);
+    }
+
+    // 关闭输出后仍在运行的进程不阻塞读取
+    let mut run = TaskRun::spawn(
+      &task(&["-c", "exec >&- 2>&-; sleep 1",],),
+      &root,
+      Default::default(),
+    )
+    .unwrap();
+    std::thread::sleep(Duration::from_millis(200,),);
+    let start = Instant::now();
+    assert_eq!(run.read().1, None);
+    assert!(start.elapsed() < Duration::from_millis(500));
+    while run.read().1.is_none() {
+      assert!(start.elapsed() < Duration::from_secs(10));
+      std::thread::sleep(Duration::from_millis(10,),);
+    }
+  }
+}
@@ -0,0 +1,796 @@
+use std::collections::VecDeque;
+
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Perform};
+
+/// 宽字符（如中文）占两格，第二格为占位
+pub const WIDE_CHAR_SPACER: char = '\0';
+
+/// 终端颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub enum Color {
+  #[default]
+  Default,
+  /// 256 色中的索引
+  Indexed(u8,),
+  Rgb(u8, u8, u8,),
+}
+
+/// 字符样式，由 SGR 设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default,)]
+pub struct CellStyle {
+  pub fg: Color,
+  pub bg: Color,
+  pub bold: bool,
+  pub italic: bool,
+  pub underline: bool,
+  pub inverse: bool,
+}
+
+/// 终端中的一格
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub struct Cell {
+  pub c: char,
+  pub style: CellStyle,
+}
+
+impl Cell {
+  /// 空白格，保留背景色
+  fn blank(style: CellStyle,) -> Self {
+    Self {
+      c: ' ',
+      style: CellStyle {
+        bg: style.bg,
+        ..Default::default()
+      },
+    }
+  }
+}
+
+/// 终端模拟器：解析 VT100/xterm 控制序列，维护屏幕内容和回滚缓冲
+pub struct Terminal {
+  parser: vte::Parser,
+  screen: Screen,
+}
+
+impl Terminal {
+  pub fn new(
+    rows: usize,
+    cols: usize,
+  ) -> Self {
+    Self {
+      parser: vte::Parser::new(),
+      screen: Screen::new(rows, cols,),
+    }
+  }
+
+  /// 处理子进程的输出
+  pub fn advance(
+    &mut self,
+    bytes: &[u8],
+  ) {
+    self.parser.advance(&mut self.screen, bytes,);
+  }
+
+  pub fn screen(&self,) -> &Screen {
+    &self.screen
+  }
+
+  pub fn resize(
+    &mut self,
+    rows: usize,
+    cols: usize,
+  ) {
+    self.screen.resize(rows, cols,);
+  }
+
+  /// 需要回复给子进程的内容，如光标位置报告
+  pub fn take_responses(&mut self,) -> Vec<u8,> {
+    std::mem::take(&mut self.screen.responses,)
+  }
+}
+
+/// 屏幕内容
+pub struct Screen {
+  rows: usize,
+  cols: usize,
+  lines: Vec<Vec<Cell,>,>,
+  /// 滚出屏幕顶部的行，备用屏幕不记录
+  scrollback: VecDeque<Vec<Cell,>,>,
+  /// 切换到备用屏幕时保存的主屏幕
+  main_lines: Option<Vec<Vec<Cell,>,>,>,
+  cursor: (usize, usize,),
+  saved_cursor: Option<((usize, usize,), CellStyle,),>,
+  style: CellStyle,
+  /// 在最后一列写入后，下一个字符换行
+  wrap_pending: bool,
+  /// 滚动区域 [top, bottom]
+  scroll_region: (usize, usize,),
+  auto_wrap: bool,
+  cursor_visible: bool,
+  app_cursor_keys: bool,
+  bracketed_paste: bool,
+  title: Option<String,>,
+  responses: Vec<u8,>,
+}
+
+impl Screen {
+  fn new(
+    rows: usize,
+    cols: usize,
+  ) -> Self {
+    let rows = rows.max(1,);
+    let cols = cols.max(2,);
+    Self {
+      rows,
+      cols,
+      lines: vec![vec![Cell::blank(CellStyle::default()); cols]; rows],
+      scrollback: VecDeque::new(),
+      main_lines: None,
+      cursor: (0, 0,),
+      saved_cursor: None,
+      style: CellStyle::default(),
+      wrap_pending: false,
+      scroll_region: (0, rows - 1,),
+      auto_wrap: true,
+      cursor_visible: true,
+      app_cursor_keys: false,
+      bracketed_paste: false,
+      title: None,
+      responses: vec![],
+    }
+  }
+
+  pub fn size(&self,) -> (usize, usize,) {
+    (self.rows, self.cols,)
+  }
+
+  /// (行, 列)，从 0 开始
+  pub fn cursor(&self,) -> (usize, usize,) {
+    self.cursor
+  }
+
+  pub fn cursor_visible(&self,) -> bool {
+    self.cursor_visible
+  }
+
+  pub fn app_cursor_keys(&self,) -> bool {
+    self.app_cursor_keys
+  }
+
+  pub fn bracketed_paste(&self,) -> bool {
+    self.bracketed_paste
+  }
+
+  pub fn title(&self,) -> Option<&str,> {
+    self.title.as_deref()
+  }
+
+  pub fn scrollback_len(&self,) -> usize {
+    self.scrollback.len()
+  }
+
+  /// 回滚缓冲和屏幕的总行数
+  pub fn total_lines(&self,) -> usize {
+    self.scrollback.len() + self.lines.len()
+  }
+
+  /// 第 index 行，先回滚缓冲后屏幕
+  pub fn line(
+    &self,
+    index: usize,
+  ) -> &[Cell] {
+    if index < self.scrollback.len() {
+      &self.scrollback[index]
+    } else {
+      self
+        .lines
+        .get(index - self.scrollback.len(),)
+        .map_or(&[], |v| v.as_slice(),)
+    }
+  }
+
+  /// from 到 to（不含）之间的文本，位置为 (行, 列)，去除行尾空白
+  pub fn text(
+    &self,
+    from: (usize, usize,),
+    to: (usize, usize,),
+  ) -> String {
+    let last = self.total_lines().saturating_sub(1,);
+    (from.0..=to.0.min(last,))
+      .map(|index| {
+        let line = self.line(index,);
+        let start = if index == from.0 { from.1 } else { 0 }.min(line.len(),);
+        let end = if index == to.0 { to.1 } else { line.len() }
+          .min(line.len(),)
+          .max(start,);
+        line[start..end]
+          .iter()
+          .filter(|v| v.c != WIDE_CHAR_SPACER,)
+          .map(|v| v.c,)
+          .collect::<String>()
+          .trim_end()
+          .to_string()
+      },)
+      .collect::<Vec<_,>>()
+      .join("\n",)
+  }
+
+  fn resize(
+    &mut self,
+    rows: usize,
+    cols: usize,
+  ) {
+    let rows = rows.max(1,);
+    let cols = cols.max(2,);
+    if (rows, cols,) == (self.rows, self.cols,) {
+      return;
+    }
+    let blank = Cell::blank(CellStyle::default(),);
+    let alt_screen = self.main_lines.is_some();
+    // 行数变少时，光标以上的行移入回滚缓冲
+    while self.lines.len() > rows {
+      if self.cursor.0 > 0 {
+        let line = self.lines.remove(0,);
+        if !alt_screen {
+          self.push_scrollback(line,);
+        }
+        self.cursor.0 -= 1;
+      } else {
+        self.lines.pop();
+      }
+    }
+    // 行数变多时，从回滚缓冲中恢复，备用屏幕不使用回滚缓冲
+    while self.lines.len() < rows {
+      let line = if alt_screen {
+        None
+      } else {
+        self.scrollback.pop_back()
+      };
+      match line {
+        Some(line,) => {
+          self.lines.insert(0, line,);
+          self.cursor.0 += 1;
+        }
+        None => self.lines.push(vec![blank; cols],),
+      }
+    }
+    for line in self.lines.iter_mut() {
+      line.resize(cols, blank,);
+    }
+    if let Some(main_lines,) = self.main_lines.as_mut() {
+      main_lines.resize(rows, vec![blank; cols],);
+      for line in main_lines.iter_mut() {
+        line.resize(cols, blank,);
+      }
+    }
+    self.rows = rows;
+    self.cols = cols;
+    self.scroll_region = (0, rows - 1,);
+    self.cursor = (self.cursor.0.min(rows - 1,), self.cursor.1.min(cols - 1,),);
+    // 保存的光标可能超出新的大小
+    if let Some((cursor, _,),) = self.saved_cursor.as_mut() {
+      *cursor = (cursor.0.min(rows - 1,), cursor.1.min(cols - 1,),);
+    }
+    self.wrap_pending = false;
+  }
+
+  fn push_scrollback(
+    &mut self,
+    line: Vec<Cell,>,
+  ) {
+    if self.scrollback.len() >= crate::style::TERMINAL_SCROLLBACK_LINES {
+      self.scrollback.pop_front();
+    }
+    self.scrollback.push_back(line,);
+  }
+
+  fn blank_line(&self,) -> Vec<Cell,> {
+    vec![Cell::blank(self.style); self.cols]
+  }
+
+  /// 滚动区域内容上移 n 行
+  fn scroll_up(
+    &mut self,
+    n: usize,
+  ) {
+    let (top, bottom,) = self.scroll_region;
+    for _ in 0..n.min(bottom - top + 1,) {
+      let line = self.lines.remove(top,);
+      if top == 0 && self.main_lines.is_none() {
+        self.push_scrollback(line,);
+      }
+      self.lines.insert(bottom, self.blank_line(),);
+    }
+  }
+
+  /// 滚动区域内容下移 n 行
+  fn scroll_down(
+    &mut self,
+    n: usize,
+  ) {
+    let (top, bottom,) = self.scroll_region;
+    for _ in 0..n.min(bottom - top + 1,) {
+      self.lines.remove(bottom,);
+      self.lines.insert(top, self.blank_line(),);
+    }
+  }
+
+  fn linefeed(&mut self,) {
+    self.wrap_pending = false;
+    if self.cursor.0 == self.scroll_region.1 {
+      self.scroll_up(1,);
+    } else if self.cursor.0 + 1 < self.rows {
+      self.cursor.0 += 1;
+    }
+  }
+
+  fn reverse_index(&mut self,) {
+    self.wrap_pending = false;
+    if self.cursor.0 == self.scroll_region.0 {
+      self.scroll_down(1,);
+    } else if self.cursor.0 > 0 {
+      self.cursor.0 -= 1;
+    }
+  }
+
+  fn goto(
+    &mut self,
+    row: usize,
+    col: usize,
+  ) {
+    self.cursor = (row.min(self.rows - 1,), col.min(self.cols - 1,),);
+    self.wrap_pending = false;
+  }
+
+  /// 清除当前行 [from, to) 中的内容
+  fn erase_in_line(
+    &mut self,
+    from: usize,
+    to: usize,
+  ) {
+    let blank = Cell::blank(self.style,);
+    let line = &mut self.lines[self.cursor.0];
+    let to = to.min(line.len(),);
+    for cell in line[from.min(to,)..to].iter_mut() {
+      *cell = blank;
+    }
+  }
+
+  fn erase_lines(
+    &mut self,
+    rows: std::ops::Range<usize,>,
+  ) {
+    let blank = self.blank_line();
+    for line in self.lines[rows].iter_mut() {
+      *line = blank.clone();
+    }
+  }
+
+  fn set_alt_screen(
+    &mut self,
+    enable: bool,
+  ) {
+    if enable && self.main_lines.is_none() {
+      let lines = std::mem::replace(
+        &mut self.lines,
+        vec![vec![Cell::blank(CellStyle::default()); self.cols]; self.rows],
+      );
+      self.main_lines = Some(lines,);
+      self.saved_cursor = Some((self.cursor, self.style,),);
+    } else if !enable {
+      if let Some(lines,) = self.main_lines.take() {
+        self.lines = lines;
+        if let Some((cursor, style,),) = self.saved_cursor {
+          self.goto(cursor.0, cursor.1,);
+          self.style = style;
+        }
+      }
+    }
+    self.wrap_pending = false;
+  }
+
+  fn set_mode(
+    &mut self,
+    params: &Params,
+    private: bool,
+    enable: bool,
+  ) {
+    for param in params.iter() {
+      match (private, param[0],) {
+        (true, 1,) => self.app_cursor_keys = enable,
+        (true, 7,) => self.auto_wrap = enable,
+        (true, 25,) => self.cursor_visible = enable,
+        (true, 47 | 1047 | 1049,) => self.set_alt_screen(enable,),
+        (true, 2004,) => self.bracketed_paste = enable,
+        _ => {}
+      }
+    }
+  }
+
+  /// SGR：设置字符样式
+  fn set_style(
+    &mut self,
+    params: &Params,
+  ) {
+    let mut iter = params.iter();
+    if params.is_empty() {
+      self.style = CellStyle::default();
+    }
+    while let Some(param,) = iter.next() {
+      match param[0] {
+        0 => self.style = CellStyle::default(),
+        1 => self.style.bold = true,
+        3 => self.style.italic = true,
+        4 => self.style.underline = true,
+        7 => self.style.inverse = true,
+        22 => self.style.bold = false,
+        23 => self.style.italic = false,
+        24 => self.style.underline = false,
+        27 => self.style.inverse = false,
+        v @ 30..=37 => self.style.fg = Color::Indexed((v - 30) as u8,),
+        38 => self.style.fg = parse_color(param, &mut iter,),
+        39 => self.style.fg = Color::Default,
+        v @ 40..=47 => self.style.bg = Color::Indexed((v - 40) as u8,),
+        48 => self.style.bg = parse_color(param, &mut iter,),
+        49 => self.style.bg = Color::Default,
+        v @ 90..=97 => self.style.fg = Color::Indexed((v - 90 + 8) as u8,),
+        v @ 100..=107 => self.style.bg = Color::Indexed((v - 100 + 8) as u8,),
+        _ => {}
+      }
+    }
+  }
+
+  fn reset(&mut self,) {
+    let mut screen = Self::new(self.rows, self.cols,);
+    screen.scrollback = std::mem::take(&mut self.scrollback,);
+    *self = screen;
+  }
+}
+
+/// 38/48 后的颜色参数，支持 `38;5;n`、`38;2;r;g;b` 及冒号分隔的形式
+fn parse_color<'a,>(
+  param: &[u16],
+  iter: &mut impl Iterator<Item = &'a [u16],>,
+) -> Color {
+  let values = if param.len() > 1 {
+    param[1..].to_vec()
+  } else {
+    match iter.next() {
+      Some([5],) => iter.next().map(|v| vec![5, v[0]],).unwrap_or_default(),
+      Some([2],) => {
+        let mut values = vec![2];
+        values.extend(iter.take(3,).map(|v| v[0],),);
+        values
+      }
+      _ => vec![],
+    }
+  };
+  match values.as_slice() {
+    [5, n] => Color::Indexed(*n as u8,),
+    [2, r, g, b] | [2, _, r, g, b] => Color::Rgb(*r as u8, *g as u8, *b as u8,),
+    _ => Color::Default,
+  }
+}
+
+/// 第 i 个参数，缺省或为 0 时使用 default
+fn param_or(
+  params: &Params,
+  i: usize,
+  default: usize,
+) -> usize {
+  match params.iter().nth(i,).map(|v| v[0],) {
+    None | Some(0,) => default,
+    Some(v,) => v as usize,
+  }
+}
+
+impl Perform for Screen {
+  fn print(
+    &mut self,
+    c: char,
+  ) {
+    let width = c.width().unwrap_or(0,);
+    if width == 0 {
+      return;
+    }
+    if self.wrap_pending {
+      if self.auto_wrap {
+        self.cursor.1 = 0;
+        self.linefeed();
+      }
+      self.wrap_pending = false;
+    }
+    // 宽字符在行尾放不下时换行
+    if width == 2 && self.cursor.1 + 1 >= self.cols {
+      if !self.auto_wrap {
+        return;
+      }
+      self.erase_in_line(self.cursor.1, self.cols,);
+      self.cursor.1 = 0;
+      self.linefeed();
+    }
+    let (row, col,) = self.cursor;
+    self.lines[row][col] = Cell {
+      c,
+      style: self.style,
+    };
+    if width == 2 {
+      self.lines[row][col + 1] = Cell {
+        c: WIDE_CHAR_SPACER,
+        style: self.style,
+      };
+    }
+    if col + width >= self.cols {
+      self.cursor.1 = self.cols - 1;
+      self.wrap_pending = true;
+    } else {
+      self.cursor.1 = col + width;
+    }
+  }
+
+  fn execute(
+    &mut self,
+    byte: u8,
+  ) {
+    match byte {
+      // BS
+      0x08 => {
+        self.cursor.1 = self.cursor.1.saturating_sub(1,);
+        self.wrap_pending = false;
+      }
+      // HT：下一个制表位（每 8 列）
+      0x09 => {
+        let col = (self.cursor.1 / 8 + 1) * 8;
+        self.goto(self.cursor.0, col,);
+      }
+      // LF VT FF
+      0x0a..=0x0c => self.linefeed(),
+      // CR
+      0x0d => {
+        self.cursor.1 = 0;
+        self.wrap_pending = false;
+      }
+      _ => {}
+    }
+  }
+
+  fn csi_dispatch(
+    &mut self,
+    params: &Params,
+    intermediates: &[u8],
+    _ignore: bool,
+    action: char,
+  ) {
+    let private = intermediates.first() == Some(&b'?',);
+    let n = param_or(params, 0, 1,);
+    let (row, col,) = self.cursor;
+    match action {
+      'A' => self.goto(
+        row.saturating_sub(n,).max(self.scroll_region.0.min(row,),),
+        col,
+      ),
+      'B' | 'e' => self.goto((row + n).min(self.scroll_region.1.max(row,),), col,),
+      'C' | 'a' => self.goto(row, col + n,),
+      'D' => self.goto(row, col.saturating_sub(n,),),
+      'E' => self.goto(row + n, 0,),
+      'F' => self.goto(row.saturating_sub(n,), 0,),
+      'G' | '`' => self.goto(row, n - 1,),
+      'd' => self.goto(n - 1, col,),
+      'H' | 'f' => self.goto(n - 1, param_or(params, 1, 1,) - 1,),
+      'J' => match param_or(params, 0, 0,) {
+        0 => {
+          self.erase_in_line(col, self.cols,);
+          self.erase_lines(row + 1..self.rows,);
+        }
+        1 => {
+          self.erase_in_line(0, col + 1,);
+          self.erase_lines(0..row,);
+        }
+        2 => self.erase_lines(0..self.rows,),
+        3 => self.scrollback.clear(),
+        _ => {}
+      },
+      'K' => match param_or(params, 0, 0,) {
+        0 => self.erase_in_line(col, self.cols,),
+        1 => self.erase_in_line(0, col + 1,),
+        2 => self.erase_in_line(0, self.cols,),
+        _ => {}
+      },
+      // 插入/删除行，只在滚动区域内生效
+      'L' | 'M' if (self.scroll_region.0..=self.scroll_region.1).contains(&row,) => {
+        let region = self.scroll_region;
+        self.scroll_region.0 = row;
+        if action == 'L' {
+          self.scroll_down(n,);
+        } else {
+          self.scroll_up(n,);
+        }
+        self.scroll_region = region;
+        self.cursor.1 = 0;
+      }
+      // 插入/删除字符
+      '@' | 'P' => {
+        let blank = Cell::blank(self.style,);
+        let line = &mut self.lines[row];
+        let n = n.min(self.cols - col,);
+        if action == '@' {
+          line.splice(col..col, std::iter::repeat_n(blank, n,),);
+          line.truncate(self.cols,);
+        } else {
+          line.drain(col..col + n,);
+          line.resize(self.cols, blank,);
+        }
+      }
+      'X' => self.erase_in_line(col, col + n,),
+      'S' => self.scroll_up(n,),
+      'T' => self.scroll_down(n,),
+      'm' => self.set_style(params,),
+      'h' => self.set_mode(params, private, true,),
+      'l' => self.set_mode(params, private, false,),
+      'r' => {
+        let top = param_or(params, 0, 1,) - 1;
+        let bottom = param_or(params, 1, self.rows,).min(self.rows,) - 1;
+        if top < bottom {
+          self.scroll_region = (top, bottom,);
+          self.goto(0, 0,);
+        }
+      }
+      's' if !private => self.saved_cursor = Some((self.cursor, self.style,),),
+      'u' if !private => {
+        if let Some((cursor, style,),) = self.saved_cursor {
+          self.goto(cursor.0, cursor.1,);
+          self.style = style;
+        }
+      }
+      // 设备状态报告
+      'n' => match param_or(params, 0, 0,) {
+        5 => self.responses.extend(b"\x1b[0n",),
+        6 => self
+          .responses
+          .extend(format!("\x1b[{};{}R", row + 1, col + 1).as_bytes(),),
+        _ => {}
+      },
+      // 设备属性：VT102
+      'c' if intermediates.is_empty() => self.responses.extend(b"\x1b[?6c",),
+      _ => {}
+    }
+  }
+
+  fn esc_dispatch(
+    &mut self,
+    intermediates: &[u8],
+    _ignore: bool,
+    byte: u8,
+  ) {
+    if !intermediates.is_empty() {
+      // 字符集选择等，忽略
+      return;
+    }
+    match byte {
+      b'7' => self.saved_cursor = Some((self.cursor, self.style,),),
+      b'8' => {
+        if let Some((cursor, style,),) = self.saved_cursor {
+          self.goto(cursor.0, cursor.1,);
+          self.style = style;
+        }
+      }
+      b'D' => self.linefeed(),
+      b'E' => {
+        self.cursor.1 = 0;
+        self.linefeed();
+      }
+      b'M' => self.reverse_index(),
+      b'c' => self.reset(),
+      _ => {}
+    }
+  }
+
+  fn osc_dispatch(
+    &mut self,
+    params: &[&[u8]],
+    _bell_terminated: bool,
+  ) {
+    // 设置窗口标题
+    if let [b"0" | b"2", title, ..] = params {
+      self.title = Some(String::from_utf8_lossy(title,).into_owned(),);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Color, Terminal, WIDE_CHAR_SPACER};
+
+  /// 屏幕上的所有行，保留行尾空白
+  fn screen_text(term: &Terminal,) -> String {
+    let screen = term.screen();
+    let first = screen.scrollback_len();
+    (first..screen.total_lines())
+      .map(|i| {
+        screen
+          .line(i,)
+          .iter()
+          .filter(|v| v.c != WIDE_CHAR_SPACER,)
+          .map(|v| v.c,)
+          .collect::<String>()
+      },)
+      .collect::<Vec<_,>>()
+      .join("\n",)
+  }
+
+  #[test]
+  fn test_terminal() {
+    let mut term = Terminal::new(3, 10,);
+    term.advance(b"hello\r\nworld",);
+    assert_eq!(screen_text(&term), "hello     \nworld     \n          ");
+    assert_eq!(term.screen().cursor(), (1, 5));
+
+    // 自动换行、滚动到回滚缓冲
+    term.advance(b"\r\n0123456789abc\r\nxyz",);
+    assert_eq!(term.screen().scrollback_len(), 2);
+    assert_eq!(term.screen().text((0, 0), (1, 10)), "hello\nworld");
+    assert_eq!(screen_text(&term), "0123456789\nabc       \nxyz       ");
+
+    // 光标移动、清除
+    term.advance(b"\x1b[1;3H\x1b[K\x1b[2;2H\x1b[1P",);
+    assert_eq!(screen_text(&term), "01        \nac        \nxyz       ");
+    term.advance(b"\x1b[2J\x1b[H\x1b[31;1mR\x1b[0m\x1b[38;2;1;2;3mG\x1b[48;5;200mB",);
+    let line = term.screen().line(term.screen().scrollback_len(),);
+    assert_eq!(line[0].style.fg, Color::Indexed(1));
+    assert!(line[0].style.bold);
+    assert_eq!(line[1].style.fg, Color::Rgb(1, 2, 3));
+    assert_eq!(line[2].style.bg, Color::Indexed(200));
+
+    // 宽字符占两格
+    term.advance(b"\x1b[2;9H\xe4\xb8\xad\xe6\x96\x87",);
+    assert_eq!(term.screen().cursor(), (2, 2));
+    assert!(screen_text(&term).ends_with("        中\n文        "));
+    assert_eq!(term.screen().text((3, 8), (4, 2)), "中\n文");
+
+    // 光标位置报告
+    term.advance(b"\x1b[6n",);
+    assert_eq!(term.take_responses(), b"\x1b[3;3R");
+  }
+
+  #[test]
+  fn test_alt_screen_and_resize() {
+    let mut term = Terminal::new(2, 5,);
+    term.advance(b"a\r\nb\r\nc",);
+    assert_eq!(term.screen().scrollback_len(), 1);
+
+    // 备用屏幕不影响主屏幕和回滚缓冲
+    term.advance(b"\x1b[?1049h\x1b[Hvim\r\n\r\n\r\n",);
+    assert_eq!(term.screen().scrollback_len(), 1);
+    term.advance(b"\x1b[?1049l",);
+    assert_eq!(screen_text(&term), "b    \nc    ");
+    assert_eq!(term.screen().cursor(), (1, 1));
+
+    // 变高时从回滚缓冲中恢复，变矮时移入回滚缓冲
+    term.resize(3, 4,);
+    assert_eq!(screen_text(&term), "a   \nb   \nc   ");
+    term.resize(1, 4,);
+    assert_eq!(screen_text(&term), "c   ");
+    assert_eq!(term.screen().scrollback_len(), 2);
+    // 在备用屏幕中变高不丢失回滚缓冲
+    term.advance(b"\x1b[?1049h",);
+    term.resize(3, 4,);
+    assert_eq!(term.screen().scrollback_len(), 2);
+    term.advance(b"\x1b[?1049l",);
+    assert_eq!(term.screen().scrollback_len(), 2);
+
+    // 滚动区域
+    let mut term = Terminal::new(4, 3,);
+    term.advance(b"1\r\n2\r\n3\r\n4\x1b[2;3r\x1b[3;1H\n",);
+    assert_eq!(screen_text(&term), "1  \n3  \n   \n4  ");
+    assert_eq!(term.screen().scrollback_len(), 0);
+
+    // 在备用屏幕中变小后退出，恢复的光标不超出屏幕
+    let mut term = Terminal::new(5, 8,);
+    term.advance(b"\x1b[5;8H\x1b[?1049h",);
+    term.resize(2, 3,);
+    term.advance(b"\x1b[?1049lx",);
+    assert_eq!(term.screen().cursor(), (1, 2));
+    assert_eq!(screen_text(&term), "   \n  x");
+  }
+}
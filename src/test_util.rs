@@ -0,0 +1,37 @@
+use std::{
+  ops::Deref,
+  path::{Path, PathBuf},
+};
+
+/// 测试用的临时文件夹，drop 时删除，断言失败时也会被清理
+pub struct TempDir(PathBuf,);
+
+impl TempDir {
+  /// 在系统临时目录中创建空的文件夹，name 区分不同的测试
+  pub fn new(name: &str,) -> Self {
+    let dir = std::env::temp_dir().join(format!("egui_code_{name}_{}", std::process::id()),);
+    let _ = std::fs::remove_dir_all(&dir,);
+    std::fs::create_dir_all(&dir,).unwrap();
+    Self(dir,)
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self,) -> &Path {
+    &self.0
+  }
+}
+
+impl AsRef<Path,> for TempDir {
+  fn as_ref(&self,) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self,) {
+    let _ = std::fs::remove_dir_all(&self.0,);
+  }
+}
@@ -27,12 +27,17 @@ pub const fn text_editor_font() -> egui::FontId {
   egui::FontId::new(16.0, egui::FontFamily::Monospace,)
 }
 
+pub const fn search_result_font() -> egui::FontId {
+  egui::FontId::new(14.0, egui::FontFamily::Monospace,)
+}
+
 pub fn window_title(id: &window::WindowId,) -> String {
   match id {
     window::WindowId::Exit => format!("{} {:?}", font::NerdFont::WARN.utf(), id),
     window::WindowId::About => format!("{} {:?}", font::NerdFont::INFO.utf(), id),
     window::WindowId::Setting => format!("{} {:?}", font::NerdFont::SETTINGS_GEAR.utf(), id),
     window::WindowId::Debug => format!("{} {:?}", font::NerdFont::TELESCOPE.utf(), id),
+    window::WindowId::Unsaved => format!("{} {:?}", font::NerdFont::WARN.utf(), id),
   }
 }
 
@@ -42,12 +47,16 @@ pub fn menu_text(id: &menu_bar::MenuId,) -> String {
 
 pub fn menu_item_text(
   act: &Action,
-  keys: &Vec<&egui::KeyboardShortcut,>,
+  keys: &Vec<&action::KeyBinding,>,
 ) -> String {
   if keys.is_empty() {
     act.name().into()
   } else {
-    format!("{:<15}{:>15}", act.name(), action::format_key(keys[0]))
+    format!(
+      "{:<15}{:>15}",
+      act.name(),
+      action::format_keys(&keys[0].keys)
+    )
   }
 }
 
@@ -70,6 +79,11 @@ pub fn open_files_context_menu_text(action: &open_file::ContextMenuAction,) -> S
 
 pub fn file_tree_context_menu_text(action: &file_tree::ContextMenuAction,) -> String {
   match action {
+    file_tree::ContextMenuAction::NewFile => "NewFile".into(),
+    file_tree::ContextMenuAction::NewFolder => "NewFolder".into(),
+    file_tree::ContextMenuAction::Rename => "Rename".into(),
+    file_tree::ContextMenuAction::Duplicate => "Duplicate".into(),
+    file_tree::ContextMenuAction::Delete => "Delete".into(),
     file_tree::ContextMenuAction::CopyFullPath => "CopyFullPath".into(),
     file_tree::ContextMenuAction::CopyRelativePath => "CopyRelativePath".into(),
     file_tree::ContextMenuAction::OpenInNative => "OpenInNative".into(),
@@ -111,6 +111,14 @@ pub fn adjust_panel_frame(frame: egui::Frame,) -> egui::Frame {
     .outer_margin(egui::Margin::same(0.,),)
 }
 
+pub fn transparency() -> f32 {
+  *TRANSPARENCY.lock()
+}
+
+pub fn set_transparency(transparency: f32,) {
+  *TRANSPARENCY.lock() = transparency.clamp(0., 1.,);
+}
+
 /// 透明度调整组件
 pub fn transparency_slider(ui: &mut egui::Ui,) {
   ui.horizontal(|ui| {
@@ -46,6 +46,32 @@ pub fn set_clipboard(
 // encoding
 ///////////////////////////////////////////////
 
+/// 读取文件并解码为 String
+pub fn read_text_file(path: &Path,) -> std::io::Result<(String, &'static encoding_rs::Encoding,),> {
+  let content_bytes = std::fs::read(path,)?;
+  decode_text(&content_bytes,)
+}
+
+/// 猜测编码并解码
+pub fn decode_text(
+  content_bytes: &[u8],
+) -> std::io::Result<(String, &'static encoding_rs::Encoding,),> {
+  let encoding = guess_encoding(content_bytes,);
+  let content_str = encoding
+    .decode_without_bom_handling_and_without_replacement(content_bytes,)
+    .ok_or(std::io::Error::other(format!(
+      "读取文件失败：{} 解码失败",
+      encoding.name()
+    ),),)?;
+  Ok((content_str.into_owned(), encoding,),)
+}
+
+/// 是否为二进制内容（与 git 相同，检查前 8000 字节中是否有 NUL）
+pub fn is_binary(content_bytes: &[u8],) -> bool {
+  let to = std::cmp::min(8000, content_bytes.len(),);
+  content_bytes[..to].contains(&0,)
+}
+
 /// 注意：chardetng 无法正确检测 UTF-16 without BOM
 pub fn guess_encoding(text: &[u8],) -> &'static encoding_rs::Encoding {
   let len = text.len();
@@ -58,7 +84,7 @@ pub fn guess_encoding(text: &[u8],) -> &'static encoding_rs::Encoding {
 ///////////////////////////////////////////////
 // line-ending
 ///////////////////////////////////////////////
-#[derive(Debug, Clone, Copy,)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize,)]
 pub enum LineEnding {
   Unknown,
   Crlf,
@@ -73,6 +99,28 @@ impl LineEnding {
       Self::Lf => "LF",
     }
   }
+
+  /// 换行时插入的文本，Unknown 时使用 \n
+  pub const fn as_text(&self,) -> &'static str {
+    match self {
+      Self::Crlf => "\r\n",
+      Self::Lf | Self::Unknown => "\n",
+    }
+  }
+
+  /// 将文本中的换行符统一为当前 line_ending（Unknown 时保持不变）
+  pub fn normalize<'a,>(
+    &self,
+    text: &'a str,
+  ) -> std::borrow::Cow<'a, str,> {
+    match self {
+      Self::Crlf if text.contains('\n',) => {
+        text.replace("\r\n", "\n",).replace('\n', "\r\n",).into()
+      }
+      Self::Lf if text.contains("\r\n",) => text.replace("\r\n", "\n",).into(),
+      _ => text.into(),
+    }
+  }
 }
 
 pub fn guess_line_ending(text: &str,) -> LineEnding {
@@ -89,6 +137,14 @@ pub fn guess_line_ending(text: &str,) -> LineEnding {
 ///////////////////////////////////////////////
 // 其他
 ///////////////////////////////////////////////
+/// 文件/目录名，没有名称（如根目录 /）时返回完整路径
+pub fn file_name(path: &Path,) -> std::borrow::Cow<'_, str,> {
+  match path.file_name() {
+    Some(v,) => v.to_string_lossy(),
+    None => path.to_string_lossy(),
+  }
+}
+
 pub fn open_in_native(path: &Path,) {
   #[cfg(target_os = "windows")]
   let _ = Command::new("explorer.exe",)
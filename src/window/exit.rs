@@ -56,6 +56,15 @@ impl ExitWindow {
       },);
   }
 
+  pub fn is_allowed_to_close(&self,) -> bool {
+    self.allowed_to_close
+  }
+
+  /// 已在其他地方确认退出，不再显示确认窗口
+  pub fn allow_close(&mut self,) {
+    self.allowed_to_close = true;
+  }
+
   pub fn on_frame_close_event(&mut self,) -> bool {
     let allowed_to_close = self.allowed_to_close;
     self.show = !allowed_to_close;
@@ -4,6 +4,7 @@ pub mod about;
 pub mod debug;
 pub mod exit;
 pub mod setting;
+pub mod unsaved;
 
 pub trait WindowExt {
   fn open_at_last_close_pos(&self,) -> bool;
@@ -54,4 +55,5 @@ pub enum WindowId {
   About,
   Setting,
   Debug,
+  Unsaved,
 }
@@ -1,6 +1,11 @@
+use std::sync::mpsc::SyncSender;
+
 use eframe::egui;
 
-use crate::{text, ui};
+use crate::{
+  action::{self, Action, KeyActions},
+  config, hl, style, text, ui, util,
+};
 
 use super::WindowExt;
 
@@ -32,6 +37,8 @@ impl SettingWindow {
     &mut self,
     ctx: &egui::Context,
     show: &mut bool,
+    key_actions: &KeyActions,
+    tx: &SyncSender<Action,>,
   ) {
     let title = text::window_title(&super::WindowId::Setting,);
     self.create_window(ctx, title, show,).show(ctx, |ui| {
@@ -48,7 +55,80 @@ impl SettingWindow {
           ui.monospace("透明度：",);
           ui::transparency_slider(ui,);
           ui.end_row();
+
+          for (dark_mode, label,) in [
+            (true, "黑夜模式编辑器主题：",),
+            (false, "白天模式编辑器主题：",),
+          ] {
+            ui.monospace(label,);
+            theme_combo_box(ui, dark_mode,);
+            ui.end_row();
+          }
         },);
+      ui.add_space(spacing_size,);
+      keybindings_ui(ui, key_actions,);
+      ui.add_space(spacing_size,);
+      if ui.button("恢复默认设置",).clicked() {
+        let _ = tx.send(Action::ResetSettings,);
+      }
     },);
   }
 }
+
+/// 选择 黑夜/白天 模式下编辑器的主题
+fn theme_combo_box(
+  ui: &mut egui::Ui,
+  dark_mode: bool,
+) {
+  let selected = hl::selected_theme(dark_mode,);
+  egui::ComboBox::from_id_source(("editor_theme", dark_mode,),)
+    .selected_text(&selected,)
+    .width(style::THEME_COMBO_BOX_WIDTH,)
+    .show_ui(ui, |ui| {
+      for name in hl::theme_names() {
+        if ui.selectable_label(name == selected, name,).clicked() {
+          hl::select_theme(dark_mode, name,);
+        }
+      }
+    },);
+}
+
+/// 生效的快捷键，用户配置见配置目录中的 keybindings.toml，保存后立即生效
+fn keybindings_ui(
+  ui: &mut egui::Ui,
+  key_actions: &KeyActions,
+) {
+  egui::CollapsingHeader::new("快捷键",).show(ui, |ui| {
+    ui.horizontal(|ui| {
+      ui.weak(format!(
+        "在配置目录的 {} 中修改，保存后立即生效",
+        action::KEYBINDINGS_FILE_NAME
+      ),);
+      if let Some(dir,) = config::config_dir() {
+        if ui.button("打开配置目录",).clicked() {
+          util::open_in_native(&dir,);
+        }
+      }
+    },);
+    egui::ScrollArea::vertical()
+      .max_height(style::KEYBINDINGS_MAX_HEIGHT,)
+      .show(ui, |ui| {
+        egui::Grid::new("keybindings_grid",)
+          .num_columns(4,)
+          .striped(true,)
+          .show(ui, |ui| {
+            for (key, action, user,) in key_actions.bindings() {
+              ui.monospace(action.name(),);
+              ui.monospace(action::format_keys(&key.keys,),);
+              ui.weak(key.when.map_or("", |v| v.name(),),);
+              if user {
+                ui.label("用户",);
+              } else {
+                ui.weak("默认",);
+              }
+              ui.end_row();
+            }
+          },);
+      },);
+  },);
+}
@@ -0,0 +1,103 @@
+use std::{path::PathBuf, sync::mpsc::SyncSender};
+
+use eframe::egui;
+
+use crate::{action::Action, style, text};
+
+/// 处理完未保存的修改后继续执行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq,)]
+pub enum UnsavedIntent {
+  /// 关闭文件
+  Close,
+  /// 退出应用
+  Exit,
+}
+
+/// 关闭文件或退出应用时，列出受影响的未保存文件，选择保存或丢弃
+#[derive(Debug,)]
+pub struct UnsavedDialog {
+  pub intent: UnsavedIntent,
+  /// (文件, 是否保存)
+  pub files: Vec<(PathBuf, bool,),>,
+}
+
+impl UnsavedDialog {
+  /// 默认保存所有文件
+  pub fn new(
+    intent: UnsavedIntent,
+    paths: Vec<PathBuf,>,
+  ) -> Self {
+    Self {
+      intent,
+      files: paths.into_iter().map(|v| (v, true,),).collect(),
+    }
+  }
+
+  pub fn contains(
+    &self,
+    path: &PathBuf,
+  ) -> bool {
+    self.files.iter().any(|(v, _,)| v == path,)
+  }
+
+  pub fn selected_paths(&self,) -> Vec<PathBuf,> {
+    self
+      .files
+      .iter()
+      .filter(|(_, selected,)| *selected,)
+      .map(|(v, _,)| v.clone(),)
+      .collect()
+  }
+}
+
+// ------------------------------------ UI
+
+impl UnsavedDialog {
+  pub fn show(
+    &mut self,
+    ctx: &egui::Context,
+    tx: &SyncSender<Action,>,
+  ) {
+    let title = text::window_title(&super::WindowId::Unsaved,);
+    egui::Window::new(title,)
+      .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::splat(0.,),)
+      .collapsible(false,)
+      .resizable(false,)
+      .show(ctx, |ui| {
+        ui.add_space(10.,);
+        ui.monospace(match self.intent {
+          UnsavedIntent::Close => "以下文件有未保存的修改，关闭前是否保存？",
+          UnsavedIntent::Exit => "以下文件有未保存的修改，退出前是否保存？",
+        },);
+        ui.add_space(10.,);
+        egui::ScrollArea::vertical()
+          .max_height(style::UNSAVED_FILES_MAX_HEIGHT,)
+          .show(ui, |ui| {
+            for (path, selected,) in self.files.iter_mut() {
+              ui.checkbox(selected, path.to_string_lossy(),);
+            }
+          },);
+        ui.add_space(10.,);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP,), |ui| {
+          if ui.button("取消",).clicked() {
+            let _ = tx.send(Action::CancelUnsaved,);
+          }
+          if ui.button("不保存",).clicked() {
+            let _ = tx.send(Action::DiscardUnsaved,);
+          }
+          let save_button = egui::Button::new("保存选中",);
+          let selected_paths = self.selected_paths();
+          if ui
+            .add_enabled(!selected_paths.is_empty(), save_button,)
+            .clicked()
+          {
+            let _ = tx.send(Action::SaveUnsaved(selected_paths,),);
+          }
+        },);
+      },);
+
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape,),) {
+      let _ = tx.send(Action::CancelUnsaved,);
+    }
+  }
+}